@@ -1,10 +1,12 @@
 use graphix_gfx as gfx;
 
 use gfx::hal::{
-    Attachment, AttachmentMode, BarrierPoint, CommandBuffer, CommandPool, CommandPoolFlags,
-    CommandQueue, Device, Format, Instance, QueueType, Surface, Swapchain, SwapchainConfig,
+    AdapterPreference, CommandBuffer, CommandPool, CommandPoolFlags, CommandQueue, Device, Format,
+    FormatProperties, Instance, QueueType, ResourceState, Surface, Swapchain, SwapchainConfig,
 };
 
+use graphix::{RenderGraph, TransientPool};
+
 use std::env;
 
 fn main() {
@@ -30,12 +32,18 @@ fn main() {
     // Create graphics backend instance
     let instance = gfx::Instance::new();
 
-    // Find a physical adapter
-    let mut adapters = instance.enumerate_adapters();
-    for adapter in &adapters {
+    // Find a physical adapter, or a software one if asked for
+    for adapter in &instance.enumerate_adapters() {
         println!("{:?}", adapter.info);
     }
-    let adapter = adapters.remove(0);
+    let preference = if config.use_warp {
+        AdapterPreference::Software
+    } else {
+        AdapterPreference::HighPerformance
+    };
+    let adapter = instance
+        .select_adapter(&preference)
+        .expect("No suitable adapter found");
     println!(
         "Using adapter '{}' ({}MB dedicated video memory)",
         adapter.info.name,
@@ -43,46 +51,63 @@ fn main() {
     );
 
     // Create a device
-    let device = adapter.create_device();
+    let device = adapter.create_device().expect("Failed to create device");
 
     // Create main present queue
-    let command_queue = device.create_command_queue(QueueType::Graphics);
+    let command_queue = device
+        .create_command_queue(QueueType::Graphics)
+        .expect("Failed to create command queue");
+
+    // Pick a back buffer format the device can render to
+    let format = device
+        .find_supported_format(
+            &[Format::Rgba8Unorm, Format::Bgra8Unorm],
+            FormatProperties::COLOR_ATTACHMENT,
+        )
+        .expect("No supported back buffer format");
 
     // Create a swapchain
     let surface = instance.create_surface(&window);
-    let swapchain = surface.create_swapchain(
-        &device,
-        &command_queue,
-        SwapchainConfig {
-            format: Format::Rgba8Unorm,
-            buffer_count: frame_count,
-            width: config.width,
-            height: config.height,
-            sync_interval: if config.is_vsync_enabled { 1 } else { 0 },
-        },
-    );
+    let swapchain = surface
+        .create_swapchain(
+            &device,
+            &command_queue,
+            SwapchainConfig {
+                format,
+                buffer_count: frame_count,
+                width: config.width,
+                height: config.height,
+                sync_interval: if config.is_vsync_enabled { 1 } else { 0 },
+            },
+        )
+        .expect("Failed to create swapchain");
     let backbuffer = swapchain.create_backbuffer();
-    let mut frame_index: usize = swapchain.acquire_buffer() as _;
+    let mut frame_index: usize = swapchain
+        .acquire_buffer()
+        .expect("Failed to acquire back buffer") as _;
 
-    let mut command_pool = device.create_command_pool(
-        QueueType::Graphics,
-        CommandPoolFlags::MULTIPLE_ALLOCATOR | CommandPoolFlags::SINGLE_LIST,
-    );
+    let mut command_pool = device
+        .create_command_pool(
+            QueueType::Graphics,
+            CommandPoolFlags::MULTIPLE_ALLOCATOR | CommandPoolFlags::SINGLE_LIST,
+        )
+        .expect("Failed to create command pool");
 
     let mut command_buffers = Vec::with_capacity(frame_count);
     for _ in 0..frame_count {
-        let command_buffer = command_pool.create_buffer();
+        let command_buffer = command_pool
+            .create_buffer()
+            .expect("Failed to create command buffer");
         command_buffers.push(command_buffer);
     }
 
-    let attachments = vec![Attachment {
-        states: AttachmentMode::Present..AttachmentMode::RenderTarget,
-    }];
+    // Render passes and transient images used by the render graph, reused from frame to frame
+    let mut transient_pool = TransientPool::<gfx::Backend>::new();
 
-    let clear_colors = vec![[0.56, 0.93, 0.56, 1.0]];
+    let clear_color = [0.56, 0.93, 0.56, 1.0];
 
     let mut fence_values: [u64; 3] = [0, 0, 0];
-    let fence = device.create_fence(0);
+    let fence = device.create_fence(0).expect("Failed to create fence");
 
     let mut is_running = true;
     while is_running {
@@ -138,36 +163,62 @@ fn main() {
 
         // Start recording commands for the current frame
         let command_buffer = &command_buffers[frame_index];
-        command_buffer.begin();
-
-        // Record commands for command buffer
-        let framebuffer = &backbuffer.framebuffers[frame_index];
-        command_buffer.insert_barriers(BarrierPoint::Pre, &attachments, framebuffer);
-        command_buffer.clear(&clear_colors, framebuffer);
-        command_buffer.insert_barriers(BarrierPoint::Post, &attachments, framebuffer);
+        command_buffer
+            .begin()
+            .expect("Failed to begin command buffer");
+
+        // Clear the back buffer and hand it back for presenting
+        let mut graph = RenderGraph::new();
+        let back_buffer = graph.import_framebuffer(
+            "back buffer",
+            &backbuffer.framebuffers[frame_index],
+            format,
+            ResourceState::PRESENT,
+        );
+        graph
+            .add_pass("clear")
+            .clear_color(back_buffer, clear_color)
+            .execute(|_, _| {});
+        transient_pool.set_fence_value(fence_values[frame_index]);
+        graph
+            .execute(&device, &mut transient_pool, command_buffer)
+            .expect("Failed to record render graph");
 
         // Stop recording commands
-        command_buffer.end();
+        command_buffer.end().expect("Failed to end command buffer");
 
         // Submit commands to the command queue
-        command_queue.submit(vec![command_buffer]);
+        command_queue
+            .submit(vec![command_buffer])
+            .expect("Failed to submit command buffer");
 
         // Present
-        swapchain.present();
+        swapchain.present().expect("Failed to present");
 
         // Signal command queue with fence value for current frame
         let frame_fence_value = fence_values[frame_index];
-        command_queue.signal_fence(&fence, frame_fence_value);
+        command_queue
+            .signal_fence(&fence, frame_fence_value)
+            .expect("Failed to signal fence");
 
         // Wait for fence value for next frame
-        frame_index = swapchain.acquire_buffer() as _;
-        device.wait_for_fence(&fence, fence_values[frame_index]);
+        frame_index = swapchain
+            .acquire_buffer()
+            .expect("Failed to acquire back buffer") as _;
+        device
+            .wait_for_fence(&fence, fence_values[frame_index])
+            .expect("Failed to wait for fence");
+        transient_pool.drop_outgrown_heaps(fence_values[frame_index]);
         fence_values[frame_index] = frame_fence_value + 1;
     }
 
     println!("Cleanup!");
-    command_queue.signal_fence(&fence, fence_values[frame_index]);
-    device.wait_for_fence(&fence, fence_values[frame_index]);
+    command_queue
+        .signal_fence(&fence, fence_values[frame_index])
+        .expect("Failed to signal fence");
+    device
+        .wait_for_fence(&fence, fence_values[frame_index])
+        .expect("Failed to wait for fence");
 
     println!("Bye!");
 }
@@ -0,0 +1,455 @@
+//! Sub-allocation of buffers and images from large heaps.
+//!
+//! Creating every resource with memory of its own is slow and wastes memory on alignment, so the
+//! `Allocator` creates heaps per memory type and places resources in them, ranges of the heaps
+//! being handed out by a two-level segregated fit allocator. Resources too large to share a heap
+//! get dedicated memory instead.
+
+use crate::hal::{
+    Backend, BufferUsage, Device, Error, Features, HeapUsage, ImageDesc, MemoryRequirements,
+    MemoryType, Result,
+};
+
+use self::tlsf::{BlockId, Tlsf};
+
+#[cfg(any(test, feature = "dx12"))]
+pub(crate) mod linear;
+#[cfg(any(test, feature = "dx12"))]
+pub(crate) mod slots;
+pub(crate) mod tlsf;
+
+/// Sizes an `Allocator` works with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AllocatorConfig {
+    /// Size of the heaps resources are placed in, in bytes.
+    pub heap_size: u64,
+    /// Resources at least this large get dedicated memory. At most half of `heap_size`, so that
+    /// any resource placed fits an empty heap whatever its alignment.
+    pub dedicated_threshold: u64,
+}
+
+impl Default for AllocatorConfig {
+    fn default() -> Self {
+        AllocatorConfig {
+            heap_size: 64 * 1024 * 1024,
+            dedicated_threshold: 32 * 1024 * 1024,
+        }
+    }
+}
+
+/// Memory usage of an `Allocator`, as returned by `Allocator::stats`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AllocatorStats {
+    pub heap_count: usize,
+    /// Resources placed in heaps.
+    pub allocation_count: usize,
+    /// Resources with dedicated memory.
+    pub dedicated_count: usize,
+    /// Memory taken by heaps and dedicated allocations, in bytes.
+    pub allocated_size: u64,
+    /// Memory taken by resources, in bytes.
+    pub used_size: u64,
+    /// Largest range of a heap no resource is placed in, in bytes.
+    pub largest_free_block: u64,
+}
+
+impl AllocatorStats {
+    /// Share of the free memory outside of the largest free block, from 0 when all the free
+    /// memory is in one block to almost 1 when it's scattered in small blocks.
+    pub fn fragmentation(&self) -> f32 {
+        let free_size = self.allocated_size - self.used_size;
+        if free_size == 0 {
+            0.0
+        } else {
+            1.0 - self.largest_free_block as f32 / free_size as f32
+        }
+    }
+}
+
+/// Memory given to a resource by an `Allocator`, handed back by `Allocator::free`.
+#[derive(Debug)]
+pub struct Allocation {
+    /// Heap and block the resource is placed in, `None` for dedicated allocations.
+    block: Option<(usize, BlockId)>,
+    offset: u64,
+    size: u64,
+}
+
+impl Allocation {
+    /// Offset of the resource in its heap, 0 for dedicated allocations.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_dedicated(&self) -> bool {
+        self.block.is_none()
+    }
+}
+
+struct MemoryHeap<B: Backend> {
+    memory_type: MemoryType,
+    usage: HeapUsage,
+    heap: B::Heap,
+    tlsf: Tlsf,
+}
+
+/// Creates buffers and images, placing them in heaps shared with other resources.
+///
+/// Heaps are created per memory type, and per kind of resource unless the device supports
+/// `Features::MIXED_RESOURCE_HEAPS`. Empty heaps are released, except the last one of each kind
+/// so that creating and freeing a resource over and over doesn't create a heap each time.
+pub struct Allocator<B: Backend> {
+    config: AllocatorConfig,
+    heaps: Vec<Option<MemoryHeap<B>>>,
+    dedicated_count: usize,
+    dedicated_size: u64,
+}
+
+impl<B: Backend> Allocator<B> {
+    pub fn new(config: AllocatorConfig) -> Self {
+        assert!(
+            config.dedicated_threshold <= config.heap_size / 2,
+            "Dedicated threshold of {} bytes over half the heap size of {} bytes",
+            config.dedicated_threshold,
+            config.heap_size
+        );
+
+        Allocator {
+            config,
+            heaps: Vec::new(),
+            dedicated_count: 0,
+            dedicated_size: 0,
+        }
+    }
+
+    pub fn config(&self) -> &AllocatorConfig {
+        &self.config
+    }
+
+    pub fn create_buffer(
+        &mut self,
+        device: &B::Device,
+        size: u64,
+        usage: BufferUsage,
+        memory_type: MemoryType,
+    ) -> Result<(B::Buffer, Allocation)> {
+        let requirements = device.get_buffer_requirements(size, usage)?;
+        self.create_resource(
+            device,
+            memory_type,
+            HeapUsage::BUFFERS,
+            requirements,
+            |heap, offset| device.create_placed_buffer(heap, offset, size, usage),
+            || device.create_buffer(size, usage, memory_type),
+        )
+    }
+
+    pub fn create_image(
+        &mut self,
+        device: &B::Device,
+        desc: ImageDesc,
+    ) -> Result<(B::Image, Allocation)> {
+        let requirements = device.get_image_requirements(&desc)?;
+        self.create_resource(
+            device,
+            MemoryType::DeviceLocal,
+            HeapUsage::from_image_usage(desc.usage),
+            requirements,
+            |heap, offset| device.create_placed_image(heap, offset, desc),
+            || device.create_image(desc),
+        )
+    }
+
+    /// Hands the memory of a resource back. The resource must not be used anymore, by the CPU or
+    /// by work the GPU hasn't finished yet, as other resources may be placed in its memory.
+    pub fn free(&mut self, allocation: Allocation) {
+        let (index, block) = match allocation.block {
+            Some(block) => block,
+            None => {
+                self.dedicated_count -= 1;
+                self.dedicated_size -= allocation.size;
+                return;
+            }
+        };
+
+        let heap = self.heaps[index].as_mut().unwrap();
+        heap.tlsf.free(block);
+        if heap.tlsf.is_empty() {
+            let (memory_type, usage) = (heap.memory_type, heap.usage);
+            let same_kind_count = self
+                .heaps
+                .iter()
+                .flatten()
+                .filter(|heap| heap.memory_type == memory_type && heap.usage == usage)
+                .count();
+            if same_kind_count > 1 {
+                self.heaps[index] = None;
+            }
+        }
+    }
+
+    pub fn stats(&self) -> AllocatorStats {
+        let heaps = self.heaps.iter().flatten();
+        AllocatorStats {
+            heap_count: heaps.clone().count(),
+            allocation_count: heaps.clone().map(|heap| heap.tlsf.allocation_count()).sum(),
+            dedicated_count: self.dedicated_count,
+            allocated_size: heaps.clone().map(|heap| heap.tlsf.size()).sum::<u64>()
+                + self.dedicated_size,
+            used_size: heaps.clone().map(|heap| heap.tlsf.used_size()).sum::<u64>()
+                + self.dedicated_size,
+            largest_free_block: heaps
+                .map(|heap| heap.tlsf.largest_free_block())
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    fn create_resource<T>(
+        &mut self,
+        device: &B::Device,
+        memory_type: MemoryType,
+        usage: HeapUsage,
+        requirements: MemoryRequirements,
+        create_placed: impl FnOnce(&B::Heap, u64) -> Result<T>,
+        create_dedicated: impl FnOnce() -> Result<T>,
+    ) -> Result<(T, Allocation)> {
+        if requirements.size >= self.config.dedicated_threshold {
+            return self.create_dedicated(requirements, create_dedicated);
+        }
+
+        let usage = if device.features().contains(Features::MIXED_RESOURCE_HEAPS) {
+            HeapUsage::all()
+        } else {
+            usage
+        };
+        let allocation = self.allocate(device, memory_type, usage, requirements)?;
+        let (index, _) = allocation.block.unwrap();
+        let heap = &self.heaps[index].as_ref().unwrap().heap;
+        match create_placed(heap, allocation.offset) {
+            Ok(resource) => Ok((resource, allocation)),
+            Err(err) => {
+                self.free(allocation);
+                match err {
+                    // Not every resource can live in the memory heaps are created with
+                    Error::Unsupported => self.create_dedicated(requirements, create_dedicated),
+                    err => Err(err),
+                }
+            }
+        }
+    }
+
+    fn create_dedicated<T>(
+        &mut self,
+        requirements: MemoryRequirements,
+        create_dedicated: impl FnOnce() -> Result<T>,
+    ) -> Result<(T, Allocation)> {
+        let resource = create_dedicated()?;
+        self.dedicated_count += 1;
+        self.dedicated_size += requirements.size;
+
+        Ok((
+            resource,
+            Allocation {
+                block: None,
+                offset: 0,
+                size: requirements.size,
+            },
+        ))
+    }
+
+    fn allocate(
+        &mut self,
+        device: &B::Device,
+        memory_type: MemoryType,
+        usage: HeapUsage,
+        requirements: MemoryRequirements,
+    ) -> Result<Allocation> {
+        let MemoryRequirements { size, alignment } = requirements;
+        for (index, heap) in self.heaps.iter_mut().enumerate() {
+            let heap = match heap {
+                Some(heap) if heap.memory_type == memory_type && heap.usage == usage => heap,
+                _ => continue,
+            };
+            if let Some((block, offset)) = heap.tlsf.allocate(size, alignment) {
+                return Ok(Allocation {
+                    block: Some((index, block)),
+                    offset,
+                    size,
+                });
+            }
+        }
+
+        let heap = device.create_heap(self.config.heap_size, memory_type, usage)?;
+        let mut tlsf = Tlsf::new(self.config.heap_size);
+        let (block, offset) = tlsf
+            .allocate(size, alignment)
+            .expect("Resource doesn't fit an empty heap");
+
+        let heap = Some(MemoryHeap {
+            memory_type,
+            usage,
+            heap,
+            tlsf,
+        });
+        let index = match self.heaps.iter().position(Option::is_none) {
+            Some(index) => {
+                self.heaps[index] = heap;
+                index
+            }
+            None => {
+                self.heaps.push(heap);
+                self.heaps.len() - 1
+            }
+        };
+
+        Ok(Allocation {
+            block: Some((index, block)),
+            offset,
+            size,
+        })
+    }
+}
+
+impl<B: Backend> Default for Allocator<B> {
+    fn default() -> Self {
+        Allocator::new(AllocatorConfig::default())
+    }
+}
+
+#[cfg(all(test, feature = "software"))]
+mod tests {
+    use super::*;
+    use crate::backend::software::Backend;
+    use crate::hal::ImageUsage;
+    use crate::testing::{create_device, image_desc};
+
+    const KIB: u64 = 1024;
+
+    fn create_allocator() -> Allocator<Backend> {
+        Allocator::new(AllocatorConfig {
+            heap_size: 1024 * KIB,
+            dedicated_threshold: 256 * KIB,
+        })
+    }
+
+    #[test]
+    fn places_resources_in_one_heap() {
+        let device = create_device();
+        let mut allocator = create_allocator();
+
+        let (_, buffer) = allocator
+            .create_buffer(&device, 1000, BufferUsage::VERTEX, MemoryType::DeviceLocal)
+            .unwrap();
+        let (_, image) = allocator
+            .create_image(&device, image_desc(64, ImageUsage::SAMPLED))
+            .unwrap();
+        assert!(!buffer.is_dedicated() && !image.is_dedicated());
+        assert_ne!(buffer.offset(), image.offset());
+
+        let stats = allocator.stats();
+        assert_eq!(stats.heap_count, 1);
+        assert_eq!(stats.allocation_count, 2);
+        assert_eq!(stats.allocated_size, 1024 * KIB);
+        assert_eq!(stats.used_size, 1000 + 64 * 64 * 4);
+    }
+
+    #[test]
+    fn dedicates_large_resources() {
+        let device = create_device();
+        let mut allocator = create_allocator();
+
+        let (_, image) = allocator
+            .create_image(&device, image_desc(512, ImageUsage::SAMPLED))
+            .unwrap();
+        assert!(image.is_dedicated());
+
+        let stats = allocator.stats();
+        assert_eq!(stats.heap_count, 0);
+        assert_eq!(stats.dedicated_count, 1);
+        assert_eq!(stats.allocated_size, 512 * 512 * 4);
+
+        allocator.free(image);
+        assert_eq!(allocator.stats(), AllocatorStats::default());
+    }
+
+    #[test]
+    fn separates_memory_types() {
+        let device = create_device();
+        let mut allocator = create_allocator();
+
+        for &memory_type in &[
+            MemoryType::DeviceLocal,
+            MemoryType::Upload,
+            MemoryType::Readback,
+        ] {
+            allocator
+                .create_buffer(&device, 256, BufferUsage::TRANSFER_SRC, memory_type)
+                .unwrap();
+        }
+        assert_eq!(allocator.stats().heap_count, 3);
+    }
+
+    #[test]
+    fn grows_and_releases_heaps() {
+        let device = create_device();
+        let mut allocator = create_allocator();
+
+        // Buffers are 256KiB apart once aligned, 4 of them fitting a heap with 56KiB left
+        let allocations: Vec<Allocation> = (0..8)
+            .map(|_| {
+                allocator
+                    .create_buffer(
+                        &device,
+                        200 * KIB,
+                        BufferUsage::STORAGE,
+                        MemoryType::DeviceLocal,
+                    )
+                    .unwrap()
+                    .1
+            })
+            .collect();
+        let stats = allocator.stats();
+        assert_eq!(stats.heap_count, 2);
+        assert_eq!(stats.largest_free_block, 56 * KIB);
+
+        for allocation in allocations {
+            allocator.free(allocation);
+        }
+        let stats = allocator.stats();
+        assert_eq!(stats.heap_count, 1);
+        assert_eq!(stats.used_size, 0);
+        assert_eq!(stats.fragmentation(), 0.0);
+    }
+
+    #[test]
+    fn reports_fragmentation() {
+        let device = create_device();
+        let mut allocator = create_allocator();
+
+        let mut allocations: Vec<Allocation> = (0..4)
+            .map(|_| {
+                allocator
+                    .create_buffer(
+                        &device,
+                        192 * KIB,
+                        BufferUsage::STORAGE,
+                        MemoryType::DeviceLocal,
+                    )
+                    .unwrap()
+                    .1
+            })
+            .collect();
+        allocator.free(allocations.remove(2));
+        allocator.free(allocations.remove(0));
+
+        // Two free blocks of 192KiB and one of 256KiB at the end
+        let stats = allocator.stats();
+        assert_eq!(stats.largest_free_block, 256 * KIB);
+        assert!((stats.fragmentation() - (1.0 - 256.0 / 640.0)).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,115 @@
+//! Allocation of ranges from pages one after the other, all freed at once, e.g. descriptors
+//! living for a frame.
+
+/// A range allocated by `Linear::allocate`, starting `offset` units into page `page`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct LinearRange {
+    pub page: usize,
+    pub offset: u32,
+}
+
+/// Hands out ranges of pages of `page_size` units, moving on to the next page once a range doesn't
+/// fit the current one. Pages are added by the owner when `allocate` runs out of them, and are
+/// kept by `reset` to allocate from again.
+pub(crate) struct Linear {
+    page_size: u32,
+    page_count: usize,
+    page: usize,
+    offset: u32,
+}
+
+impl Linear {
+    pub fn new(page_size: u32) -> Self {
+        Linear {
+            page_size,
+            page_count: 0,
+            page: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn page_size(&self) -> u32 {
+        self.page_size
+    }
+
+    /// Allocates `count` contiguous units, at most the page size, or returns `None` if a page
+    /// needs to be added first.
+    pub fn allocate(&mut self, count: u32) -> Option<LinearRange> {
+        assert!(
+            count <= self.page_size,
+            "{} units allocated from pages of {}",
+            count,
+            self.page_size
+        );
+
+        let (page, offset) = if self.offset + count <= self.page_size {
+            (self.page, self.offset)
+        } else {
+            (self.page + 1, 0)
+        };
+        if page >= self.page_count {
+            return None;
+        }
+
+        self.page = page;
+        self.offset = offset + count;
+        Some(LinearRange { page, offset })
+    }
+
+    pub fn add_page(&mut self) {
+        self.page_count += 1;
+    }
+
+    /// Frees all the ranges allocated, starting over from the first page.
+    pub fn reset(&mut self) {
+        self.page = 0;
+        self.offset = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocate(linear: &mut Linear, count: u32) -> LinearRange {
+        linear.allocate(count).unwrap_or_else(|| {
+            linear.add_page();
+            linear.allocate(count).unwrap()
+        })
+    }
+
+    #[test]
+    fn moves_to_next_page() {
+        let mut linear = Linear::new(8);
+        assert_eq!(linear.allocate(1), None);
+
+        assert_eq!(allocate(&mut linear, 3), LinearRange { page: 0, offset: 0 });
+        assert_eq!(allocate(&mut linear, 5), LinearRange { page: 0, offset: 3 });
+        // Ranges don't span pages
+        assert_eq!(linear.allocate(2), None);
+        assert_eq!(allocate(&mut linear, 2), LinearRange { page: 1, offset: 0 });
+    }
+
+    #[test]
+    fn reuses_pages_once_reset() {
+        let mut linear = Linear::new(8);
+        let ranges: Vec<LinearRange> = [6, 4, 8]
+            .iter()
+            .map(|&count| allocate(&mut linear, count))
+            .collect();
+
+        // The same slots are handed out again without adding pages
+        linear.reset();
+        for (&count, &range) in [6, 4, 8].iter().zip(&ranges) {
+            assert_eq!(linear.allocate(count), Some(range));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "9 units allocated from pages of 8")]
+    fn rejects_ranges_larger_than_pages() {
+        let mut linear = Linear::new(8);
+        linear.add_page();
+        linear.allocate(linear.page_size() + 1);
+    }
+}
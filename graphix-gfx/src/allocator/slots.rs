@@ -0,0 +1,116 @@
+//! Allocation of fixed-size slots one at a time, e.g. descriptors of a heap.
+//!
+//! Each slot has a generation bumped whenever it's allocated or freed, which handles to it carry
+//! so that using a slot once freed is caught even after it's handed out again.
+
+/// A slot allocated by `Slots::allocate`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SlotId {
+    index: u32,
+    generation: u32,
+}
+
+impl SlotId {
+    pub fn index(self) -> u32 {
+        self.index
+    }
+}
+
+pub(crate) struct Slots {
+    // Even while a slot is free and odd while it's allocated
+    generations: Vec<u32>,
+    free_slots: Vec<u32>,
+}
+
+impl Slots {
+    pub fn new(count: u32) -> Self {
+        Slots {
+            generations: vec![0; count as usize],
+            // Slots are handed out from the first one
+            free_slots: (0..count).rev().collect(),
+        }
+    }
+
+    /// Allocates a free slot, or returns `None` if they're all allocated.
+    pub fn allocate(&mut self) -> Option<SlotId> {
+        let index = self.free_slots.pop()?;
+        self.generations[index as usize] += 1;
+        Some(SlotId {
+            index,
+            generation: self.generations[index as usize],
+        })
+    }
+
+    pub fn free(&mut self, slot: SlotId) {
+        self.check(slot);
+        self.generations[slot.index as usize] += 1;
+        self.free_slots.push(slot.index);
+    }
+
+    /// Panics if `slot` was freed since it was allocated.
+    pub fn check(&self, slot: SlotId) {
+        assert_eq!(
+            slot.generation, self.generations[slot.index as usize],
+            "Slot {} used after being freed",
+            slot.index
+        );
+    }
+
+    pub fn has_free_slots(&self) -> bool {
+        !self.free_slots.is_empty()
+    }
+
+    /// Number of slots allocated and not freed yet.
+    pub fn allocated_count(&self) -> usize {
+        self.generations.len() - self.free_slots.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocates_every_slot() {
+        let mut slots = Slots::new(3);
+        let indices: Vec<u32> = (0..3).map(|_| slots.allocate().unwrap().index()).collect();
+        assert_eq!(indices, [0, 1, 2]);
+        assert!(!slots.has_free_slots());
+        assert_eq!(slots.allocate(), None);
+        assert_eq!(slots.allocated_count(), 3);
+    }
+
+    #[test]
+    fn reuses_freed_slots() {
+        let mut slots = Slots::new(2);
+        let first = slots.allocate().unwrap();
+        slots.allocate().unwrap();
+        slots.free(first);
+        assert_eq!(slots.allocated_count(), 1);
+
+        // The slot comes back with a new generation
+        let reused = slots.allocate().unwrap();
+        assert_eq!(reused.index(), first.index());
+        assert_ne!(reused, first);
+        slots.check(reused);
+    }
+
+    #[test]
+    #[should_panic(expected = "Slot 0 used after being freed")]
+    fn rejects_freed_slot() {
+        let mut slots = Slots::new(1);
+        let slot = slots.allocate().unwrap();
+        slots.free(slot);
+        slots.allocate().unwrap();
+        slots.check(slot);
+    }
+
+    #[test]
+    #[should_panic(expected = "Slot 0 used after being freed")]
+    fn rejects_double_free() {
+        let mut slots = Slots::new(1);
+        let slot = slots.allocate().unwrap();
+        slots.free(slot);
+        slots.free(slot);
+    }
+}
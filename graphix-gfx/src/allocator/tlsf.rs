@@ -0,0 +1,444 @@
+//! Two-level segregated fit allocation of ranges of a heap.
+//!
+//! Free blocks are kept in lists by size: the first level splits sizes by power of two and the
+//! second one splits each power of two in `SL_COUNT` ranges. Bitmaps of the non-empty lists find
+//! a free block large enough in constant time, and freed blocks merge with their free neighbors.
+
+const SL_LOG2: u32 = 4;
+const SL_COUNT: usize = 1 << SL_LOG2;
+// Sizes below `SL_COUNT` all go in the first list, one second level list per size
+const FL_COUNT: usize = 64 - SL_LOG2 as usize + 1;
+
+/// A block allocated by `Tlsf::allocate`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct BlockId(u32);
+
+#[derive(Clone)]
+struct Block {
+    offset: u64,
+    size: u64,
+    is_free: bool,
+    prev_physical: Option<u32>,
+    next_physical: Option<u32>,
+    prev_free: Option<u32>,
+    next_free: Option<u32>,
+}
+
+pub(crate) struct Tlsf {
+    blocks: Vec<Block>,
+    unused_blocks: Vec<u32>,
+    fl_bitmap: u64,
+    sl_bitmaps: [u32; FL_COUNT],
+    free_lists: [[Option<u32>; SL_COUNT]; FL_COUNT],
+    size: u64,
+    used_size: u64,
+    allocation_count: usize,
+}
+
+impl Tlsf {
+    pub fn new(size: u64) -> Self {
+        let mut tlsf = Tlsf {
+            blocks: Vec::new(),
+            unused_blocks: Vec::new(),
+            fl_bitmap: 0,
+            sl_bitmaps: [0; FL_COUNT],
+            free_lists: [[None; SL_COUNT]; FL_COUNT],
+            size,
+            used_size: 0,
+            allocation_count: 0,
+        };
+
+        if size > 0 {
+            let block = tlsf.add_block(Block {
+                offset: 0,
+                size,
+                is_free: true,
+                prev_physical: None,
+                next_physical: None,
+                prev_free: None,
+                next_free: None,
+            });
+            tlsf.insert_free(block);
+        }
+        tlsf
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn used_size(&self) -> u64 {
+        self.used_size
+    }
+
+    pub fn allocation_count(&self) -> usize {
+        self.allocation_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allocation_count == 0
+    }
+
+    /// Size of the largest free block, the largest allocation without alignment that can succeed.
+    pub fn largest_free_block(&self) -> u64 {
+        if self.fl_bitmap == 0 {
+            return 0;
+        }
+        let fl = 63 - self.fl_bitmap.leading_zeros() as usize;
+        let sl = 31 - self.sl_bitmaps[fl].leading_zeros() as usize;
+
+        // Blocks of a list have different sizes within its range
+        let mut largest = 0;
+        let mut next = self.free_lists[fl][sl];
+        while let Some(index) = next {
+            let block = &self.blocks[index as usize];
+            largest = largest.max(block.size);
+            next = block.next_free;
+        }
+        largest
+    }
+
+    /// Allocates `size` bytes at an offset multiple of `alignment`, which must be a power of two.
+    /// Returns the block and its offset, or `None` if no free block is large enough.
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<(BlockId, u64)> {
+        debug_assert!(alignment.is_power_of_two());
+        let size = size.max(1);
+
+        // Any block at least that large fits once its start is aligned
+        let search_size = size.checked_add(alignment - 1)?;
+        let index = match get_search_list(search_size).and_then(|(fl, sl)| self.find_free(fl, sl)) {
+            Some(index) => Some(index),
+            // Lists before the one searched may still hold a block large enough, e.g. one freed
+            // by an allocation of the same size
+            None => self.find_fitting(size, alignment),
+        }?;
+        self.remove_free(index);
+
+        // Whatever comes before the aligned offset is left free, the block before being used
+        let block = self.blocks[index as usize].clone();
+        let offset = align(block.offset, alignment);
+        if offset > block.offset {
+            let padding = self.add_block(Block {
+                offset: block.offset,
+                size: offset - block.offset,
+                is_free: true,
+                prev_physical: block.prev_physical,
+                next_physical: Some(index),
+                prev_free: None,
+                next_free: None,
+            });
+            if let Some(prev) = block.prev_physical {
+                self.blocks[prev as usize].next_physical = Some(padding);
+            }
+            self.insert_free(padding);
+
+            let block = &mut self.blocks[index as usize];
+            block.prev_physical = Some(padding);
+            block.size -= offset - block.offset;
+            block.offset = offset;
+        }
+
+        // And so is whatever comes after the allocation, the block after being used too
+        let remaining = self.blocks[index as usize].size - size;
+        if remaining > 0 {
+            let next_physical = self.blocks[index as usize].next_physical;
+            let rest = self.add_block(Block {
+                offset: offset + size,
+                size: remaining,
+                is_free: true,
+                prev_physical: Some(index),
+                next_physical,
+                prev_free: None,
+                next_free: None,
+            });
+            if let Some(next) = next_physical {
+                self.blocks[next as usize].prev_physical = Some(rest);
+            }
+            self.insert_free(rest);
+            self.blocks[index as usize].next_physical = Some(rest);
+        }
+
+        let block = &mut self.blocks[index as usize];
+        block.size = size;
+        block.is_free = false;
+        self.used_size += size;
+        self.allocation_count += 1;
+        Some((BlockId(index), offset))
+    }
+
+    /// Frees a block, merging it with the free blocks around it.
+    pub fn free(&mut self, block: BlockId) {
+        let mut index = block.0;
+        {
+            let block = &mut self.blocks[index as usize];
+            assert!(!block.is_free, "Block freed twice");
+            block.is_free = true;
+            self.used_size -= block.size;
+            self.allocation_count -= 1;
+        }
+
+        if let Some(prev) = self.blocks[index as usize].prev_physical {
+            if self.blocks[prev as usize].is_free {
+                self.remove_free(prev);
+                self.merge_next(prev);
+                index = prev;
+            }
+        }
+        if let Some(next) = self.blocks[index as usize].next_physical {
+            if self.blocks[next as usize].is_free {
+                self.remove_free(next);
+                self.merge_next(index);
+            }
+        }
+
+        self.insert_free(index);
+    }
+
+    /// Merges the block following `index` into it.
+    fn merge_next(&mut self, index: u32) {
+        let next = self.blocks[index as usize].next_physical.unwrap();
+        let Block {
+            size,
+            next_physical,
+            ..
+        } = self.blocks[next as usize];
+
+        self.blocks[index as usize].size += size;
+        self.blocks[index as usize].next_physical = next_physical;
+        if let Some(after) = next_physical {
+            self.blocks[after as usize].prev_physical = Some(index);
+        }
+        self.unused_blocks.push(next);
+    }
+
+    fn add_block(&mut self, block: Block) -> u32 {
+        match self.unused_blocks.pop() {
+            Some(index) => {
+                self.blocks[index as usize] = block;
+                index
+            }
+            None => {
+                self.blocks.push(block);
+                self.blocks.len() as u32 - 1
+            }
+        }
+    }
+
+    /// First block of the first non-empty list at or after `(fl, sl)`.
+    fn find_free(&self, fl: usize, sl: usize) -> Option<u32> {
+        let mut fl = fl;
+        let mut sl_bitmap = self.sl_bitmaps[fl] & (!0 << sl);
+        if sl_bitmap == 0 {
+            let fl_bitmap = self.fl_bitmap.checked_shr(fl as u32 + 1)? << (fl + 1);
+            if fl_bitmap == 0 {
+                return None;
+            }
+            fl = fl_bitmap.trailing_zeros() as usize;
+            sl_bitmap = self.sl_bitmaps[fl];
+        }
+
+        self.free_lists[fl][sl_bitmap.trailing_zeros() as usize]
+    }
+
+    /// First block of the list `size` falls in that fits `size` bytes once aligned.
+    fn find_fitting(&self, size: u64, alignment: u64) -> Option<u32> {
+        let (fl, sl) = get_list(size);
+        let mut next = self.free_lists[fl][sl];
+        while let Some(index) = next {
+            let block = &self.blocks[index as usize];
+            if align(block.offset, alignment) + size <= block.offset + block.size {
+                return Some(index);
+            }
+            next = block.next_free;
+        }
+        None
+    }
+
+    fn insert_free(&mut self, index: u32) {
+        let (fl, sl) = get_list(self.blocks[index as usize].size);
+        let head = self.free_lists[fl][sl];
+        if let Some(head) = head {
+            self.blocks[head as usize].prev_free = Some(index);
+        }
+
+        let block = &mut self.blocks[index as usize];
+        block.prev_free = None;
+        block.next_free = head;
+        self.free_lists[fl][sl] = Some(index);
+        self.fl_bitmap |= 1 << fl;
+        self.sl_bitmaps[fl] |= 1 << sl;
+    }
+
+    fn remove_free(&mut self, index: u32) {
+        let Block {
+            size,
+            prev_free,
+            next_free,
+            ..
+        } = self.blocks[index as usize];
+
+        if let Some(next) = next_free {
+            self.blocks[next as usize].prev_free = prev_free;
+        }
+        match prev_free {
+            Some(prev) => self.blocks[prev as usize].next_free = next_free,
+            None => {
+                let (fl, sl) = get_list(size);
+                self.free_lists[fl][sl] = next_free;
+                if next_free.is_none() {
+                    self.sl_bitmaps[fl] &= !(1 << sl);
+                    if self.sl_bitmaps[fl] == 0 {
+                        self.fl_bitmap &= !(1 << fl);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// List holding free blocks of `size`.
+fn get_list(size: u64) -> (usize, usize) {
+    if size < SL_COUNT as u64 {
+        (0, size as usize)
+    } else {
+        let log2 = 63 - size.leading_zeros();
+        let sl = (size >> (log2 - SL_LOG2)) as usize - SL_COUNT;
+        ((log2 - SL_LOG2 + 1) as usize, sl)
+    }
+}
+
+/// First list whose blocks are all at least `size` bytes.
+fn get_search_list(size: u64) -> Option<(usize, usize)> {
+    if size < SL_COUNT as u64 {
+        return Some(get_list(size));
+    }
+    let log2 = 63 - size.leading_zeros();
+    let rounded = size.checked_add((1 << (log2 - SL_LOG2)) - 1)?;
+    Some(get_list(rounded))
+}
+
+fn align(offset: u64, alignment: u64) -> u64 {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_invariants(tlsf: &Tlsf, allocations: &[(BlockId, u64, u64)]) {
+        let mut ranges: Vec<(u64, u64)> = allocations
+            .iter()
+            .map(|&(_, offset, size)| (offset, offset + size))
+            .collect();
+        ranges.sort();
+        for pair in ranges.windows(2) {
+            assert!(pair[0].1 <= pair[1].0, "Allocations overlap: {:?}", pair);
+        }
+        assert!(ranges.last().is_none_or(|range| range.1 <= tlsf.size()));
+
+        let used_size: u64 = allocations.iter().map(|&(_, _, size)| size).sum();
+        assert_eq!(tlsf.used_size(), used_size);
+        assert_eq!(tlsf.allocation_count(), allocations.len());
+    }
+
+    #[test]
+    fn allocates_whole_heap() {
+        let mut tlsf = Tlsf::new(1024);
+        let (block, offset) = tlsf.allocate(1024, 1).unwrap();
+        assert_eq!(offset, 0);
+        assert_eq!(tlsf.largest_free_block(), 0);
+        assert!(tlsf.allocate(1, 1).is_none());
+
+        tlsf.free(block);
+        assert!(tlsf.is_empty());
+        assert_eq!(tlsf.largest_free_block(), 1024);
+    }
+
+    #[test]
+    fn aligns_offsets() {
+        let mut tlsf = Tlsf::new(1 << 20);
+        let mut allocations = Vec::new();
+        for &(size, alignment) in &[(3, 1), (100, 256), (7, 4096), (65536, 65536), (1, 16)] {
+            let (block, offset) = tlsf.allocate(size, alignment).unwrap();
+            assert_eq!(offset % alignment, 0);
+            allocations.push((block, offset, size));
+        }
+        check_invariants(&tlsf, &allocations);
+    }
+
+    #[test]
+    fn merges_freed_blocks() {
+        let mut tlsf = Tlsf::new(3000);
+        let blocks: Vec<BlockId> = (0..3).map(|_| tlsf.allocate(1000, 1).unwrap().0).collect();
+        assert!(tlsf.allocate(1, 1).is_none());
+
+        // The middle block merges with both of its neighbors once they're freed
+        tlsf.free(blocks[1]);
+        assert_eq!(tlsf.largest_free_block(), 1000);
+        tlsf.free(blocks[0]);
+        assert_eq!(tlsf.largest_free_block(), 2000);
+        tlsf.free(blocks[2]);
+        assert_eq!(tlsf.largest_free_block(), 3000);
+
+        let (_, offset) = tlsf.allocate(3000, 1).unwrap();
+        assert_eq!(offset, 0);
+    }
+
+    #[test]
+    fn reuses_freed_memory() {
+        let mut tlsf = Tlsf::new(1 << 16);
+        let (first, _) = tlsf.allocate(1 << 15, 1).unwrap();
+        let (_, second_offset) = tlsf.allocate(1 << 15, 1).unwrap();
+        assert!(tlsf.allocate(1 << 15, 1).is_none());
+
+        tlsf.free(first);
+        let (_, offset) = tlsf.allocate(1 << 15, 1).unwrap();
+        assert_ne!(offset, second_offset);
+    }
+
+    #[test]
+    #[should_panic(expected = "Block freed twice")]
+    fn panics_on_double_free() {
+        let mut tlsf = Tlsf::new(1024);
+        let (block, _) = tlsf.allocate(16, 1).unwrap();
+        tlsf.free(block);
+        tlsf.free(block);
+    }
+
+    #[test]
+    fn never_overlaps_allocations() {
+        // Deterministic pseudo-random sizes, alignments and frees
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next_random = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        let size = 1 << 24;
+        let mut tlsf = Tlsf::new(size);
+        let mut allocations: Vec<(BlockId, u64, u64)> = Vec::new();
+        for _ in 0..2000 {
+            if allocations.is_empty() || next_random() % 3 != 0 {
+                let alloc_size = 1 + next_random() % (1 << 16);
+                let alignment = 1 << (next_random() % 17);
+                if let Some((block, offset)) = tlsf.allocate(alloc_size, alignment) {
+                    assert_eq!(offset % alignment, 0);
+                    allocations.push((block, offset, alloc_size));
+                }
+            } else {
+                let index = (next_random() % allocations.len() as u64) as usize;
+                let (block, _, _) = allocations.swap_remove(index);
+                tlsf.free(block);
+            }
+            check_invariants(&tlsf, &allocations);
+        }
+
+        for (block, _, _) in allocations.drain(..) {
+            tlsf.free(block);
+        }
+        assert!(tlsf.is_empty());
+        assert_eq!(tlsf.largest_free_block(), size);
+    }
+}
@@ -1,17 +1,126 @@
 #[cfg(feature = "dx12")]
 extern crate graphix_native_dx12;
 
-#[cfg(feature = "dx12")]
-pub use crate::backend::dx12::{adapter, command, device, heap, instance, queue, resource, window};
+#[cfg(any(
+    feature = "dx12",
+    feature = "vulkan",
+    feature = "software",
+    feature = "null"
+))]
+pub mod any;
+
+#[cfg(any(feature = "null", feature = "software"))]
+mod sync;
+#[cfg(any(feature = "dx12", feature = "vulkan", feature = "software"))]
+mod tracker;
+#[cfg(any(feature = "null", feature = "software"))]
+mod validation;
 
 #[cfg(feature = "dx12")]
-mod dx12 {
+pub mod dx12 {
     pub mod adapter;
     pub mod command;
+    pub mod descriptor;
     pub mod device;
     pub mod heap;
     pub mod instance;
     pub mod queue;
     pub mod resource;
     pub mod window;
+
+    pub use self::adapter::PhysicalAdapter;
+    pub use self::command::{CommandBuffer, CommandPool};
+    pub use self::descriptor::{DescriptorSet, DescriptorSetLayout};
+    pub use self::device::Device;
+    pub use self::instance::{Backend, Instance};
+    pub use self::queue::CommandQueue;
+    pub use self::resource::{Buffer, BufferView, FrameBuffer, Heap, Image, RenderPass};
+    pub use self::window::{Surface, Swapchain};
 }
+
+#[cfg(feature = "null")]
+pub mod null {
+    pub mod adapter;
+    pub mod command;
+    pub mod device;
+    pub mod instance;
+    pub mod queue;
+    pub mod resource;
+    pub mod window;
+
+    pub use self::adapter::PhysicalAdapter;
+    pub use self::command::{CommandBuffer, CommandPool};
+    pub use self::device::Device;
+    pub use self::instance::{Backend, Instance};
+    pub use self::queue::CommandQueue;
+    pub use self::resource::{
+        Buffer, BufferView, DescriptorSet, DescriptorSetLayout, FrameBuffer, Heap, Image,
+        RenderPass,
+    };
+    pub use self::window::{Surface, Swapchain};
+
+    pub type Fence = crate::backend::sync::Fence<Backend>;
+}
+
+#[cfg(feature = "software")]
+pub mod software {
+    pub mod adapter;
+    pub mod command;
+    pub mod device;
+    pub mod instance;
+    pub mod queue;
+    pub mod resource;
+    pub mod window;
+
+    pub use self::adapter::PhysicalAdapter;
+    pub use self::command::{CommandBuffer, CommandPool};
+    pub use self::device::Device;
+    pub use self::instance::{Backend, Instance};
+    pub use self::queue::CommandQueue;
+    pub use self::resource::{
+        Buffer, BufferView, DescriptorSet, DescriptorSetLayout, FrameBuffer, Heap, Image,
+        RenderPass,
+    };
+    pub use self::window::{Surface, Swapchain};
+
+    pub type Fence = crate::backend::sync::Fence<Backend>;
+}
+
+#[cfg(feature = "vulkan")]
+pub mod vulkan {
+    pub mod adapter;
+    pub mod command;
+    pub mod descriptor;
+    pub mod device;
+    pub mod instance;
+    pub mod queue;
+    pub mod resource;
+    pub mod sync;
+    pub mod window;
+
+    pub use self::adapter::PhysicalAdapter;
+    pub use self::command::{CommandBuffer, CommandPool};
+    pub use self::descriptor::{DescriptorSet, DescriptorSetLayout};
+    pub use self::device::Device;
+    pub use self::instance::{Backend, Instance};
+    pub use self::queue::CommandQueue;
+    pub use self::resource::{Buffer, BufferView, FrameBuffer, Heap, Image, RenderPass};
+    pub use self::sync::Fence;
+    pub use self::window::{Surface, Swapchain};
+}
+
+// The default backend is the most capable one enabled: dx12, vulkan, software, then null
+#[cfg(feature = "dx12")]
+pub use self::dx12 as default;
+#[cfg(all(feature = "vulkan", not(feature = "dx12")))]
+pub use self::vulkan as default;
+#[cfg(all(
+    feature = "software",
+    not(any(feature = "dx12", feature = "vulkan"))
+))]
+pub use self::software as default;
+#[cfg(all(
+    feature = "null",
+    not(any(feature = "dx12", feature = "vulkan", feature = "software"))
+))]
+pub use self::null as default;
@@ -0,0 +1,690 @@
+//! A backend that forwards to one of the compiled-in backends, chosen at runtime.
+//!
+//! Every object is an enum with one variant per enabled backend feature. Objects created from
+//! an `Instance` always share its variant, so mixing objects from two instances is a bug and
+//! panics.
+
+#[cfg(feature = "dx12")]
+use crate::backend::dx12;
+#[cfg(feature = "null")]
+use crate::backend::null;
+#[cfg(feature = "software")]
+use crate::backend::software;
+#[cfg(feature = "vulkan")]
+use crate::backend::vulkan;
+use crate::hal;
+
+use std::fmt;
+
+/// Borrows the backend specific object wrapped by an `any` object.
+trait Downcast<T> {
+    fn downcast(&self) -> &T;
+}
+
+// Declares an `any` object wrapping the `hal::Backend` associated type of the same name
+macro_rules! any_type {
+    ($name:ident) => {
+        pub enum $name {
+            #[cfg(feature = "dx12")]
+            Dx12(<dx12::Backend as hal::Backend>::$name),
+            #[cfg(feature = "vulkan")]
+            Vulkan(<vulkan::Backend as hal::Backend>::$name),
+            #[cfg(feature = "software")]
+            Software(<software::Backend as hal::Backend>::$name),
+            #[cfg(feature = "null")]
+            Null(<null::Backend as hal::Backend>::$name),
+        }
+
+        any_type!(@variant $name, Dx12, dx12, "dx12");
+        any_type!(@variant $name, Vulkan, vulkan, "vulkan");
+        any_type!(@variant $name, Software, software, "software");
+        any_type!(@variant $name, Null, null, "null");
+    };
+    (@variant $name:ident, $variant:ident, $backend:ident, $feature:tt) => {
+        #[cfg(feature = $feature)]
+        impl From<<$backend::Backend as hal::Backend>::$name> for $name {
+            fn from(inner: <$backend::Backend as hal::Backend>::$name) -> Self {
+                $name::$variant(inner)
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl Downcast<<$backend::Backend as hal::Backend>::$name> for $name {
+            #[allow(unreachable_patterns)]
+            fn downcast(&self) -> &<$backend::Backend as hal::Backend>::$name {
+                match *self {
+                    $name::$variant(ref inner) => inner,
+                    _ => panic!(
+                        "{} belongs to a different backend than {}",
+                        stringify!($name),
+                        $feature
+                    ),
+                }
+            }
+        }
+    };
+}
+
+// Forwards an expression to whichever backend variant `$value` holds
+macro_rules! dispatch {
+    ($value:expr, $name:ident($($binding:tt)*) => $body:expr) => {
+        match $value {
+            #[cfg(feature = "dx12")]
+            $name::Dx12($($binding)*) => $body,
+            #[cfg(feature = "vulkan")]
+            $name::Vulkan($($binding)*) => $body,
+            #[cfg(feature = "software")]
+            $name::Software($($binding)*) => $body,
+            #[cfg(feature = "null")]
+            $name::Null($($binding)*) => $body,
+        }
+    };
+}
+
+fn downcast_barrier_resource<'a, B>(
+    resource: hal::BarrierResource<'a, Backend>,
+) -> hal::BarrierResource<'a, B>
+where
+    B: hal::Backend,
+    Buffer: Downcast<B::Buffer>,
+    Image: Downcast<B::Image>,
+{
+    match resource {
+        hal::BarrierResource::Buffer(buffer) => hal::BarrierResource::Buffer(buffer.downcast()),
+        hal::BarrierResource::Image(image) => hal::BarrierResource::Image(image.downcast()),
+    }
+}
+
+fn downcast_barriers<'a, B>(barriers: &[hal::Barrier<'a, Backend>]) -> Vec<hal::Barrier<'a, B>>
+where
+    B: hal::Backend,
+    Buffer: Downcast<B::Buffer>,
+    Image: Downcast<B::Image>,
+{
+    barriers
+        .iter()
+        .map(|barrier| match *barrier {
+            hal::Barrier::Buffer { buffer, state } => hal::Barrier::Buffer {
+                buffer: buffer.downcast(),
+                state,
+            },
+            hal::Barrier::Image {
+                image,
+                ref range,
+                state,
+            } => hal::Barrier::Image {
+                image: image.downcast(),
+                range: range.clone(),
+                state,
+            },
+            hal::Barrier::Storage(resource) => {
+                hal::Barrier::Storage(downcast_barrier_resource(resource))
+            }
+            hal::Barrier::Aliasing { before, after } => hal::Barrier::Aliasing {
+                before: before.map(downcast_barrier_resource),
+                after: after.map(downcast_barrier_resource),
+            },
+        })
+        .collect()
+}
+
+fn downcast_descriptors<'a, B>(
+    descriptors: &[hal::Descriptor<'a, Backend>],
+) -> Vec<hal::Descriptor<'a, B>>
+where
+    B: hal::Backend,
+    Buffer: Downcast<B::Buffer>,
+    Image: Downcast<B::Image>,
+{
+    descriptors
+        .iter()
+        .map(|descriptor| match *descriptor {
+            hal::Descriptor::Buffer(buffer) => hal::Descriptor::Buffer(buffer.downcast()),
+            hal::Descriptor::Image(image) => hal::Descriptor::Image(image.downcast()),
+        })
+        .collect()
+}
+
+pub enum Backend {}
+impl hal::Backend for Backend {
+    type PhysicalAdapter = PhysicalAdapter;
+    type Device = Device;
+
+    type CommandQueue = CommandQueue;
+    type CommandPool = CommandPool;
+    type CommandBuffer = CommandBuffer;
+
+    type Surface = Surface;
+    type Swapchain = Swapchain;
+
+    type RenderPass = RenderPass;
+    type FrameBuffer = FrameBuffer;
+    type Buffer = Buffer;
+    type Image = Image;
+    type Heap = Heap;
+    type DescriptorSetLayout = DescriptorSetLayout;
+    type DescriptorSet = DescriptorSet;
+    type PipelineLayout = PipelineLayout;
+
+    type Fence = Fence;
+}
+
+any_type!(PhysicalAdapter);
+any_type!(Device);
+any_type!(CommandQueue);
+any_type!(CommandPool);
+any_type!(CommandBuffer);
+any_type!(Surface);
+any_type!(Swapchain);
+any_type!(RenderPass);
+any_type!(FrameBuffer);
+any_type!(Buffer);
+any_type!(Image);
+any_type!(Heap);
+any_type!(DescriptorSetLayout);
+any_type!(DescriptorSet);
+any_type!(PipelineLayout);
+any_type!(Fence);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BackendType {
+    Dx12,
+    Vulkan,
+    Software,
+    Null,
+}
+
+impl BackendType {
+    /// All backend types, from the most to the least capable.
+    pub const ALL: [BackendType; 4] = [
+        BackendType::Dx12,
+        BackendType::Vulkan,
+        BackendType::Software,
+        BackendType::Null,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        BackendType::ALL
+            .iter()
+            .cloned()
+            .find(|backend_type| backend_type.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BackendType::Dx12 => "dx12",
+            BackendType::Vulkan => "vulkan",
+            BackendType::Software => "software",
+            BackendType::Null => "null",
+        }
+    }
+
+    /// Whether the backend was compiled in through its cargo feature.
+    pub fn is_enabled(self) -> bool {
+        match self {
+            BackendType::Dx12 => cfg!(feature = "dx12"),
+            BackendType::Vulkan => cfg!(feature = "vulkan"),
+            BackendType::Software => cfg!(feature = "software"),
+            BackendType::Null => cfg!(feature = "null"),
+        }
+    }
+
+    /// The compiled-in backends, from the most to the least capable.
+    pub fn enabled() -> Vec<BackendType> {
+        BackendType::ALL
+            .iter()
+            .cloned()
+            .filter(|backend_type| backend_type.is_enabled())
+            .collect()
+    }
+}
+
+impl fmt::Display for BackendType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+pub enum Instance {
+    #[cfg(feature = "dx12")]
+    Dx12(dx12::Instance),
+    #[cfg(feature = "vulkan")]
+    Vulkan(vulkan::Instance),
+    #[cfg(feature = "software")]
+    Software(software::Instance),
+    #[cfg(feature = "null")]
+    Null(null::Instance),
+}
+
+impl Instance {
+    /// Creates an instance of the given backend, or `None` if it is not compiled in or its
+    /// runtime is not available.
+    pub fn new(backend_type: BackendType) -> Option<Self> {
+        match backend_type {
+            #[cfg(feature = "dx12")]
+            BackendType::Dx12 => dx12::Instance::try_new().map(Instance::Dx12),
+            #[cfg(feature = "vulkan")]
+            BackendType::Vulkan => vulkan::Instance::try_new().map(Instance::Vulkan),
+            #[cfg(feature = "software")]
+            BackendType::Software => Some(Instance::Software(software::Instance::new())),
+            #[cfg(feature = "null")]
+            BackendType::Null => Some(Instance::Null(null::Instance::new())),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// Creates an instance of the backend with the given name, e.g. `"vulkan"`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        BackendType::from_name(name).and_then(Instance::new)
+    }
+
+    /// Creates an instance of the first backend in `preference` that is available and has at
+    /// least one adapter.
+    pub fn with_preference(preference: &[BackendType]) -> Option<Self> {
+        preference
+            .iter()
+            .filter_map(|&backend_type| Instance::new(backend_type))
+            .find(|instance| !hal::Instance::enumerate_adapters(instance).is_empty())
+    }
+
+    pub fn backend_type(&self) -> BackendType {
+        match *self {
+            #[cfg(feature = "dx12")]
+            Instance::Dx12(_) => BackendType::Dx12,
+            #[cfg(feature = "vulkan")]
+            Instance::Vulkan(_) => BackendType::Vulkan,
+            #[cfg(feature = "software")]
+            Instance::Software(_) => BackendType::Software,
+            #[cfg(feature = "null")]
+            Instance::Null(_) => BackendType::Null,
+        }
+    }
+
+    /// Creates a surface with no window behind it, if the backend supports them.
+    pub fn create_surface_headless(&self) -> Option<Surface> {
+        match *self {
+            #[cfg(feature = "dx12")]
+            Instance::Dx12(_) => None,
+            #[cfg(feature = "vulkan")]
+            Instance::Vulkan(ref instance) => Some(instance.create_surface_headless().into()),
+            #[cfg(feature = "software")]
+            Instance::Software(ref instance) => Some(instance.create_surface_headless().into()),
+            #[cfg(feature = "null")]
+            Instance::Null(ref instance) => Some(instance.create_surface_headless().into()),
+        }
+    }
+
+    /// Creates a surface presenting to `window`, if the backend supports windows on this
+    /// platform.
+    #[cfg(feature = "winit")]
+    pub fn create_surface(&self, window: &winit::Window) -> Option<Surface> {
+        match *self {
+            #[cfg(feature = "dx12")]
+            Instance::Dx12(ref instance) => Some(instance.create_surface(window).into()),
+            #[cfg(all(feature = "vulkan", unix))]
+            Instance::Vulkan(ref instance) => Some(instance.create_surface(window).into()),
+            // Vulkan window surfaces are only supported on Xlib
+            #[cfg(all(feature = "vulkan", not(unix)))]
+            Instance::Vulkan(_) => None,
+            #[cfg(feature = "software")]
+            Instance::Software(ref instance) => Some(instance.create_surface(window).into()),
+            #[cfg(feature = "null")]
+            Instance::Null(ref instance) => Some(instance.create_surface(window).into()),
+        }
+    }
+}
+
+impl hal::Instance for Instance {
+    type Backend = Backend;
+
+    fn enumerate_adapters(&self) -> Vec<hal::Adapter<Backend>> {
+        dispatch!(*self, Instance(ref instance) => instance
+            .enumerate_adapters()
+            .into_iter()
+            .map(|adapter| hal::Adapter {
+                adapter: adapter.adapter.into(),
+                info: adapter.info,
+            })
+            .collect())
+    }
+
+    fn select_adapter(&self, preference: &hal::AdapterPreference) -> Option<hal::Adapter<Backend>> {
+        dispatch!(*self, Instance(ref instance) => {
+            instance.select_adapter(preference).map(|adapter| hal::Adapter {
+                adapter: adapter.adapter.into(),
+                info: adapter.info,
+            })
+        })
+    }
+}
+
+impl hal::PhysicalAdapter<Backend> for PhysicalAdapter {
+    fn create_device(&self) -> hal::Result<Device> {
+        dispatch!(*self, PhysicalAdapter(ref adapter) => adapter.create_device().map(Into::into))
+    }
+}
+
+impl hal::Device<Backend> for Device {
+    fn create_command_queue(&self, queue_type: hal::QueueType) -> hal::Result<CommandQueue> {
+        dispatch!(*self, Device(ref device) => {
+            device.create_command_queue(queue_type).map(Into::into)
+        })
+    }
+
+    fn create_command_pool(
+        &self,
+        pool_type: hal::QueueType,
+        flags: hal::CommandPoolFlags,
+    ) -> hal::Result<CommandPool> {
+        dispatch!(*self, Device(ref device) => {
+            device.create_command_pool(pool_type, flags).map(Into::into)
+        })
+    }
+
+    fn create_buffer(
+        &self,
+        size: u64,
+        usage: hal::BufferUsage,
+        memory_type: hal::MemoryType,
+    ) -> hal::Result<Buffer> {
+        dispatch!(*self, Device(ref device) => {
+            device.create_buffer(size, usage, memory_type).map(Into::into)
+        })
+    }
+
+    fn map_buffer(&self, buffer: &Buffer) -> hal::Result<*mut u8> {
+        dispatch!(*self, Device(ref device) => device.map_buffer(buffer.downcast()))
+    }
+
+    fn unmap_buffer(&self, buffer: &Buffer) {
+        dispatch!(*self, Device(ref device) => device.unmap_buffer(buffer.downcast()))
+    }
+
+    fn limits(&self) -> hal::Limits {
+        dispatch!(*self, Device(ref device) => device.limits())
+    }
+
+    fn features(&self) -> hal::Features {
+        dispatch!(*self, Device(ref device) => device.features())
+    }
+
+    fn format_properties(&self, format: hal::Format) -> hal::FormatProperties {
+        dispatch!(*self, Device(ref device) => device.format_properties(format))
+    }
+
+    fn create_image(&self, desc: hal::ImageDesc) -> hal::Result<Image> {
+        dispatch!(*self, Device(ref device) => device.create_image(desc).map(Into::into))
+    }
+
+    fn create_heap(
+        &self,
+        size: u64,
+        memory_type: hal::MemoryType,
+        usage: hal::HeapUsage,
+    ) -> hal::Result<Heap> {
+        dispatch!(*self, Device(ref device) => {
+            device.create_heap(size, memory_type, usage).map(Into::into)
+        })
+    }
+
+    fn get_buffer_requirements(
+        &self,
+        size: u64,
+        usage: hal::BufferUsage,
+    ) -> hal::Result<hal::MemoryRequirements> {
+        dispatch!(*self, Device(ref device) => device.get_buffer_requirements(size, usage))
+    }
+
+    fn get_image_requirements(
+        &self,
+        desc: &hal::ImageDesc,
+    ) -> hal::Result<hal::MemoryRequirements> {
+        dispatch!(*self, Device(ref device) => device.get_image_requirements(desc))
+    }
+
+    fn create_placed_buffer(
+        &self,
+        heap: &Heap,
+        offset: u64,
+        size: u64,
+        usage: hal::BufferUsage,
+    ) -> hal::Result<Buffer> {
+        dispatch!(*self, Device(ref device) => {
+            device
+                .create_placed_buffer(heap.downcast(), offset, size, usage)
+                .map(Into::into)
+        })
+    }
+
+    fn create_placed_image(
+        &self,
+        heap: &Heap,
+        offset: u64,
+        desc: hal::ImageDesc,
+    ) -> hal::Result<Image> {
+        dispatch!(*self, Device(ref device) => {
+            device
+                .create_placed_image(heap.downcast(), offset, desc)
+                .map(Into::into)
+        })
+    }
+
+    fn create_render_pass(&self, desc: &hal::RenderPassDesc) -> hal::Result<RenderPass> {
+        dispatch!(*self, Device(ref device) => device.create_render_pass(desc).map(Into::into))
+    }
+
+    fn create_framebuffer(&self, attachments: &[&Image]) -> hal::Result<FrameBuffer> {
+        dispatch!(*self, Device(ref device) => {
+            let attachments = attachments
+                .iter()
+                .map(|&image| image.downcast())
+                .collect::<Vec<_>>();
+            device.create_framebuffer(&attachments).map(Into::into)
+        })
+    }
+
+    fn create_descriptor_set_layout(
+        &self,
+        desc: &hal::DescriptorSetLayoutDesc,
+    ) -> hal::Result<DescriptorSetLayout> {
+        dispatch!(*self, Device(ref device) => {
+            device.create_descriptor_set_layout(desc).map(Into::into)
+        })
+    }
+
+    fn create_descriptor_set(&self, layout: &DescriptorSetLayout) -> hal::Result<DescriptorSet> {
+        dispatch!(*self, Device(ref device) => {
+            device.create_descriptor_set(layout.downcast()).map(Into::into)
+        })
+    }
+
+    fn write_descriptors(&self, writes: &[hal::DescriptorWrite<Backend>]) -> hal::Result<()> {
+        dispatch!(*self, Device(ref device) => {
+            // Writes borrow their descriptors, so those are downcast first
+            let descriptors = writes
+                .iter()
+                .map(|write| downcast_descriptors(write.descriptors))
+                .collect::<Vec<_>>();
+            let writes = writes
+                .iter()
+                .zip(descriptors.iter())
+                .map(|(write, descriptors)| hal::DescriptorWrite {
+                    set: write.set.downcast(),
+                    binding: write.binding,
+                    array_element: write.array_element,
+                    descriptors,
+                })
+                .collect::<Vec<_>>();
+            device.write_descriptors(&writes)
+        })
+    }
+
+    fn create_pipeline_layout(
+        &self,
+        set_layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[hal::PushConstantRange],
+    ) -> hal::Result<PipelineLayout> {
+        dispatch!(*self, Device(ref device) => {
+            let set_layouts = set_layouts
+                .iter()
+                .map(|&set_layout| set_layout.downcast())
+                .collect::<Vec<_>>();
+            device
+                .create_pipeline_layout(&set_layouts, push_constant_ranges)
+                .map(Into::into)
+        })
+    }
+
+    fn create_fence(&self, initial_value: u64) -> hal::Result<Fence> {
+        dispatch!(*self, Device(ref device) => device.create_fence(initial_value).map(Into::into))
+    }
+
+    fn reset_fence(&self, fence: &Fence) -> hal::Result<()> {
+        dispatch!(*self, Device(ref device) => device.reset_fence(fence.downcast()))
+    }
+
+    fn wait_for_fence_with_timeout(
+        &self,
+        fence: &Fence,
+        value: u64,
+        timeout: u64,
+    ) -> hal::Result<()> {
+        dispatch!(*self, Device(ref device) => {
+            device.wait_for_fence_with_timeout(fence.downcast(), value, timeout)
+        })
+    }
+
+    fn device_lost_reason(&self) -> Option<hal::DeviceLostReason> {
+        dispatch!(*self, Device(ref device) => device.device_lost_reason())
+    }
+}
+
+impl hal::CommandQueue<Backend> for CommandQueue {
+    fn submit(&self, command_buffers: Vec<&CommandBuffer>) -> hal::Result<()> {
+        dispatch!(*self, CommandQueue(ref queue) => {
+            queue.submit(command_buffers.into_iter().map(Downcast::downcast).collect())
+        })
+    }
+
+    fn signal_fence(&self, fence: &Fence, value: u64) -> hal::Result<()> {
+        dispatch!(*self, CommandQueue(ref queue) => queue.signal_fence(fence.downcast(), value))
+    }
+}
+
+impl hal::CommandPool<Backend> for CommandPool {
+    fn reset(&self) -> hal::Result<()> {
+        dispatch!(*self, CommandPool(ref pool) => pool.reset())
+    }
+
+    fn create_buffer(&mut self) -> hal::Result<CommandBuffer> {
+        dispatch!(*self, CommandPool(ref mut pool) => pool.create_buffer().map(Into::into))
+    }
+}
+
+impl hal::CommandBuffer<Backend> for CommandBuffer {
+    fn begin(&self) -> hal::Result<()> {
+        dispatch!(*self, CommandBuffer(ref command_buffer) => command_buffer.begin())
+    }
+
+    fn end(&self) -> hal::Result<()> {
+        dispatch!(*self, CommandBuffer(ref command_buffer) => command_buffer.end())
+    }
+
+    fn insert_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        dispatch!(*self, CommandBuffer(ref command_buffer) => {
+            command_buffer.insert_barriers(&downcast_barriers(barriers))
+        })
+    }
+
+    fn begin_split_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        dispatch!(*self, CommandBuffer(ref command_buffer) => {
+            command_buffer.begin_split_barriers(&downcast_barriers(barriers))
+        })
+    }
+
+    fn end_split_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        dispatch!(*self, CommandBuffer(ref command_buffer) => {
+            command_buffer.end_split_barriers(&downcast_barriers(barriers))
+        })
+    }
+
+    fn begin_render_pass(
+        &self,
+        render_pass: &RenderPass,
+        framebuffer: &FrameBuffer,
+        clear_values: &[hal::ClearColor],
+    ) {
+        dispatch!(*self, CommandBuffer(ref command_buffer) => {
+            command_buffer.begin_render_pass(
+                render_pass.downcast(),
+                framebuffer.downcast(),
+                clear_values,
+            )
+        })
+    }
+
+    fn end_render_pass(&self) {
+        dispatch!(*self, CommandBuffer(ref command_buffer) => command_buffer.end_render_pass())
+    }
+
+    fn bind_descriptor_sets(
+        &self,
+        bind_point: hal::PipelineBindPoint,
+        layout: &PipelineLayout,
+        first_set: u32,
+        sets: &[&DescriptorSet],
+    ) {
+        dispatch!(*self, CommandBuffer(ref command_buffer) => {
+            let sets = sets.iter().map(|&set| set.downcast()).collect::<Vec<_>>();
+            command_buffer.bind_descriptor_sets(bind_point, layout.downcast(), first_set, &sets)
+        })
+    }
+
+    fn push_constants(
+        &self,
+        bind_point: hal::PipelineBindPoint,
+        layout: &PipelineLayout,
+        stages: hal::ShaderStageFlags,
+        offset: u32,
+        data: &[u8],
+    ) {
+        dispatch!(*self, CommandBuffer(ref command_buffer) => {
+            command_buffer.push_constants(bind_point, layout.downcast(), stages, offset, data)
+        })
+    }
+}
+
+impl hal::Surface<Backend> for Surface {
+    fn create_swapchain(
+        &self,
+        device: &Device,
+        command_queue: &CommandQueue,
+        config: hal::SwapchainConfig,
+    ) -> hal::Result<Swapchain> {
+        dispatch!(*self, Surface(ref surface) => surface
+            .create_swapchain(device.downcast(), command_queue.downcast(), config)
+            .map(Into::into))
+    }
+}
+
+impl hal::Swapchain<Backend> for Swapchain {
+    fn acquire_buffer(&self) -> hal::Result<hal::SwapchainBufferIndex> {
+        dispatch!(*self, Swapchain(ref swapchain) => swapchain.acquire_buffer())
+    }
+
+    fn present(&self) -> hal::Result<()> {
+        dispatch!(*self, Swapchain(ref swapchain) => swapchain.present())
+    }
+
+    fn create_backbuffer(&self) -> hal::BackBuffer<Backend> {
+        dispatch!(*self, Swapchain(ref swapchain) => hal::BackBuffer {
+            framebuffers: swapchain
+                .create_backbuffer()
+                .framebuffers
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        })
+    }
+}
@@ -9,7 +9,7 @@ pub struct PhysicalAdapter {
 }
 
 impl hal::PhysicalAdapter<Backend> for PhysicalAdapter {
-    fn create_device(&self) -> Device {
+    fn create_device(&self) -> hal::Result<Device> {
         Device::new(self)
     }
 }
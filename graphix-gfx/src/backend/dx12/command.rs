@@ -1,11 +1,18 @@
-use crate::backend::dx12::device::Device;
+use crate::backend::dx12::descriptor::{DescriptorSet, PipelineLayout};
+use crate::backend::dx12::device::{get_hal_error, Device};
+use crate::backend::dx12::heap::{LinearDescriptorAllocator, ShaderVisibleHeap};
 use crate::backend::dx12::instance::Backend;
 use crate::backend::dx12::queue;
-use crate::backend::dx12::resource::FrameBuffer;
+use crate::backend::dx12::resource::{BufferView, FrameBuffer, RenderPass};
+use crate::backend::dx12::window;
+use crate::backend::tracker::{StateTracker, SubmittedState, Transition};
 use crate::hal;
 
 use graphix_native_dx12 as native;
 
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
 pub enum CommandPoolAllocator {
     Shared(native::command_allocator::CommandAllocator), // 1 command allocator to many command lists
     Multiple(Vec<native::command_allocator::CommandAllocator>), // 1 command allocator to 1 command lists
@@ -13,6 +20,7 @@ pub enum CommandPoolAllocator {
 
 pub struct CommandPool {
     device: native::device::Device,
+    shader_visible: Rc<RefCell<ShaderVisibleHeap>>,
     pool_allocator: CommandPoolAllocator,
     single_command_list: Option<native::command_list::GraphicsCommandList>,
     pool_type: native::command_list::CommandListType,
@@ -24,7 +32,7 @@ impl CommandPool {
         device: &Device,
         pool_type: hal::QueueType,
         flags: hal::CommandPoolFlags,
-    ) -> Self {
+    ) -> hal::Result<Self> {
         let pool_type = queue::get_native_type(pool_type);
 
         let pool_allocator = if flags.contains(hal::CommandPoolFlags::MULTIPLE_ALLOCATOR) {
@@ -32,57 +40,53 @@ impl CommandPool {
         } else {
             let command_allocator =
                 native::command_allocator::CommandAllocator::new(&device.native, pool_type)
-                    .expect("Failed to create a D3D12 command allocator");
+                    .map_err(get_hal_error)?;
 
             CommandPoolAllocator::Shared(command_allocator)
         };
 
-        CommandPool {
+        Ok(CommandPool {
             device: device.native.clone(),
+            shader_visible: device.shader_visible.clone(),
             pool_allocator,
             single_command_list: None,
             pool_type,
             flags,
-        }
+        })
     }
 
     fn create_command_list(
         device: &native::device::Device,
         allocator: &native::command_allocator::CommandAllocator,
         list_type: native::command_list::CommandListType,
-    ) -> native::command_list::GraphicsCommandList {
+    ) -> hal::Result<native::command_list::GraphicsCommandList> {
         let command_list =
             native::command_list::GraphicsCommandList::new(&device, &allocator, list_type)
-                .expect("Failed to create a D3D12 graphics command list");
+                .map_err(get_hal_error)?;
 
         // Close command list as they're initialised as recording, but only one can be recording for each allocator
-        command_list
-            .close()
-            .expect("Failed to close D3D12 graphics command list");
+        command_list.close().map_err(get_hal_error)?;
 
-        command_list
+        Ok(command_list)
     }
 }
 
 impl hal::CommandPool<Backend> for CommandPool {
-    fn reset(&self) {
+    fn reset(&self) -> hal::Result<()> {
         match self.pool_allocator {
             CommandPoolAllocator::Shared(ref allocator) => {
-                allocator
-                    .reset()
-                    .expect("Failed to reset D3D12 command allocator");
+                allocator.reset().map_err(get_hal_error)?;
             }
             CommandPoolAllocator::Multiple(ref allocators) => {
                 for allocator in allocators.iter() {
-                    allocator
-                        .reset()
-                        .expect("Failed to reset D3D12 command allocator");
+                    allocator.reset().map_err(get_hal_error)?;
                 }
             }
         }
+        Ok(())
     }
 
-    fn create_buffer(&mut self) -> CommandBuffer {
+    fn create_buffer(&mut self) -> hal::Result<CommandBuffer> {
         let (command_allocator, command_list) = match self.pool_allocator {
             CommandPoolAllocator::Shared(ref allocator) => {
                 let command_allocator = allocator.clone();
@@ -90,14 +94,14 @@ impl hal::CommandPool<Backend> for CommandPool {
                     &self.device,
                     &command_allocator,
                     self.pool_type,
-                );
+                )?;
 
                 (command_allocator, command_list)
             }
             CommandPoolAllocator::Multiple(ref mut allocators) => {
                 let command_allocator =
                     native::command_allocator::CommandAllocator::new(&self.device, self.pool_type)
-                        .expect("Failed to create D3D12 command allocator");
+                        .map_err(get_hal_error)?;
 
                 let command_list = if self.flags.contains(hal::CommandPoolFlags::SINGLE_LIST) {
                     match self.single_command_list {
@@ -107,7 +111,7 @@ impl hal::CommandPool<Backend> for CommandPool {
                                 &self.device,
                                 &command_allocator,
                                 self.pool_type,
-                            );
+                            )?;
                             self.single_command_list = Some(single_command_list);
                             (*self.single_command_list.as_ref().unwrap()).clone()
                         }
@@ -117,7 +121,7 @@ impl hal::CommandPool<Backend> for CommandPool {
                         &self.device,
                         &command_allocator,
                         self.pool_type,
-                    )
+                    )?
                 };
 
                 allocators.push(command_allocator.clone());
@@ -125,105 +129,618 @@ impl hal::CommandPool<Backend> for CommandPool {
             }
         };
 
-        CommandBuffer::new(command_allocator, command_list, self.flags)
+        Ok(CommandBuffer::new(
+            &self.device,
+            command_allocator,
+            command_list,
+            LinearDescriptorAllocator::new(&self.device, self.shader_visible.clone()),
+            self.pool_type,
+            self.flags,
+        ))
     }
 }
 
 pub struct CommandBuffer {
+    device: native::device::Device,
     pub(crate) command_allocator: native::command_allocator::CommandAllocator,
     pub(crate) graphics_command_list: native::command_list::GraphicsCommandList,
+    // Records native render passes, which tiling GPUs make use of, when the runtime supports them
+    render_pass_list: Option<native::command_list::GraphicsCommandList4>,
+    list_type: native::command_list::CommandListType,
     flags: hal::CommandPoolFlags,
+    pub(crate) tracker: RefCell<StateTracker<native::resource::Resource, hal::ResourceState>>,
+    // Records the transitions patched in at submit, with an allocator of its own as the one of
+    // the command buffer may be shared with lists still recording
+    patch: RefCell<
+        Option<(
+            native::command_allocator::CommandAllocator,
+            native::command_list::GraphicsCommandList,
+        )>,
+    >,
+    // Attachments of the current render pass and the states they end it in
+    pass_final_states: RefCell<Vec<(BufferView, hal::ResourceState)>>,
+    // Root signatures and tables bound for graphics and compute pipelines
+    bindings: [RefCell<Bindings>; 2],
+    // Tables of the sets bound, freed once the command buffer is recorded again
+    transient_descriptors: RefCell<LinearDescriptorAllocator>,
+    // First error binding sets ran into, returned by `end`
+    error: Cell<Option<hal::Error>>,
+}
+
+#[derive(Default)]
+struct Bindings {
+    root_signature: Option<native::root_signature::RootSignature>,
+    // Tables of the sets bound, `None` for sets without descriptors
+    tables: Vec<Option<native::heap::GPUDescriptor>>,
 }
 
 impl CommandBuffer {
     pub(crate) fn new(
+        device: &native::device::Device,
         command_allocator: native::command_allocator::CommandAllocator,
         graphics_command_list: native::command_list::GraphicsCommandList,
+        transient_descriptors: LinearDescriptorAllocator,
+        list_type: native::command_list::CommandListType,
         flags: hal::CommandPoolFlags,
     ) -> Self {
+        let render_pass_list = graphics_command_list.as_graphics_command_list4();
         CommandBuffer {
+            device: device.clone(),
             command_allocator,
             graphics_command_list,
+            render_pass_list,
+            list_type,
             flags,
+            tracker: RefCell::new(StateTracker::new()),
+            patch: RefCell::new(None),
+            pass_final_states: RefCell::new(Vec::new()),
+            bindings: Default::default(),
+            transient_descriptors: RefCell::new(transient_descriptors),
+            error: Cell::new(None),
         }
     }
 
+    /// Records the transitions bringing resources from their submitted states to the ones this
+    /// command buffer starts with, returning `None` when there are none.
+    pub(crate) fn record_patch(&self) -> hal::Result<Option<native::command_list::CommandList>> {
+        let transitions = self.tracker.borrow().get_patch_transitions();
+        if transitions.is_empty() {
+            return Ok(None);
+        }
+
+        let mut patch = self.patch.borrow_mut();
+        if patch.is_none() {
+            let allocator =
+                native::command_allocator::CommandAllocator::new(&self.device, self.list_type)
+                    .map_err(get_hal_error)?;
+            let command_list =
+                CommandPool::create_command_list(&self.device, &allocator, self.list_type)?;
+            *patch = Some((allocator, command_list));
+        }
+        let (ref allocator, ref command_list) = *patch.as_ref().unwrap();
+
+        command_list.reset(allocator).map_err(get_hal_error)?;
+        insert_transition_barriers(
+            command_list,
+            &transitions,
+            native::barrier::BarrierFlags::NONE,
+        );
+        command_list.close().map_err(get_hal_error)?;
+
+        Ok(Some(command_list.as_command_list()))
+    }
+
+    /// Moves `subresources` of `resource` to `state`, returning the transitions to record.
+    fn transition(
+        &self,
+        resource: &native::resource::Resource,
+        submitted_state: &SubmittedState<hal::ResourceState>,
+        subresources: Option<&[u32]>,
+        state: hal::ResourceState,
+    ) -> Vec<Transition<native::resource::Resource, hal::ResourceState>> {
+        debug_assert!(state.is_valid(), "Invalid resource state {:?}", state);
+        self.tracker
+            .borrow_mut()
+            .transition(resource, submitted_state, subresources, state)
+    }
+
     pub(crate) fn as_command_list(&self) -> native::command_list::CommandList {
         self.graphics_command_list.as_command_list()
     }
 
-    fn reset(&self) {
+    fn reset(&self) -> hal::Result<()> {
         self.graphics_command_list
             .reset(&self.command_allocator)
-            .expect("Failed to reset command list");
+            .map_err(get_hal_error)
+    }
+
+    /// Sets the root signature of `layout` unless it's already set, which unbinds all tables,
+    /// so the tables of the sets it has are bound again.
+    fn set_pipeline_layout(
+        &self,
+        bind_point: hal::PipelineBindPoint,
+        layout: &PipelineLayout,
+        bindings: &mut Bindings,
+    ) {
+        if bindings.root_signature.as_ref() == Some(&layout.root_signature) {
+            return;
+        }
+
+        match bind_point {
+            hal::PipelineBindPoint::Graphics => self
+                .graphics_command_list
+                .set_graphics_root_signature(&layout.root_signature),
+            hal::PipelineBindPoint::Compute => self
+                .graphics_command_list
+                .set_compute_root_signature(&layout.root_signature),
+        }
+        bindings.root_signature = Some(layout.root_signature.clone());
+        bindings.tables.truncate(layout.set_parameters.len());
+        for (&parameter, &table) in layout.set_parameters.iter().zip(&bindings.tables) {
+            if let (Some(parameter), Some(table)) = (parameter, table) {
+                self.set_root_descriptor_table(bind_point, parameter, table);
+            }
+        }
+    }
+
+    fn set_root_descriptor_table(
+        &self,
+        bind_point: hal::PipelineBindPoint,
+        parameter: u32,
+        table: native::heap::GPUDescriptor,
+    ) {
+        match bind_point {
+            hal::PipelineBindPoint::Graphics => self
+                .graphics_command_list
+                .set_graphics_root_descriptor_table(parameter, table),
+            hal::PipelineBindPoint::Compute => self
+                .graphics_command_list
+                .set_compute_root_descriptor_table(parameter, table),
+        }
     }
 }
 
 impl hal::CommandBuffer<Backend> for CommandBuffer {
-    fn begin(&self) {
+    fn begin(&self) -> hal::Result<()> {
         if self
             .flags
             .contains(hal::CommandPoolFlags::MULTIPLE_ALLOCATOR)
         {
-            self.command_allocator
-                .reset()
-                .expect("Failed to reset command allocator");
+            self.command_allocator.reset().map_err(get_hal_error)?;
+        }
+        // Patches of previous submissions are done once the command buffer is recorded again
+        if let Some((ref allocator, _)) = *self.patch.borrow() {
+            allocator.reset().map_err(get_hal_error)?;
         }
+        // As are the tables of the sets it bound
+        self.transient_descriptors.borrow_mut().reset();
+        self.error.set(None);
+        self.tracker.borrow_mut().reset();
+        for bindings in &self.bindings {
+            *bindings.borrow_mut() = Bindings::default();
+        }
+
+        self.reset()
+    }
 
-        self.reset();
+    fn end(&self) -> hal::Result<()> {
+        debug_assert!(
+            !self.tracker.borrow().has_split_transitions(),
+            "Command buffer ended with split barriers that were not ended"
+        );
+        self.graphics_command_list.close().map_err(get_hal_error)?;
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
     }
 
-    fn end(&self) {
+    fn insert_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        let mut transitions = Vec::new();
+        let mut uav_resources = Vec::new();
+        let mut aliasing_barriers = Vec::new();
+        let mut aliased_resources = Vec::new();
+        for barrier in barriers {
+            match *barrier {
+                hal::Barrier::Buffer { .. } | hal::Barrier::Image { .. } => {
+                    if let Some((resource, submitted_state, subresources, state)) =
+                        get_transition(barrier)
+                    {
+                        transitions.extend(self.transition(
+                            resource,
+                            submitted_state,
+                            subresources.as_deref(),
+                            state,
+                        ));
+                    }
+                }
+                hal::Barrier::Storage(resource) => {
+                    uav_resources.push(get_barrier_resource(resource).clone());
+                }
+                hal::Barrier::Aliasing { before, after } => {
+                    let mut get_index = |resource: Option<hal::BarrierResource<Backend>>| {
+                        resource.map(|resource| {
+                            aliased_resources.push(get_barrier_resource(resource).clone());
+                            aliased_resources.len() - 1
+                        })
+                    };
+                    let before = get_index(before);
+                    let after = get_index(after);
+                    aliasing_barriers.push(native::barrier::AliasingBarrierDesc { before, after });
+                }
+            }
+        }
+
+        // The memory has to be handed over before resources placed in it get transitioned
         self.graphics_command_list
-            .close()
-            .expect("Failed to close D3D12 command list");
+            .insert_aliasing_barriers(&aliasing_barriers, &aliased_resources);
+        self.graphics_command_list
+            .insert_uav_barriers(&uav_resources);
+        insert_transition_barriers(
+            &self.graphics_command_list,
+            &transitions,
+            native::barrier::BarrierFlags::NONE,
+        );
+    }
+
+    fn begin_split_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        let mut tracker = self.tracker.borrow_mut();
+        let transitions = barriers
+            .iter()
+            .filter_map(get_transition)
+            .flat_map(|(resource, submitted_state, subresources, state)| {
+                debug_assert!(state.is_valid(), "Invalid resource state {:?}", state);
+                tracker.begin_split_transition(
+                    resource,
+                    submitted_state,
+                    subresources.as_deref(),
+                    state,
+                )
+            })
+            .collect::<Vec<_>>();
+        insert_transition_barriers(
+            &self.graphics_command_list,
+            &transitions,
+            native::barrier::BarrierFlags::BEGIN_ONLY,
+        );
+    }
+
+    fn end_split_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        let mut tracker = self.tracker.borrow_mut();
+        let transitions = barriers
+            .iter()
+            .filter_map(get_transition)
+            .flat_map(|(_, submitted_state, subresources, state)| {
+                tracker.end_split_transition(submitted_state, subresources.as_deref(), state)
+            })
+            .collect::<Vec<_>>();
+        insert_transition_barriers(
+            &self.graphics_command_list,
+            &transitions,
+            native::barrier::BarrierFlags::END_ONLY,
+        );
     }
 
-    fn insert_barriers(
+    fn begin_render_pass(
         &self,
-        barrier_point: hal::BarrierPoint,
-        attachments: &[hal::Attachment],
+        render_pass: &RenderPass,
         framebuffer: &FrameBuffer,
+        clear_values: &[hal::ClearColor],
     ) {
-        let barriers = attachments
+        let mut pass_final_states = self.pass_final_states.borrow_mut();
+        pass_final_states.clear();
+        let mut transitions = Vec::new();
+        for (view, attachment) in framebuffer
+            .attachments
             .iter()
-            .enumerate()
-            .map(|(attachment_id, attachment)| {
-                let initial_state = match barrier_point {
-                    hal::BarrierPoint::Pre => get_native_resource_state(attachment.states.start),
-                    hal::BarrierPoint::Post => get_native_resource_state(attachment.states.end),
-                };
-                let target_state = match barrier_point {
-                    hal::BarrierPoint::Pre => get_native_resource_state(attachment.states.end),
-                    hal::BarrierPoint::Post => get_native_resource_state(attachment.states.start),
-                };
-                native::barrier::BarrierDesc::new(attachment_id, initial_state..target_state)
-            })
-            .collect::<Vec<_>>();
+            .zip(render_pass.attachments.iter())
+        {
+            // Attachments used for the first time get patched to render targets at submit
+            transitions.extend(self.transition(
+                &view.resource,
+                &view.state,
+                None,
+                hal::ResourceState::RENDER_TARGET,
+            ));
+            pass_final_states.push((view.clone(), attachment.final_state));
+        }
+        insert_transition_barriers(
+            &self.graphics_command_list,
+            &transitions,
+            native::barrier::BarrierFlags::NONE,
+        );
 
-        let resources = framebuffer
+        let rtvs = framebuffer
             .attachments
             .iter()
-            .map(|view| view.resource.clone())
+            .map(|view| view.rtv_handle)
             .collect::<Vec<_>>();
 
-        self.graphics_command_list
-            .insert_transition_barriers(&barriers, &resources);
+        match self.render_pass_list {
+            Some(ref render_pass_list) => {
+                let render_targets = render_pass
+                    .attachments
+                    .iter()
+                    .zip(rtvs.iter())
+                    .enumerate()
+                    .map(|(index, (attachment, &rtv))| {
+                        native::command_list::RenderPassRenderTarget {
+                            descriptor: rtv,
+                            beginning_access: get_native_beginning_access(
+                                attachment,
+                                clear_values.get(index),
+                            ),
+                            ending_access: get_native_ending_access(attachment.store_op),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                render_pass_list.begin_render_pass(&render_targets);
+            }
+            None => {
+                for (index, (attachment, &rtv)) in
+                    render_pass.attachments.iter().zip(rtvs.iter()).enumerate()
+                {
+                    if attachment.load_op == hal::LoadOp::Clear {
+                        self.graphics_command_list
+                            .clear_render_target_view(rtv, clear_values[index]);
+                    }
+                }
+                self.graphics_command_list.set_render_targets(&rtvs);
+            }
+        }
+    }
+
+    fn end_render_pass(&self) {
+        if let Some(ref render_pass_list) = self.render_pass_list {
+            render_pass_list.end_render_pass();
+        }
+
+        let transitions = self
+            .pass_final_states
+            .borrow_mut()
+            .drain(..)
+            .flat_map(|(view, final_state)| {
+                self.transition(&view.resource, &view.state, None, final_state)
+            })
+            .collect::<Vec<_>>();
+        insert_transition_barriers(
+            &self.graphics_command_list,
+            &transitions,
+            native::barrier::BarrierFlags::NONE,
+        );
+    }
+
+    fn bind_descriptor_sets(
+        &self,
+        bind_point: hal::PipelineBindPoint,
+        layout: &PipelineLayout,
+        first_set: u32,
+        sets: &[&DescriptorSet],
+    ) {
+        let mut transient_descriptors = self.transient_descriptors.borrow_mut();
+        let tables: Vec<_> = sets
+            .iter()
+            .map(|set| {
+                set.get_table(&mut transient_descriptors)
+                    .unwrap_or_else(|err| {
+                        // The set is left unbound, the error being reported when the command
+                        // buffer ends
+                        if self.error.get().is_none() {
+                            self.error.set(Some(err));
+                        }
+                        None
+                    })
+            })
+            .collect();
+        // Tables with descriptors all live in the shader-visible heap of the device
+        if tables.iter().any(Option::is_some) {
+            let heap = transient_descriptors.heap().unwrap();
+            self.graphics_command_list.set_descriptor_heaps(&[heap]);
+        }
+
+        let mut bindings = self.bindings[bind_point as usize].borrow_mut();
+        self.set_pipeline_layout(bind_point, layout, &mut bindings);
+        // Set indices skipped over get no table
+        bindings.tables.resize(first_set as usize, None);
+        for table in tables {
+            let parameter = layout.set_parameters[bindings.tables.len()];
+            if let (Some(parameter), Some(table)) = (parameter, table) {
+                self.set_root_descriptor_table(bind_point, parameter, table);
+            }
+            bindings.tables.push(table);
+        }
     }
 
-    fn clear(&self, clear_colors: &[hal::ClearColor], framebuffer: &FrameBuffer) {
-        for (view, clear_color) in framebuffer.attachments.iter().zip(clear_colors.iter()) {
-            self.graphics_command_list
-                .clear_render_target_view(view.rtv_handle.unwrap(), *clear_color);
+    fn push_constants(
+        &self,
+        bind_point: hal::PipelineBindPoint,
+        layout: &PipelineLayout,
+        _stages: hal::ShaderStageFlags,
+        offset: u32,
+        data: &[u8],
+    ) {
+        let mut bindings = self.bindings[bind_point as usize].borrow_mut();
+        self.set_pipeline_layout(bind_point, layout, &mut bindings);
+        // All the push constants are one parameter, visible to every stage of the ranges
+        let parameter = layout
+            .push_constants_parameter
+            .expect("Push constants set with a pipeline layout without push constants");
+        match bind_point {
+            hal::PipelineBindPoint::Graphics => self
+                .graphics_command_list
+                .set_graphics_root_32bit_constants(parameter, data, offset / 4),
+            hal::PipelineBindPoint::Compute => self
+                .graphics_command_list
+                .set_compute_root_32bit_constants(parameter, data, offset / 4),
         }
     }
 }
 
-fn get_native_resource_state(
-    attachment_mode: hal::AttachmentMode,
+fn get_barrier_resource<'a>(
+    resource: hal::BarrierResource<'a, Backend>,
+) -> &'a native::resource::Resource {
+    match resource {
+        hal::BarrierResource::Buffer(buffer) => &buffer.resource,
+        hal::BarrierResource::Image(image) => &image.resource,
+    }
+}
+
+/// Resource, submitted state, subresources and target state of a transition, `None` for host
+/// visible buffers which stay in the state of their heap.
+fn get_transition<'a>(
+    barrier: &hal::Barrier<'a, Backend>,
+) -> Option<(
+    &'a native::resource::Resource,
+    &'a SubmittedState<hal::ResourceState>,
+    Option<Vec<u32>>,
+    hal::ResourceState,
+)> {
+    match *barrier {
+        hal::Barrier::Buffer { buffer, state } => buffer
+            .state
+            .as_ref()
+            .map(|submitted_state| (&buffer.resource, submitted_state, None, state)),
+        hal::Barrier::Image {
+            image,
+            ref range,
+            state,
+        } => {
+            let subresources = range
+                .as_ref()
+                .map(|range| image.desc().get_subresource_indices(range));
+            Some((&image.resource, &image.state, subresources, state))
+        }
+        _ => panic!("Only buffer and image transitions can be split"),
+    }
+}
+
+fn insert_transition_barriers(
+    command_list: &native::command_list::GraphicsCommandList,
+    transitions: &[Transition<native::resource::Resource, hal::ResourceState>],
+    flags: native::barrier::BarrierFlags,
+) {
+    // Several states share a native one, e.g. `PRESENT` and `COMMON`, which needs no barrier
+    let transitions = transitions
+        .iter()
+        .map(|transition| {
+            let states = &transition.states;
+            let states =
+                get_native_resource_state(states.start)..get_native_resource_state(states.end);
+            (transition, states)
+        })
+        .filter(|(_, states)| states.start != states.end)
+        .collect::<Vec<_>>();
+    if transitions.is_empty() {
+        return;
+    }
+
+    let barriers = transitions
+        .iter()
+        .enumerate()
+        .map(
+            |(index, (transition, states))| native::barrier::BarrierDesc {
+                flags,
+                subresource: transition
+                    .subresource
+                    .unwrap_or(native::barrier::ALL_SUBRESOURCES),
+                ..native::barrier::BarrierDesc::new(index, states.clone())
+            },
+        )
+        .collect::<Vec<_>>();
+    let resources = transitions
+        .iter()
+        .map(|(transition, _)| transition.resource.clone())
+        .collect::<Vec<_>>();
+
+    command_list.insert_transition_barriers(&barriers, &resources);
+}
+
+fn get_native_beginning_access(
+    attachment: &hal::AttachmentDesc,
+    clear_value: Option<&hal::ClearColor>,
+) -> native::command_list::BeginningAccess {
+    match attachment.load_op {
+        hal::LoadOp::Load => native::command_list::BeginningAccess::Preserve,
+        hal::LoadOp::Clear => native::command_list::BeginningAccess::Clear {
+            format: window::get_native_format(attachment.format),
+            color: *clear_value.expect("Missing clear value for a cleared attachment"),
+        },
+        hal::LoadOp::DontCare => native::command_list::BeginningAccess::Discard,
+    }
+}
+
+fn get_native_ending_access(store_op: hal::StoreOp) -> native::command_list::EndingAccess {
+    match store_op {
+        hal::StoreOp::Store => native::command_list::EndingAccess::Preserve,
+        hal::StoreOp::DontCare => native::command_list::EndingAccess::Discard,
+    }
+}
+
+pub(crate) fn get_native_resource_state(
+    state: hal::ResourceState,
 ) -> native::resource::ResourceState {
-    match attachment_mode {
-        hal::AttachmentMode::Present => native::resource::ResourceState::PRESENT,
-        hal::AttachmentMode::RenderTarget => native::resource::ResourceState::RENDER_TARGET,
+    let mut native_state = native::resource::ResourceState::COMMON;
+    for &(flag, native_flag) in &[
+        (
+            hal::ResourceState::VERTEX_BUFFER | hal::ResourceState::CONSTANT_BUFFER,
+            native::resource::ResourceState::VERTEX_AND_CONSTANT_BUFFER,
+        ),
+        (
+            hal::ResourceState::INDEX_BUFFER,
+            native::resource::ResourceState::INDEX_BUFFER,
+        ),
+        (
+            hal::ResourceState::INDIRECT_ARGUMENT,
+            native::resource::ResourceState::INDIRECT_ARGUMENT,
+        ),
+        (
+            hal::ResourceState::NON_PIXEL_SHADER_READ,
+            native::resource::ResourceState::NON_PIXEL_SHADER_RESOURCE,
+        ),
+        (
+            hal::ResourceState::PIXEL_SHADER_READ,
+            native::resource::ResourceState::PIXEL_SHADER_RESOURCE,
+        ),
+        (
+            hal::ResourceState::DEPTH_READ,
+            native::resource::ResourceState::DEPTH_READ,
+        ),
+        (
+            hal::ResourceState::COPY_SRC,
+            native::resource::ResourceState::COPY_SOURCE,
+        ),
+        (
+            hal::ResourceState::RESOLVE_SRC,
+            native::resource::ResourceState::RESOLVE_SOURCE,
+        ),
+        (
+            hal::ResourceState::RENDER_TARGET,
+            native::resource::ResourceState::RENDER_TARGET,
+        ),
+        (
+            hal::ResourceState::DEPTH_WRITE,
+            native::resource::ResourceState::DEPTH_WRITE,
+        ),
+        (
+            hal::ResourceState::STORAGE,
+            native::resource::ResourceState::UNORDERED_ACCESS,
+        ),
+        (
+            hal::ResourceState::COPY_DST,
+            native::resource::ResourceState::COPY_DEST,
+        ),
+        (
+            hal::ResourceState::RESOLVE_DST,
+            native::resource::ResourceState::RESOLVE_DEST,
+        ),
+        (
+            hal::ResourceState::PRESENT,
+            native::resource::ResourceState::PRESENT,
+        ),
+    ] {
+        if state.intersects(flag) {
+            native_state |= native_flag;
+        }
     }
+    native_state
 }
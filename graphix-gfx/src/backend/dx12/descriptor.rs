@@ -0,0 +1,428 @@
+use crate::allocator::tlsf::BlockId;
+use crate::backend::dx12::device::{get_hal_error, Device};
+use crate::backend::dx12::heap::{
+    DescriptorHeap, DescriptorHeapHandle, LinearDescriptorAllocator, ShaderVisibleHeap,
+};
+use crate::backend::dx12::instance::Backend;
+use crate::backend::dx12::resource::{Buffer, Image};
+use crate::backend::dx12::window;
+use crate::hal;
+
+use graphix_native_dx12 as native;
+
+use winapi::um::d3d12;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+// Past the register spaces of the sets, as there are at most 32 of them
+const PUSH_CONSTANTS_REGISTER_SPACE: u32 = 32;
+
+type PipelineLayoutDesc = (
+    Vec<hal::DescriptorSetLayoutDesc>,
+    Vec<hal::PushConstantRange>,
+);
+
+/// Root signatures of pipeline layouts, created once per description so that identically
+/// defined layouts share theirs.
+#[derive(Default)]
+pub(crate) struct RootSignatureCache {
+    signatures: RefCell<HashMap<PipelineLayoutDesc, native::root_signature::RootSignature>>,
+}
+
+impl RootSignatureCache {
+    pub(crate) fn get(
+        &self,
+        device: &native::device::Device,
+        desc: PipelineLayoutDesc,
+    ) -> hal::Result<native::root_signature::RootSignature> {
+        if let Some(root_signature) = self.signatures.borrow().get(&desc) {
+            return Ok(root_signature.clone());
+        }
+
+        let (ref set_layouts, ref push_constant_ranges) = desc;
+        let push_constants = get_push_constants_count(push_constant_ranges).map(|count| {
+            native::root_signature::RootParameter::Constants {
+                count,
+                register: 0,
+                register_space: PUSH_CONSTANTS_REGISTER_SPACE,
+                visibility: get_native_visibility(
+                    push_constant_ranges
+                        .iter()
+                        .fold(hal::ShaderStageFlags::empty(), |stages, range| {
+                            stages | range.stages
+                        }),
+                ),
+            }
+        });
+        // Sets are register spaces, so that bindings of different sets don't collide
+        let tables = set_layouts
+            .iter()
+            .enumerate()
+            .filter(|(_, desc)| desc.get_descriptor_count() > 0)
+            .map(
+                |(set, desc)| native::root_signature::RootParameter::DescriptorTable {
+                    ranges: desc
+                        .bindings
+                        .iter()
+                        .map(|binding| native::root_signature::DescriptorRange {
+                            ty: get_native_range_type(binding.ty),
+                            count: binding.count,
+                            base_register: binding.binding,
+                            register_space: set as _,
+                            offset_in_table: get_binding_offset(desc, binding.binding),
+                        })
+                        .collect(),
+                    visibility: get_native_visibility(desc.get_stages()),
+                },
+            );
+        let parameters = push_constants.into_iter().chain(tables).collect();
+        let root_signature = device
+            .create_root_signature(&native::root_signature::RootSignatureDesc { parameters })
+            .map_err(get_hal_error)?;
+
+        self.signatures
+            .borrow_mut()
+            .insert(desc, root_signature.clone());
+        Ok(root_signature)
+    }
+}
+
+/// A root signature holding the push constants as its first parameter, if there are any,
+/// followed by a descriptor table per set with descriptors.
+pub struct PipelineLayout {
+    pub(crate) root_signature: native::root_signature::RootSignature,
+    // Root parameter of the table of each set, `None` for sets without descriptors
+    pub(crate) set_parameters: Vec<Option<u32>>,
+    pub(crate) push_constants_parameter: Option<u32>,
+}
+
+impl PipelineLayout {
+    pub(crate) fn new(
+        device: &Device,
+        set_layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[hal::PushConstantRange],
+    ) -> hal::Result<Self> {
+        let desc = (
+            set_layouts
+                .iter()
+                .map(|set_layout| set_layout.desc.clone())
+                .collect::<Vec<_>>(),
+            push_constant_ranges.to_vec(),
+        );
+        let push_constants_parameter = get_push_constants_count(push_constant_ranges).map(|_| 0);
+        let mut next_parameter = push_constants_parameter.map_or(0, |_| 1);
+        let set_parameters = desc
+            .0
+            .iter()
+            .map(|set_layout| {
+                if set_layout.get_descriptor_count() > 0 {
+                    next_parameter += 1;
+                    Some(next_parameter - 1)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        Ok(PipelineLayout {
+            root_signature: device.root_signatures.get(&device.native, desc)?,
+            set_parameters,
+            push_constants_parameter,
+        })
+    }
+}
+
+pub struct DescriptorSetLayout {
+    pub(crate) desc: hal::DescriptorSetLayoutDesc,
+}
+
+/// Where the descriptors of a set are written.
+enum SetDescriptors {
+    // A table in the shader-visible heap, its range freed once dropped. Shaders read the
+    // descriptors where they're written, so that `update_after_bind` sets see writes once bound.
+    ShaderVisible {
+        shader_visible: Rc<RefCell<ShaderVisibleHeap>>,
+        block: BlockId,
+        start: DescriptorHeapHandle,
+    },
+    // A heap shaders can't see, copied to the shader-visible heap each time the set is bound, so
+    // that sets can be written for the next frame while the GPU still reads the previous one.
+    Staging(DescriptorHeap),
+}
+
+/// A descriptor table, copied to the shader-visible heap of the device when bound unless it's
+/// `update_after_bind`.
+pub struct DescriptorSet {
+    device: native::device::Device,
+    // Sets without descriptors have none to write
+    descriptors: Option<SetDescriptors>,
+    handle_size: u64,
+    pub(crate) layout: hal::DescriptorSetLayoutDesc,
+}
+
+impl DescriptorSet {
+    pub(crate) fn new(device: &Device, layout: &DescriptorSetLayout) -> hal::Result<Self> {
+        let heap_type = native::heap::DescriptorHeapType::CbvSrvUav;
+        let count = layout.desc.get_descriptor_count() as usize;
+        let descriptors = if count == 0 {
+            None
+        } else if layout.desc.update_after_bind {
+            let (block, start) = device
+                .shader_visible
+                .borrow_mut()
+                .allocate(&device.native, count)?;
+            Some(SetDescriptors::ShaderVisible {
+                shader_visible: device.shader_visible.clone(),
+                block,
+                start,
+            })
+        } else {
+            Some(SetDescriptors::Staging(DescriptorHeap::new(
+                device, heap_type, count,
+            )?))
+        };
+
+        Ok(DescriptorSet {
+            device: device.native.clone(),
+            descriptors,
+            handle_size: u64::from(device.native.get_descriptor_increment_size(heap_type)),
+            layout: layout.desc.clone(),
+        })
+    }
+
+    /// Returns the table shaders read the descriptors from, copying them to descriptors allocated
+    /// from `transient_descriptors` unless the set is `update_after_bind`. `None` for sets
+    /// without descriptors.
+    pub(crate) fn get_table(
+        &self,
+        transient_descriptors: &mut LinearDescriptorAllocator,
+    ) -> hal::Result<Option<native::heap::GPUDescriptor>> {
+        match self.descriptors {
+            None => Ok(None),
+            Some(SetDescriptors::ShaderVisible { start, .. }) => Ok(Some(start.gpu)),
+            Some(SetDescriptors::Staging(ref heap)) => {
+                let count = self.layout.get_descriptor_count();
+                let table = transient_descriptors.allocate(&self.device, count)?;
+                self.device.copy_descriptors_simple(
+                    count,
+                    table.cpu,
+                    heap.start.cpu,
+                    native::heap::DescriptorHeapType::CbvSrvUav,
+                );
+                Ok(Some(table.gpu))
+            }
+        }
+    }
+
+    pub(crate) fn write(&self, write: &hal::DescriptorWrite<Backend>) {
+        let binding = self.check_write(write);
+        let first_index = get_binding_offset(&self.layout, write.binding) + write.array_element;
+
+        for (index, descriptor) in write.descriptors.iter().enumerate() {
+            let handle = self.get_cpu_handle(first_index + index as u32);
+            match *descriptor {
+                hal::Descriptor::Buffer(buffer) => self.write_buffer(binding.ty, buffer, handle),
+                hal::Descriptor::Image(image) => self.write_image(binding.ty, image, handle),
+            }
+        }
+    }
+
+    fn check_write(&self, write: &hal::DescriptorWrite<Backend>) -> &hal::DescriptorBinding {
+        let binding = self.layout.get_binding(write.binding).unwrap_or_else(|| {
+            panic!(
+                "Descriptors written to binding {} missing from the set layout",
+                write.binding
+            )
+        });
+        assert!(
+            write.array_element + write.descriptors.len() as u32 <= binding.count,
+            "{} descriptors written from element {} of a binding of {}",
+            write.descriptors.len(),
+            write.array_element,
+            binding.count
+        );
+        for descriptor in write.descriptors {
+            match *descriptor {
+                hal::Descriptor::Buffer(buffer) => assert!(
+                    binding.ty.is_buffer()
+                        && buffer.usage().contains(binding.ty.get_buffer_usage()),
+                    "Buffer of {:?} written to a {:?} binding",
+                    buffer.usage(),
+                    binding.ty
+                ),
+                hal::Descriptor::Image(image) => assert!(
+                    !binding.ty.is_buffer()
+                        && image.desc().usage.contains(binding.ty.get_image_usage()),
+                    "Image of {:?} written to a {:?} binding",
+                    image.desc().usage,
+                    binding.ty
+                ),
+            }
+        }
+        binding
+    }
+
+    fn get_cpu_handle(&self, index: u32) -> native::heap::CPUDescriptor {
+        let start = match self.descriptors {
+            Some(SetDescriptors::ShaderVisible { start, .. }) => start,
+            Some(SetDescriptors::Staging(ref heap)) => heap.start,
+            None => panic!("Descriptors written to a set without descriptors"),
+        };
+        start.offset(u64::from(index), self.handle_size).cpu
+    }
+
+    fn write_buffer(
+        &self,
+        ty: hal::DescriptorType,
+        buffer: &Buffer,
+        handle: native::heap::CPUDescriptor,
+    ) {
+        match ty {
+            hal::DescriptorType::UniformBuffer => {
+                // Constant buffers are padded to the alignment views need, and views see at
+                // most the elements a constant buffer can hold
+                let alignment = u64::from(d3d12::D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT);
+                let max_size = u64::from(d3d12::D3D12_REQ_CONSTANT_BUFFER_ELEMENT_COUNT) * 16;
+                let size = ((buffer.size() + alignment - 1) & !(alignment - 1)).min(max_size);
+                let desc = native::heap::ConstantBufferViewDesc::new(
+                    buffer.resource.get_gpu_virtual_address(),
+                    size as _,
+                );
+                self.device.create_constant_buffer_view(&desc, handle);
+            }
+            hal::DescriptorType::StorageBuffer => {
+                let desc = native::heap::UnorderedAccessViewDesc::raw_buffer(buffer.size());
+                self.device
+                    .create_unordered_access_view(&buffer.resource, &desc, handle);
+            }
+            hal::DescriptorType::SampledImage | hal::DescriptorType::StorageImage => {
+                panic!("Buffer written to a {:?} binding", ty)
+            }
+        }
+    }
+
+    fn write_image(
+        &self,
+        ty: hal::DescriptorType,
+        image: &Image,
+        handle: native::heap::CPUDescriptor,
+    ) {
+        let desc = image.desc();
+        let format = window::get_native_format(desc.format);
+        let is_layered = desc.array_layers > 1;
+
+        match ty {
+            hal::DescriptorType::SampledImage => {
+                let (dimension, array_size) = match desc.kind {
+                    hal::ImageKind::D1 if is_layered => (
+                        native::heap::SrvDimension::Texture1DArray,
+                        desc.array_layers,
+                    ),
+                    hal::ImageKind::D1 => (native::heap::SrvDimension::Texture1D, 1),
+                    hal::ImageKind::D2 if is_layered => (
+                        native::heap::SrvDimension::Texture2DArray,
+                        desc.array_layers,
+                    ),
+                    hal::ImageKind::D2 => (native::heap::SrvDimension::Texture2D, 1),
+                    hal::ImageKind::D3 => (native::heap::SrvDimension::Texture3D, 1),
+                    hal::ImageKind::Cube if desc.array_layers > 6 => (
+                        native::heap::SrvDimension::TextureCubeArray,
+                        desc.array_layers / 6,
+                    ),
+                    hal::ImageKind::Cube => (native::heap::SrvDimension::TextureCube, 1),
+                };
+                let view_desc = native::heap::ShaderResourceViewDesc::texture(
+                    format,
+                    dimension,
+                    desc.mip_levels,
+                    array_size,
+                );
+                self.device
+                    .create_shader_resource_view(&image.resource, &view_desc, handle);
+            }
+            hal::DescriptorType::StorageImage => {
+                // Cube images are written as arrays of their faces
+                let (dimension, array_size) = match desc.kind {
+                    hal::ImageKind::D1 if is_layered => (
+                        native::heap::UavDimension::Texture1DArray,
+                        desc.array_layers,
+                    ),
+                    hal::ImageKind::D1 => (native::heap::UavDimension::Texture1D, 1),
+                    hal::ImageKind::D2 | hal::ImageKind::Cube if is_layered => (
+                        native::heap::UavDimension::Texture2DArray,
+                        desc.array_layers,
+                    ),
+                    hal::ImageKind::D2 | hal::ImageKind::Cube => {
+                        (native::heap::UavDimension::Texture2D, 1)
+                    }
+                    hal::ImageKind::D3 => {
+                        (native::heap::UavDimension::Texture3D, desc.extent.depth)
+                    }
+                };
+                let view_desc =
+                    native::heap::UnorderedAccessViewDesc::texture(format, dimension, array_size);
+                self.device
+                    .create_unordered_access_view(&image.resource, &view_desc, handle);
+            }
+            hal::DescriptorType::UniformBuffer | hal::DescriptorType::StorageBuffer => {
+                panic!("Image written to a {:?} binding", ty)
+            }
+        }
+    }
+}
+
+impl Drop for DescriptorSet {
+    fn drop(&mut self) {
+        if let Some(SetDescriptors::ShaderVisible {
+            ref shader_visible,
+            block,
+            ..
+        }) = self.descriptors
+        {
+            shader_visible.borrow_mut().free(block);
+        }
+    }
+}
+
+/// Index in the table of a set of the first descriptor of `binding`, tables holding the
+/// descriptors of the bindings in the order the layout lists them.
+pub(crate) fn get_binding_offset(desc: &hal::DescriptorSetLayoutDesc, binding: u32) -> u32 {
+    desc.bindings
+        .iter()
+        .take_while(|desc_binding| desc_binding.binding != binding)
+        .map(|desc_binding| desc_binding.count)
+        .sum()
+}
+
+/// Number of 32-bit root constants holding the push constants, `None` if there are none.
+fn get_push_constants_count(push_constant_ranges: &[hal::PushConstantRange]) -> Option<u32> {
+    match hal::PushConstantRange::get_total_size(push_constant_ranges) {
+        0 => None,
+        size => Some(size / 4),
+    }
+}
+
+fn get_native_range_type(ty: hal::DescriptorType) -> native::root_signature::DescriptorRangeType {
+    match ty {
+        hal::DescriptorType::UniformBuffer => native::root_signature::DescriptorRangeType::Cbv,
+        hal::DescriptorType::SampledImage => native::root_signature::DescriptorRangeType::Srv,
+        hal::DescriptorType::StorageBuffer | hal::DescriptorType::StorageImage => {
+            native::root_signature::DescriptorRangeType::Uav
+        }
+    }
+}
+
+fn get_native_visibility(
+    stages: hal::ShaderStageFlags,
+) -> native::root_signature::ShaderVisibility {
+    // Compute shaders only see parameters visible to all stages
+    if stages == hal::ShaderStageFlags::VERTEX {
+        native::root_signature::ShaderVisibility::Vertex
+    } else if stages == hal::ShaderStageFlags::FRAGMENT {
+        native::root_signature::ShaderVisibility::Pixel
+    } else {
+        native::root_signature::ShaderVisibility::All
+    }
+}
@@ -1,7 +1,13 @@
 use crate::backend::dx12::adapter::PhysicalAdapter;
 use crate::backend::dx12::command::CommandPool;
+use crate::backend::dx12::descriptor::{
+    DescriptorSet, DescriptorSetLayout, PipelineLayout, RootSignatureCache,
+};
+use crate::backend::dx12::heap::{DescriptorAllocator, ShaderVisibleHeap};
 use crate::backend::dx12::instance::Backend;
 use crate::backend::dx12::queue::CommandQueue;
+use crate::backend::dx12::resource::{self, Buffer, FrameBuffer, Heap, Image, RenderPass};
+use crate::backend::dx12::window;
 use crate::hal;
 
 use graphix_native_dx12 as native;
@@ -11,21 +17,29 @@ use winapi::um::unknwnbase::IUnknown;
 use winapi::um::{d3d12, d3d12sdklayers, d3dcommon};
 use winapi::Interface;
 
+use std::cell::RefCell;
 use std::mem;
 use std::ptr;
+use std::rc::Rc;
 
 pub struct Device {
     pub(crate) native: native::device::Device,
-    feature_level: d3dcommon::D3D_FEATURE_LEVEL,
+    // Shared with the objects holding descriptors, which free them once dropped
+    pub(crate) descriptors: Rc<RefCell<DescriptorAllocator>>,
+    // Shared with the descriptor sets, which free their tables once dropped
+    pub(crate) shader_visible: Rc<RefCell<ShaderVisibleHeap>>,
+    pub(crate) root_signatures: RootSignatureCache,
+    limits: hal::Limits,
+    features: hal::Features,
 }
 
 impl Device {
-    pub(crate) fn new(adapter: &PhysicalAdapter) -> Self {
+    pub(crate) fn new(adapter: &PhysicalAdapter) -> hal::Result<Self> {
         Device::setup_debug_layer();
 
         let device =
             native::device::Device::new(&adapter.native, native::device::FeatureLevel::L11_0)
-                .expect("Failed to create D3D12 device.");
+                .map_err(get_hal_error)?;
 
         // Check actual feature level obtained
         let min_feature_level = d3dcommon::D3D_FEATURE_LEVEL_11_0;
@@ -50,14 +64,122 @@ impl Device {
             Err(_) => min_feature_level,
         };
 
+        let mut options: d3d12::D3D12_FEATURE_DATA_D3D12_OPTIONS = unsafe { mem::zeroed() };
+        device
+            .check_feature_support(
+                native::device::Feature::Options,
+                &mut options as *mut _ as *mut _,
+                mem::size_of::<d3d12::D3D12_FEATURE_DATA_D3D12_OPTIONS>() as _,
+            )
+            .map_err(get_hal_error)?;
+
+        let limits = Device::get_limits(&device, feature_level, &options);
+        let features = Device::get_features(&options);
+
         Device::setup_debug_settings(&device);
 
-        Device {
+        Ok(Device {
             native: device,
-            feature_level,
+            descriptors: Rc::new(RefCell::new(DescriptorAllocator::new())),
+            shader_visible: Rc::new(RefCell::new(ShaderVisibleHeap::new(
+                limits.max_descriptors as _,
+            ))),
+            root_signatures: RootSignatureCache::default(),
+            limits,
+            features,
+        })
+    }
+
+    fn get_limits(
+        device: &native::device::Device,
+        feature_level: d3dcommon::D3D_FEATURE_LEVEL,
+        options: &d3d12::D3D12_FEATURE_DATA_D3D12_OPTIONS,
+    ) -> hal::Limits {
+        // Sample counts are queried on a format every device can render to and multisample
+        let sample_counts = [1, 2, 4, 8, 16, 32]
+            .iter()
+            .filter(|&&sample_count| {
+                let mut quality_levels = d3d12::D3D12_FEATURE_DATA_MULTISAMPLE_QUALITY_LEVELS {
+                    Format: native::dxgi::Format::R8G8B8A8_UNORM as _,
+                    SampleCount: sample_count,
+                    Flags: d3d12::D3D12_MULTISAMPLE_QUALITY_LEVELS_FLAG_NONE,
+                    NumQualityLevels: 0,
+                };
+                let result = device.check_feature_support(
+                    native::device::Feature::MultisampleQualityLevels,
+                    &mut quality_levels as *mut _ as *mut _,
+                    mem::size_of::<d3d12::D3D12_FEATURE_DATA_MULTISAMPLE_QUALITY_LEVELS>() as _,
+                );
+                result.is_ok() && quality_levels.NumQualityLevels > 0
+            })
+            .fold(0, |sample_counts, &sample_count| {
+                sample_counts | sample_count
+            });
+
+        hal::Limits {
+            feature_level: match feature_level {
+                d3dcommon::D3D_FEATURE_LEVEL_12_1 => hal::FeatureLevel::L12_1,
+                d3dcommon::D3D_FEATURE_LEVEL_12_0 => hal::FeatureLevel::L12_0,
+                d3dcommon::D3D_FEATURE_LEVEL_11_1 => hal::FeatureLevel::L11_1,
+                _ => hal::FeatureLevel::L11_0,
+            },
+            // Texture sizes are fixed for all feature levels from 11_0 on
+            max_image_dimension_1d: d3d12::D3D12_REQ_TEXTURE1D_U_DIMENSION,
+            max_image_dimension_2d: d3d12::D3D12_REQ_TEXTURE2D_U_OR_V_DIMENSION,
+            max_image_dimension_3d: d3d12::D3D12_REQ_TEXTURE3D_U_V_OR_W_DIMENSION,
+            max_image_dimension_cube: d3d12::D3D12_REQ_TEXTURECUBE_DIMENSION,
+            max_image_array_layers: d3d12::D3D12_REQ_TEXTURE2D_ARRAY_AXIS_DIMENSION,
+            max_color_attachments: d3d12::D3D12_SIMULTANEOUS_RENDER_TARGET_COUNT,
+            max_descriptors: d3d12::D3D12_MAX_SHADER_VISIBLE_DESCRIPTOR_HEAP_SIZE_TIER_1,
+            max_samplers: d3d12::D3D12_MAX_SHADER_VISIBLE_SAMPLER_HEAP_SIZE,
+            // Each set takes one of the 64 DWORDs of a root signature as a descriptor table,
+            // leaving the others for root constants
+            max_descriptor_sets: 32,
+            max_bindless_descriptors: if options.ResourceBindingTier
+                >= d3d12::D3D12_RESOURCE_BINDING_TIER_3
+            {
+                d3d12::D3D12_MAX_SHADER_VISIBLE_DESCRIPTOR_HEAP_SIZE_TIER_1
+            } else {
+                0
+            },
+            // Root constants take the 32 DWORDs of a root signature sets don't
+            max_push_constants_size: 32 * 4,
+            sample_counts,
+            min_constant_buffer_offset_alignment: u64::from(
+                d3d12::D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT,
+            ),
         }
     }
 
+    fn get_features(options: &d3d12::D3D12_FEATURE_DATA_D3D12_OPTIONS) -> hal::Features {
+        // Anisotropic filtering and BC formats are required from feature level 11_0 on
+        let mut features =
+            hal::Features::SAMPLER_ANISOTROPY | hal::Features::TEXTURE_COMPRESSION_BC;
+        if options.TiledResourcesTier != d3d12::D3D12_TILED_RESOURCES_TIER_NOT_SUPPORTED {
+            features |= hal::Features::TILED_RESOURCES;
+        }
+        if options.ConservativeRasterizationTier
+            != d3d12::D3D12_CONSERVATIVE_RASTERIZATION_TIER_NOT_SUPPORTED
+        {
+            features |= hal::Features::CONSERVATIVE_RASTERIZATION;
+        }
+        if options.DoublePrecisionFloatShaderOps != 0 {
+            features |= hal::Features::SHADER_FLOAT64;
+        }
+        if options.ROVsSupported != 0 {
+            features |= hal::Features::RASTERIZER_ORDERED_VIEWS;
+        }
+        if options.ResourceHeapTier >= d3d12::D3D12_RESOURCE_HEAP_TIER_2 {
+            features |= hal::Features::MIXED_RESOURCE_HEAPS;
+        }
+        // Lower tiers cap the descriptors of each type a table can hold well below the heap size,
+        // and need all the descriptors of bound tables written
+        if options.ResourceBindingTier >= d3d12::D3D12_RESOURCE_BINDING_TIER_3 {
+            features |= hal::Features::BINDLESS;
+        }
+        features
+    }
+
     fn setup_debug_layer() {
         #[cfg(debug_assertions)]
         {
@@ -113,7 +235,7 @@ impl Drop for Device {
 }
 
 impl hal::Device<Backend> for Device {
-    fn create_command_queue(&self, queue_type: hal::QueueType) -> CommandQueue {
+    fn create_command_queue(&self, queue_type: hal::QueueType) -> hal::Result<CommandQueue> {
         CommandQueue::new(self, queue_type)
     }
 
@@ -121,17 +243,174 @@ impl hal::Device<Backend> for Device {
         &self,
         pool_type: hal::QueueType,
         flags: hal::CommandPoolFlags,
-    ) -> CommandPool {
+    ) -> hal::Result<CommandPool> {
         CommandPool::new(self, pool_type, flags)
     }
 
-    fn create_fence(&self, initial_value: u64) -> native::sync::Fence {
-        native::sync::Fence::new_with_value(&self.native, initial_value)
-            .expect("Failed to create D3D12 fence")
+    fn create_buffer(
+        &self,
+        size: u64,
+        usage: hal::BufferUsage,
+        memory_type: hal::MemoryType,
+    ) -> hal::Result<Buffer> {
+        Buffer::new(self, size, usage, memory_type)
+    }
+
+    fn map_buffer(&self, buffer: &Buffer) -> hal::Result<*mut u8> {
+        buffer.map()
     }
 
-    fn reset_fence(&self, fence: &native::sync::Fence) {
-        fence.reset().expect("Failed to reset D3D12 fence")
+    fn unmap_buffer(&self, buffer: &Buffer) {
+        buffer.unmap()
+    }
+
+    fn limits(&self) -> hal::Limits {
+        self.limits
+    }
+
+    fn features(&self) -> hal::Features {
+        self.features
+    }
+
+    fn format_properties(&self, format: hal::Format) -> hal::FormatProperties {
+        let mut format_support = d3d12::D3D12_FEATURE_DATA_FORMAT_SUPPORT {
+            Format: window::get_native_format(format) as _,
+            Support1: d3d12::D3D12_FORMAT_SUPPORT1_NONE,
+            Support2: d3d12::D3D12_FORMAT_SUPPORT2_NONE,
+        };
+
+        match self.native.check_feature_support(
+            native::device::Feature::FormatSupport,
+            &mut format_support as *mut _ as *mut _,
+            mem::size_of::<d3d12::D3D12_FEATURE_DATA_FORMAT_SUPPORT>() as _,
+        ) {
+            Ok(_) => get_format_properties(format_support.Support1),
+            // Formats unknown to the driver fail the query
+            Err(_) => hal::FormatProperties::empty(),
+        }
+    }
+
+    fn create_image(&self, desc: hal::ImageDesc) -> hal::Result<Image> {
+        Image::new(self, desc)
+    }
+
+    fn create_heap(
+        &self,
+        size: u64,
+        memory_type: hal::MemoryType,
+        usage: hal::HeapUsage,
+    ) -> hal::Result<Heap> {
+        if usage.bits().count_ones() > 1
+            && !self.features.contains(hal::Features::MIXED_RESOURCE_HEAPS)
+        {
+            return Err(hal::Error::Unsupported);
+        }
+        Heap::new(self, size, memory_type, usage)
+    }
+
+    fn get_buffer_requirements(
+        &self,
+        size: u64,
+        usage: hal::BufferUsage,
+    ) -> hal::Result<hal::MemoryRequirements> {
+        let desc = resource::get_buffer_desc(size, usage);
+        Ok(resource::get_requirements(
+            self.native.get_resource_allocation_info(&desc),
+        ))
+    }
+
+    fn get_image_requirements(
+        &self,
+        desc: &hal::ImageDesc,
+    ) -> hal::Result<hal::MemoryRequirements> {
+        let desc = resource::get_image_desc(desc);
+        Ok(resource::get_requirements(
+            self.native.get_resource_allocation_info(&desc),
+        ))
+    }
+
+    fn create_placed_buffer(
+        &self,
+        heap: &Heap,
+        offset: u64,
+        size: u64,
+        usage: hal::BufferUsage,
+    ) -> hal::Result<Buffer> {
+        Buffer::new_placed(self, heap, offset, size, usage)
+    }
+
+    fn create_placed_image(
+        &self,
+        heap: &Heap,
+        offset: u64,
+        desc: hal::ImageDesc,
+    ) -> hal::Result<Image> {
+        Image::new_placed(self, heap, offset, desc)
+    }
+
+    fn create_render_pass(&self, desc: &hal::RenderPassDesc) -> hal::Result<RenderPass> {
+        // Framebuffers only get render target views, there are no depth-stencil views yet
+        if !desc.has_only_color_attachments() {
+            return Err(hal::Error::Unsupported);
+        }
+
+        Ok(RenderPass {
+            attachments: desc.attachments.clone(),
+        })
+    }
+
+    fn create_framebuffer(&self, attachments: &[&Image]) -> hal::Result<FrameBuffer> {
+        FrameBuffer::new(self, attachments)
+    }
+
+    fn create_descriptor_set_layout(
+        &self,
+        desc: &hal::DescriptorSetLayoutDesc,
+    ) -> hal::Result<DescriptorSetLayout> {
+        let max_descriptors = if desc.update_after_bind {
+            self.limits.max_bindless_descriptors
+        } else {
+            self.limits.max_descriptors
+        };
+        if desc.get_descriptor_count() > max_descriptors {
+            return Err(hal::Error::Unsupported);
+        }
+        // Root signatures 1.0 treat descriptors as volatile, so tables can always be updated
+        // after being bound
+        Ok(DescriptorSetLayout { desc: desc.clone() })
+    }
+
+    fn create_descriptor_set(&self, layout: &DescriptorSetLayout) -> hal::Result<DescriptorSet> {
+        DescriptorSet::new(self, layout)
+    }
+
+    fn write_descriptors(&self, writes: &[hal::DescriptorWrite<Backend>]) -> hal::Result<()> {
+        for write in writes {
+            write.set.write(write);
+        }
+        Ok(())
+    }
+
+    fn create_pipeline_layout(
+        &self,
+        set_layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[hal::PushConstantRange],
+    ) -> hal::Result<PipelineLayout> {
+        let push_constants_size = hal::PushConstantRange::get_total_size(push_constant_ranges);
+        if set_layouts.len() as u32 > self.limits.max_descriptor_sets
+            || push_constants_size > self.limits.max_push_constants_size
+        {
+            return Err(hal::Error::Unsupported);
+        }
+        PipelineLayout::new(self, set_layouts, push_constant_ranges)
+    }
+
+    fn create_fence(&self, initial_value: u64) -> hal::Result<native::sync::Fence> {
+        native::sync::Fence::new_with_value(&self.native, initial_value).map_err(get_hal_error)
+    }
+
+    fn reset_fence(&self, fence: &native::sync::Fence) -> hal::Result<()> {
+        fence.reset().map_err(get_hal_error)
     }
 
     fn wait_for_fence_with_timeout(
@@ -139,10 +418,68 @@ impl hal::Device<Backend> for Device {
         fence: &native::sync::Fence,
         value: u64,
         timeout: u64,
-    ) -> bool {
+    ) -> hal::Result<()> {
         let event = native::sync::Event::new(false, false);
-        fence
-            .wait_for_value_with_timeout(event, value, timeout)
-            .expect("Failed to wait for D3D12 fence")
+        let result = fence.wait_for_value_with_timeout(event, value, timeout);
+        event.close();
+
+        if result.map_err(get_hal_error)? {
+            Ok(())
+        } else {
+            Err(hal::Error::Timeout)
+        }
+    }
+
+    fn device_lost_reason(&self) -> Option<hal::DeviceLostReason> {
+        self.native
+            .get_device_removed_reason()
+            .err()
+            .map(get_device_lost_reason)
+    }
+}
+
+fn get_format_properties(support: d3d12::D3D12_FORMAT_SUPPORT1) -> hal::FormatProperties {
+    let mut properties = hal::FormatProperties::empty();
+    if support & d3d12::D3D12_FORMAT_SUPPORT1_SHADER_SAMPLE != 0 {
+        properties |= hal::FormatProperties::SAMPLED;
+    }
+    if support & d3d12::D3D12_FORMAT_SUPPORT1_TYPED_UNORDERED_ACCESS_VIEW != 0 {
+        properties |= hal::FormatProperties::STORAGE;
+    }
+    if support & d3d12::D3D12_FORMAT_SUPPORT1_RENDER_TARGET != 0 {
+        properties |= hal::FormatProperties::COLOR_ATTACHMENT;
+    }
+    if support & d3d12::D3D12_FORMAT_SUPPORT1_BLENDABLE != 0 {
+        properties |= hal::FormatProperties::BLEND;
+    }
+    if support & d3d12::D3D12_FORMAT_SUPPORT1_DEPTH_STENCIL != 0 {
+        properties |= hal::FormatProperties::DEPTH_STENCIL_ATTACHMENT;
+    }
+    properties
+}
+
+fn get_device_lost_reason(hr: winerror::HRESULT) -> hal::DeviceLostReason {
+    match hr {
+        winerror::DXGI_ERROR_DEVICE_HUNG => hal::DeviceLostReason::Hung,
+        winerror::DXGI_ERROR_DEVICE_REMOVED => hal::DeviceLostReason::Removed,
+        winerror::DXGI_ERROR_DEVICE_RESET => hal::DeviceLostReason::Reset,
+        winerror::DXGI_ERROR_DRIVER_INTERNAL_ERROR => hal::DeviceLostReason::DriverInternalError,
+        winerror::DXGI_ERROR_INVALID_CALL => hal::DeviceLostReason::InvalidCall,
+        _ => hal::DeviceLostReason::Unknown,
+    }
+}
+
+pub(crate) fn get_hal_error(hr: winerror::HRESULT) -> hal::Error {
+    match hr {
+        winerror::E_OUTOFMEMORY => hal::Error::OutOfHostMemory,
+        winerror::DXGI_ERROR_DEVICE_REMOVED
+        | winerror::DXGI_ERROR_DEVICE_HUNG
+        | winerror::DXGI_ERROR_DEVICE_RESET
+        | winerror::DXGI_ERROR_DRIVER_INTERNAL_ERROR => hal::Error::DeviceLost,
+        winerror::E_NOINTERFACE
+        | winerror::E_NOTIMPL
+        | winerror::DXGI_ERROR_UNSUPPORTED
+        | winerror::DXGI_ERROR_NOT_CURRENTLY_AVAILABLE => hal::Error::Unsupported,
+        _ => hal::Error::Internal(hr),
     }
 }
@@ -1,12 +1,34 @@
-use crate::backend::dx12::device::Device;
+use crate::allocator::linear::Linear;
+use crate::allocator::slots::{SlotId, Slots};
+use crate::allocator::tlsf::{BlockId, Tlsf};
+use crate::backend::dx12::device::{get_hal_error, Device};
+use crate::hal;
 
 use graphix_native_dx12 as native;
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Copy, Clone)]
 pub struct DescriptorHeapHandle {
     pub(crate) cpu: native::heap::CPUDescriptor,
     pub(crate) gpu: native::heap::GPUDescriptor,
 }
 
+impl DescriptorHeapHandle {
+    /// Handle of the descriptor `index` descriptors of `handle_size` bytes past this one.
+    pub(crate) fn offset(self, index: u64, handle_size: u64) -> Self {
+        DescriptorHeapHandle {
+            cpu: native::heap::CPUDescriptor {
+                ptr: self.cpu.ptr + (handle_size * index) as usize,
+            },
+            gpu: native::heap::GPUDescriptor {
+                ptr: self.gpu.ptr + handle_size * index,
+            },
+        }
+    }
+}
+
 pub struct DescriptorHeap {
     pub(crate) native: native::heap::DescriptorHeap,
     pub(crate) handle_size: u64,
@@ -19,21 +41,29 @@ impl DescriptorHeap {
         device: &Device,
         heap_type: native::heap::DescriptorHeapType,
         capacity: usize,
-    ) -> Self {
-        let heap = native::heap::DescriptorHeap::new(
+    ) -> hal::Result<Self> {
+        DescriptorHeap::with_flags(
             &device.native,
             heap_type,
             native::heap::DescriptorHeapFlags::NONE,
-            capacity as _,
-            0,
+            capacity,
         )
-        .expect("Failed to create D3D12 descriptor heap");
+    }
+
+    pub(crate) fn with_flags(
+        device: &native::device::Device,
+        heap_type: native::heap::DescriptorHeapType,
+        flags: native::heap::DescriptorHeapFlags,
+        capacity: usize,
+    ) -> hal::Result<Self> {
+        let heap = native::heap::DescriptorHeap::new(device, heap_type, flags, capacity as _, 0)
+            .map_err(get_hal_error)?;
 
-        let handle_size = device.native.get_descriptor_increment_size(heap_type);
+        let handle_size = device.get_descriptor_increment_size(heap_type);
         let cpu_handle = heap.get_cpu_descriptor_start();
         let gpu_handle = heap.get_gpu_descriptor_start();
 
-        DescriptorHeap {
+        Ok(DescriptorHeap {
             native: heap,
             handle_size: handle_size as _,
             handle_count: capacity as _,
@@ -41,17 +71,339 @@ impl DescriptorHeap {
                 cpu: cpu_handle,
                 gpu: gpu_handle,
             },
-        }
+        })
     }
 
     pub(crate) fn offset(&self, index: u64) -> DescriptorHeapHandle {
-        DescriptorHeapHandle {
-            cpu: native::heap::CPUDescriptor {
-                ptr: self.start.cpu.ptr + (self.handle_size * index) as usize,
-            },
-            gpu: native::heap::GPUDescriptor {
-                ptr: self.start.gpu.ptr + (self.handle_size * index) as u64,
-            },
+        debug_assert!(index < self.handle_count, "Descriptor out of the heap");
+        self.start.offset(index, self.handle_size)
+    }
+}
+
+/// Descriptors per heap of the pools of a type, pools growing by one heap at a time.
+fn get_page_size(heap_type: native::heap::DescriptorHeapType) -> usize {
+    match heap_type {
+        native::heap::DescriptorHeapType::Rtv | native::heap::DescriptorHeapType::Dsv => 64,
+        native::heap::DescriptorHeapType::CbvSrvUav => 1024,
+        native::heap::DescriptorHeapType::Sampler => 256,
+    }
+}
+
+/// A descriptor allocated from a `DescriptorPool`. Using it once freed panics, as the slot it
+/// points to may hold another descriptor by then.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Descriptor {
+    heap_type: native::heap::DescriptorHeapType,
+    page: u32,
+    slot: SlotId,
+}
+
+struct DescriptorPage {
+    heap: DescriptorHeap,
+    slots: Slots,
+}
+
+/// Descriptors of one type, allocated and freed one at a time from heaps added as it fills up.
+pub struct DescriptorPool {
+    heap_type: native::heap::DescriptorHeapType,
+    page_size: usize,
+    pages: Vec<DescriptorPage>,
+}
+
+impl DescriptorPool {
+    pub fn new(heap_type: native::heap::DescriptorHeapType, page_size: usize) -> Self {
+        DescriptorPool {
+            heap_type,
+            page_size,
+            pages: Vec::new(),
+        }
+    }
+
+    pub fn allocate(&mut self, device: &Device) -> hal::Result<Descriptor> {
+        let page_index = match self
+            .pages
+            .iter()
+            .position(|page| page.slots.has_free_slots())
+        {
+            Some(page_index) => page_index,
+            None => {
+                let heap = DescriptorHeap::new(device, self.heap_type, self.page_size)?;
+                self.pages.push(DescriptorPage {
+                    heap,
+                    slots: Slots::new(self.page_size as _),
+                });
+                self.pages.len() - 1
+            }
+        };
+
+        Ok(Descriptor {
+            heap_type: self.heap_type,
+            page: page_index as _,
+            slot: self.pages[page_index].slots.allocate().unwrap(),
+        })
+    }
+
+    pub fn free(&mut self, descriptor: Descriptor) {
+        self.check(descriptor);
+        self.pages[descriptor.page as usize]
+            .slots
+            .free(descriptor.slot);
+    }
+
+    pub fn get_handle(&self, descriptor: Descriptor) -> DescriptorHeapHandle {
+        self.check(descriptor);
+        self.pages[descriptor.page as usize]
+            .heap
+            .offset(u64::from(descriptor.slot.index()))
+    }
+
+    /// Number of descriptors allocated and not freed yet.
+    pub fn allocated_count(&self) -> usize {
+        self.pages
+            .iter()
+            .map(|page| page.slots.allocated_count())
+            .sum()
+    }
+
+    fn check(&self, descriptor: Descriptor) {
+        assert_eq!(
+            descriptor.heap_type, self.heap_type,
+            "Descriptor used with a pool of another type"
+        );
+        self.pages[descriptor.page as usize]
+            .slots
+            .check(descriptor.slot);
+    }
+}
+
+/// The pools of each descriptor type of a device.
+pub struct DescriptorAllocator {
+    rtv: DescriptorPool,
+    dsv: DescriptorPool,
+    cbv_srv_uav: DescriptorPool,
+    sampler: DescriptorPool,
+}
+
+impl DescriptorAllocator {
+    pub fn new() -> Self {
+        let create_pool = |heap_type| DescriptorPool::new(heap_type, get_page_size(heap_type));
+        DescriptorAllocator {
+            rtv: create_pool(native::heap::DescriptorHeapType::Rtv),
+            dsv: create_pool(native::heap::DescriptorHeapType::Dsv),
+            cbv_srv_uav: create_pool(native::heap::DescriptorHeapType::CbvSrvUav),
+            sampler: create_pool(native::heap::DescriptorHeapType::Sampler),
+        }
+    }
+
+    pub fn pool(&self, heap_type: native::heap::DescriptorHeapType) -> &DescriptorPool {
+        match heap_type {
+            native::heap::DescriptorHeapType::Rtv => &self.rtv,
+            native::heap::DescriptorHeapType::Dsv => &self.dsv,
+            native::heap::DescriptorHeapType::CbvSrvUav => &self.cbv_srv_uav,
+            native::heap::DescriptorHeapType::Sampler => &self.sampler,
+        }
+    }
+
+    pub fn pool_mut(&mut self, heap_type: native::heap::DescriptorHeapType) -> &mut DescriptorPool {
+        match heap_type {
+            native::heap::DescriptorHeapType::Rtv => &mut self.rtv,
+            native::heap::DescriptorHeapType::Dsv => &mut self.dsv,
+            native::heap::DescriptorHeapType::CbvSrvUav => &mut self.cbv_srv_uav,
+            native::heap::DescriptorHeapType::Sampler => &mut self.sampler,
+        }
+    }
+
+    pub fn allocate(
+        &mut self,
+        device: &Device,
+        heap_type: native::heap::DescriptorHeapType,
+    ) -> hal::Result<Descriptor> {
+        self.pool_mut(heap_type).allocate(device)
+    }
+
+    pub fn free(&mut self, descriptor: Descriptor) {
+        self.pool_mut(descriptor.heap_type).free(descriptor)
+    }
+
+    pub fn get_handle(&self, descriptor: Descriptor) -> DescriptorHeapHandle {
+        self.pool(descriptor.heap_type).get_handle(descriptor)
+    }
+}
+
+impl Default for DescriptorAllocator {
+    fn default() -> Self {
+        DescriptorAllocator::new()
+    }
+}
+
+/// Descriptors allocated from the device, freed once dropped.
+///
+/// Only suits descriptors the GPU doesn't read from their heap, such as render target views
+/// which command lists copy when recorded.
+pub(crate) struct OwnedDescriptors {
+    allocator: Rc<RefCell<DescriptorAllocator>>,
+    descriptors: Vec<Descriptor>,
+}
+
+impl OwnedDescriptors {
+    pub(crate) fn new(device: &Device) -> Self {
+        OwnedDescriptors {
+            allocator: device.descriptors.clone(),
+            descriptors: Vec::new(),
+        }
+    }
+
+    pub(crate) fn allocate(
+        &mut self,
+        device: &Device,
+        heap_type: native::heap::DescriptorHeapType,
+    ) -> hal::Result<DescriptorHeapHandle> {
+        let mut allocator = self.allocator.borrow_mut();
+        let descriptor = allocator.allocate(device, heap_type)?;
+        self.descriptors.push(descriptor);
+        Ok(allocator.get_handle(descriptor))
+    }
+}
+
+impl Drop for OwnedDescriptors {
+    fn drop(&mut self) {
+        let mut allocator = self.allocator.borrow_mut();
+        for &descriptor in &self.descriptors {
+            allocator.free(descriptor);
+        }
+    }
+}
+
+/// The shader-visible CBV_SRV_UAV heap of a device, handing out the contiguous ranges descriptor
+/// tables live in. Command lists bind a single heap of the type at a time, so all the tables
+/// share it. It's created on first use, as it's sized for the most descriptors shaders can see.
+pub struct ShaderVisibleHeap {
+    capacity: usize,
+    heap: Option<DescriptorHeap>,
+    ranges: Tlsf,
+}
+
+impl ShaderVisibleHeap {
+    pub fn new(capacity: usize) -> Self {
+        ShaderVisibleHeap {
+            capacity,
+            heap: None,
+            ranges: Tlsf::new(capacity as _),
+        }
+    }
+
+    /// Allocates `count` contiguous descriptors, returning the block to free and the first one.
+    pub(crate) fn allocate(
+        &mut self,
+        device: &native::device::Device,
+        count: usize,
+    ) -> hal::Result<(BlockId, DescriptorHeapHandle)> {
+        if self.heap.is_none() {
+            self.heap = Some(DescriptorHeap::with_flags(
+                device,
+                native::heap::DescriptorHeapType::CbvSrvUav,
+                native::heap::DescriptorHeapFlags::SHADER_VISIBLE,
+                self.capacity,
+            )?);
+        }
+
+        let (block, offset) = self
+            .ranges
+            .allocate(count as _, 1)
+            .ok_or(hal::Error::OutOfDeviceMemory)?;
+        Ok((block, self.heap.as_ref().unwrap().offset(offset)))
+    }
+
+    pub(crate) fn free(&mut self, block: BlockId) {
+        self.ranges.free(block);
+    }
+
+    pub(crate) fn heap(&self) -> Option<&DescriptorHeap> {
+        self.heap.as_ref()
+    }
+}
+
+/// Shader-visible descriptors living for a frame, allocated in contiguous ranges of pages taken
+/// from the shader-visible heap and all freed at once by `reset`.
+///
+/// Command buffers copy the descriptors of the sets they bind into one, and reset it when they're
+/// recorded again. That's only once the GPU is done with their previous submission, i.e. once the
+/// fence of the frame they were recorded for completed.
+pub struct LinearDescriptorAllocator {
+    shader_visible: Rc<RefCell<ShaderVisibleHeap>>,
+    ranges: Linear,
+    // Blocks of the shader-visible heap and their first descriptor
+    pages: Vec<(BlockId, DescriptorHeapHandle)>,
+    // Ranges larger than a page, freed by `reset`
+    large_blocks: Vec<BlockId>,
+    handle_size: u64,
+}
+
+impl LinearDescriptorAllocator {
+    pub(crate) fn new(
+        device: &native::device::Device,
+        shader_visible: Rc<RefCell<ShaderVisibleHeap>>,
+    ) -> Self {
+        let heap_type = native::heap::DescriptorHeapType::CbvSrvUav;
+        LinearDescriptorAllocator {
+            shader_visible,
+            ranges: Linear::new(get_page_size(heap_type) as _),
+            pages: Vec::new(),
+            large_blocks: Vec::new(),
+            handle_size: u64::from(device.get_descriptor_increment_size(heap_type)),
+        }
+    }
+
+    /// Allocates `count` contiguous descriptors, returning the first one.
+    pub(crate) fn allocate(
+        &mut self,
+        device: &native::device::Device,
+        count: u32,
+    ) -> hal::Result<DescriptorHeapHandle> {
+        let mut shader_visible = self.shader_visible.borrow_mut();
+        if count > self.ranges.page_size() {
+            let (block, start) = shader_visible.allocate(device, count as _)?;
+            self.large_blocks.push(block);
+            return Ok(start);
+        }
+
+        let range = match self.ranges.allocate(count) {
+            Some(range) => range,
+            None => {
+                let page = shader_visible.allocate(device, self.ranges.page_size() as _)?;
+                self.pages.push(page);
+                self.ranges.add_page();
+                self.ranges.allocate(count).unwrap()
+            }
+        };
+        let (_, start) = self.pages[range.page];
+        Ok(start.offset(u64::from(range.offset), self.handle_size))
+    }
+
+    /// Frees all the descriptors allocated, keeping the pages for the next frame.
+    pub(crate) fn reset(&mut self) {
+        self.ranges.reset();
+        let mut shader_visible = self.shader_visible.borrow_mut();
+        for block in self.large_blocks.drain(..) {
+            shader_visible.free(block);
+        }
+    }
+
+    /// The heap all the descriptors are allocated from, once created.
+    pub(crate) fn heap(&self) -> Option<native::heap::DescriptorHeap> {
+        self.shader_visible
+            .borrow()
+            .heap()
+            .map(|heap| heap.native.clone())
+    }
+}
+
+impl Drop for LinearDescriptorAllocator {
+    fn drop(&mut self) {
+        self.reset();
+        let mut shader_visible = self.shader_visible.borrow_mut();
+        for &(block, _) in &self.pages {
+            shader_visible.free(block);
         }
     }
 }
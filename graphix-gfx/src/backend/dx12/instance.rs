@@ -1,15 +1,16 @@
 use crate::backend::dx12::adapter::PhysicalAdapter;
 use crate::backend::dx12::command::{CommandBuffer, CommandPool};
+use crate::backend::dx12::descriptor::{DescriptorSet, DescriptorSetLayout, PipelineLayout};
 use crate::backend::dx12::device::Device;
 use crate::backend::dx12::queue::CommandQueue;
-use crate::backend::dx12::resource::FrameBuffer;
+use crate::backend::dx12::resource::{Buffer, FrameBuffer, Heap, Image, RenderPass};
 use crate::backend::dx12::window::{Surface, Swapchain};
 use crate::hal;
 
 use graphix_native_dx12 as native;
 
 use winapi::shared::{dxgi, dxgi1_3, winerror};
-use winapi::um::dxgidebug;
+use winapi::um::{d3d12, dxgidebug};
 use winapi::Interface;
 
 use std::ffi::OsString;
@@ -29,7 +30,14 @@ impl hal::Backend for Backend {
     type Surface = Surface;
     type Swapchain = Swapchain;
 
+    type RenderPass = RenderPass;
     type FrameBuffer = FrameBuffer;
+    type Buffer = Buffer;
+    type Image = Image;
+    type Heap = Heap;
+    type DescriptorSetLayout = DescriptorSetLayout;
+    type DescriptorSet = DescriptorSet;
+    type PipelineLayout = PipelineLayout;
 
     type Fence = native::sync::Fence;
 }
@@ -43,6 +51,18 @@ impl Instance {
         Default::default()
     }
 
+    /// Creates an instance, or returns `None` if no DXGI factory can be created.
+    pub fn try_new() -> Option<Self> {
+        let factory_flags = if cfg!(debug_assertions) && Instance::setup_dxgi_debug_layer() {
+            native::dxgi::FactoryCreationFlags::DEBUG
+        } else {
+            native::dxgi::FactoryCreationFlags::empty()
+        };
+
+        let factory = native::dxgi::Factory::new(factory_flags).ok()?;
+        Some(Instance { factory })
+    }
+
     fn setup_dxgi_debug_layer() -> bool {
         let mut info_queue: *mut dxgidebug::IDXGIInfoQueue = ptr::null_mut();
         let hr = unsafe {
@@ -105,27 +125,55 @@ impl Instance {
             vendor: desc.VendorId,
             device: desc.DeviceId,
             video_memory: desc.DedicatedVideoMemory,
-            device_type: if (desc.Flags & dxgi::DXGI_ADAPTER_FLAG_SOFTWARE) == 0 {
-                hal::DeviceType::DiscreteGpu
+            device_type: if (desc.Flags & dxgi::DXGI_ADAPTER_FLAG_SOFTWARE) != 0 {
+                hal::DeviceType::Cpu
+            } else if Instance::is_uma_adapter(adapter) {
+                hal::DeviceType::IntegratedGpu
             } else {
-                hal::DeviceType::VirtualGpu
+                hal::DeviceType::DiscreteGpu
             },
         }
     }
+
+    fn is_uma_adapter(adapter: &native::dxgi::Adapter) -> bool {
+        // DXGI doesn't tell integrated GPUs apart, but they share memory with the CPU
+        let device = match native::device::Device::new(adapter, native::device::FeatureLevel::L11_0)
+        {
+            Ok(device) => device,
+            Err(_) => return false,
+        };
+        let mut architecture = d3d12::D3D12_FEATURE_DATA_ARCHITECTURE {
+            NodeIndex: 0,
+            TileBasedRenderer: 0,
+            UMA: 0,
+            CacheCoherentUMA: 0,
+        };
+
+        device
+            .check_feature_support(
+                native::device::Feature::Architecture,
+                &mut architecture as *mut _ as *mut _,
+                mem::size_of::<d3d12::D3D12_FEATURE_DATA_ARCHITECTURE>() as _,
+            )
+            .is_ok_and(|()| architecture.UMA != 0)
+    }
+
+    /// Returns the WARP software adapter, which is not part of the enumerated adapters.
+    pub fn enumerate_warp_adapter(&self) -> Option<hal::Adapter<Backend>> {
+        let native_adapter = self.factory.enumerate_warp().ok()?;
+        let info = Instance::get_adapter_info(&native_adapter);
+        Some(hal::Adapter {
+            adapter: PhysicalAdapter {
+                native: native_adapter,
+            },
+            info,
+        })
+    }
 }
 
 impl Default for Instance {
     fn default() -> Self {
-        let factory_flags = if cfg!(debug_assertions) && Instance::setup_dxgi_debug_layer() {
-            native::dxgi::FactoryCreationFlags::DEBUG
-        } else {
-            native::dxgi::FactoryCreationFlags::empty()
-        };
-
-        Instance {
-            factory: native::dxgi::Factory::new(factory_flags)
-                .expect("Failed to create DXGI factory."),
-        }
+        Instance::try_new().expect("Failed to create DXGI factory.")
     }
 }
 
@@ -147,6 +195,14 @@ impl hal::Instance for Instance {
             })
             .collect()
     }
+
+    fn select_adapter(&self, preference: &hal::AdapterPreference) -> Option<hal::Adapter<Backend>> {
+        // WARP ranks last for the power preferences, so it's only picked when asked for by
+        // name or id, or as a fallback when there is no hardware adapter
+        let mut adapters = self.enumerate_adapters();
+        adapters.extend(self.enumerate_warp_adapter());
+        preference.select(adapters)
+    }
 }
 
 impl Drop for Instance {
@@ -1,5 +1,5 @@
 use crate::backend::dx12::command::CommandBuffer;
-use crate::backend::dx12::device::Device;
+use crate::backend::dx12::device::{get_hal_error, Device};
 use crate::backend::dx12::instance::Backend;
 use crate::hal;
 
@@ -10,32 +10,36 @@ pub struct CommandQueue {
 }
 
 impl CommandQueue {
-    pub(crate) fn new(device: &Device, queue_type: hal::QueueType) -> Self {
+    pub(crate) fn new(device: &Device, queue_type: hal::QueueType) -> hal::Result<Self> {
         let queue = native::queue::CommandQueue::new(
             &device.native,
             get_native_type(queue_type),
             native::queue::CommandQueuePriority::Normal,
             native::queue::CommandQueueFlags::empty(),
         )
-        .expect("Failed to create D3D12 command queue.");
+        .map_err(get_hal_error)?;
 
-        CommandQueue { native: queue }
+        Ok(CommandQueue { native: queue })
     }
 }
 
 impl hal::CommandQueue<Backend> for CommandQueue {
-    fn submit(&self, commnad_buffers: Vec<&CommandBuffer>) {
-        let lists = commnad_buffers
-            .into_iter()
-            .map(CommandBuffer::as_command_list)
-            .collect::<Vec<_>>();
+    fn submit(&self, command_buffers: Vec<&CommandBuffer>) -> hal::Result<()> {
+        // Resources are patched to the states each command buffer starts with right before it
+        let mut lists = Vec::with_capacity(command_buffers.len());
+        for command_buffer in command_buffers {
+            if let Some(patch) = command_buffer.record_patch()? {
+                lists.push(patch);
+            }
+            lists.push(command_buffer.as_command_list());
+            command_buffer.tracker.borrow().commit();
+        }
         self.native.execute_command_lists(&lists);
+        Ok(())
     }
 
-    fn signal_fence(&self, fence: &native::sync::Fence, value: u64) {
-        self.native
-            .signal(fence, value)
-            .expect("Failed to signal D3D12 command queue");
+    fn signal_fence(&self, fence: &native::sync::Fence, value: u64) -> hal::Result<()> {
+        self.native.signal(fence, value).map_err(get_hal_error)
     }
 }
 
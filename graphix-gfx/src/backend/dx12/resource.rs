@@ -1,12 +1,439 @@
+use crate::backend::dx12::command;
+use crate::backend::dx12::device::{get_hal_error, Device};
+use crate::backend::dx12::heap::OwnedDescriptors;
+use crate::backend::dx12::window;
+use crate::backend::tracker::SubmittedState;
+use crate::hal;
+
 use graphix_native_dx12 as native;
 
+use winapi::shared::winerror;
+use winapi::um::d3d12;
+
+use std::rc::Rc;
+
+pub struct Buffer {
+    pub(crate) resource: native::resource::Resource,
+    // Only tracked on the default heap, resources on other heaps can't leave their initial state
+    pub(crate) state: Option<SubmittedState<hal::ResourceState>>,
+    size: u64,
+    usage: hal::BufferUsage,
+    memory_type: hal::MemoryType,
+    // Keeps the heap of placed buffers alive
+    #[allow(dead_code)]
+    heap: Option<native::resource::Heap>,
+}
+
+impl Buffer {
+    pub(crate) fn new(
+        device: &Device,
+        size: u64,
+        usage: hal::BufferUsage,
+        memory_type: hal::MemoryType,
+    ) -> hal::Result<Self> {
+        let (heap_type, initial_state) = get_native_heap_type(memory_type);
+        let resource = device
+            .native
+            .create_committed_resource(heap_type, &get_buffer_desc(size, usage), initial_state)
+            .map_err(get_resource_error)?;
+
+        Ok(Buffer::from_resource(
+            resource,
+            size,
+            usage,
+            memory_type,
+            None,
+        ))
+    }
+
+    pub(crate) fn new_placed(
+        device: &Device,
+        heap: &Heap,
+        offset: u64,
+        size: u64,
+        usage: hal::BufferUsage,
+    ) -> hal::Result<Self> {
+        let desc = get_buffer_desc(size, usage);
+        heap.check_placement(
+            hal::HeapUsage::BUFFERS,
+            get_requirements(device.native.get_resource_allocation_info(&desc)),
+            offset,
+        );
+
+        let (_, initial_state) = get_native_heap_type(heap.memory_type);
+        let resource = device
+            .native
+            .create_placed_resource(&heap.native, offset, &desc, initial_state)
+            .map_err(get_resource_error)?;
+
+        Ok(Buffer::from_resource(
+            resource,
+            size,
+            usage,
+            heap.memory_type,
+            Some(heap.native.clone()),
+        ))
+    }
+
+    fn from_resource(
+        resource: native::resource::Resource,
+        size: u64,
+        usage: hal::BufferUsage,
+        memory_type: hal::MemoryType,
+        heap: Option<native::resource::Heap>,
+    ) -> Self {
+        let state = match memory_type {
+            hal::MemoryType::DeviceLocal => Some(SubmittedState::new(hal::ResourceState::COMMON)),
+            hal::MemoryType::Upload | hal::MemoryType::Readback => None,
+        };
+
+        Buffer {
+            resource,
+            state,
+            size,
+            usage,
+            memory_type,
+            heap,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn usage(&self) -> hal::BufferUsage {
+        self.usage
+    }
+
+    pub(crate) fn map(&self) -> hal::Result<*mut u8> {
+        // Tell the driver whether the CPU will read, so upload heaps can stay write-combined
+        let read_range = match self.memory_type {
+            hal::MemoryType::DeviceLocal => return Err(hal::Error::Unsupported),
+            hal::MemoryType::Upload => Some(0..0),
+            hal::MemoryType::Readback => None,
+        };
+
+        self.resource.map(0, read_range).map_err(get_hal_error)
+    }
+
+    pub(crate) fn unmap(&self) {
+        let written_range = match self.memory_type {
+            hal::MemoryType::Readback => Some(0..0),
+            _ => None,
+        };
+
+        self.resource.unmap(0, written_range);
+    }
+}
+
+pub struct Image {
+    pub(crate) resource: native::resource::Resource,
+    pub(crate) state: SubmittedState<hal::ResourceState>,
+    desc: hal::ImageDesc,
+    // Keeps the heap of placed images alive
+    #[allow(dead_code)]
+    heap: Option<native::resource::Heap>,
+}
+
+impl Image {
+    pub(crate) fn new(device: &Device, desc: hal::ImageDesc) -> hal::Result<Self> {
+        let state = get_initial_image_state(&desc);
+        let resource = device
+            .native
+            .create_committed_resource(
+                native::resource::HeapType::Default,
+                &get_image_desc(&desc),
+                command::get_native_resource_state(state),
+            )
+            .map_err(get_resource_error)?;
+
+        Ok(Image {
+            resource,
+            state: SubmittedState::with_subresources(state, desc.get_subresource_count()),
+            desc,
+            heap: None,
+        })
+    }
+
+    pub(crate) fn new_placed(
+        device: &Device,
+        heap: &Heap,
+        offset: u64,
+        desc: hal::ImageDesc,
+    ) -> hal::Result<Self> {
+        let resource_desc = get_image_desc(&desc);
+        heap.check_placement(
+            hal::HeapUsage::from_image_usage(desc.usage),
+            get_requirements(device.native.get_resource_allocation_info(&resource_desc)),
+            offset,
+        );
+
+        let state = get_initial_image_state(&desc);
+        let resource = device
+            .native
+            .create_placed_resource(
+                &heap.native,
+                offset,
+                &resource_desc,
+                command::get_native_resource_state(state),
+            )
+            .map_err(get_resource_error)?;
+
+        Ok(Image {
+            resource,
+            state: SubmittedState::with_subresources(state, desc.get_subresource_count()),
+            desc,
+            heap: Some(heap.native.clone()),
+        })
+    }
+
+    pub fn desc(&self) -> &hal::ImageDesc {
+        &self.desc
+    }
+
+    pub(crate) fn get_rtv_desc(&self) -> native::heap::RenderTargetViewDesc {
+        let is_layered = self.desc.array_layers > 1;
+        let dimension = match self.desc.kind {
+            hal::ImageKind::D1 => native::heap::RtvDimension::Texture1D,
+            hal::ImageKind::D3 => native::heap::RtvDimension::Texture3D,
+            hal::ImageKind::Cube => native::heap::RtvDimension::Texture2DArray,
+            hal::ImageKind::D2 if self.desc.samples > 1 && is_layered => {
+                native::heap::RtvDimension::Texture2DMsArray
+            }
+            hal::ImageKind::D2 if self.desc.samples > 1 => native::heap::RtvDimension::Texture2DMs,
+            hal::ImageKind::D2 if is_layered => native::heap::RtvDimension::Texture2DArray,
+            hal::ImageKind::D2 => native::heap::RtvDimension::Texture2D,
+        };
+
+        native::heap::RenderTargetViewDesc::with_dimension(
+            window::get_native_format(self.desc.format),
+            dimension,
+        )
+    }
+}
+
+pub struct Heap {
+    pub(crate) native: native::resource::Heap,
+    size: u64,
+    memory_type: hal::MemoryType,
+    usage: hal::HeapUsage,
+}
+
+impl Heap {
+    pub(crate) fn new(
+        device: &Device,
+        size: u64,
+        memory_type: hal::MemoryType,
+        usage: hal::HeapUsage,
+    ) -> hal::Result<Self> {
+        // Heaps holding more than one kind of resource need resource heap tier 2
+        let flags = if usage == hal::HeapUsage::BUFFERS {
+            native::resource::HeapFlags::ALLOW_ONLY_BUFFERS
+        } else if usage == hal::HeapUsage::IMAGES {
+            native::resource::HeapFlags::ALLOW_ONLY_NON_RT_DS_TEXTURES
+        } else if usage == hal::HeapUsage::ATTACHMENTS {
+            native::resource::HeapFlags::ALLOW_ONLY_RT_DS_TEXTURES
+        } else {
+            native::resource::HeapFlags::ALLOW_ALL_BUFFERS_AND_TEXTURES
+        };
+        let (heap_type, _) = get_native_heap_type(memory_type);
+        let native = device
+            .native
+            .create_heap(size, heap_type, flags)
+            .map_err(get_resource_error)?;
+
+        Ok(Heap {
+            native,
+            size,
+            memory_type,
+            usage,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn check_placement(
+        &self,
+        usage: hal::HeapUsage,
+        requirements: hal::MemoryRequirements,
+        offset: u64,
+    ) {
+        assert!(
+            self.usage.contains(usage),
+            "{:?} placed in a heap for {:?}",
+            usage,
+            self.usage
+        );
+        assert!(
+            requirements.fits(offset, self.size),
+            "Resource of {:?} placed at offset {} of a {} bytes heap",
+            requirements,
+            offset,
+            self.size
+        );
+    }
+}
+
+pub struct RenderPass {
+    pub(crate) attachments: Vec<hal::AttachmentDesc>,
+}
+
 #[derive(Clone)]
 pub struct BufferView {
     pub(crate) resource: native::resource::Resource,
-    pub(crate) rtv_handle: Option<native::heap::CPUDescriptor>,
+    pub(crate) rtv_handle: native::heap::CPUDescriptor,
+    pub(crate) state: SubmittedState<hal::ResourceState>,
 }
 
 #[derive(Clone)]
 pub struct FrameBuffer {
     pub attachments: Vec<BufferView>,
+    // Keeps the render target views alive, shared with the swapchain for back buffers
+    #[allow(dead_code)]
+    pub(crate) descriptors: Rc<OwnedDescriptors>,
+}
+
+impl FrameBuffer {
+    pub(crate) fn new(device: &Device, attachments: &[&Image]) -> hal::Result<Self> {
+        let mut descriptors = OwnedDescriptors::new(device);
+        let attachments = attachments
+            .iter()
+            .map(|image| {
+                // Only color attachments are supported, see `create_render_pass`
+                if !image.desc.usage.contains(hal::ImageUsage::COLOR_ATTACHMENT) {
+                    return Err(hal::Error::Unsupported);
+                }
+
+                let rtv_handle = descriptors
+                    .allocate(device, native::heap::DescriptorHeapType::Rtv)?
+                    .cpu;
+                device.native.create_render_target_view(
+                    &image.resource,
+                    &image.get_rtv_desc(),
+                    rtv_handle,
+                );
+
+                Ok(BufferView {
+                    resource: image.resource.clone(),
+                    rtv_handle,
+                    state: image.state.clone(),
+                })
+            })
+            .collect::<hal::Result<_>>()?;
+
+        Ok(FrameBuffer {
+            attachments,
+            descriptors: Rc::new(descriptors),
+        })
+    }
+}
+
+pub(crate) fn get_buffer_desc(
+    size: u64,
+    usage: hal::BufferUsage,
+) -> native::resource::ResourceDesc {
+    // Constant buffer views must cover a multiple of 256 bytes
+    let resource_size = if usage.contains(hal::BufferUsage::CONSTANT) {
+        let alignment = u64::from(d3d12::D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT);
+        (size + alignment - 1) & !(alignment - 1)
+    } else {
+        size
+    };
+
+    native::resource::ResourceDesc::buffer(resource_size, get_native_buffer_flags(usage))
+}
+
+pub(crate) fn get_image_desc(desc: &hal::ImageDesc) -> native::resource::ResourceDesc {
+    let (dimension, depth_or_array_size) = match desc.kind {
+        hal::ImageKind::D1 => (
+            native::resource::ResourceDimension::Texture1D,
+            desc.array_layers,
+        ),
+        hal::ImageKind::D2 | hal::ImageKind::Cube => (
+            native::resource::ResourceDimension::Texture2D,
+            desc.array_layers,
+        ),
+        hal::ImageKind::D3 => (
+            native::resource::ResourceDimension::Texture3D,
+            desc.extent.depth,
+        ),
+    };
+
+    native::resource::ResourceDesc::texture(
+        dimension,
+        u64::from(desc.extent.width),
+        desc.extent.height,
+        depth_or_array_size as _,
+        desc.mip_levels as _,
+        window::get_native_format(desc.format),
+        desc.samples,
+        get_native_image_flags(desc.usage),
+    )
+}
+
+pub(crate) fn get_requirements(info: native::resource::AllocationInfo) -> hal::MemoryRequirements {
+    hal::MemoryRequirements {
+        size: info.size,
+        alignment: info.alignment,
+    }
+}
+
+fn get_initial_image_state(desc: &hal::ImageDesc) -> hal::ResourceState {
+    if desc.usage.contains(hal::ImageUsage::COLOR_ATTACHMENT) {
+        hal::ResourceState::RENDER_TARGET
+    } else {
+        hal::ResourceState::COMMON
+    }
+}
+
+// Resources are placed in video memory, so running out of memory there is what the driver reports
+fn get_resource_error(hr: winerror::HRESULT) -> hal::Error {
+    match get_hal_error(hr) {
+        hal::Error::OutOfHostMemory => hal::Error::OutOfDeviceMemory,
+        err => err,
+    }
+}
+
+fn get_native_buffer_flags(usage: hal::BufferUsage) -> native::resource::ResourceFlags {
+    if usage.contains(hal::BufferUsage::STORAGE) {
+        native::resource::ResourceFlags::ALLOW_UNORDERED_ACCESS
+    } else {
+        native::resource::ResourceFlags::NONE
+    }
+}
+
+fn get_native_image_flags(usage: hal::ImageUsage) -> native::resource::ResourceFlags {
+    let mut flags = native::resource::ResourceFlags::NONE;
+    if usage.contains(hal::ImageUsage::COLOR_ATTACHMENT) {
+        flags |= native::resource::ResourceFlags::ALLOW_RENDER_TARGET;
+    }
+    if usage.contains(hal::ImageUsage::DEPTH_STENCIL_ATTACHMENT) {
+        flags |= native::resource::ResourceFlags::ALLOW_DEPTH_STENCIL;
+    }
+    if usage.contains(hal::ImageUsage::STORAGE) {
+        flags |= native::resource::ResourceFlags::ALLOW_UNORDERED_ACCESS;
+    }
+    flags
+}
+
+fn get_native_heap_type(
+    memory_type: hal::MemoryType,
+) -> (native::resource::HeapType, native::resource::ResourceState) {
+    // Resources on upload and readback heaps can never leave their initial state
+    match memory_type {
+        hal::MemoryType::DeviceLocal => (
+            native::resource::HeapType::Default,
+            native::resource::ResourceState::COMMON,
+        ),
+        hal::MemoryType::Upload => (
+            native::resource::HeapType::Upload,
+            native::resource::ResourceState::GENERIC_READ,
+        ),
+        hal::MemoryType::Readback => (
+            native::resource::HeapType::Readback,
+            native::resource::ResourceState::COPY_DEST,
+        ),
+    }
 }
@@ -1,14 +1,17 @@
-use crate::backend::dx12::device::Device;
-use crate::backend::dx12::heap::DescriptorHeap;
+use crate::backend::dx12::device::{get_hal_error, Device};
+use crate::backend::dx12::heap::OwnedDescriptors;
 use crate::backend::dx12::instance::{Backend, Instance};
 use crate::backend::dx12::queue::CommandQueue;
 use crate::backend::dx12::resource::{BufferView, FrameBuffer};
+use crate::backend::tracker::SubmittedState;
 use crate::hal;
 
 use graphix_native_dx12 as native;
 
 use winapi::shared::windef::HWND;
 
+use std::rc::Rc;
+
 impl Instance {
     pub fn create_surface_from_hwnd(&self, window_handle: HWND) -> Surface {
         Surface {
@@ -35,15 +38,17 @@ impl hal::Surface<Backend> for Surface {
         device: &Device,
         command_queue: &CommandQueue,
         config: hal::SwapchainConfig,
-    ) -> Swapchain {
+    ) -> hal::Result<Swapchain> {
         Swapchain::new(self, device, command_queue, config)
     }
 }
 
 pub struct Swapchain {
     pub(crate) native: native::dxgi::Swapchain,
-    pub(crate) heap: DescriptorHeap,
+    descriptors: Rc<OwnedDescriptors>,
+    rtv_handles: Vec<native::heap::CPUDescriptor>,
     pub(crate) resources: Vec<native::resource::Resource>,
+    states: Vec<SubmittedState<hal::ResourceState>>,
 }
 
 impl Swapchain {
@@ -52,7 +57,7 @@ impl Swapchain {
         device: &Device,
         command_queue: &CommandQueue,
         config: hal::window::SwapchainConfig,
-    ) -> Self {
+    ) -> hal::Result<Self> {
         let swap_chain_desc = native::dxgi::SwapChainDesc {
             width: config.width,
             height: config.height,
@@ -81,46 +86,57 @@ impl Swapchain {
             &swap_chain_desc,
             surface.window_handle,
         )
-        .expect("Failed to create DXGI swap chain");
-
-        let heap = DescriptorHeap::new(
-            device,
-            native::heap::DescriptorHeapType::Rtv,
-            config.buffer_count,
-        );
+        .map_err(get_hal_error)?;
 
         let rtv_desc = native::heap::RenderTargetViewDesc::new(get_native_format(config.format));
 
+        let mut descriptors = OwnedDescriptors::new(device);
+        let mut rtv_handles = Vec::with_capacity(config.buffer_count);
         let mut resources = Vec::with_capacity(config.buffer_count);
         for i in 0..config.buffer_count {
-            let resource = swapchain
-                .get_buffer(i as _)
-                .expect("Failed to obtain D3D12 resource");
-            let rtv_handle = heap.offset(i as _).cpu;
+            let resource = swapchain.get_buffer(i as _).map_err(get_hal_error)?;
+            let rtv_handle = descriptors
+                .allocate(device, native::heap::DescriptorHeapType::Rtv)?
+                .cpu;
             device
                 .native
                 .create_render_target_view(&resource, &rtv_desc, rtv_handle);
+            rtv_handles.push(rtv_handle);
             resources.push(resource);
         }
 
-        Swapchain {
+        let states = resources
+            .iter()
+            .map(|_| SubmittedState::new(hal::ResourceState::PRESENT))
+            .collect();
+
+        Ok(Swapchain {
             native: swapchain,
-            heap,
+            descriptors: Rc::new(descriptors),
+            rtv_handles,
             resources,
-        }
+            states,
+        })
     }
 }
 
 impl hal::Swapchain<Backend> for Swapchain {
-    fn acquire_buffer(&self) -> hal::SwapchainBufferIndex {
-        self.native.get_current_back_buffer_index()
+    fn acquire_buffer(&self) -> hal::Result<hal::SwapchainBufferIndex> {
+        Ok(self.native.get_current_back_buffer_index())
     }
 
-    fn present(&self) {
+    fn present(&self) -> hal::Result<()> {
+        let index = self.native.get_current_back_buffer_index() as usize;
+        debug_assert_eq!(
+            self.states[index].get(),
+            hal::ResourceState::PRESENT,
+            "Back buffer presented while not in present state"
+        );
+
         // TODO: Add proper support for tearing
         self.native
             .present(1, native::dxgi::PresentFlags::empty())
-            .expect("Failed to present DXGI swapchain")
+            .map_err(get_hal_error)
     }
 
     fn create_backbuffer(&self) -> hal::BackBuffer<Backend> {
@@ -129,14 +145,15 @@ impl hal::Swapchain<Backend> for Swapchain {
             .iter()
             .enumerate()
             .map(|(i, resource)| {
-                let rtv_handle = self.heap.offset(i as _).cpu;
                 let buffer_view = BufferView {
                     resource: resource.clone(),
-                    rtv_handle: Some(rtv_handle),
+                    rtv_handle: self.rtv_handles[i],
+                    state: self.states[i].clone(),
                 };
 
                 FrameBuffer {
                     attachments: vec![buffer_view],
+                    descriptors: self.descriptors.clone(),
                 }
             })
             .collect();
@@ -145,9 +162,73 @@ impl hal::Swapchain<Backend> for Swapchain {
     }
 }
 
-fn get_native_format(format: hal::format::Format) -> native::dxgi::Format {
+pub(crate) fn get_native_format(format: hal::Format) -> native::dxgi::Format {
     match format {
-        hal::format::Format::Rgba8Unorm => native::dxgi::Format::R8G8B8A8_UNORM,
-        // TODO: Add conversions for other formats
+        hal::Format::R8Unorm => native::dxgi::Format::R8_UNORM,
+        hal::Format::R8Snorm => native::dxgi::Format::R8_SNORM,
+        hal::Format::R8Uint => native::dxgi::Format::R8_UINT,
+        hal::Format::R8Sint => native::dxgi::Format::R8_SINT,
+        hal::Format::Rg8Unorm => native::dxgi::Format::R8G8_UNORM,
+        hal::Format::Rg8Snorm => native::dxgi::Format::R8G8_SNORM,
+        hal::Format::Rg8Uint => native::dxgi::Format::R8G8_UINT,
+        hal::Format::Rg8Sint => native::dxgi::Format::R8G8_SINT,
+        hal::Format::Rgba8Unorm => native::dxgi::Format::R8G8B8A8_UNORM,
+        hal::Format::Rgba8UnormSrgb => native::dxgi::Format::R8G8B8A8_UNORM_SRGB,
+        hal::Format::Rgba8Snorm => native::dxgi::Format::R8G8B8A8_SNORM,
+        hal::Format::Rgba8Uint => native::dxgi::Format::R8G8B8A8_UINT,
+        hal::Format::Rgba8Sint => native::dxgi::Format::R8G8B8A8_SINT,
+        hal::Format::Bgra8Unorm => native::dxgi::Format::B8G8R8A8_UNORM,
+        hal::Format::Bgra8UnormSrgb => native::dxgi::Format::B8G8R8A8_UNORM_SRGB,
+        hal::Format::R16Unorm => native::dxgi::Format::R16_UNORM,
+        hal::Format::R16Snorm => native::dxgi::Format::R16_SNORM,
+        hal::Format::R16Uint => native::dxgi::Format::R16_UINT,
+        hal::Format::R16Sint => native::dxgi::Format::R16_SINT,
+        hal::Format::R16Float => native::dxgi::Format::R16_FLOAT,
+        hal::Format::Rg16Unorm => native::dxgi::Format::R16G16_UNORM,
+        hal::Format::Rg16Snorm => native::dxgi::Format::R16G16_SNORM,
+        hal::Format::Rg16Uint => native::dxgi::Format::R16G16_UINT,
+        hal::Format::Rg16Sint => native::dxgi::Format::R16G16_SINT,
+        hal::Format::Rg16Float => native::dxgi::Format::R16G16_FLOAT,
+        hal::Format::Rgba16Unorm => native::dxgi::Format::R16G16B16A16_UNORM,
+        hal::Format::Rgba16Snorm => native::dxgi::Format::R16G16B16A16_SNORM,
+        hal::Format::Rgba16Uint => native::dxgi::Format::R16G16B16A16_UINT,
+        hal::Format::Rgba16Sint => native::dxgi::Format::R16G16B16A16_SINT,
+        hal::Format::Rgba16Float => native::dxgi::Format::R16G16B16A16_FLOAT,
+        hal::Format::R32Uint => native::dxgi::Format::R32_UINT,
+        hal::Format::R32Sint => native::dxgi::Format::R32_SINT,
+        hal::Format::R32Float => native::dxgi::Format::R32_FLOAT,
+        hal::Format::Rg32Uint => native::dxgi::Format::R32G32_UINT,
+        hal::Format::Rg32Sint => native::dxgi::Format::R32G32_SINT,
+        hal::Format::Rg32Float => native::dxgi::Format::R32G32_FLOAT,
+        hal::Format::Rgb32Uint => native::dxgi::Format::R32G32B32_UINT,
+        hal::Format::Rgb32Sint => native::dxgi::Format::R32G32B32_SINT,
+        hal::Format::Rgb32Float => native::dxgi::Format::R32G32B32_FLOAT,
+        hal::Format::Rgba32Uint => native::dxgi::Format::R32G32B32A32_UINT,
+        hal::Format::Rgba32Sint => native::dxgi::Format::R32G32B32A32_SINT,
+        hal::Format::Rgba32Float => native::dxgi::Format::R32G32B32A32_FLOAT,
+        hal::Format::B5g6r5Unorm => native::dxgi::Format::B5G6R5_UNORM,
+        hal::Format::Bgr5a1Unorm => native::dxgi::Format::B5G5R5A1_UNORM,
+        hal::Format::Rgb10a2Unorm => native::dxgi::Format::R10G10B10A2_UNORM,
+        hal::Format::Rgb10a2Uint => native::dxgi::Format::R10G10B10A2_UINT,
+        hal::Format::Rg11b10Float => native::dxgi::Format::R11G11B10_FLOAT,
+        hal::Format::Rgb9e5Float => native::dxgi::Format::R9G9B9E5_SHAREDEXP,
+        hal::Format::D16Unorm => native::dxgi::Format::D16_UNORM,
+        hal::Format::D24UnormS8Uint => native::dxgi::Format::D24_UNORM_S8_UINT,
+        hal::Format::D32Float => native::dxgi::Format::D32_FLOAT,
+        hal::Format::D32FloatS8Uint => native::dxgi::Format::D32_FLOAT_S8X24_UINT,
+        hal::Format::Bc1Unorm => native::dxgi::Format::BC1_UNORM,
+        hal::Format::Bc1UnormSrgb => native::dxgi::Format::BC1_UNORM_SRGB,
+        hal::Format::Bc2Unorm => native::dxgi::Format::BC2_UNORM,
+        hal::Format::Bc2UnormSrgb => native::dxgi::Format::BC2_UNORM_SRGB,
+        hal::Format::Bc3Unorm => native::dxgi::Format::BC3_UNORM,
+        hal::Format::Bc3UnormSrgb => native::dxgi::Format::BC3_UNORM_SRGB,
+        hal::Format::Bc4Unorm => native::dxgi::Format::BC4_UNORM,
+        hal::Format::Bc4Snorm => native::dxgi::Format::BC4_SNORM,
+        hal::Format::Bc5Unorm => native::dxgi::Format::BC5_UNORM,
+        hal::Format::Bc5Snorm => native::dxgi::Format::BC5_SNORM,
+        hal::Format::Bc6hUfloat => native::dxgi::Format::BC6H_UF16,
+        hal::Format::Bc6hSfloat => native::dxgi::Format::BC6H_SF16,
+        hal::Format::Bc7Unorm => native::dxgi::Format::BC7_UNORM,
+        hal::Format::Bc7UnormSrgb => native::dxgi::Format::BC7_UNORM_SRGB,
     }
 }
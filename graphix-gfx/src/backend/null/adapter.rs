@@ -0,0 +1,11 @@
+use crate::backend::null::device::Device;
+use crate::backend::null::instance::Backend;
+use crate::hal;
+
+pub struct PhysicalAdapter;
+
+impl hal::PhysicalAdapter<Backend> for PhysicalAdapter {
+    fn create_device(&self) -> hal::Result<Device> {
+        Ok(Device::new())
+    }
+}
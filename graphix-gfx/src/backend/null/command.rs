@@ -0,0 +1,221 @@
+use crate::backend::null::device::{Device, DeviceState};
+use crate::backend::null::instance::Backend;
+use crate::backend::null::resource::{DescriptorSet, FrameBuffer, PipelineLayout, RenderPass};
+use crate::backend::validation;
+use crate::hal;
+
+use std::cell::Cell;
+
+pub struct CommandPool {
+    device_state: DeviceState,
+    pool_type: hal::QueueType,
+    flags: hal::CommandPoolFlags,
+}
+
+impl CommandPool {
+    pub(crate) fn new(
+        device: &Device,
+        pool_type: hal::QueueType,
+        flags: hal::CommandPoolFlags,
+    ) -> Self {
+        CommandPool {
+            device_state: device.state.clone(),
+            pool_type,
+            flags,
+        }
+    }
+}
+
+impl hal::CommandPool<Backend> for CommandPool {
+    fn reset(&self) -> hal::Result<()> {
+        self.device_state.check()
+    }
+
+    fn create_buffer(&mut self) -> hal::Result<CommandBuffer> {
+        self.device_state.check()?;
+        Ok(CommandBuffer::new(
+            &self.device_state,
+            self.pool_type,
+            self.flags,
+        ))
+    }
+}
+
+pub struct CommandBuffer {
+    device_state: DeviceState,
+    pub(crate) buffer_type: hal::QueueType,
+    flags: hal::CommandPoolFlags,
+    recording: Cell<bool>,
+    in_render_pass: Cell<bool>,
+    split_barrier_count: Cell<usize>,
+}
+
+impl CommandBuffer {
+    pub(crate) fn new(
+        device_state: &DeviceState,
+        buffer_type: hal::QueueType,
+        flags: hal::CommandPoolFlags,
+    ) -> Self {
+        CommandBuffer {
+            device_state: device_state.clone(),
+            buffer_type,
+            flags,
+            recording: Cell::new(false),
+            in_render_pass: Cell::new(false),
+            split_barrier_count: Cell::new(0),
+        }
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recording.get()
+    }
+
+    fn check_barriers(&self, barriers: &[hal::Barrier<Backend>], is_split: bool) {
+        debug_assert!(self.is_recording(), "Command buffer is not recording");
+        debug_assert!(
+            !self.in_render_pass.get(),
+            "Barriers inserted inside a render pass"
+        );
+        for barrier in barriers {
+            match *barrier {
+                hal::Barrier::Buffer { state, .. } => {
+                    debug_assert!(state.is_valid(), "Invalid resource state {:?}", state);
+                }
+                hal::Barrier::Image {
+                    image,
+                    ref range,
+                    state,
+                } => {
+                    debug_assert!(state.is_valid(), "Invalid resource state {:?}", state);
+                    if let Some(range) = range {
+                        let desc = image.desc();
+                        debug_assert!(
+                            range.mip_levels.end <= desc.mip_levels
+                                && range.array_layers.end <= desc.array_layers
+                                && desc.format.aspects().contains(range.aspects),
+                            "Subresource range {:?} is out of the image",
+                            range
+                        );
+                    }
+                }
+                hal::Barrier::Storage(_) | hal::Barrier::Aliasing { .. } => {
+                    debug_assert!(!is_split, "Only buffer and image transitions can be split");
+                }
+            }
+        }
+    }
+}
+
+impl hal::CommandBuffer<Backend> for CommandBuffer {
+    fn begin(&self) -> hal::Result<()> {
+        self.device_state.check()?;
+        debug_assert!(
+            !self.is_recording() || self.flags.contains(hal::CommandPoolFlags::SINGLE_LIST),
+            "Command buffer is already recording"
+        );
+        self.recording.set(true);
+        self.in_render_pass.set(false);
+        self.split_barrier_count.set(0);
+        Ok(())
+    }
+
+    fn end(&self) -> hal::Result<()> {
+        self.device_state.check()?;
+        debug_assert!(self.is_recording(), "Command buffer is not recording");
+        debug_assert!(
+            !self.in_render_pass.get(),
+            "Command buffer ended inside a render pass"
+        );
+        debug_assert_eq!(
+            self.split_barrier_count.get(),
+            0,
+            "Command buffer ended with split barriers that were not ended"
+        );
+        self.recording.set(false);
+        Ok(())
+    }
+
+    fn insert_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        self.check_barriers(barriers, false);
+    }
+
+    fn begin_split_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        self.check_barriers(barriers, true);
+        self.split_barrier_count
+            .set(self.split_barrier_count.get() + barriers.len());
+    }
+
+    fn end_split_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        self.check_barriers(barriers, true);
+        debug_assert!(
+            self.split_barrier_count.get() >= barriers.len(),
+            "Split barriers ended without being begun"
+        );
+        self.split_barrier_count.set(
+            self.split_barrier_count
+                .get()
+                .saturating_sub(barriers.len()),
+        );
+    }
+
+    fn begin_render_pass(
+        &self,
+        render_pass: &RenderPass,
+        framebuffer: &FrameBuffer,
+        _clear_values: &[hal::ClearColor],
+    ) {
+        debug_assert!(self.is_recording(), "Command buffer is not recording");
+        debug_assert!(
+            !self.in_render_pass.get(),
+            "Render pass begun inside another render pass"
+        );
+        debug_assert_eq!(
+            render_pass.attachments.len(),
+            framebuffer.attachments.len(),
+            "Render pass and framebuffer attachments don't match"
+        );
+        self.in_render_pass.set(true);
+    }
+
+    fn end_render_pass(&self) {
+        debug_assert!(self.is_recording(), "Command buffer is not recording");
+        debug_assert!(
+            self.in_render_pass.get(),
+            "Render pass ended without being begun"
+        );
+        self.in_render_pass.set(false);
+    }
+
+    fn bind_descriptor_sets(
+        &self,
+        _bind_point: hal::PipelineBindPoint,
+        layout: &PipelineLayout,
+        first_set: u32,
+        sets: &[&DescriptorSet],
+    ) {
+        debug_assert!(self.is_recording(), "Command buffer is not recording");
+        // Layouts have at most as many sets as the device supports
+        validation::check_sets(
+            &layout.set_layouts,
+            first_set,
+            sets.iter().map(|set| &*set.layout),
+        );
+    }
+
+    fn push_constants(
+        &self,
+        _bind_point: hal::PipelineBindPoint,
+        layout: &PipelineLayout,
+        stages: hal::ShaderStageFlags,
+        offset: u32,
+        data: &[u8],
+    ) {
+        debug_assert!(self.is_recording(), "Command buffer is not recording");
+        validation::check_push_constants(
+            &layout.push_constant_ranges,
+            stages,
+            offset,
+            data.len() as u32,
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use crate::backend::null::command::CommandPool;
+use crate::backend::null::instance::Backend;
+use crate::backend::null::queue::CommandQueue;
+use crate::backend::null::resource::{
+    Buffer, BufferView, DescriptorSet, DescriptorSetLayout, FrameBuffer, Heap, Image,
+    PipelineLayout, RenderPass,
+};
+use crate::backend::null::Fence;
+use crate::backend::validation;
+use crate::hal;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+// Placement alignment of most GPUs
+const RESOURCE_ALIGNMENT: u64 = 64 * 1024;
+const MAX_DESCRIPTORS: u32 = 1_000_000;
+const MAX_DESCRIPTOR_SETS: u32 = 8;
+const MAX_PUSH_CONSTANTS_SIZE: u32 = 128;
+
+/// Loss state shared by a device and every object created from it.
+#[derive(Clone, Default)]
+pub(crate) struct DeviceState {
+    lost_reason: Rc<Cell<Option<hal::DeviceLostReason>>>,
+}
+
+impl DeviceState {
+    pub(crate) fn check(&self) -> hal::Result<()> {
+        match self.lost_reason.get() {
+            Some(_) => Err(hal::Error::DeviceLost),
+            None => Ok(()),
+        }
+    }
+}
+
+pub struct Device {
+    pub(crate) state: DeviceState,
+}
+
+impl Device {
+    pub(crate) fn new() -> Self {
+        Device {
+            state: Default::default(),
+        }
+    }
+
+    /// Makes the device behave as if it had been lost, to exercise recovery paths.
+    pub fn simulate_device_lost(&self, reason: hal::DeviceLostReason) {
+        self.state.lost_reason.set(Some(reason));
+    }
+}
+
+impl hal::Device<Backend> for Device {
+    fn create_command_queue(&self, queue_type: hal::QueueType) -> hal::Result<CommandQueue> {
+        self.state.check()?;
+        Ok(CommandQueue::new(self, queue_type))
+    }
+
+    fn create_command_pool(
+        &self,
+        pool_type: hal::QueueType,
+        flags: hal::CommandPoolFlags,
+    ) -> hal::Result<CommandPool> {
+        self.state.check()?;
+        Ok(CommandPool::new(self, pool_type, flags))
+    }
+
+    fn create_buffer(
+        &self,
+        size: u64,
+        usage: hal::BufferUsage,
+        memory_type: hal::MemoryType,
+    ) -> hal::Result<Buffer> {
+        self.state.check()?;
+        Ok(Buffer::new(size, usage, memory_type))
+    }
+
+    fn map_buffer(&self, buffer: &Buffer) -> hal::Result<*mut u8> {
+        self.state.check()?;
+        buffer.map()
+    }
+
+    fn unmap_buffer(&self, buffer: &Buffer) {
+        buffer.unmap()
+    }
+
+    fn limits(&self) -> hal::Limits {
+        // Matches a typical feature level 12_0 desktop GPU
+        hal::Limits {
+            feature_level: hal::FeatureLevel::L12_0,
+            max_image_dimension_1d: 16384,
+            max_image_dimension_2d: 16384,
+            max_image_dimension_3d: 2048,
+            max_image_dimension_cube: 16384,
+            max_image_array_layers: 2048,
+            max_color_attachments: 8,
+            max_descriptors: MAX_DESCRIPTORS,
+            max_samplers: 2048,
+            max_descriptor_sets: MAX_DESCRIPTOR_SETS,
+            max_bindless_descriptors: MAX_DESCRIPTORS,
+            max_push_constants_size: MAX_PUSH_CONSTANTS_SIZE,
+            sample_counts: 1 | 2 | 4 | 8,
+            min_constant_buffer_offset_alignment: 256,
+        }
+    }
+
+    fn features(&self) -> hal::Features {
+        hal::Features::TILED_RESOURCES
+            | hal::Features::SAMPLER_ANISOTROPY
+            | hal::Features::TEXTURE_COMPRESSION_BC
+            | hal::Features::SHADER_FLOAT64
+            | hal::Features::MIXED_RESOURCE_HEAPS
+            | hal::Features::BINDLESS
+    }
+
+    fn format_properties(&self, format: hal::Format) -> hal::FormatProperties {
+        // Reports what is commonly supported on desktop GPUs, so that fallbacks get exercised
+        let desc = format.desc();
+        if desc.aspects != hal::Aspects::COLOR {
+            return hal::FormatProperties::SAMPLED
+                | hal::FormatProperties::DEPTH_STENCIL_ATTACHMENT;
+        }
+        if desc.is_compressed() {
+            return hal::FormatProperties::SAMPLED;
+        }
+
+        let mut properties = hal::FormatProperties::SAMPLED;
+        match format {
+            hal::Format::Rgb32Uint
+            | hal::Format::Rgb32Sint
+            | hal::Format::Rgb32Float
+            | hal::Format::Rgb9e5Float => {}
+            _ => {
+                properties |= hal::FormatProperties::COLOR_ATTACHMENT;
+                if let hal::NumericType::Unorm | hal::NumericType::Snorm | hal::NumericType::Float =
+                    desc.numeric_type
+                {
+                    properties |= hal::FormatProperties::BLEND;
+                }
+            }
+        }
+        if !desc.is_srgb && !desc.is_packed && desc.channel_count != 3 {
+            properties |= hal::FormatProperties::STORAGE;
+        }
+        properties
+    }
+
+    fn create_image(&self, desc: hal::ImageDesc) -> hal::Result<Image> {
+        self.state.check()?;
+        Ok(Image::new(desc))
+    }
+
+    fn create_heap(
+        &self,
+        size: u64,
+        memory_type: hal::MemoryType,
+        usage: hal::HeapUsage,
+    ) -> hal::Result<Heap> {
+        self.state.check()?;
+        Ok(Heap::new(size, memory_type, usage))
+    }
+
+    fn get_buffer_requirements(
+        &self,
+        size: u64,
+        _usage: hal::BufferUsage,
+    ) -> hal::Result<hal::MemoryRequirements> {
+        self.state.check()?;
+        Ok(hal::MemoryRequirements {
+            size,
+            alignment: RESOURCE_ALIGNMENT,
+        })
+    }
+
+    fn get_image_requirements(
+        &self,
+        desc: &hal::ImageDesc,
+    ) -> hal::Result<hal::MemoryRequirements> {
+        self.state.check()?;
+        Ok(hal::MemoryRequirements {
+            size: desc.get_packed_size(),
+            alignment: RESOURCE_ALIGNMENT,
+        })
+    }
+
+    fn create_placed_buffer(
+        &self,
+        heap: &Heap,
+        offset: u64,
+        size: u64,
+        usage: hal::BufferUsage,
+    ) -> hal::Result<Buffer> {
+        let requirements = self.get_buffer_requirements(size, usage)?;
+        validation::check_placement(
+            heap.usage(),
+            heap.size(),
+            hal::HeapUsage::BUFFERS,
+            requirements,
+            offset,
+        );
+        Ok(Buffer::new(size, usage, heap.memory_type()))
+    }
+
+    fn create_placed_image(
+        &self,
+        heap: &Heap,
+        offset: u64,
+        desc: hal::ImageDesc,
+    ) -> hal::Result<Image> {
+        let requirements = self.get_image_requirements(&desc)?;
+        validation::check_placement(
+            heap.usage(),
+            heap.size(),
+            hal::HeapUsage::from_image_usage(desc.usage),
+            requirements,
+            offset,
+        );
+        Ok(Image::new(desc))
+    }
+
+    fn create_render_pass(&self, desc: &hal::RenderPassDesc) -> hal::Result<RenderPass> {
+        self.state.check()?;
+        // Matches the other backends, which only render to color attachments for now
+        if !desc.has_only_color_attachments() {
+            return Err(hal::Error::Unsupported);
+        }
+
+        Ok(RenderPass {
+            attachments: desc.attachments.clone(),
+        })
+    }
+
+    fn create_framebuffer(&self, attachments: &[&Image]) -> hal::Result<FrameBuffer> {
+        self.state.check()?;
+        if attachments.iter().any(|image| {
+            !image
+                .desc()
+                .usage
+                .contains(hal::ImageUsage::COLOR_ATTACHMENT)
+        }) {
+            return Err(hal::Error::Unsupported);
+        }
+        let attachments = (0..attachments.len())
+            .map(|buffer_index| BufferView { buffer_index })
+            .collect();
+
+        Ok(FrameBuffer { attachments })
+    }
+
+    fn create_descriptor_set_layout(
+        &self,
+        desc: &hal::DescriptorSetLayoutDesc,
+    ) -> hal::Result<DescriptorSetLayout> {
+        self.state.check()?;
+        if desc.get_descriptor_count() > MAX_DESCRIPTORS {
+            return Err(hal::Error::Unsupported);
+        }
+        Ok(DescriptorSetLayout {
+            desc: Rc::new(desc.clone()),
+        })
+    }
+
+    fn create_descriptor_set(&self, layout: &DescriptorSetLayout) -> hal::Result<DescriptorSet> {
+        self.state.check()?;
+        Ok(DescriptorSet {
+            layout: layout.desc.clone(),
+        })
+    }
+
+    fn write_descriptors(&self, writes: &[hal::DescriptorWrite<Backend>]) -> hal::Result<()> {
+        self.state.check()?;
+        for write in writes {
+            validation::check_write(&write.set.layout, write);
+        }
+        Ok(())
+    }
+
+    fn create_pipeline_layout(
+        &self,
+        set_layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[hal::PushConstantRange],
+    ) -> hal::Result<PipelineLayout> {
+        self.state.check()?;
+        let push_constants_size = hal::PushConstantRange::get_total_size(push_constant_ranges);
+        if set_layouts.len() as u32 > MAX_DESCRIPTOR_SETS
+            || push_constants_size > MAX_PUSH_CONSTANTS_SIZE
+        {
+            return Err(hal::Error::Unsupported);
+        }
+        Ok(PipelineLayout {
+            set_layouts: set_layouts
+                .iter()
+                .map(|set_layout| set_layout.desc.clone())
+                .collect(),
+            push_constant_ranges: push_constant_ranges.to_vec(),
+        })
+    }
+
+    fn create_fence(&self, initial_value: u64) -> hal::Result<Fence> {
+        self.state.check()?;
+        Ok(Fence::new(initial_value))
+    }
+
+    fn reset_fence(&self, fence: &Fence) -> hal::Result<()> {
+        self.state.check()?;
+        fence.signal(0);
+        Ok(())
+    }
+
+    fn wait_for_fence_with_timeout(
+        &self,
+        fence: &Fence,
+        value: u64,
+        _timeout: u64,
+    ) -> hal::Result<()> {
+        self.state.check()?;
+
+        // Work completes on submit, so a value that has not been signaled yet never will be
+        if fence.get_value() >= value {
+            Ok(())
+        } else {
+            Err(hal::Error::Timeout)
+        }
+    }
+
+    fn device_lost_reason(&self) -> Option<hal::DeviceLostReason> {
+        self.state.lost_reason.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::null::{Fence, Instance};
+    use crate::hal::{
+        self, CommandQueue as _, Device as _, Instance as _, Surface as _, Swapchain as _,
+    };
+
+    #[test]
+    fn recovers_from_device_loss() {
+        let instance = Instance::new();
+        let adapter = instance
+            .select_adapter(&hal::AdapterPreference::HighPerformance)
+            .unwrap();
+        let surface = instance.create_surface_headless();
+        let config = || hal::SwapchainConfig {
+            format: hal::Format::Rgba8Unorm,
+            buffer_count: 2,
+            width: 64,
+            height: 64,
+            sync_interval: 1,
+        };
+
+        let device = adapter.create_device().unwrap();
+        let queue = device
+            .create_command_queue(hal::QueueType::Graphics)
+            .unwrap();
+        let swapchain = surface.create_swapchain(&device, &queue, config()).unwrap();
+        let fence: Fence = device.create_fence(0).unwrap();
+        queue.signal_fence(&fence, 1).unwrap();
+        assert_eq!(device.device_lost_reason(), None);
+
+        device.simulate_device_lost(hal::DeviceLostReason::Hung);
+        assert_eq!(
+            device.device_lost_reason(),
+            Some(hal::DeviceLostReason::Hung)
+        );
+        assert_eq!(swapchain.present(), Err(hal::Error::DeviceLost));
+        assert_eq!(
+            device.wait_for_fence(&fence, 1),
+            Err(hal::Error::DeviceLost)
+        );
+        assert_eq!(
+            queue.signal_fence(&fence, 2).err(),
+            Some(hal::Error::DeviceLost)
+        );
+        drop((swapchain, fence, queue, device));
+
+        // The adapter and surface outlive the device and can be reused
+        let device = adapter.create_device().unwrap();
+        assert_eq!(device.device_lost_reason(), None);
+        let queue = device
+            .create_command_queue(hal::QueueType::Graphics)
+            .unwrap();
+        let swapchain = surface.create_swapchain(&device, &queue, config()).unwrap();
+        swapchain.acquire_buffer().unwrap();
+        swapchain.present().unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+use crate::backend::null::adapter::PhysicalAdapter;
+use crate::backend::null::command::{CommandBuffer, CommandPool};
+use crate::backend::null::device::Device;
+use crate::backend::null::queue::CommandQueue;
+use crate::backend::null::resource::{
+    Buffer, DescriptorSet, DescriptorSetLayout, FrameBuffer, Heap, Image, PipelineLayout,
+    RenderPass,
+};
+use crate::backend::null::window::{Surface, Swapchain};
+use crate::backend::null::Fence;
+use crate::hal;
+
+pub enum Backend {}
+impl hal::Backend for Backend {
+    type PhysicalAdapter = PhysicalAdapter;
+    type Device = Device;
+
+    type CommandQueue = CommandQueue;
+    type CommandPool = CommandPool;
+    type CommandBuffer = CommandBuffer;
+
+    type Surface = Surface;
+    type Swapchain = Swapchain;
+
+    type RenderPass = RenderPass;
+    type FrameBuffer = FrameBuffer;
+    type Buffer = Buffer;
+    type Image = Image;
+    type Heap = Heap;
+    type DescriptorSetLayout = DescriptorSetLayout;
+    type DescriptorSet = DescriptorSet;
+    type PipelineLayout = PipelineLayout;
+
+    type Fence = Fence;
+}
+
+#[derive(Default)]
+pub struct Instance;
+
+impl Instance {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl hal::Instance for Instance {
+    type Backend = Backend;
+
+    fn enumerate_adapters(&self) -> Vec<hal::Adapter<Backend>> {
+        vec![hal::Adapter {
+            adapter: PhysicalAdapter,
+            info: hal::AdapterInfo {
+                name: "Null Adapter".to_owned(),
+                vendor: 0,
+                device: 0,
+                video_memory: 0,
+                device_type: hal::DeviceType::VirtualGpu,
+            },
+        }]
+    }
+}
@@ -0,0 +1,42 @@
+use crate::backend::null::command::CommandBuffer;
+use crate::backend::null::device::{Device, DeviceState};
+use crate::backend::null::instance::Backend;
+use crate::backend::null::Fence;
+use crate::hal;
+
+pub struct CommandQueue {
+    pub(crate) device_state: DeviceState,
+    pub(crate) queue_type: hal::QueueType,
+}
+
+impl CommandQueue {
+    pub(crate) fn new(device: &Device, queue_type: hal::QueueType) -> Self {
+        CommandQueue {
+            device_state: device.state.clone(),
+            queue_type,
+        }
+    }
+}
+
+impl hal::CommandQueue<Backend> for CommandQueue {
+    fn submit(&self, command_buffers: Vec<&CommandBuffer>) -> hal::Result<()> {
+        self.device_state.check()?;
+        for command_buffer in command_buffers {
+            debug_assert!(
+                !command_buffer.is_recording(),
+                "Submitted a command buffer that is still recording"
+            );
+            debug_assert_eq!(
+                command_buffer.buffer_type, self.queue_type,
+                "Submitted a command buffer to a queue of a different type"
+            );
+        }
+        Ok(())
+    }
+
+    fn signal_fence(&self, fence: &Fence, value: u64) -> hal::Result<()> {
+        self.device_state.check()?;
+        fence.signal(value);
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use crate::backend::null::instance::Backend;
+use crate::backend::validation::ResourceUsages;
+use crate::hal;
+
+use std::cell::UnsafeCell;
+use std::rc::Rc;
+
+pub struct Buffer {
+    size: u64,
+    usage: hal::BufferUsage,
+    memory_type: hal::MemoryType,
+    // Host visible buffers get real memory so that mapped pointers can be written to
+    data: UnsafeCell<Vec<u8>>,
+}
+
+impl Buffer {
+    pub(crate) fn new(size: u64, usage: hal::BufferUsage, memory_type: hal::MemoryType) -> Self {
+        let data = if memory_type.is_host_visible() {
+            vec![0; size as usize]
+        } else {
+            Vec::new()
+        };
+
+        Buffer {
+            size,
+            usage,
+            memory_type,
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn usage(&self) -> hal::BufferUsage {
+        self.usage
+    }
+
+    pub fn memory_type(&self) -> hal::MemoryType {
+        self.memory_type
+    }
+
+    pub(crate) fn map(&self) -> hal::Result<*mut u8> {
+        if !self.memory_type.is_host_visible() {
+            return Err(hal::Error::Unsupported);
+        }
+
+        Ok(unsafe { (*self.data.get()).as_mut_ptr() })
+    }
+
+    pub(crate) fn unmap(&self) {}
+}
+
+pub struct Image {
+    desc: hal::ImageDesc,
+}
+
+impl Image {
+    pub(crate) fn new(desc: hal::ImageDesc) -> Self {
+        Image { desc }
+    }
+
+    pub fn desc(&self) -> &hal::ImageDesc {
+        &self.desc
+    }
+}
+
+impl ResourceUsages for Backend {
+    fn get_buffer_usage(buffer: &Buffer) -> hal::BufferUsage {
+        buffer.usage()
+    }
+
+    fn get_image_usage(image: &Image) -> hal::ImageUsage {
+        image.desc().usage
+    }
+}
+
+/// Memory resources are placed in. Placed resources get memory of their own, as aliasing only
+/// leaves their contents undefined.
+pub struct Heap {
+    size: u64,
+    memory_type: hal::MemoryType,
+    usage: hal::HeapUsage,
+}
+
+impl Heap {
+    pub(crate) fn new(size: u64, memory_type: hal::MemoryType, usage: hal::HeapUsage) -> Self {
+        Heap {
+            size,
+            memory_type,
+            usage,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn memory_type(&self) -> hal::MemoryType {
+        self.memory_type
+    }
+
+    pub fn usage(&self) -> hal::HeapUsage {
+        self.usage
+    }
+}
+
+#[derive(Clone)]
+pub struct BufferView {
+    pub buffer_index: usize,
+}
+
+pub struct RenderPass {
+    pub(crate) attachments: Vec<hal::AttachmentDesc>,
+}
+
+#[derive(Clone)]
+pub struct FrameBuffer {
+    pub attachments: Vec<BufferView>,
+}
+
+pub struct DescriptorSetLayout {
+    pub(crate) desc: Rc<hal::DescriptorSetLayoutDesc>,
+}
+
+pub struct DescriptorSet {
+    pub(crate) layout: Rc<hal::DescriptorSetLayoutDesc>,
+}
+
+pub struct PipelineLayout {
+    pub(crate) set_layouts: Vec<Rc<hal::DescriptorSetLayoutDesc>>,
+    pub(crate) push_constant_ranges: Vec<hal::PushConstantRange>,
+}
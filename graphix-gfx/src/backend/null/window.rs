@@ -0,0 +1,157 @@
+use crate::backend::null::device::{Device, DeviceState};
+use crate::backend::null::instance::{Backend, Instance};
+use crate::backend::null::queue::CommandQueue;
+use crate::backend::null::resource::{BufferView, FrameBuffer};
+use crate::hal;
+
+use std::cell::Cell;
+
+impl Instance {
+    pub fn create_surface_headless(&self) -> Surface {
+        Surface
+    }
+
+    #[cfg(feature = "winit")]
+    pub fn create_surface(&self, _window: &winit::Window) -> Surface {
+        self.create_surface_headless()
+    }
+}
+
+pub struct Surface;
+
+impl hal::Surface<Backend> for Surface {
+    fn create_swapchain(
+        &self,
+        device: &Device,
+        _command_queue: &CommandQueue,
+        config: hal::SwapchainConfig,
+    ) -> hal::Result<Swapchain> {
+        device.state.check()?;
+        Ok(Swapchain::new(device, config))
+    }
+}
+
+pub struct Swapchain {
+    device_state: DeviceState,
+    pub(crate) config: hal::SwapchainConfig,
+    current_index: Cell<usize>,
+}
+
+impl Swapchain {
+    pub fn new(device: &Device, config: hal::SwapchainConfig) -> Self {
+        assert!(
+            config.buffer_count > 0,
+            "Swapchain needs at least one buffer"
+        );
+
+        Swapchain {
+            device_state: device.state.clone(),
+            config,
+            current_index: Cell::new(0),
+        }
+    }
+}
+
+impl hal::Swapchain<Backend> for Swapchain {
+    fn acquire_buffer(&self) -> hal::Result<hal::SwapchainBufferIndex> {
+        self.device_state.check()?;
+        Ok(self.current_index.get() as _)
+    }
+
+    fn present(&self) -> hal::Result<()> {
+        self.device_state.check()?;
+        // Flip to the next virtual back buffer
+        let next_index = (self.current_index.get() + 1) % self.config.buffer_count;
+        self.current_index.set(next_index);
+        Ok(())
+    }
+
+    fn create_backbuffer(&self) -> hal::BackBuffer<Backend> {
+        let framebuffers = (0..self.config.buffer_count)
+            .map(|buffer_index| FrameBuffer {
+                attachments: vec![BufferView { buffer_index }],
+            })
+            .collect();
+
+        hal::BackBuffer { framebuffers }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::null::Fence;
+    use crate::hal::{
+        CommandBuffer as _, CommandPool as _, CommandQueue as _, Device as _, Instance as _,
+        Surface as _, Swapchain as _,
+    };
+
+    #[test]
+    fn runs_frame_loop() {
+        let instance = Instance::new();
+        let adapter = instance
+            .select_adapter(&hal::AdapterPreference::HighPerformance)
+            .unwrap();
+        let device = adapter.create_device().unwrap();
+        let queue = device
+            .create_command_queue(hal::QueueType::Graphics)
+            .unwrap();
+        let swapchain = instance
+            .create_surface_headless()
+            .create_swapchain(
+                &device,
+                &queue,
+                hal::SwapchainConfig {
+                    format: hal::Format::Rgba8Unorm,
+                    buffer_count: 3,
+                    width: 64,
+                    height: 64,
+                    sync_interval: 1,
+                },
+            )
+            .unwrap();
+        let backbuffer = swapchain.create_backbuffer();
+        assert_eq!(backbuffer.framebuffers.len(), 3);
+
+        let render_pass = device
+            .create_render_pass(&hal::RenderPassDesc {
+                attachments: vec![hal::AttachmentDesc {
+                    format: hal::Format::Rgba8Unorm,
+                    load_op: hal::LoadOp::Clear,
+                    store_op: hal::StoreOp::Store,
+                    final_state: hal::ResourceState::PRESENT,
+                }],
+            })
+            .unwrap();
+        let mut command_pool = device
+            .create_command_pool(hal::QueueType::Graphics, hal::CommandPoolFlags::empty())
+            .unwrap();
+        let command_buffers = command_pool.create_buffers(3).unwrap();
+        let fence: Fence = device.create_fence(0).unwrap();
+
+        for frame in 0..7 {
+            // Back buffers are handed out in turn
+            let index = swapchain.acquire_buffer().unwrap() as usize;
+            assert_eq!(index, frame % 3);
+
+            let command_buffer = &command_buffers[index];
+            command_buffer.begin().unwrap();
+            command_buffer.begin_render_pass(
+                &render_pass,
+                &backbuffer.framebuffers[index],
+                &[[0.0, 0.0, 0.0, 1.0]],
+            );
+            command_buffer.end_render_pass();
+            command_buffer.end().unwrap();
+            queue.submit(vec![command_buffer]).unwrap();
+            swapchain.present().unwrap();
+
+            // Work completes on submission, so waiting never blocks
+            queue.signal_fence(&fence, frame as u64 + 1).unwrap();
+            assert_eq!(fence.get_value(), frame as u64 + 1);
+            device
+                .wait_for_fence_with_timeout(&fence, frame as u64 + 1, 0)
+                .unwrap();
+        }
+    }
+}
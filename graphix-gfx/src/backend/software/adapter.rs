@@ -0,0 +1,11 @@
+use crate::backend::software::device::Device;
+use crate::backend::software::instance::Backend;
+use crate::hal;
+
+pub struct PhysicalAdapter;
+
+impl hal::PhysicalAdapter<Backend> for PhysicalAdapter {
+    fn create_device(&self) -> hal::Result<Device> {
+        Ok(Device::new())
+    }
+}
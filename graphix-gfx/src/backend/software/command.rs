@@ -0,0 +1,414 @@
+use crate::backend::software::instance::Backend;
+use crate::backend::software::resource::{
+    DescriptorSet, FrameBuffer, Image, PipelineLayout, RenderPass,
+};
+use crate::backend::tracker::{StateTracker, SubmittedState, Transition};
+use crate::backend::validation;
+use crate::hal;
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+pub(crate) enum Command {
+    Transition(Transition<SubmittedState<hal::ResourceState>, hal::ResourceState>),
+    Clear {
+        image: Rc<Image>,
+        clear_color: hal::ClearColor,
+    },
+}
+
+impl Command {
+    pub(crate) fn execute(&self) {
+        match *self {
+            Command::Transition(ref transition) => apply_transition(transition),
+            Command::Clear {
+                ref image,
+                clear_color,
+            } => image.clear(clear_color),
+        }
+    }
+}
+
+/// Moves subresources of a buffer or image out of `states.start`, which they must currently be
+/// in.
+pub(crate) fn apply_transition(
+    transition: &Transition<SubmittedState<hal::ResourceState>, hal::ResourceState>,
+) {
+    let state = &transition.resource;
+    let subresources = match transition.subresource {
+        Some(subresource) => subresource..subresource + 1,
+        None => 0..state.subresource_count(),
+    };
+    for subresource in subresources {
+        assert_eq!(
+            state.get_subresource(subresource),
+            transition.states.start,
+            "Subresource {} transitioned from {:?} while in {:?} state",
+            subresource,
+            transition.states.start,
+            state.get_subresource(subresource)
+        );
+        state.set_subresource(subresource, transition.states.end);
+    }
+}
+
+/// Submitted state, subresources and target state of a buffer or image transition.
+fn get_transition<'a>(
+    barrier: &hal::Barrier<'a, Backend>,
+) -> (
+    &'a SubmittedState<hal::ResourceState>,
+    Option<Vec<u32>>,
+    hal::ResourceState,
+) {
+    match *barrier {
+        hal::Barrier::Buffer { buffer, state } => (&buffer.state, None, state),
+        hal::Barrier::Image {
+            image,
+            ref range,
+            state,
+        } => {
+            let subresources = range
+                .as_ref()
+                .map(|range| image.desc().get_subresource_indices(range));
+            (&image.state, subresources, state)
+        }
+        _ => panic!("Only buffer and image transitions can be split"),
+    }
+}
+
+pub struct CommandPool {
+    pool_type: hal::QueueType,
+    flags: hal::CommandPoolFlags,
+}
+
+impl CommandPool {
+    pub(crate) fn new(pool_type: hal::QueueType, flags: hal::CommandPoolFlags) -> Self {
+        CommandPool { pool_type, flags }
+    }
+}
+
+impl hal::CommandPool<Backend> for CommandPool {
+    fn reset(&self) -> hal::Result<()> {
+        Ok(())
+    }
+
+    fn create_buffer(&mut self) -> hal::Result<CommandBuffer> {
+        Ok(CommandBuffer::new(self.pool_type, self.flags))
+    }
+}
+
+pub struct CommandBuffer {
+    pub(crate) buffer_type: hal::QueueType,
+    flags: hal::CommandPoolFlags,
+    recording: Cell<bool>,
+    pub(crate) commands: RefCell<Vec<Command>>,
+    pub(crate) tracker:
+        RefCell<StateTracker<SubmittedState<hal::ResourceState>, hal::ResourceState>>,
+    in_render_pass: Cell<bool>,
+    // Attachments of the current render pass and the states they end it in
+    pass_final_states: RefCell<Vec<(Rc<Image>, hal::ResourceState)>>,
+}
+
+impl CommandBuffer {
+    pub(crate) fn new(buffer_type: hal::QueueType, flags: hal::CommandPoolFlags) -> Self {
+        CommandBuffer {
+            buffer_type,
+            flags,
+            recording: Cell::new(false),
+            commands: RefCell::new(Vec::new()),
+            tracker: RefCell::new(StateTracker::new()),
+            in_render_pass: Cell::new(false),
+            pass_final_states: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recording.get()
+    }
+
+    fn transition(
+        &self,
+        submitted_state: &SubmittedState<hal::ResourceState>,
+        subresources: Option<&[u32]>,
+        state: hal::ResourceState,
+    ) {
+        assert!(state.is_valid(), "Invalid resource state {:?}", state);
+        let transitions = self.tracker.borrow_mut().transition(
+            submitted_state,
+            submitted_state,
+            subresources,
+            state,
+        );
+        for transition in transitions {
+            self.record(Command::Transition(transition));
+        }
+    }
+
+    fn record(&self, command: Command) {
+        assert!(self.is_recording(), "Command buffer is not recording");
+        self.commands.borrow_mut().push(command);
+    }
+}
+
+impl hal::CommandBuffer<Backend> for CommandBuffer {
+    fn begin(&self) -> hal::Result<()> {
+        assert!(
+            !self.is_recording() || self.flags.contains(hal::CommandPoolFlags::SINGLE_LIST),
+            "Command buffer is already recording"
+        );
+        self.commands.borrow_mut().clear();
+        self.tracker.borrow_mut().reset();
+        self.in_render_pass.set(false);
+        self.recording.set(true);
+        Ok(())
+    }
+
+    fn end(&self) -> hal::Result<()> {
+        assert!(self.is_recording(), "Command buffer is not recording");
+        assert!(
+            !self.in_render_pass.get(),
+            "Command buffer ended inside a render pass"
+        );
+        assert!(
+            !self.tracker.borrow().has_split_transitions(),
+            "Command buffer ended with split barriers that were not ended"
+        );
+        self.recording.set(false);
+        Ok(())
+    }
+
+    fn insert_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        assert!(
+            !self.in_render_pass.get(),
+            "Barriers inserted inside a render pass"
+        );
+        for barrier in barriers {
+            // Commands run one after the other on memory of their own, so only states matter
+            match *barrier {
+                hal::Barrier::Buffer { .. } | hal::Barrier::Image { .. } => {
+                    let (submitted_state, subresources, state) = get_transition(barrier);
+                    self.transition(submitted_state, subresources.as_deref(), state);
+                }
+                hal::Barrier::Storage(_) | hal::Barrier::Aliasing { .. } => {}
+            }
+        }
+    }
+
+    fn begin_split_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        assert!(
+            !self.in_render_pass.get(),
+            "Barriers inserted inside a render pass"
+        );
+        for barrier in barriers {
+            let (submitted_state, subresources, state) = get_transition(barrier);
+            assert!(state.is_valid(), "Invalid resource state {:?}", state);
+            self.tracker.borrow_mut().begin_split_transition(
+                submitted_state,
+                submitted_state,
+                subresources.as_deref(),
+                state,
+            );
+        }
+    }
+
+    fn end_split_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        assert!(
+            !self.in_render_pass.get(),
+            "Barriers inserted inside a render pass"
+        );
+        for barrier in barriers {
+            // Resources keep their previous state until the end, catching uses in between
+            let (submitted_state, subresources, state) = get_transition(barrier);
+            let transitions = self.tracker.borrow_mut().end_split_transition(
+                submitted_state,
+                subresources.as_deref(),
+                state,
+            );
+            for transition in transitions {
+                self.record(Command::Transition(transition));
+            }
+        }
+    }
+
+    fn begin_render_pass(
+        &self,
+        render_pass: &RenderPass,
+        framebuffer: &FrameBuffer,
+        clear_values: &[hal::ClearColor],
+    ) {
+        assert!(
+            !self.in_render_pass.get(),
+            "Render pass begun inside another render pass"
+        );
+        assert_eq!(
+            render_pass.attachments.len(),
+            framebuffer.attachments.len(),
+            "Render pass and framebuffer attachments don't match"
+        );
+
+        self.in_render_pass.set(true);
+        let mut final_states = self.pass_final_states.borrow_mut();
+        for (index, (view, attachment)) in framebuffer
+            .attachments
+            .iter()
+            .zip(render_pass.attachments.iter())
+            .enumerate()
+        {
+            self.transition(&view.image.state, None, hal::ResourceState::RENDER_TARGET);
+            if attachment.load_op == hal::LoadOp::Clear {
+                self.record(Command::Clear {
+                    image: view.image.clone(),
+                    clear_color: clear_values[index],
+                });
+            }
+            final_states.push((view.image.clone(), attachment.final_state));
+        }
+    }
+
+    fn end_render_pass(&self) {
+        assert!(
+            self.in_render_pass.get(),
+            "Render pass ended without being begun"
+        );
+        self.in_render_pass.set(false);
+        for (image, final_state) in self.pass_final_states.borrow_mut().drain(..) {
+            self.transition(&image.state, None, final_state);
+        }
+    }
+
+    fn bind_descriptor_sets(
+        &self,
+        _bind_point: hal::PipelineBindPoint,
+        layout: &PipelineLayout,
+        first_set: u32,
+        sets: &[&DescriptorSet],
+    ) {
+        assert!(self.is_recording(), "Command buffer is not recording");
+        // Layouts have at most as many sets as the device supports
+        validation::check_sets(
+            &layout.set_layouts,
+            first_set,
+            sets.iter().map(|set| &*set.layout),
+        );
+    }
+
+    fn push_constants(
+        &self,
+        _bind_point: hal::PipelineBindPoint,
+        layout: &PipelineLayout,
+        stages: hal::ShaderStageFlags,
+        offset: u32,
+        data: &[u8],
+    ) {
+        assert!(self.is_recording(), "Command buffer is not recording");
+        validation::check_push_constants(
+            &layout.push_constant_ranges,
+            stages,
+            offset,
+            data.len() as u32,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backend::software::Fence;
+    use crate::hal::{self, CommandBuffer as _, CommandPool as _, CommandQueue as _, Device as _};
+    use crate::testing::{create_device, image_desc};
+
+    #[test]
+    fn reads_back_cleared_framebuffer() {
+        let device = create_device();
+        let queue = device
+            .create_command_queue(hal::QueueType::Graphics)
+            .unwrap();
+        let image = device
+            .create_image(image_desc(4, hal::ImageUsage::COLOR_ATTACHMENT))
+            .unwrap();
+        let framebuffer = device.create_framebuffer(&[&image]).unwrap();
+        let render_pass = device
+            .create_render_pass(&hal::RenderPassDesc {
+                attachments: vec![hal::AttachmentDesc {
+                    format: hal::Format::Rgba8Unorm,
+                    load_op: hal::LoadOp::Clear,
+                    store_op: hal::StoreOp::Store,
+                    final_state: hal::ResourceState::COPY_SRC,
+                }],
+            })
+            .unwrap();
+        let mut command_pool = device
+            .create_command_pool(hal::QueueType::Graphics, hal::CommandPoolFlags::empty())
+            .unwrap();
+        let command_buffers = command_pool.create_buffers(1).unwrap();
+        let fence: Fence = device.create_fence(0).unwrap();
+
+        let command_buffer = &command_buffers[0];
+        command_buffer.begin().unwrap();
+        command_buffer.begin_render_pass(&render_pass, &framebuffer, &[[1.0, 0.5, 0.0, 1.0]]);
+        command_buffer.end_render_pass();
+        command_buffer.end().unwrap();
+
+        // Nothing runs until the command buffer gets submitted
+        assert!(image.data().iter().all(|&byte| byte == 0));
+        queue.submit(vec![command_buffer]).unwrap();
+        queue.signal_fence(&fence, 1).unwrap();
+        device.wait_for_fence(&fence, 1).unwrap();
+
+        assert_eq!(image.state(), hal::ResourceState::COPY_SRC);
+        let data = image.data();
+        assert_eq!(data.len(), 4 * 4 * 4);
+        for pixel in data.chunks(4) {
+            assert_eq!(pixel, [255, 128, 0, 255]);
+        }
+    }
+
+    #[test]
+    fn rejects_depth_attachments() {
+        let device = create_device();
+        let result = device.create_render_pass(&hal::RenderPassDesc {
+            attachments: vec![hal::AttachmentDesc {
+                format: hal::Format::D32Float,
+                load_op: hal::LoadOp::Clear,
+                store_op: hal::StoreOp::Store,
+                final_state: hal::ResourceState::SHADER_READ,
+            }],
+        });
+        assert_eq!(result.err(), Some(hal::Error::Unsupported));
+
+        let image = device
+            .create_image(hal::ImageDesc {
+                format: hal::Format::D32Float,
+                ..image_desc(4, hal::ImageUsage::DEPTH_STENCIL_ATTACHMENT)
+            })
+            .unwrap();
+        let result = device.create_framebuffer(&[&image]);
+        assert_eq!(result.err(), Some(hal::Error::Unsupported));
+    }
+
+    #[test]
+    #[should_panic(expected = "Mip levels 1..3 out of range")]
+    fn rejects_out_of_range_barrier() {
+        let device = create_device();
+        let image = device
+            .create_image(hal::ImageDesc {
+                mip_levels: 2,
+                ..image_desc(4, hal::ImageUsage::SAMPLED)
+            })
+            .unwrap();
+        let mut command_pool = device
+            .create_command_pool(hal::QueueType::Graphics, hal::CommandPoolFlags::empty())
+            .unwrap();
+        let command_buffer = command_pool.create_buffer().unwrap();
+
+        command_buffer.begin().unwrap();
+        command_buffer.insert_barriers(&[hal::Barrier::Image {
+            image: &image,
+            range: Some(hal::SubresourceRange {
+                aspects: hal::Aspects::COLOR,
+                mip_levels: 1..3,
+                array_layers: 0..1,
+            }),
+            state: hal::ResourceState::SHADER_READ,
+        }]);
+    }
+}
@@ -0,0 +1,298 @@
+use crate::backend::software::command::CommandPool;
+use crate::backend::software::instance::Backend;
+use crate::backend::software::queue::CommandQueue;
+use crate::backend::software::resource::{
+    self, Buffer, BufferView, DescriptorSet, DescriptorSetLayout, FrameBuffer, Heap, Image,
+    PipelineLayout, RenderPass,
+};
+use crate::backend::software::Fence;
+use crate::backend::validation;
+use crate::hal;
+
+use std::rc::Rc;
+
+// Placement alignment of most GPUs, so that heaps are laid out the same way
+const RESOURCE_ALIGNMENT: u64 = 64 * 1024;
+const MAX_DESCRIPTORS: u32 = 1_000_000;
+const MAX_DESCRIPTOR_SETS: u32 = 8;
+const MAX_PUSH_CONSTANTS_SIZE: u32 = 128;
+
+pub struct Device;
+
+impl Device {
+    pub(crate) fn new() -> Self {
+        Device
+    }
+}
+
+impl hal::Device<Backend> for Device {
+    fn create_command_queue(&self, queue_type: hal::QueueType) -> hal::Result<CommandQueue> {
+        Ok(CommandQueue::new(queue_type))
+    }
+
+    fn create_command_pool(
+        &self,
+        pool_type: hal::QueueType,
+        flags: hal::CommandPoolFlags,
+    ) -> hal::Result<CommandPool> {
+        Ok(CommandPool::new(pool_type, flags))
+    }
+
+    fn create_buffer(
+        &self,
+        size: u64,
+        usage: hal::BufferUsage,
+        memory_type: hal::MemoryType,
+    ) -> hal::Result<Buffer> {
+        Ok(Buffer::new(size, usage, memory_type))
+    }
+
+    fn map_buffer(&self, buffer: &Buffer) -> hal::Result<*mut u8> {
+        buffer.map()
+    }
+
+    fn unmap_buffer(&self, buffer: &Buffer) {
+        buffer.unmap()
+    }
+
+    fn limits(&self) -> hal::Limits {
+        // The values required from feature level 11_0, only reported as images are plain memory
+        hal::Limits {
+            feature_level: hal::FeatureLevel::L11_0,
+            max_image_dimension_1d: 16384,
+            max_image_dimension_2d: 16384,
+            max_image_dimension_3d: 2048,
+            max_image_dimension_cube: 16384,
+            max_image_array_layers: 2048,
+            max_color_attachments: 8,
+            max_descriptors: MAX_DESCRIPTORS,
+            max_samplers: 2048,
+            max_descriptor_sets: MAX_DESCRIPTOR_SETS,
+            max_bindless_descriptors: MAX_DESCRIPTORS,
+            max_push_constants_size: MAX_PUSH_CONSTANTS_SIZE,
+            sample_counts: 1 | 2 | 4 | 8,
+            min_constant_buffer_offset_alignment: 256,
+        }
+    }
+
+    fn features(&self) -> hal::Features {
+        // Descriptors are never read by shaders, so they can be written at any time
+        hal::Features::MIXED_RESOURCE_HEAPS | hal::Features::BINDLESS
+    }
+
+    fn format_properties(&self, format: hal::Format) -> hal::FormatProperties {
+        let desc = format.desc();
+        if desc.aspects != hal::Aspects::COLOR {
+            return hal::FormatProperties::SAMPLED
+                | hal::FormatProperties::DEPTH_STENCIL_ATTACHMENT;
+        }
+
+        // There is no block decoder, so compressed images can only hold data
+        if desc.is_compressed() {
+            return hal::FormatProperties::empty();
+        }
+
+        let mut properties = hal::FormatProperties::SAMPLED | hal::FormatProperties::STORAGE;
+        if resource::is_clearable_format(format) {
+            properties |= hal::FormatProperties::COLOR_ATTACHMENT;
+            // Integer formats can't be blended
+            if let hal::NumericType::Unorm | hal::NumericType::Snorm | hal::NumericType::Float =
+                desc.numeric_type
+            {
+                properties |= hal::FormatProperties::BLEND;
+            }
+        }
+        properties
+    }
+
+    fn create_image(&self, desc: hal::ImageDesc) -> hal::Result<Rc<Image>> {
+        // Render targets need to be cleared, which only works for byte-aligned color formats
+        if desc.usage.contains(hal::ImageUsage::COLOR_ATTACHMENT)
+            && !resource::is_clearable_format(desc.format)
+        {
+            return Err(hal::Error::Unsupported);
+        }
+
+        let state = if desc.usage.contains(hal::ImageUsage::COLOR_ATTACHMENT) {
+            hal::ResourceState::RENDER_TARGET
+        } else {
+            hal::ResourceState::COMMON
+        };
+        Ok(Rc::new(Image::new(desc, state)))
+    }
+
+    fn create_heap(
+        &self,
+        size: u64,
+        memory_type: hal::MemoryType,
+        usage: hal::HeapUsage,
+    ) -> hal::Result<Heap> {
+        Ok(Heap::new(size, memory_type, usage))
+    }
+
+    fn get_buffer_requirements(
+        &self,
+        size: u64,
+        _usage: hal::BufferUsage,
+    ) -> hal::Result<hal::MemoryRequirements> {
+        Ok(hal::MemoryRequirements {
+            size,
+            alignment: RESOURCE_ALIGNMENT,
+        })
+    }
+
+    fn get_image_requirements(
+        &self,
+        desc: &hal::ImageDesc,
+    ) -> hal::Result<hal::MemoryRequirements> {
+        Ok(hal::MemoryRequirements {
+            size: desc.get_packed_size(),
+            alignment: RESOURCE_ALIGNMENT,
+        })
+    }
+
+    fn create_placed_buffer(
+        &self,
+        heap: &Heap,
+        offset: u64,
+        size: u64,
+        usage: hal::BufferUsage,
+    ) -> hal::Result<Buffer> {
+        let requirements = self.get_buffer_requirements(size, usage)?;
+        validation::check_placement(
+            heap.usage(),
+            heap.size(),
+            hal::HeapUsage::BUFFERS,
+            requirements,
+            offset,
+        );
+        Ok(Buffer::new(size, usage, heap.memory_type()))
+    }
+
+    fn create_placed_image(
+        &self,
+        heap: &Heap,
+        offset: u64,
+        desc: hal::ImageDesc,
+    ) -> hal::Result<Rc<Image>> {
+        let requirements = self.get_image_requirements(&desc)?;
+        validation::check_placement(
+            heap.usage(),
+            heap.size(),
+            hal::HeapUsage::from_image_usage(desc.usage),
+            requirements,
+            offset,
+        );
+        self.create_image(desc)
+    }
+
+    fn create_render_pass(&self, desc: &hal::RenderPassDesc) -> hal::Result<RenderPass> {
+        // Attachments get cleared as color images, there is no depth-stencil rendering
+        if !desc
+            .attachments
+            .iter()
+            .all(|attachment| resource::is_clearable_format(attachment.format))
+        {
+            return Err(hal::Error::Unsupported);
+        }
+
+        Ok(RenderPass {
+            attachments: desc.attachments.clone(),
+        })
+    }
+
+    fn create_framebuffer(&self, attachments: &[&Rc<Image>]) -> hal::Result<FrameBuffer> {
+        // Only color attachments are supported, see `create_render_pass`
+        if attachments.iter().any(|image| {
+            !image
+                .desc()
+                .usage
+                .contains(hal::ImageUsage::COLOR_ATTACHMENT)
+        }) {
+            return Err(hal::Error::Unsupported);
+        }
+
+        let attachments = attachments
+            .iter()
+            .map(|&image| BufferView {
+                image: image.clone(),
+            })
+            .collect();
+
+        Ok(FrameBuffer { attachments })
+    }
+
+    fn create_descriptor_set_layout(
+        &self,
+        desc: &hal::DescriptorSetLayoutDesc,
+    ) -> hal::Result<DescriptorSetLayout> {
+        if desc.get_descriptor_count() > MAX_DESCRIPTORS {
+            return Err(hal::Error::Unsupported);
+        }
+        Ok(DescriptorSetLayout {
+            desc: Rc::new(desc.clone()),
+        })
+    }
+
+    fn create_descriptor_set(&self, layout: &DescriptorSetLayout) -> hal::Result<DescriptorSet> {
+        Ok(DescriptorSet {
+            layout: layout.desc.clone(),
+        })
+    }
+
+    fn write_descriptors(&self, writes: &[hal::DescriptorWrite<Backend>]) -> hal::Result<()> {
+        // There are no shaders to read descriptors, so writes are only validated
+        for write in writes {
+            validation::check_write(&write.set.layout, write);
+        }
+        Ok(())
+    }
+
+    fn create_pipeline_layout(
+        &self,
+        set_layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[hal::PushConstantRange],
+    ) -> hal::Result<PipelineLayout> {
+        let push_constants_size = hal::PushConstantRange::get_total_size(push_constant_ranges);
+        if set_layouts.len() as u32 > MAX_DESCRIPTOR_SETS
+            || push_constants_size > MAX_PUSH_CONSTANTS_SIZE
+        {
+            return Err(hal::Error::Unsupported);
+        }
+        Ok(PipelineLayout {
+            set_layouts: set_layouts
+                .iter()
+                .map(|set_layout| set_layout.desc.clone())
+                .collect(),
+            push_constant_ranges: push_constant_ranges.to_vec(),
+        })
+    }
+
+    fn create_fence(&self, initial_value: u64) -> hal::Result<Fence> {
+        Ok(Fence::new(initial_value))
+    }
+
+    fn reset_fence(&self, fence: &Fence) -> hal::Result<()> {
+        fence.signal(0);
+        Ok(())
+    }
+
+    fn wait_for_fence_with_timeout(
+        &self,
+        fence: &Fence,
+        value: u64,
+        _timeout: u64,
+    ) -> hal::Result<()> {
+        // Queues execute synchronously on submit, so anything not signaled yet never will be
+        if fence.get_value() >= value {
+            Ok(())
+        } else {
+            Err(hal::Error::Timeout)
+        }
+    }
+
+    fn device_lost_reason(&self) -> Option<hal::DeviceLostReason> {
+        // Rendering happens on the CPU, there's no GPU to lose
+        None
+    }
+}
@@ -0,0 +1,47 @@
+use crate::backend::software::command::{self, CommandBuffer};
+use crate::backend::software::instance::Backend;
+use crate::backend::software::Fence;
+use crate::hal;
+
+pub struct CommandQueue {
+    pub(crate) queue_type: hal::QueueType,
+}
+
+impl CommandQueue {
+    pub(crate) fn new(queue_type: hal::QueueType) -> Self {
+        CommandQueue { queue_type }
+    }
+}
+
+impl hal::CommandQueue<Backend> for CommandQueue {
+    fn submit(&self, command_buffers: Vec<&CommandBuffer>) -> hal::Result<()> {
+        // Work is executed in submission order before returning, so later fence signals
+        // on this queue always observe its results
+        for command_buffer in command_buffers {
+            assert!(
+                !command_buffer.is_recording(),
+                "Submitted a command buffer that is still recording"
+            );
+            assert_eq!(
+                command_buffer.buffer_type, self.queue_type,
+                "Submitted a command buffer to a queue of a different type"
+            );
+
+            // Bring resources to the states the command buffer was recorded for
+            let tracker = command_buffer.tracker.borrow();
+            for transition in tracker.get_patch_transitions() {
+                command::apply_transition(&transition);
+            }
+            for command in command_buffer.commands.borrow().iter() {
+                command.execute();
+            }
+            tracker.commit();
+        }
+        Ok(())
+    }
+
+    fn signal_fence(&self, fence: &Fence, value: u64) -> hal::Result<()> {
+        fence.signal(value);
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+use crate::backend::software::instance::Backend;
+use crate::backend::tracker::SubmittedState;
+use crate::backend::validation::ResourceUsages;
+use crate::hal;
+
+use std::cell::{Cell, Ref, RefCell, UnsafeCell};
+use std::rc::Rc;
+
+pub struct Buffer {
+    size: u64,
+    usage: hal::BufferUsage,
+    memory_type: hal::MemoryType,
+    // Written through pointers handed out by `map`, so it can't live in a `RefCell`
+    data: UnsafeCell<Vec<u8>>,
+    is_mapped: Cell<bool>,
+    pub(crate) state: SubmittedState<hal::ResourceState>,
+}
+
+impl Buffer {
+    pub(crate) fn new(size: u64, usage: hal::BufferUsage, memory_type: hal::MemoryType) -> Self {
+        Buffer {
+            size,
+            usage,
+            memory_type,
+            data: UnsafeCell::new(vec![0; size as usize]),
+            is_mapped: Cell::new(false),
+            state: SubmittedState::new(hal::ResourceState::COMMON),
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn usage(&self) -> hal::BufferUsage {
+        self.usage
+    }
+
+    pub fn memory_type(&self) -> hal::MemoryType {
+        self.memory_type
+    }
+
+    pub fn state(&self) -> hal::ResourceState {
+        self.state.get()
+    }
+
+    pub(crate) fn map(&self) -> hal::Result<*mut u8> {
+        // All memory is host memory here, but keep the same rules as GPU backends
+        if !self.memory_type.is_host_visible() {
+            return Err(hal::Error::Unsupported);
+        }
+
+        self.is_mapped.set(true);
+        Ok(unsafe { (*self.data.get()).as_mut_ptr() })
+    }
+
+    pub(crate) fn unmap(&self) {
+        assert!(self.is_mapped.get(), "Unmapped a buffer that is not mapped");
+        self.is_mapped.set(false);
+    }
+}
+
+pub struct Image {
+    desc: hal::ImageDesc,
+    data: RefCell<Vec<u8>>,
+    pub(crate) state: SubmittedState<hal::ResourceState>,
+}
+
+impl Image {
+    pub(crate) fn new(desc: hal::ImageDesc, state: hal::ResourceState) -> Self {
+        Image {
+            desc,
+            data: RefCell::new(vec![0; desc.get_packed_size() as usize]),
+            state: SubmittedState::with_subresources(state, desc.get_subresource_count()),
+        }
+    }
+
+    pub fn desc(&self) -> &hal::ImageDesc {
+        &self.desc
+    }
+
+    pub fn width(&self) -> u32 {
+        self.desc.extent.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.desc.extent.height
+    }
+
+    pub fn format(&self) -> hal::Format {
+        self.desc.format
+    }
+
+    /// State of the first mip level and array layer, the one framebuffers render into.
+    pub fn state(&self) -> hal::ResourceState {
+        self.state.get()
+    }
+
+    pub fn get_subresource_state(&self, mip_level: u32, array_layer: u32) -> hal::ResourceState {
+        self.state
+            .get_subresource(self.desc.get_subresource_index(mip_level, array_layer, 0))
+    }
+
+    /// Raw pixel data, tightly packed row by row in the image format. Array layers follow each
+    /// other, each one holding all of its mip levels from the largest to the smallest.
+    pub fn data(&self) -> Ref<'_, [u8]> {
+        Ref::map(self.data.borrow(), |data| data.as_slice())
+    }
+
+    pub(crate) fn clear(&self, clear_color: hal::ClearColor) {
+        assert_eq!(
+            self.state.get(),
+            hal::ResourceState::RENDER_TARGET,
+            "Image cleared while in {:?} state",
+            self.state.get()
+        );
+
+        // Framebuffers render into the first mip level and array layer, which come first
+        let texel = encode_color(self.desc.format, clear_color);
+        let size = get_subresource_size(&self.desc, 0);
+        for pixel in self.data.borrow_mut()[..size].chunks_mut(texel.len()) {
+            pixel.copy_from_slice(&texel);
+        }
+    }
+}
+
+impl ResourceUsages for Backend {
+    fn get_buffer_usage(buffer: &Buffer) -> hal::BufferUsage {
+        buffer.usage()
+    }
+
+    fn get_image_usage(image: &Rc<Image>) -> hal::ImageUsage {
+        image.desc().usage
+    }
+}
+
+/// Memory resources are placed in. Placed resources get memory of their own, as aliasing only
+/// leaves their contents undefined.
+pub struct Heap {
+    size: u64,
+    memory_type: hal::MemoryType,
+    usage: hal::HeapUsage,
+}
+
+impl Heap {
+    pub(crate) fn new(size: u64, memory_type: hal::MemoryType, usage: hal::HeapUsage) -> Self {
+        Heap {
+            size,
+            memory_type,
+            usage,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn memory_type(&self) -> hal::MemoryType {
+        self.memory_type
+    }
+
+    pub fn usage(&self) -> hal::HeapUsage {
+        self.usage
+    }
+}
+
+#[derive(Clone)]
+pub struct BufferView {
+    pub(crate) image: Rc<Image>,
+}
+
+impl BufferView {
+    pub fn image(&self) -> &Image {
+        &self.image
+    }
+}
+
+pub struct RenderPass {
+    pub(crate) attachments: Vec<hal::AttachmentDesc>,
+}
+
+#[derive(Clone)]
+pub struct FrameBuffer {
+    pub attachments: Vec<BufferView>,
+}
+
+pub struct DescriptorSetLayout {
+    pub(crate) desc: Rc<hal::DescriptorSetLayoutDesc>,
+}
+
+pub struct DescriptorSet {
+    pub(crate) layout: Rc<hal::DescriptorSetLayoutDesc>,
+}
+
+pub struct PipelineLayout {
+    pub(crate) set_layouts: Vec<Rc<hal::DescriptorSetLayoutDesc>>,
+    pub(crate) push_constant_ranges: Vec<hal::PushConstantRange>,
+}
+
+fn get_subresource_size(desc: &hal::ImageDesc, mip_level: u32) -> usize {
+    let extent = desc.get_mip_extent(mip_level);
+    let layer_size = desc.format.get_region_size(extent.width, extent.height) as usize;
+    layer_size * extent.depth as usize * desc.samples as usize
+}
+
+/// Whether images of `format` can be cleared, i.e. it's a color format with whole byte channels.
+pub(crate) fn is_clearable_format(format: hal::Format) -> bool {
+    let desc = format.desc();
+    desc.aspects == hal::Aspects::COLOR && !desc.is_packed
+}
+
+fn encode_color(format: hal::Format, color: hal::ClearColor) -> Vec<u8> {
+    assert!(
+        is_clearable_format(format),
+        "Clearing {:?} images is not supported",
+        format
+    );
+
+    let desc = format.desc();
+    let mut channels = color;
+    if desc.is_srgb {
+        for channel in &mut channels[..3] {
+            *channel = encode_srgb(*channel);
+        }
+    }
+    if let hal::Format::Bgra8Unorm | hal::Format::Bgra8UnormSrgb = format {
+        channels.swap(0, 2);
+    }
+
+    let channel_size = desc.bytes_per_block / desc.channel_count;
+    let mut texel = Vec::with_capacity(desc.bytes_per_block as usize);
+    for &value in &channels[..desc.channel_count as usize] {
+        match (desc.numeric_type, channel_size) {
+            (hal::NumericType::Unorm, 1) => texel.push(encode_unorm(value, 255.0) as u8),
+            (hal::NumericType::Unorm, 2) => {
+                texel.extend(&(encode_unorm(value, 65535.0) as u16).to_le_bytes())
+            }
+            (hal::NumericType::Snorm, 1) => texel.push(encode_snorm(value, 127.0) as i8 as u8),
+            (hal::NumericType::Snorm, 2) => {
+                texel.extend(&(encode_snorm(value, 32767.0) as i16).to_le_bytes())
+            }
+            (hal::NumericType::Uint, 1) => texel.push(value as u8),
+            (hal::NumericType::Uint, 2) => texel.extend(&(value as u16).to_le_bytes()),
+            (hal::NumericType::Uint, 4) => texel.extend(&(value as u32).to_le_bytes()),
+            (hal::NumericType::Sint, 1) => texel.push(value as i8 as u8),
+            (hal::NumericType::Sint, 2) => texel.extend(&(value as i16).to_le_bytes()),
+            (hal::NumericType::Sint, 4) => texel.extend(&(value as i32).to_le_bytes()),
+            (hal::NumericType::Float, 2) => texel.extend(&encode_f16(value).to_le_bytes()),
+            (hal::NumericType::Float, 4) => texel.extend(&value.to_le_bytes()),
+            (numeric_type, size) => unreachable!(
+                "Unexpected {}-byte {:?} channel in {:?}",
+                size, numeric_type, format
+            ),
+        }
+    }
+    texel
+}
+
+fn encode_unorm(value: f32, max: f32) -> f32 {
+    value.clamp(0.0, 1.0) * max + 0.5
+}
+
+fn encode_snorm(value: f32, max: f32) -> f32 {
+    (value.clamp(-1.0, 1.0) * max).round()
+}
+
+fn encode_srgb(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Converts to a half precision float, rounding the mantissa to the nearest value.
+fn encode_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // Infinity stays infinity and NaN stays a quiet NaN
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        // Too small for a normal half, so it becomes a denormal or zero
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = (mantissa >> shift) + ((mantissa >> (shift - 1)) & 1);
+        return sign | half as u16;
+    }
+
+    // Rounding may carry into the exponent, which correctly rounds up to the next power of two
+    let half = ((exponent as u32) << 10 | mantissa >> 13) + ((mantissa >> 12) & 1);
+    sign | half as u16
+}
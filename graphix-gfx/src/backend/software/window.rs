@@ -0,0 +1,121 @@
+use crate::backend::software::device::Device;
+use crate::backend::software::instance::{Backend, Instance};
+use crate::backend::software::queue::CommandQueue;
+use crate::backend::software::resource::{self, BufferView, FrameBuffer, Image};
+use crate::hal;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+impl Instance {
+    pub fn create_surface_headless(&self) -> Surface {
+        Surface
+    }
+
+    #[cfg(feature = "winit")]
+    pub fn create_surface(&self, _window: &winit::Window) -> Surface {
+        self.create_surface_headless()
+    }
+}
+
+pub struct Surface;
+
+impl hal::Surface<Backend> for Surface {
+    fn create_swapchain(
+        &self,
+        _device: &Device,
+        _command_queue: &CommandQueue,
+        config: hal::SwapchainConfig,
+    ) -> hal::Result<Swapchain> {
+        if !resource::is_clearable_format(config.format) {
+            return Err(hal::Error::Unsupported);
+        }
+
+        Ok(Swapchain::new(config))
+    }
+}
+
+pub struct Swapchain {
+    pub(crate) images: Vec<Rc<Image>>,
+    current_index: Cell<usize>,
+    presented_index: Cell<Option<usize>>,
+}
+
+impl Swapchain {
+    pub fn new(config: hal::SwapchainConfig) -> Self {
+        assert!(
+            config.buffer_count > 0,
+            "Swapchain needs at least one buffer"
+        );
+
+        let images = (0..config.buffer_count)
+            .map(|_| {
+                let desc = hal::ImageDesc {
+                    kind: hal::ImageKind::D2,
+                    extent: hal::Extent {
+                        width: config.width,
+                        height: config.height,
+                        depth: 1,
+                    },
+                    mip_levels: 1,
+                    array_layers: 1,
+                    samples: 1,
+                    format: config.format,
+                    usage: hal::ImageUsage::COLOR_ATTACHMENT,
+                };
+                Rc::new(Image::new(desc, hal::ResourceState::PRESENT))
+            })
+            .collect();
+
+        Swapchain {
+            images,
+            current_index: Cell::new(0),
+            presented_index: Cell::new(None),
+        }
+    }
+
+    pub fn get_image(&self, index: hal::SwapchainBufferIndex) -> &Image {
+        &self.images[index as usize]
+    }
+
+    /// The back buffer shown by the latest `present`, if any.
+    pub fn presented_image(&self) -> Option<&Image> {
+        self.presented_index.get().map(|index| &*self.images[index])
+    }
+}
+
+impl hal::Swapchain<Backend> for Swapchain {
+    fn acquire_buffer(&self) -> hal::Result<hal::SwapchainBufferIndex> {
+        Ok(self.current_index.get() as _)
+    }
+
+    fn present(&self) -> hal::Result<()> {
+        let current_index = self.current_index.get();
+        let state = self.images[current_index].state();
+        assert_eq!(
+            state,
+            hal::ResourceState::PRESENT,
+            "Back buffer presented while in {:?} state",
+            state
+        );
+
+        self.presented_index.set(Some(current_index));
+        self.current_index
+            .set((current_index + 1) % self.images.len());
+        Ok(())
+    }
+
+    fn create_backbuffer(&self) -> hal::BackBuffer<Backend> {
+        let framebuffers = self
+            .images
+            .iter()
+            .map(|image| FrameBuffer {
+                attachments: vec![BufferView {
+                    image: image.clone(),
+                }],
+            })
+            .collect();
+
+        hal::BackBuffer { framebuffers }
+    }
+}
@@ -0,0 +1,26 @@
+use std::cell::Cell;
+use std::marker::PhantomData;
+
+/// Fence of the backends executing work on the host, which reaches its values as soon as they're
+/// signaled. Each backend gets a type of its own through `B`.
+pub struct Fence<B> {
+    value: Cell<u64>,
+    backend: PhantomData<B>,
+}
+
+impl<B> Fence<B> {
+    pub(crate) fn new(value: u64) -> Self {
+        Fence {
+            value: Cell::new(value),
+            backend: PhantomData,
+        }
+    }
+
+    pub(crate) fn signal(&self, value: u64) {
+        self.value.set(value);
+    }
+
+    pub fn get_value(&self) -> u64 {
+        self.value.get()
+    }
+}
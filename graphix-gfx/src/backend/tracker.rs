@@ -0,0 +1,485 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// States a resource is left in by the work submitted to the queues so far, one per
+/// subresource, shared by every handle to the resource.
+pub(crate) struct SubmittedState<S>(Rc<[Cell<S>]>);
+
+impl<S: Copy> SubmittedState<S> {
+    pub(crate) fn new(state: S) -> Self {
+        SubmittedState::with_subresources(state, 1)
+    }
+
+    pub(crate) fn with_subresources(state: S, subresource_count: u32) -> Self {
+        SubmittedState((0..subresource_count).map(|_| Cell::new(state)).collect())
+    }
+
+    /// State of the first subresource, the only one of buffers and swapchain images.
+    pub(crate) fn get(&self) -> S {
+        self.get_subresource(0)
+    }
+
+    pub(crate) fn get_subresource(&self, subresource: u32) -> S {
+        self.0[subresource as usize].get()
+    }
+
+    pub(crate) fn set_subresource(&self, subresource: u32, state: S) {
+        self.0[subresource as usize].set(state)
+    }
+
+    pub(crate) fn subresource_count(&self) -> u32 {
+        self.0.len() as _
+    }
+
+    fn is_same(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl<S> Clone for SubmittedState<S> {
+    fn clone(&self) -> Self {
+        SubmittedState(self.0.clone())
+    }
+}
+
+/// A state change to record for a resource, applying to a single subresource or to all of them.
+#[derive(Clone)]
+pub(crate) struct Transition<R, S> {
+    pub(crate) resource: R,
+    pub(crate) subresource: Option<u32>,
+    pub(crate) states: Range<S>,
+}
+
+struct TrackedResource<R, S> {
+    resource: R,
+    submitted_state: SubmittedState<S>,
+    // Per subresource, the state the command buffer expects it in when it starts executing and
+    // the one it's in so far, `None` for subresources that aren't used
+    initial_states: Vec<Option<S>>,
+    current_states: Vec<Option<S>>,
+}
+
+struct SplitTransition<R, S> {
+    submitted_state: SubmittedState<S>,
+    subresources: Option<Vec<u32>>,
+    state: S,
+    transitions: Vec<Transition<R, S>>,
+}
+
+/// Follows the states resources go through while a command buffer is recorded, subresource by
+/// subresource.
+///
+/// Transitions are only recorded once a subresource is used again, as the state it's in before
+/// the command buffer runs isn't known until submission. Submitting then patches in transitions
+/// from the submitted states to the initial ones.
+pub(crate) struct StateTracker<R, S> {
+    resources: Vec<TrackedResource<R, S>>,
+    indices: HashMap<*const Cell<S>, usize>,
+    // Split transitions begun but not ended yet
+    split_transitions: Vec<SplitTransition<R, S>>,
+}
+
+impl<R: Clone, S: Copy + PartialEq> StateTracker<R, S> {
+    pub(crate) fn new() -> Self {
+        StateTracker {
+            resources: Vec::new(),
+            indices: HashMap::new(),
+            split_transitions: Vec::new(),
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.resources.clear();
+        self.indices.clear();
+        self.split_transitions.clear();
+    }
+
+    /// Moves `subresources` of `resource` to `state`, all of them if `None`, returning the
+    /// transitions to record for the ones used before in another state.
+    pub(crate) fn transition(
+        &mut self,
+        resource: &R,
+        submitted_state: &SubmittedState<S>,
+        subresources: Option<&[u32]>,
+        state: S,
+    ) -> Vec<Transition<R, S>> {
+        let tracked = self.get_tracked(resource, submitted_state);
+        let subresources = match subresources {
+            Some(subresources) => subresources.to_vec(),
+            None => (0..submitted_state.subresource_count()).collect(),
+        };
+        let mut transitions = Vec::new();
+        for subresource in subresources {
+            let current_state = &mut tracked.current_states[subresource as usize];
+            match *current_state {
+                Some(previous_state) if previous_state != state => {
+                    transitions.push((subresource, previous_state..state))
+                }
+                Some(_) => {}
+                None => tracked.initial_states[subresource as usize] = Some(state),
+            }
+            *current_state = Some(state);
+        }
+
+        merge_transitions(resource, submitted_state.subresource_count(), transitions)
+    }
+
+    /// States the subresources of `resource` are in at this point, as transitions from and to the
+    /// same state. Subresources not used yet are taken to be in their submitted states, which get
+    /// patched in at submission if they changed by then.
+    #[cfg(feature = "vulkan")]
+    pub(crate) fn get_current_states(
+        &mut self,
+        resource: &R,
+        submitted_state: &SubmittedState<S>,
+    ) -> Vec<Transition<R, S>> {
+        let tracked = self.get_tracked(resource, submitted_state);
+        let states = (0..submitted_state.subresource_count())
+            .map(|subresource| {
+                let index = subresource as usize;
+                let state = match tracked.current_states[index] {
+                    Some(state) => state,
+                    None => {
+                        let state = submitted_state.get_subresource(subresource);
+                        tracked.initial_states[index] = Some(state);
+                        tracked.current_states[index] = Some(state);
+                        state
+                    }
+                };
+                (subresource, state..state)
+            })
+            .collect();
+
+        merge_transitions(resource, submitted_state.subresource_count(), states)
+    }
+
+    /// Begins moving `subresources` of `resource` to `state`, returning the transitions to begin.
+    /// The same transitions are returned again by `end_split_transition`.
+    pub(crate) fn begin_split_transition(
+        &mut self,
+        resource: &R,
+        submitted_state: &SubmittedState<S>,
+        subresources: Option<&[u32]>,
+        state: S,
+    ) -> Vec<Transition<R, S>> {
+        let transitions = self.transition(resource, submitted_state, subresources, state);
+        self.split_transitions.push(SplitTransition {
+            submitted_state: submitted_state.clone(),
+            subresources: subresources.map(<[u32]>::to_vec),
+            state,
+            transitions: transitions.clone(),
+        });
+        transitions
+    }
+
+    /// Ends the split transition of `subresources` of a resource to `state`, returning the
+    /// transitions to end. It must have been begun with the same subresources and state.
+    pub(crate) fn end_split_transition(
+        &mut self,
+        submitted_state: &SubmittedState<S>,
+        subresources: Option<&[u32]>,
+        state: S,
+    ) -> Vec<Transition<R, S>> {
+        let index = self
+            .split_transitions
+            .iter()
+            .position(|split| {
+                split.submitted_state.is_same(submitted_state)
+                    && split.subresources.as_deref() == subresources
+                    && split.state == state
+            })
+            .unwrap_or_else(|| {
+                panic!(
+                    "Split barrier ended without being begun with the same resource, range and \
+                     state"
+                )
+            });
+        self.split_transitions.swap_remove(index).transitions
+    }
+
+    pub(crate) fn has_split_transitions(&self) -> bool {
+        !self.split_transitions.is_empty()
+    }
+
+    /// Transitions to record before the command buffer so that resources start in the states
+    /// it expects.
+    pub(crate) fn get_patch_transitions(&self) -> Vec<Transition<R, S>> {
+        self.resources
+            .iter()
+            .flat_map(|tracked| {
+                let submitted_state = &tracked.submitted_state;
+                let transitions = tracked
+                    .initial_states
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(subresource, &initial_state)| {
+                        let subresource = subresource as u32;
+                        let initial_state = initial_state?;
+                        let submitted = submitted_state.get_subresource(subresource);
+                        if submitted != initial_state {
+                            Some((subresource, submitted..initial_state))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                merge_transitions(
+                    &tracked.resource,
+                    submitted_state.subresource_count(),
+                    transitions,
+                )
+            })
+            .collect()
+    }
+
+    /// Marks the resources as left in their final states, once the command buffer is submitted.
+    pub(crate) fn commit(&self) {
+        for tracked in &self.resources {
+            for (subresource, &state) in tracked.current_states.iter().enumerate() {
+                if let Some(state) = state {
+                    tracked
+                        .submitted_state
+                        .set_subresource(subresource as u32, state);
+                }
+            }
+        }
+    }
+
+    fn get_tracked(
+        &mut self,
+        resource: &R,
+        submitted_state: &SubmittedState<S>,
+    ) -> &mut TrackedResource<R, S> {
+        let key = submitted_state.0.as_ptr();
+        let index = match self.indices.get(&key) {
+            Some(&index) => index,
+            None => {
+                let subresource_count = submitted_state.subresource_count() as usize;
+                self.indices.insert(key, self.resources.len());
+                self.resources.push(TrackedResource {
+                    resource: resource.clone(),
+                    submitted_state: submitted_state.clone(),
+                    initial_states: vec![None; subresource_count],
+                    current_states: vec![None; subresource_count],
+                });
+                self.resources.len() - 1
+            }
+        };
+        &mut self.resources[index]
+    }
+}
+
+// Transitions of every subresource between the same states become a single one, which backends
+// can record as a whole resource barrier
+fn merge_transitions<R: Clone, S: Copy + PartialEq>(
+    resource: &R,
+    subresource_count: u32,
+    transitions: Vec<(u32, Range<S>)>,
+) -> Vec<Transition<R, S>> {
+    let is_whole_resource = transitions.len() == subresource_count as usize
+        && transitions
+            .iter()
+            .all(|(_, states)| *states == transitions[0].1);
+    if is_whole_resource {
+        let (_, states) = transitions.into_iter().next().unwrap();
+        return vec![Transition {
+            resource: resource.clone(),
+            subresource: None,
+            states,
+        }];
+    }
+
+    transitions
+        .into_iter()
+        .map(|(subresource, states)| Transition {
+            resource: resource.clone(),
+            subresource: Some(subresource),
+            states,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    enum State {
+        Common,
+        RenderTarget,
+        ShaderRead,
+    }
+
+    fn get_states(transitions: Vec<Transition<u32, State>>) -> Vec<(Option<u32>, Range<State>)> {
+        transitions
+            .into_iter()
+            .map(|transition| (transition.subresource, transition.states))
+            .collect()
+    }
+
+    #[test]
+    fn records_transitions_lazily() {
+        let mut tracker = StateTracker::new();
+        let state = SubmittedState::new(State::Common);
+
+        // The state before the command buffer runs isn't known yet
+        let transitions = tracker.transition(&0, &state, None, State::RenderTarget);
+        assert!(transitions.is_empty());
+        let transitions = tracker.transition(&0, &state, None, State::RenderTarget);
+        assert!(transitions.is_empty());
+
+        let transitions = tracker.transition(&0, &state, None, State::ShaderRead);
+        assert_eq!(
+            get_states(transitions),
+            [(None, State::RenderTarget..State::ShaderRead)]
+        );
+    }
+
+    #[test]
+    fn patches_from_submitted_states() {
+        let mut tracker = StateTracker::new();
+        let first = SubmittedState::new(State::Common);
+        let second = SubmittedState::new(State::RenderTarget);
+        tracker.transition(&0, &first, None, State::RenderTarget);
+        tracker.transition(&1, &second, None, State::RenderTarget);
+        tracker.transition(&0, &first, None, State::ShaderRead);
+
+        // Only resources submitted in another state than the one first used need patching
+        let transitions = tracker.get_patch_transitions();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].resource, 0);
+        assert_eq!(
+            get_states(transitions),
+            [(None, State::Common..State::RenderTarget)]
+        );
+
+        // Patches follow the submitted state at the time of submission
+        first.set_subresource(0, State::RenderTarget);
+        assert!(tracker.get_patch_transitions().is_empty());
+    }
+
+    #[test]
+    fn commits_final_states() {
+        let mut tracker = StateTracker::new();
+        let state = SubmittedState::with_subresources(State::Common, 2);
+        tracker.transition(&0, &state, Some(&[1]), State::RenderTarget);
+        tracker.transition(&0, &state, Some(&[1]), State::ShaderRead);
+        assert_eq!(state.get_subresource(1), State::Common);
+
+        tracker.commit();
+        assert_eq!(state.get_subresource(0), State::Common);
+        assert_eq!(state.get_subresource(1), State::ShaderRead);
+
+        // Reset command buffers start tracking from scratch
+        tracker.reset();
+        let transitions = tracker.transition(&0, &state, None, State::RenderTarget);
+        assert!(transitions.is_empty());
+        assert_eq!(
+            get_states(tracker.get_patch_transitions()),
+            [
+                (Some(0), State::Common..State::RenderTarget),
+                (Some(1), State::ShaderRead..State::RenderTarget)
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "vulkan")]
+    fn tracks_current_states() {
+        let mut tracker = StateTracker::new();
+        let state = SubmittedState::with_subresources(State::Common, 2);
+        tracker.transition(&0, &state, Some(&[1]), State::RenderTarget);
+
+        // Unused subresources are tracked from their submitted states
+        assert_eq!(
+            get_states(tracker.get_current_states(&0, &state)),
+            [
+                (Some(0), State::Common..State::Common),
+                (Some(1), State::RenderTarget..State::RenderTarget)
+            ]
+        );
+        state.set_subresource(0, State::ShaderRead);
+        assert_eq!(
+            get_states(tracker.get_patch_transitions()),
+            [
+                (Some(0), State::ShaderRead..State::Common),
+                (Some(1), State::Common..State::RenderTarget)
+            ]
+        );
+    }
+
+    #[test]
+    fn transitions_mip_levels() {
+        let mut tracker = StateTracker::new();
+        let state = SubmittedState::with_subresources(State::Common, 3);
+        tracker.transition(&0, &state, None, State::RenderTarget);
+
+        let transitions = tracker.transition(&0, &state, Some(&[1]), State::ShaderRead);
+        assert_eq!(
+            get_states(transitions),
+            [(Some(1), State::RenderTarget..State::ShaderRead)]
+        );
+        let transitions = tracker.transition(&0, &state, Some(&[1, 2]), State::ShaderRead);
+        assert_eq!(
+            get_states(transitions),
+            [(Some(2), State::RenderTarget..State::ShaderRead)]
+        );
+    }
+
+    #[test]
+    fn merges_whole_resource_transitions() {
+        let mut tracker = StateTracker::new();
+        let state = SubmittedState::with_subresources(State::Common, 2);
+        tracker.transition(&0, &state, Some(&[0]), State::RenderTarget);
+        tracker.transition(&0, &state, Some(&[1]), State::RenderTarget);
+
+        // Subresources reaching the same state separately still share a single barrier
+        let transitions = tracker.transition(&0, &state, None, State::ShaderRead);
+        assert_eq!(
+            get_states(transitions),
+            [(None, State::RenderTarget..State::ShaderRead)]
+        );
+
+        // Unless they leave different states
+        tracker.transition(&0, &state, Some(&[0]), State::RenderTarget);
+        let transitions = tracker.transition(&0, &state, None, State::Common);
+        assert_eq!(
+            get_states(transitions),
+            [
+                (Some(0), State::RenderTarget..State::Common),
+                (Some(1), State::ShaderRead..State::Common)
+            ]
+        );
+    }
+
+    #[test]
+    fn pairs_split_transitions() {
+        let mut tracker = StateTracker::new();
+        let first = SubmittedState::with_subresources(State::Common, 2);
+        let second = SubmittedState::new(State::Common);
+        tracker.transition(&0, &first, None, State::RenderTarget);
+        tracker.transition(&1, &second, None, State::RenderTarget);
+
+        let begun_first = tracker.begin_split_transition(&0, &first, Some(&[1]), State::ShaderRead);
+        let begun_second = tracker.begin_split_transition(&1, &second, None, State::ShaderRead);
+        assert!(tracker.has_split_transitions());
+
+        // Ends can come in any order and hand back the transitions begun
+        let ended_second = tracker.end_split_transition(&second, None, State::ShaderRead);
+        let ended_first = tracker.end_split_transition(&first, Some(&[1]), State::ShaderRead);
+        assert_eq!(get_states(ended_first), get_states(begun_first));
+        assert_eq!(get_states(ended_second), get_states(begun_second));
+        assert!(!tracker.has_split_transitions());
+    }
+
+    #[test]
+    #[should_panic(expected = "Split barrier ended without being begun")]
+    fn rejects_unmatched_split_transition() {
+        let mut tracker = StateTracker::new();
+        let state = SubmittedState::with_subresources(State::Common, 2);
+        tracker.begin_split_transition(&0, &state, Some(&[1]), State::ShaderRead);
+        tracker.end_split_transition(&state, None, State::ShaderRead);
+    }
+}
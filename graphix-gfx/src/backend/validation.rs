@@ -0,0 +1,138 @@
+//! Validation of the backends executing work on the host, shared so that they reject the same
+//! misuse with the same messages.
+
+use crate::hal;
+
+use std::rc::Rc;
+
+/// Usages of the resources of a backend, checked against the bindings descriptors are written to.
+pub(crate) trait ResourceUsages: hal::Backend {
+    fn get_buffer_usage(buffer: &Self::Buffer) -> hal::BufferUsage;
+    fn get_image_usage(image: &Self::Image) -> hal::ImageUsage;
+}
+
+/// Panics unless a resource of `requirements` placed at `offset` fits in the heap and the heap
+/// allows its `usage`.
+pub(crate) fn check_placement(
+    heap_usage: hal::HeapUsage,
+    heap_size: u64,
+    usage: hal::HeapUsage,
+    requirements: hal::MemoryRequirements,
+    offset: u64,
+) {
+    assert!(
+        heap_usage.contains(usage),
+        "{:?} placed in a heap for {:?}",
+        usage,
+        heap_usage
+    );
+    assert!(
+        requirements.fits(offset, heap_size),
+        "Resource of {:?} placed at offset {} of a {} bytes heap",
+        requirements,
+        offset,
+        heap_size
+    );
+}
+
+/// Panics unless `write` stays within a binding of `layout` and its resources allow the binding
+/// type.
+pub(crate) fn check_write<B: ResourceUsages>(
+    layout: &hal::DescriptorSetLayoutDesc,
+    write: &hal::DescriptorWrite<B>,
+) {
+    let binding = layout.get_binding(write.binding).unwrap_or_else(|| {
+        panic!(
+            "Descriptors written to binding {} missing from the set layout",
+            write.binding
+        )
+    });
+    assert!(
+        write.array_element + write.descriptors.len() as u32 <= binding.count,
+        "{} descriptors written from element {} of a binding of {}",
+        write.descriptors.len(),
+        write.array_element,
+        binding.count
+    );
+    for descriptor in write.descriptors {
+        match *descriptor {
+            hal::Descriptor::Buffer(buffer) => {
+                let usage = B::get_buffer_usage(buffer);
+                assert!(
+                    binding.ty.is_buffer() && usage.contains(binding.ty.get_buffer_usage()),
+                    "Buffer of {:?} written to a {:?} binding",
+                    usage,
+                    binding.ty
+                );
+            }
+            hal::Descriptor::Image(image) => {
+                let usage = B::get_image_usage(image);
+                assert!(
+                    !binding.ty.is_buffer() && usage.contains(binding.ty.get_image_usage()),
+                    "Image of {:?} written to a {:?} binding",
+                    usage,
+                    binding.ty
+                );
+            }
+        }
+    }
+}
+
+/// Panics unless sets of `layouts` can be bound from `first_set` with a pipeline layout of
+/// `set_layouts`.
+pub(crate) fn check_sets<'a>(
+    set_layouts: &[Rc<hal::DescriptorSetLayoutDesc>],
+    first_set: u32,
+    layouts: impl IntoIterator<Item = &'a hal::DescriptorSetLayoutDesc>,
+) {
+    for (index, layout) in layouts.into_iter().enumerate() {
+        let set_index = first_set as usize + index;
+        let set_layout = set_layouts.get(set_index).unwrap_or_else(|| {
+            panic!(
+                "Set bound at index {} past the {} sets of the pipeline layout",
+                set_index,
+                set_layouts.len()
+            )
+        });
+        assert!(
+            **set_layout == *layout,
+            "Set bound at index {} doesn't match the set layout of the pipeline layout",
+            set_index
+        );
+    }
+}
+
+/// Panics unless `size` bytes of push constants at `offset` are within `ranges` for every stage
+/// of `stages`.
+pub(crate) fn check_push_constants(
+    ranges: &[hal::PushConstantRange],
+    stages: hal::ShaderStageFlags,
+    offset: u32,
+    size: u32,
+) {
+    assert!(
+        offset.is_multiple_of(4) && size.is_multiple_of(4),
+        "Push constants set at offset {} with a size of {}, not multiples of 4",
+        offset,
+        size
+    );
+    // Every stage given needs a single range holding all the bytes set
+    let stage_flags = [
+        hal::ShaderStageFlags::VERTEX,
+        hal::ShaderStageFlags::FRAGMENT,
+        hal::ShaderStageFlags::COMPUTE,
+    ];
+    for &stage in stage_flags.iter().filter(|&&stage| stages.contains(stage)) {
+        assert!(
+            ranges.iter().any(|range| {
+                range.stages.contains(stage)
+                    && range.offset <= offset
+                    && offset + size <= range.offset + range.size
+            }),
+            "Push constants {}..{} set for {:?} outside the ranges of the pipeline layout",
+            offset,
+            offset + size,
+            stage
+        );
+    }
+}
@@ -0,0 +1,18 @@
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::instance::{Backend, RawInstance};
+use crate::hal;
+
+use ash::vk;
+
+use std::rc::Rc;
+
+pub struct PhysicalAdapter {
+    pub(crate) instance: Rc<RawInstance>,
+    pub(crate) native: vk::PhysicalDevice,
+}
+
+impl hal::PhysicalAdapter<Backend> for PhysicalAdapter {
+    fn create_device(&self) -> hal::Result<Device> {
+        Device::new(self)
+    }
+}
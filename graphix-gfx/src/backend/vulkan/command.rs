@@ -0,0 +1,801 @@
+use crate::backend::tracker::{StateTracker, SubmittedState, Transition};
+use crate::backend::vulkan::descriptor::{self, DescriptorSet, PipelineLayout};
+use crate::backend::vulkan::device::{get_hal_error, Device, RawDevice};
+use crate::backend::vulkan::instance::Backend;
+use crate::backend::vulkan::resource::{self, BufferView, FrameBuffer, RenderPass};
+use crate::hal;
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+pub enum CommandPoolAllocator {
+    Shared(vk::CommandPool),        // 1 command pool to many command buffers
+    Multiple(Vec<vk::CommandPool>), // 1 command pool to 1 command buffer
+}
+
+pub struct CommandPool {
+    device: Rc<RawDevice>,
+    pool_allocator: CommandPoolAllocator,
+    family_index: u32,
+    flags: hal::CommandPoolFlags,
+}
+
+impl CommandPool {
+    pub(crate) fn new(
+        device: &Device,
+        pool_type: hal::QueueType,
+        flags: hal::CommandPoolFlags,
+    ) -> hal::Result<Self> {
+        let family_index = device.raw.queue_families.get(pool_type);
+
+        let pool_allocator = if flags.contains(hal::CommandPoolFlags::MULTIPLE_ALLOCATOR) {
+            CommandPoolAllocator::Multiple(Vec::new())
+        } else {
+            CommandPoolAllocator::Shared(CommandPool::create_native_pool(
+                &device.raw,
+                family_index,
+                flags,
+            )?)
+        };
+
+        Ok(CommandPool {
+            device: device.raw.clone(),
+            pool_allocator,
+            family_index,
+            flags,
+        })
+    }
+
+    pub(crate) fn create_native_pool(
+        device: &RawDevice,
+        family_index: u32,
+        flags: hal::CommandPoolFlags,
+    ) -> hal::Result<vk::CommandPool> {
+        let create_info = vk::CommandPoolCreateInfo::builder()
+            .queue_family_index(family_index)
+            .flags(get_native_pool_flags(flags));
+
+        unsafe { device.native.create_command_pool(&create_info, None) }.map_err(get_hal_error)
+    }
+
+    pub(crate) fn allocate_native_buffer(
+        device: &RawDevice,
+        pool: vk::CommandPool,
+    ) -> hal::Result<vk::CommandBuffer> {
+        let allocate_info = vk::CommandBufferAllocateInfo::builder()
+            .command_pool(pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+
+        unsafe { device.native.allocate_command_buffers(&allocate_info) }
+            .map(|mut command_buffers| command_buffers.remove(0))
+            .map_err(get_hal_error)
+    }
+}
+
+impl hal::CommandPool<Backend> for CommandPool {
+    fn reset(&self) -> hal::Result<()> {
+        let pools = match self.pool_allocator {
+            CommandPoolAllocator::Shared(ref pool) => std::slice::from_ref(pool),
+            CommandPoolAllocator::Multiple(ref pools) => pools.as_slice(),
+        };
+        for &pool in pools {
+            unsafe {
+                self.device
+                    .native
+                    .reset_command_pool(pool, vk::CommandPoolResetFlags::empty())
+            }
+            .map_err(get_hal_error)?;
+        }
+        Ok(())
+    }
+
+    fn create_buffer(&mut self) -> hal::Result<CommandBuffer> {
+        // Vulkan command buffers can't be shared between pools, so `SINGLE_LIST` has no
+        // equivalent and every buffer gets its own native command buffer
+        let pool = match self.pool_allocator {
+            CommandPoolAllocator::Shared(pool) => pool,
+            CommandPoolAllocator::Multiple(ref mut pools) => {
+                let pool =
+                    CommandPool::create_native_pool(&self.device, self.family_index, self.flags)?;
+                pools.push(pool);
+                pool
+            }
+        };
+
+        let command_buffer = CommandPool::allocate_native_buffer(&self.device, pool)?;
+
+        Ok(CommandBuffer::new(
+            &self.device,
+            pool,
+            command_buffer,
+            self.family_index,
+            self.flags,
+        ))
+    }
+}
+
+impl Drop for CommandPool {
+    fn drop(&mut self) {
+        let pools = match self.pool_allocator {
+            CommandPoolAllocator::Shared(ref pool) => std::slice::from_ref(pool),
+            CommandPoolAllocator::Multiple(ref pools) => pools.as_slice(),
+        };
+        for &pool in pools {
+            unsafe {
+                self.device.native.destroy_command_pool(pool, None);
+            }
+        }
+    }
+}
+
+/// A buffer or image whose state is tracked, along with what barriers need to address it.
+#[derive(Copy, Clone)]
+pub(crate) enum Resource {
+    Buffer(vk::Buffer),
+    Image(vk::Image, vk::ImageSubresourceRange),
+}
+
+/// Barriers recorded together in a single pipeline barrier.
+#[derive(Default)]
+struct Barriers {
+    memory_barriers: Vec<vk::MemoryBarrier>,
+    buffer_barriers: Vec<vk::BufferMemoryBarrier>,
+    image_barriers: Vec<vk::ImageMemoryBarrier>,
+    src_stage_mask: vk::PipelineStageFlags,
+    dst_stage_mask: vk::PipelineStageFlags,
+}
+
+impl Barriers {
+    fn push(&mut self, transition: &Transition<Resource, hal::ResourceState>) {
+        let states = &transition.states;
+        let (src_access, src_stage) = get_native_access(states.start);
+        let (dst_access, dst_stage) = get_native_access(states.end);
+        self.src_stage_mask |= src_stage;
+        self.dst_stage_mask |= dst_stage;
+        match transition.resource {
+            Resource::Buffer(buffer) => self.buffer_barriers.push(
+                vk::BufferMemoryBarrier::builder()
+                    .src_access_mask(src_access)
+                    .dst_access_mask(dst_access)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .buffer(buffer)
+                    .offset(0)
+                    .size(vk::WHOLE_SIZE)
+                    .build(),
+            ),
+            Resource::Image(image, range) => self.image_barriers.push(get_image_barrier(
+                image,
+                get_subresource_range(range, transition.subresource),
+                (get_native_layout(states.start), src_access),
+                (get_native_layout(states.end), dst_access),
+            )),
+        }
+    }
+
+    /// Lets the driver drop the previous contents of the images transitioned so far.
+    fn discard_contents(&mut self) {
+        for barrier in &mut self.image_barriers {
+            barrier.old_layout = vk::ImageLayout::UNDEFINED;
+        }
+    }
+
+    /// Pushes a transition of an image whose previous contents can be dropped.
+    fn push_discarding(&mut self, transition: &Transition<Resource, hal::ResourceState>) {
+        self.push(transition);
+        if let Some(barrier) = self.image_barriers.last_mut() {
+            barrier.old_layout = vk::ImageLayout::UNDEFINED;
+        }
+    }
+
+    /// Makes all memory writes visible to all later accesses.
+    fn push_memory_barrier(&mut self) {
+        self.src_stage_mask |= vk::PipelineStageFlags::ALL_COMMANDS;
+        self.dst_stage_mask |= vk::PipelineStageFlags::ALL_COMMANDS;
+        self.memory_barriers.push(
+            vk::MemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::MEMORY_WRITE)
+                .dst_access_mask(vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE)
+                .build(),
+        );
+    }
+
+    fn record(&self, device: &RawDevice, command_buffer: vk::CommandBuffer) {
+        if self.memory_barriers.is_empty()
+            && self.buffer_barriers.is_empty()
+            && self.image_barriers.is_empty()
+        {
+            return;
+        }
+
+        unsafe {
+            device.native.cmd_pipeline_barrier(
+                command_buffer,
+                self.src_stage_mask,
+                self.dst_stage_mask,
+                vk::DependencyFlags::empty(),
+                &self.memory_barriers,
+                &self.buffer_barriers,
+                &self.image_barriers,
+            );
+        }
+    }
+}
+
+pub struct CommandBuffer {
+    device: Rc<RawDevice>,
+    pub(crate) pool: vk::CommandPool,
+    pub(crate) native: vk::CommandBuffer,
+    family_index: u32,
+    flags: hal::CommandPoolFlags,
+    pub(crate) tracker: RefCell<StateTracker<Resource, hal::ResourceState>>,
+    // Records the transitions patched in at submit, in a pool of its own so that it can be
+    // reset whatever the flags of the command buffer pool
+    patch: RefCell<Option<(vk::CommandPool, vk::CommandBuffer)>>,
+    // Attachments of the current render pass and the states they end it in
+    pass_final_states: RefCell<Vec<(BufferView, hal::ResourceState)>>,
+}
+
+impl CommandBuffer {
+    pub(crate) fn new(
+        device: &Rc<RawDevice>,
+        pool: vk::CommandPool,
+        native: vk::CommandBuffer,
+        family_index: u32,
+        flags: hal::CommandPoolFlags,
+    ) -> Self {
+        CommandBuffer {
+            device: device.clone(),
+            pool,
+            native,
+            family_index,
+            flags,
+            tracker: RefCell::new(StateTracker::new()),
+            patch: RefCell::new(None),
+            pass_final_states: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Records the transitions bringing resources from their submitted states to the ones this
+    /// command buffer starts with, returning `None` when there are none.
+    pub(crate) fn record_patch(&self) -> hal::Result<Option<vk::CommandBuffer>> {
+        let transitions = self.tracker.borrow().get_patch_transitions();
+        if transitions.is_empty() {
+            return Ok(None);
+        }
+
+        let mut patch = self.patch.borrow_mut();
+        let command_buffer = match *patch {
+            Some((_, command_buffer)) => command_buffer,
+            None => {
+                let pool = CommandPool::create_native_pool(
+                    &self.device,
+                    self.family_index,
+                    hal::CommandPoolFlags::empty(),
+                )?;
+                let command_buffer = match CommandPool::allocate_native_buffer(&self.device, pool) {
+                    Ok(command_buffer) => command_buffer,
+                    Err(err) => {
+                        unsafe {
+                            self.device.native.destroy_command_pool(pool, None);
+                        }
+                        return Err(err);
+                    }
+                };
+                *patch = Some((pool, command_buffer));
+                command_buffer
+            }
+        };
+
+        let mut barriers = Barriers::default();
+        for transition in &transitions {
+            barriers.push(transition);
+        }
+
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe {
+            self.device
+                .native
+                .begin_command_buffer(command_buffer, &begin_info)
+                .map(|()| barriers.record(&self.device, command_buffer))
+                .and_then(|()| self.device.native.end_command_buffer(command_buffer))
+        }
+        .map_err(get_hal_error)?;
+
+        Ok(Some(command_buffer))
+    }
+
+    /// Moves `subresources` of `resource` to `state`, returning the transitions to record.
+    fn transition(
+        &self,
+        resource: Resource,
+        submitted_state: &SubmittedState<hal::ResourceState>,
+        subresources: Option<&[u32]>,
+        state: hal::ResourceState,
+    ) -> Vec<Transition<Resource, hal::ResourceState>> {
+        debug_assert!(state.is_valid(), "Invalid resource state {:?}", state);
+        self.tracker
+            .borrow_mut()
+            .transition(&resource, submitted_state, subresources, state)
+    }
+}
+
+impl hal::CommandBuffer<Backend> for CommandBuffer {
+    fn begin(&self) -> hal::Result<()> {
+        if self
+            .flags
+            .contains(hal::CommandPoolFlags::MULTIPLE_ALLOCATOR)
+        {
+            unsafe {
+                self.device
+                    .native
+                    .reset_command_pool(self.pool, vk::CommandPoolResetFlags::empty())
+            }
+            .map_err(get_hal_error)?;
+        } else {
+            unsafe {
+                self.device
+                    .native
+                    .reset_command_buffer(self.native, vk::CommandBufferResetFlags::empty())
+            }
+            .map_err(get_hal_error)?;
+        }
+
+        self.tracker.borrow_mut().reset();
+
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe {
+            self.device
+                .native
+                .begin_command_buffer(self.native, &begin_info)
+        }
+        .map_err(get_hal_error)
+    }
+
+    fn end(&self) -> hal::Result<()> {
+        debug_assert!(
+            !self.tracker.borrow().has_split_transitions(),
+            "Command buffer ended with split barriers that were not ended"
+        );
+        unsafe { self.device.native.end_command_buffer(self.native) }.map_err(get_hal_error)
+    }
+
+    fn insert_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        let mut native_barriers = Barriers::default();
+        for barrier in barriers {
+            match *barrier {
+                hal::Barrier::Buffer { .. } | hal::Barrier::Image { .. } => {
+                    let (resource, submitted_state, subresources, state) = get_transition(barrier);
+                    let transitions =
+                        self.transition(resource, submitted_state, subresources.as_deref(), state);
+                    for transition in &transitions {
+                        native_barriers.push(transition);
+                    }
+                }
+                hal::Barrier::Storage(resource) => {
+                    let (resource, _) = get_barrier_resource(resource);
+                    let state = hal::ResourceState::STORAGE;
+                    native_barriers.push(&Transition {
+                        resource,
+                        subresource: None,
+                        states: state..state,
+                    });
+                }
+                hal::Barrier::Aliasing { after, .. } => {
+                    // Images taking over the memory have undefined layouts, so they're moved to
+                    // the states they're tracked in from UNDEFINED
+                    if let Some(after @ hal::BarrierResource::Image(_)) = after {
+                        let (resource, submitted_state) = get_barrier_resource(after);
+                        let transitions = self
+                            .tracker
+                            .borrow_mut()
+                            .get_current_states(&resource, submitted_state);
+                        for transition in &transitions {
+                            native_barriers.push_discarding(transition);
+                        }
+                    }
+                    native_barriers.push_memory_barrier();
+                }
+            }
+        }
+        native_barriers.record(&self.device, self.native);
+    }
+
+    fn begin_split_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        // Events would let the transitions overlap with the work in between, but a full barrier
+        // where they end is simpler and just as correct
+        let mut tracker = self.tracker.borrow_mut();
+        for barrier in barriers {
+            let (resource, submitted_state, subresources, state) = get_transition(barrier);
+            debug_assert!(state.is_valid(), "Invalid resource state {:?}", state);
+            tracker.begin_split_transition(
+                &resource,
+                submitted_state,
+                subresources.as_deref(),
+                state,
+            );
+        }
+    }
+
+    fn end_split_barriers(&self, barriers: &[hal::Barrier<Backend>]) {
+        let mut native_barriers = Barriers::default();
+        for barrier in barriers {
+            let (_, submitted_state, subresources, state) = get_transition(barrier);
+            let transitions = self.tracker.borrow_mut().end_split_transition(
+                submitted_state,
+                subresources.as_deref(),
+                state,
+            );
+            for transition in &transitions {
+                native_barriers.push(transition);
+            }
+        }
+        native_barriers.record(&self.device, self.native);
+    }
+
+    fn begin_render_pass(
+        &self,
+        render_pass: &RenderPass,
+        framebuffer: &FrameBuffer,
+        clear_values: &[hal::ClearColor],
+    ) {
+        let mut pass_final_states = self.pass_final_states.borrow_mut();
+        pass_final_states.clear();
+        for (index, (view, attachment)) in framebuffer
+            .attachments
+            .iter()
+            .zip(render_pass.attachments.iter())
+            .enumerate()
+        {
+            // Attachments used for the first time get patched to render targets at submit
+            let mut barriers = Barriers::default();
+            let transitions = self.transition(
+                Resource::Image(view.image, view.range),
+                &view.state,
+                None,
+                hal::ResourceState::RENDER_TARGET,
+            );
+            for transition in &transitions {
+                barriers.push(transition);
+            }
+            // Discarding the previous contents lets the driver skip preserving them, as long as
+            // the attachment covers the whole image
+            let is_whole_image = view.range.level_count == 1 && view.range.layer_count == 1;
+            if attachment.load_op != hal::LoadOp::Load && is_whole_image {
+                barriers.discard_contents();
+            }
+            barriers.record(&self.device, self.native);
+
+            if attachment.load_op == hal::LoadOp::Clear {
+                // Color attachments can only be cleared outside of a render pass in a transfer
+                // layout, so go through TRANSFER_DST_OPTIMAL
+                let range = resource::get_color_subresource_range();
+                let resource = Resource::Image(view.image, range);
+                let mut barriers = Barriers::default();
+                barriers.push(&Transition {
+                    resource,
+                    subresource: None,
+                    states: hal::ResourceState::RENDER_TARGET..hal::ResourceState::COPY_DST,
+                });
+                barriers.record(&self.device, self.native);
+
+                let clear_value = vk::ClearColorValue {
+                    float32: clear_values[index],
+                };
+                unsafe {
+                    self.device.native.cmd_clear_color_image(
+                        self.native,
+                        view.image,
+                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                        &clear_value,
+                        &[range],
+                    );
+                }
+
+                let mut barriers = Barriers::default();
+                barriers.push(&Transition {
+                    resource,
+                    subresource: None,
+                    states: hal::ResourceState::COPY_DST..hal::ResourceState::RENDER_TARGET,
+                });
+                barriers.record(&self.device, self.native);
+            }
+
+            pass_final_states.push((view.clone(), attachment.final_state));
+        }
+    }
+
+    fn end_render_pass(&self) {
+        let mut barriers = Barriers::default();
+        for (view, final_state) in self.pass_final_states.borrow_mut().drain(..) {
+            let resource = Resource::Image(view.image, view.range);
+            for transition in &self.transition(resource, &view.state, None, final_state) {
+                barriers.push(transition);
+            }
+        }
+        barriers.record(&self.device, self.native);
+    }
+
+    fn bind_descriptor_sets(
+        &self,
+        bind_point: hal::PipelineBindPoint,
+        layout: &PipelineLayout,
+        first_set: u32,
+        sets: &[&DescriptorSet],
+    ) {
+        let native_sets = sets.iter().map(|set| set.native).collect::<Vec<_>>();
+        unsafe {
+            self.device.native.cmd_bind_descriptor_sets(
+                self.native,
+                descriptor::get_native_bind_point(bind_point),
+                layout.native,
+                first_set,
+                &native_sets,
+                &[],
+            );
+        }
+    }
+
+    fn push_constants(
+        &self,
+        _bind_point: hal::PipelineBindPoint,
+        layout: &PipelineLayout,
+        stages: hal::ShaderStageFlags,
+        offset: u32,
+        data: &[u8],
+    ) {
+        // Push constants are shared by all bind points, the stages telling which pipelines see them
+        unsafe {
+            self.device.native.cmd_push_constants(
+                self.native,
+                layout.native,
+                descriptor::get_native_stages(stages),
+                offset,
+                data,
+            );
+        }
+    }
+}
+
+impl Drop for CommandBuffer {
+    fn drop(&mut self) {
+        if let Some((pool, _)) = *self.patch.borrow() {
+            unsafe {
+                self.device.native.destroy_command_pool(pool, None);
+            }
+        }
+    }
+}
+
+fn get_barrier_resource<'a>(
+    resource: hal::BarrierResource<'a, Backend>,
+) -> (Resource, &'a SubmittedState<hal::ResourceState>) {
+    match resource {
+        hal::BarrierResource::Buffer(buffer) => (Resource::Buffer(buffer.native), &buffer.state),
+        hal::BarrierResource::Image(image) => {
+            (Resource::Image(image.native, image.range), &image.state)
+        }
+    }
+}
+
+/// Resource, submitted state, subresources and target state of a buffer or image transition.
+fn get_transition<'a>(
+    barrier: &hal::Barrier<'a, Backend>,
+) -> (
+    Resource,
+    &'a SubmittedState<hal::ResourceState>,
+    Option<Vec<u32>>,
+    hal::ResourceState,
+) {
+    match *barrier {
+        hal::Barrier::Buffer { buffer, state } => {
+            let (resource, submitted_state) =
+                get_barrier_resource(hal::BarrierResource::Buffer(buffer));
+            (resource, submitted_state, None, state)
+        }
+        hal::Barrier::Image {
+            image,
+            ref range,
+            state,
+        } => {
+            let (resource, submitted_state) =
+                get_barrier_resource(hal::BarrierResource::Image(image));
+            let subresources = range
+                .as_ref()
+                .map(|range| image.desc().get_subresource_indices(range));
+            (resource, submitted_state, subresources, state)
+        }
+        _ => panic!("Only buffer and image transitions can be split"),
+    }
+}
+
+/// Range of a single subresource of an image covering `range`, or all of `range` if `None`.
+/// Subresources are counted like `hal::ImageDesc::get_subresource_index` does.
+fn get_subresource_range(
+    range: vk::ImageSubresourceRange,
+    subresource: Option<u32>,
+) -> vk::ImageSubresourceRange {
+    let subresource = match subresource {
+        Some(subresource) => subresource,
+        None => return range,
+    };
+
+    let mip_level = subresource % range.level_count;
+    let array_layer = subresource / range.level_count % range.layer_count;
+    let plane = subresource / (range.level_count * range.layer_count);
+    let depth_stencil = vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL;
+    let aspect_mask = if range.aspect_mask.contains(depth_stencil) {
+        [vk::ImageAspectFlags::DEPTH, vk::ImageAspectFlags::STENCIL][plane as usize]
+    } else {
+        range.aspect_mask
+    };
+
+    vk::ImageSubresourceRange {
+        aspect_mask,
+        base_mip_level: mip_level,
+        level_count: 1,
+        base_array_layer: array_layer,
+        layer_count: 1,
+    }
+}
+
+fn get_image_barrier(
+    image: vk::Image,
+    range: vk::ImageSubresourceRange,
+    (old_layout, src_access_mask): (vk::ImageLayout, vk::AccessFlags),
+    (new_layout, dst_access_mask): (vk::ImageLayout, vk::AccessFlags),
+) -> vk::ImageMemoryBarrier {
+    vk::ImageMemoryBarrier::builder()
+        .src_access_mask(src_access_mask)
+        .dst_access_mask(dst_access_mask)
+        .old_layout(old_layout)
+        .new_layout(new_layout)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .image(image)
+        .subresource_range(range)
+        .build()
+}
+
+pub(crate) fn get_native_pool_flags(flags: hal::CommandPoolFlags) -> vk::CommandPoolCreateFlags {
+    if flags.contains(hal::CommandPoolFlags::MULTIPLE_ALLOCATOR) {
+        // The whole pool is reset whenever its only command buffer begins recording
+        vk::CommandPoolCreateFlags::TRANSIENT
+    } else {
+        vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER
+    }
+}
+
+pub(crate) fn get_native_layout(state: hal::ResourceState) -> vk::ImageLayout {
+    if state == hal::ResourceState::COMMON || state.contains(hal::ResourceState::STORAGE) {
+        return vk::ImageLayout::GENERAL;
+    }
+    if state.contains(hal::ResourceState::PRESENT) {
+        return vk::ImageLayout::PRESENT_SRC_KHR;
+    }
+    if state.contains(hal::ResourceState::RENDER_TARGET) {
+        return vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL;
+    }
+    if state.contains(hal::ResourceState::DEPTH_WRITE) {
+        return vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL;
+    }
+    if state.intersects(hal::ResourceState::COPY_DST | hal::ResourceState::RESOLVE_DST) {
+        return vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+    }
+
+    // Combined read states need a layout that fits all of them
+    let transfer_src = hal::ResourceState::COPY_SRC | hal::ResourceState::RESOLVE_SRC;
+    if transfer_src.contains(state) {
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL
+    } else if hal::ResourceState::SHADER_READ.contains(state) {
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+    } else if (hal::ResourceState::SHADER_READ | hal::ResourceState::DEPTH_READ).contains(state) {
+        vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+    } else {
+        vk::ImageLayout::GENERAL
+    }
+}
+
+pub(crate) fn get_native_access(
+    state: hal::ResourceState,
+) -> (vk::AccessFlags, vk::PipelineStageFlags) {
+    if state == hal::ResourceState::COMMON {
+        return (
+            vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+            vk::PipelineStageFlags::ALL_COMMANDS,
+        );
+    }
+
+    let non_pixel_shader_stages = vk::PipelineStageFlags::VERTEX_SHADER
+        | vk::PipelineStageFlags::TESSELLATION_CONTROL_SHADER
+        | vk::PipelineStageFlags::TESSELLATION_EVALUATION_SHADER
+        | vk::PipelineStageFlags::GEOMETRY_SHADER
+        | vk::PipelineStageFlags::COMPUTE_SHADER;
+    let shader_stages = non_pixel_shader_stages | vk::PipelineStageFlags::FRAGMENT_SHADER;
+    let fragment_test_stages =
+        vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+
+    let mut access = vk::AccessFlags::empty();
+    let mut stages = vk::PipelineStageFlags::empty();
+    for &(flag, flag_access, flag_stages) in &[
+        (
+            hal::ResourceState::VERTEX_BUFFER,
+            vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+        ),
+        (
+            hal::ResourceState::INDEX_BUFFER,
+            vk::AccessFlags::INDEX_READ,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+        ),
+        (
+            hal::ResourceState::CONSTANT_BUFFER,
+            vk::AccessFlags::UNIFORM_READ,
+            shader_stages,
+        ),
+        (
+            hal::ResourceState::INDIRECT_ARGUMENT,
+            vk::AccessFlags::INDIRECT_COMMAND_READ,
+            vk::PipelineStageFlags::DRAW_INDIRECT,
+        ),
+        (
+            hal::ResourceState::NON_PIXEL_SHADER_READ,
+            vk::AccessFlags::SHADER_READ,
+            non_pixel_shader_stages,
+        ),
+        (
+            hal::ResourceState::PIXEL_SHADER_READ,
+            vk::AccessFlags::SHADER_READ,
+            vk::PipelineStageFlags::FRAGMENT_SHADER,
+        ),
+        (
+            hal::ResourceState::DEPTH_READ,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ,
+            fragment_test_stages,
+        ),
+        (
+            hal::ResourceState::COPY_SRC | hal::ResourceState::RESOLVE_SRC,
+            vk::AccessFlags::TRANSFER_READ,
+            vk::PipelineStageFlags::TRANSFER,
+        ),
+        (
+            hal::ResourceState::RENDER_TARGET,
+            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        ),
+        (
+            hal::ResourceState::DEPTH_WRITE,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            fragment_test_stages,
+        ),
+        (
+            hal::ResourceState::STORAGE,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            shader_stages,
+        ),
+        (
+            hal::ResourceState::COPY_DST | hal::ResourceState::RESOLVE_DST,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::TRANSFER,
+        ),
+        // Presentation synchronizes on its own, nothing needs to wait on or for it
+        (
+            hal::ResourceState::PRESENT,
+            vk::AccessFlags::empty(),
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+        ),
+    ] {
+        if state.intersects(flag) {
+            access |= flag_access;
+            stages |= flag_stages;
+        }
+    }
+    (access, stages)
+}
@@ -0,0 +1,380 @@
+use crate::backend::vulkan::command;
+use crate::backend::vulkan::device::{get_hal_error, RawDevice};
+use crate::backend::vulkan::instance::Backend;
+use crate::backend::vulkan::resource::{self, Image};
+use crate::backend::vulkan::window;
+use crate::hal;
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Descriptor set layouts and pipeline layouts, created once per description and destroyed with
+/// the device, so that command buffers still executing never outlive the layouts they use.
+#[derive(Default)]
+pub(crate) struct LayoutCache {
+    set_layouts: RefCell<HashMap<hal::DescriptorSetLayoutDesc, vk::DescriptorSetLayout>>,
+    pipeline_layouts: RefCell<HashMap<PipelineLayoutDesc, vk::PipelineLayout>>,
+}
+
+type PipelineLayoutDesc = (
+    Vec<hal::DescriptorSetLayoutDesc>,
+    Vec<hal::PushConstantRange>,
+);
+
+impl LayoutCache {
+    pub(crate) fn get_set_layout(
+        &self,
+        device: &ash::Device,
+        desc: &hal::DescriptorSetLayoutDesc,
+    ) -> hal::Result<vk::DescriptorSetLayout> {
+        if let Some(&set_layout) = self.set_layouts.borrow().get(desc) {
+            return Ok(set_layout);
+        }
+
+        let bindings = desc
+            .bindings
+            .iter()
+            .map(|binding| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(binding.binding)
+                    .descriptor_type(get_native_descriptor_type(binding.ty))
+                    .descriptor_count(binding.count)
+                    .stage_flags(get_native_stages(binding.stages))
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let binding_flags = if desc.update_after_bind {
+            vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+                | vk::DescriptorBindingFlags::PARTIALLY_BOUND
+        } else {
+            vk::DescriptorBindingFlags::empty()
+        };
+        let binding_flags = vec![binding_flags; bindings.len()];
+        let mut binding_flags_info =
+            vk::DescriptorSetLayoutBindingFlagsCreateInfo::builder().binding_flags(&binding_flags);
+        let create_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .flags(if desc.update_after_bind {
+                vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL
+            } else {
+                vk::DescriptorSetLayoutCreateFlags::empty()
+            })
+            .bindings(&bindings)
+            .push_next(&mut binding_flags_info);
+        let set_layout = unsafe { device.create_descriptor_set_layout(&create_info, None) }
+            .map_err(get_hal_error)?;
+
+        self.set_layouts
+            .borrow_mut()
+            .insert(desc.clone(), set_layout);
+        Ok(set_layout)
+    }
+
+    pub(crate) fn get_pipeline_layout(
+        &self,
+        device: &ash::Device,
+        desc: PipelineLayoutDesc,
+    ) -> hal::Result<vk::PipelineLayout> {
+        if let Some(&pipeline_layout) = self.pipeline_layouts.borrow().get(&desc) {
+            return Ok(pipeline_layout);
+        }
+
+        let (ref set_layouts, ref push_constant_ranges) = desc;
+        let native_set_layouts = set_layouts
+            .iter()
+            .map(|desc| self.get_set_layout(device, desc))
+            .collect::<hal::Result<Vec<_>>>()?;
+        let native_ranges = push_constant_ranges
+            .iter()
+            .map(|range| vk::PushConstantRange {
+                stage_flags: get_native_stages(range.stages),
+                offset: range.offset,
+                size: range.size,
+            })
+            .collect::<Vec<_>>();
+        let create_info = vk::PipelineLayoutCreateInfo::builder()
+            .set_layouts(&native_set_layouts)
+            .push_constant_ranges(&native_ranges);
+        let pipeline_layout =
+            unsafe { device.create_pipeline_layout(&create_info, None) }.map_err(get_hal_error)?;
+
+        self.pipeline_layouts
+            .borrow_mut()
+            .insert(desc, pipeline_layout);
+        Ok(pipeline_layout)
+    }
+
+    /// Destroys the layouts, which must not be used by command buffers still executing.
+    pub(crate) fn destroy(&self, device: &ash::Device) {
+        for (_, pipeline_layout) in self.pipeline_layouts.borrow_mut().drain() {
+            unsafe {
+                device.destroy_pipeline_layout(pipeline_layout, None);
+            }
+        }
+        for (_, set_layout) in self.set_layouts.borrow_mut().drain() {
+            unsafe {
+                device.destroy_descriptor_set_layout(set_layout, None);
+            }
+        }
+    }
+}
+
+pub struct DescriptorSetLayout {
+    pub(crate) native: vk::DescriptorSetLayout,
+    pub(crate) desc: hal::DescriptorSetLayoutDesc,
+}
+
+impl DescriptorSetLayout {
+    pub(crate) fn new(
+        device: &RawDevice,
+        desc: &hal::DescriptorSetLayoutDesc,
+    ) -> hal::Result<Self> {
+        Ok(DescriptorSetLayout {
+            native: device.layouts.get_set_layout(&device.native, desc)?,
+            desc: desc.clone(),
+        })
+    }
+}
+
+pub struct PipelineLayout {
+    pub(crate) native: vk::PipelineLayout,
+}
+
+impl PipelineLayout {
+    pub(crate) fn new(
+        device: &RawDevice,
+        set_layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[hal::PushConstantRange],
+    ) -> hal::Result<Self> {
+        let desc = (
+            set_layouts
+                .iter()
+                .map(|set_layout| set_layout.desc.clone())
+                .collect(),
+            push_constant_ranges.to_vec(),
+        );
+        Ok(PipelineLayout {
+            native: device.layouts.get_pipeline_layout(&device.native, desc)?,
+        })
+    }
+}
+
+/// A descriptor set allocated from a pool of its own, sized for its layout.
+pub struct DescriptorSet {
+    device: Rc<RawDevice>,
+    pool: vk::DescriptorPool,
+    pub(crate) native: vk::DescriptorSet,
+    pub(crate) layout: hal::DescriptorSetLayoutDesc,
+    // Views of the images written, by binding and array element, destroyed once overwritten
+    image_views: RefCell<HashMap<(u32, u32), vk::ImageView>>,
+}
+
+impl DescriptorSet {
+    pub(crate) fn new(device: &Rc<RawDevice>, layout: &DescriptorSetLayout) -> hal::Result<Self> {
+        let mut counts = HashMap::new();
+        for binding in &layout.desc.bindings {
+            *counts.entry(binding.ty).or_insert(0) += binding.count;
+        }
+        let pool_sizes = counts
+            .into_iter()
+            .map(|(ty, descriptor_count)| vk::DescriptorPoolSize {
+                ty: get_native_descriptor_type(ty),
+                descriptor_count,
+            })
+            .collect::<Vec<_>>();
+
+        let create_info = vk::DescriptorPoolCreateInfo::builder()
+            .flags(if layout.desc.update_after_bind {
+                vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND
+            } else {
+                vk::DescriptorPoolCreateFlags::empty()
+            })
+            .max_sets(1)
+            .pool_sizes(&pool_sizes);
+        let pool = unsafe { device.native.create_descriptor_pool(&create_info, None) }
+            .map_err(get_hal_error)?;
+
+        let set_layouts = [layout.native];
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(&set_layouts);
+        let native = match unsafe { device.native.allocate_descriptor_sets(&allocate_info) } {
+            Ok(mut sets) => sets.remove(0),
+            Err(err) => {
+                unsafe {
+                    device.native.destroy_descriptor_pool(pool, None);
+                }
+                return Err(get_hal_error(err));
+            }
+        };
+
+        Ok(DescriptorSet {
+            device: device.clone(),
+            pool,
+            native,
+            layout: layout.desc.clone(),
+            image_views: RefCell::new(HashMap::new()),
+        })
+    }
+
+    pub(crate) fn write(&self, write: &hal::DescriptorWrite<Backend>) -> hal::Result<()> {
+        let binding = self.check_write(write);
+        let descriptor_type = get_native_descriptor_type(binding.ty);
+
+        let mut buffer_infos = Vec::new();
+        let mut image_infos = Vec::new();
+        let mut image_views = self.image_views.borrow_mut();
+        for (index, descriptor) in write.descriptors.iter().enumerate() {
+            match *descriptor {
+                hal::Descriptor::Buffer(buffer) => buffer_infos.push(vk::DescriptorBufferInfo {
+                    buffer: buffer.native,
+                    offset: 0,
+                    range: vk::WHOLE_SIZE,
+                }),
+                hal::Descriptor::Image(image) => {
+                    let image_view = create_image_view(&self.device, image)?;
+                    let key = (write.binding, write.array_element + index as u32);
+                    if let Some(old_view) = image_views.insert(key, image_view) {
+                        unsafe {
+                            self.device.native.destroy_image_view(old_view, None);
+                        }
+                    }
+
+                    // Images are expected in the state their descriptor type is meant for
+                    let state = match binding.ty {
+                        hal::DescriptorType::StorageImage => hal::ResourceState::STORAGE,
+                        _ => hal::ResourceState::SHADER_READ,
+                    };
+                    image_infos.push(vk::DescriptorImageInfo {
+                        sampler: vk::Sampler::null(),
+                        image_view,
+                        image_layout: command::get_native_layout(state),
+                    });
+                }
+            }
+        }
+
+        let native_write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.native)
+            .dst_binding(write.binding)
+            .dst_array_element(write.array_element)
+            .descriptor_type(descriptor_type);
+        let native_write = if binding.ty.is_buffer() {
+            native_write.buffer_info(&buffer_infos)
+        } else {
+            native_write.image_info(&image_infos)
+        };
+        unsafe {
+            self.device
+                .native
+                .update_descriptor_sets(&[native_write.build()], &[]);
+        }
+        Ok(())
+    }
+
+    fn check_write(&self, write: &hal::DescriptorWrite<Backend>) -> &hal::DescriptorBinding {
+        let binding = self.layout.get_binding(write.binding).unwrap_or_else(|| {
+            panic!(
+                "Descriptors written to binding {} missing from the set layout",
+                write.binding
+            )
+        });
+        assert!(
+            write.array_element + write.descriptors.len() as u32 <= binding.count,
+            "{} descriptors written from element {} of a binding of {}",
+            write.descriptors.len(),
+            write.array_element,
+            binding.count
+        );
+        for descriptor in write.descriptors {
+            match *descriptor {
+                hal::Descriptor::Buffer(buffer) => assert!(
+                    binding.ty.is_buffer()
+                        && buffer.usage().contains(binding.ty.get_buffer_usage()),
+                    "Buffer of {:?} written to a {:?} binding",
+                    buffer.usage(),
+                    binding.ty
+                ),
+                hal::Descriptor::Image(image) => assert!(
+                    !binding.ty.is_buffer()
+                        && image.desc().usage.contains(binding.ty.get_image_usage()),
+                    "Image of {:?} written to a {:?} binding",
+                    image.desc().usage,
+                    binding.ty
+                ),
+            }
+        }
+        binding
+    }
+}
+
+impl Drop for DescriptorSet {
+    fn drop(&mut self) {
+        unsafe {
+            for (_, image_view) in self.image_views.borrow_mut().drain() {
+                self.device.native.destroy_image_view(image_view, None);
+            }
+            // Destroying the pool frees the set along with it
+            self.device.native.destroy_descriptor_pool(self.pool, None);
+        }
+    }
+}
+
+/// Creates a view of the whole image, or of its depth plane for depth stencil formats.
+fn create_image_view(device: &RawDevice, image: &Image) -> hal::Result<vk::ImageView> {
+    let desc = image.desc();
+    let is_layered = desc.array_layers > 1;
+    let view_type = match desc.kind {
+        hal::ImageKind::D1 if is_layered => vk::ImageViewType::TYPE_1D_ARRAY,
+        hal::ImageKind::D1 => vk::ImageViewType::TYPE_1D,
+        hal::ImageKind::D2 if is_layered => vk::ImageViewType::TYPE_2D_ARRAY,
+        hal::ImageKind::D2 => vk::ImageViewType::TYPE_2D,
+        hal::ImageKind::D3 => vk::ImageViewType::TYPE_3D,
+        hal::ImageKind::Cube if desc.array_layers > 6 => vk::ImageViewType::CUBE_ARRAY,
+        hal::ImageKind::Cube => vk::ImageViewType::CUBE,
+    };
+    let mut range = image.range;
+    if desc.format.aspects().contains(hal::Aspects::DEPTH) {
+        range.aspect_mask = resource::get_native_aspects(hal::Aspects::DEPTH);
+    }
+
+    let create_info = vk::ImageViewCreateInfo::builder()
+        .image(image.native)
+        .view_type(view_type)
+        .format(window::get_native_format(desc.format))
+        .subresource_range(range);
+    unsafe { device.native.create_image_view(&create_info, None) }.map_err(get_hal_error)
+}
+
+pub(crate) fn get_native_descriptor_type(ty: hal::DescriptorType) -> vk::DescriptorType {
+    match ty {
+        hal::DescriptorType::UniformBuffer => vk::DescriptorType::UNIFORM_BUFFER,
+        hal::DescriptorType::StorageBuffer => vk::DescriptorType::STORAGE_BUFFER,
+        hal::DescriptorType::SampledImage => vk::DescriptorType::SAMPLED_IMAGE,
+        hal::DescriptorType::StorageImage => vk::DescriptorType::STORAGE_IMAGE,
+    }
+}
+
+pub(crate) fn get_native_stages(stages: hal::ShaderStageFlags) -> vk::ShaderStageFlags {
+    let mut flags = vk::ShaderStageFlags::empty();
+    if stages.contains(hal::ShaderStageFlags::VERTEX) {
+        flags |= vk::ShaderStageFlags::VERTEX;
+    }
+    if stages.contains(hal::ShaderStageFlags::FRAGMENT) {
+        flags |= vk::ShaderStageFlags::FRAGMENT;
+    }
+    if stages.contains(hal::ShaderStageFlags::COMPUTE) {
+        flags |= vk::ShaderStageFlags::COMPUTE;
+    }
+    flags
+}
+
+pub(crate) fn get_native_bind_point(bind_point: hal::PipelineBindPoint) -> vk::PipelineBindPoint {
+    match bind_point {
+        hal::PipelineBindPoint::Graphics => vk::PipelineBindPoint::GRAPHICS,
+        hal::PipelineBindPoint::Compute => vk::PipelineBindPoint::COMPUTE,
+    }
+}
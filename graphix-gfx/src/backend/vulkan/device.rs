@@ -0,0 +1,513 @@
+use crate::backend::vulkan::adapter::PhysicalAdapter;
+use crate::backend::vulkan::command::CommandPool;
+use crate::backend::vulkan::descriptor::{
+    DescriptorSet, DescriptorSetLayout, LayoutCache, PipelineLayout,
+};
+use crate::backend::vulkan::instance::{Backend, RawInstance};
+use crate::backend::vulkan::queue::{CommandQueue, QueueFamilies};
+use crate::backend::vulkan::resource::{
+    self, Buffer, BufferView, FrameBuffer, Heap, Image, RenderPass,
+};
+use crate::backend::vulkan::sync::Fence;
+use crate::backend::vulkan::window;
+use crate::hal;
+
+use ash::extensions::khr;
+use ash::version::{DeviceV1_0, InstanceV1_0, InstanceV1_1};
+use ash::vk;
+
+use std::ffi::{c_void, CStr};
+use std::rc::Rc;
+
+pub(crate) struct RawDevice {
+    pub(crate) native: ash::Device,
+    // Never signaled, only polled since querying any fence reports whether the device was lost
+    lost_probe: vk::Fence,
+    pub(crate) instance: Rc<RawInstance>,
+    pub(crate) physical_device: vk::PhysicalDevice,
+    pub(crate) memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pub(crate) queue_families: QueueFamilies,
+    pub(crate) layouts: LayoutCache,
+}
+
+impl RawDevice {
+    pub(crate) fn find_memory_type_index(
+        &self,
+        type_bits: u32,
+        memory_type: hal::MemoryType,
+    ) -> Option<u32> {
+        let (required_flags, preferred_flags) = resource::get_native_memory_flags(memory_type);
+        let memory_types =
+            &self.memory_properties.memory_types[..self.memory_properties.memory_type_count as _];
+        let find = |flags: vk::MemoryPropertyFlags| {
+            memory_types
+                .iter()
+                .enumerate()
+                .position(|(index, ty)| {
+                    type_bits & (1 << index) != 0 && ty.property_flags.contains(flags)
+                })
+                .map(|index| index as u32)
+        };
+
+        find(required_flags | preferred_flags).or_else(|| find(required_flags))
+    }
+}
+
+impl Drop for RawDevice {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.native.device_wait_idle();
+            self.layouts.destroy(&self.native);
+            self.native.destroy_fence(self.lost_probe, None);
+            self.native.destroy_device(None);
+        }
+    }
+}
+
+pub struct Device {
+    pub(crate) raw: Rc<RawDevice>,
+    limits: hal::Limits,
+    features: hal::Features,
+    // Buffers and images sharing memory must be this far apart, so placements are aligned to it
+    buffer_image_granularity: u64,
+}
+
+impl Device {
+    pub(crate) fn new(adapter: &PhysicalAdapter) -> hal::Result<Self> {
+        let instance = &adapter.instance.native;
+
+        let queue_family_properties =
+            unsafe { instance.get_physical_device_queue_family_properties(adapter.native) };
+        let queue_families =
+            QueueFamilies::select(&queue_family_properties).ok_or(hal::Error::Unsupported)?;
+
+        let queue_priorities = [1.0];
+        let queue_create_infos = queue_families
+            .unique()
+            .into_iter()
+            .map(|family_index| {
+                vk::DeviceQueueCreateInfo::builder()
+                    .queue_family_index(family_index)
+                    .queue_priorities(&queue_priorities)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        // Swapchains are optional, devices without presentation support can still render offscreen
+        let available_extensions =
+            unsafe { instance.enumerate_device_extension_properties(adapter.native) }
+                .map_err(get_hal_error)?;
+        let has_swapchain = available_extensions.iter().any(|extension| {
+            let name = unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) };
+            name == khr::Swapchain::name()
+        });
+        let extension_names = if has_swapchain {
+            vec![khr::Swapchain::name().as_ptr()]
+        } else {
+            Vec::new()
+        };
+
+        // ash doesn't let the Vulkan 1.2 structures extend the queries, so they're chained by hand
+        let mut supported_vulkan12_features = vk::PhysicalDeviceVulkan12Features::default();
+        let mut supported_features = vk::PhysicalDeviceFeatures2 {
+            p_next: &mut supported_vulkan12_features as *mut _ as *mut c_void,
+            ..Default::default()
+        };
+        unsafe { instance.get_physical_device_features2(adapter.native, &mut supported_features) };
+        let mut vulkan12_properties = vk::PhysicalDeviceVulkan12Properties::default();
+        let mut properties = vk::PhysicalDeviceProperties2::builder()
+            .push_next(&mut vulkan12_properties)
+            .build();
+        unsafe { instance.get_physical_device_properties2(adapter.native, &mut properties) };
+        let properties = properties.properties;
+
+        let features =
+            Device::get_features(&supported_features.features, &supported_vulkan12_features);
+        let limits = Device::get_limits(&properties.limits, &vulkan12_properties, features);
+
+        // Optional features are enabled whenever they are supported, so that the hal reports them
+        let enabled_features = vk::PhysicalDeviceFeatures::builder()
+            .sparse_binding(features.contains(hal::Features::TILED_RESOURCES))
+            .sparse_residency_buffer(features.contains(hal::Features::TILED_RESOURCES))
+            .sparse_residency_image2_d(features.contains(hal::Features::TILED_RESOURCES))
+            .sampler_anisotropy(features.contains(hal::Features::SAMPLER_ANISOTROPY))
+            .texture_compression_bc(features.contains(hal::Features::TEXTURE_COMPRESSION_BC))
+            .shader_float64(features.contains(hal::Features::SHADER_FLOAT64));
+        let bindless = features.contains(hal::Features::BINDLESS);
+        let mut vulkan12_features = vk::PhysicalDeviceVulkan12Features::builder()
+            .timeline_semaphore(true)
+            .runtime_descriptor_array(bindless)
+            .descriptor_binding_partially_bound(bindless)
+            .descriptor_binding_uniform_buffer_update_after_bind(bindless)
+            .descriptor_binding_storage_buffer_update_after_bind(bindless)
+            .descriptor_binding_sampled_image_update_after_bind(bindless)
+            .descriptor_binding_storage_image_update_after_bind(bindless)
+            .shader_sampled_image_array_non_uniform_indexing(bindless)
+            .shader_storage_buffer_array_non_uniform_indexing(bindless);
+        let create_info = vk::DeviceCreateInfo::builder()
+            .queue_create_infos(&queue_create_infos)
+            .enabled_extension_names(&extension_names)
+            .enabled_features(&enabled_features)
+            .push_next(&mut vulkan12_features);
+
+        let device = unsafe { instance.create_device(adapter.native, &create_info, None) }
+            .map_err(get_hal_error)?;
+
+        let lost_probe = match unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None) }
+        {
+            Ok(fence) => fence,
+            Err(err) => {
+                unsafe {
+                    device.destroy_device(None);
+                }
+                return Err(get_hal_error(err));
+            }
+        };
+
+        Ok(Device {
+            raw: Rc::new(RawDevice {
+                native: device,
+                lost_probe,
+                instance: adapter.instance.clone(),
+                physical_device: adapter.native,
+                memory_properties: unsafe {
+                    instance.get_physical_device_memory_properties(adapter.native)
+                },
+                queue_families,
+                layouts: Default::default(),
+            }),
+            limits,
+            features,
+            buffer_image_granularity: properties.limits.buffer_image_granularity,
+        })
+    }
+
+    fn get_requirements(&self, requirements: vk::MemoryRequirements) -> hal::MemoryRequirements {
+        hal::MemoryRequirements {
+            size: requirements.size,
+            alignment: requirements.alignment.max(self.buffer_image_granularity),
+        }
+    }
+
+    fn get_limits(
+        limits: &vk::PhysicalDeviceLimits,
+        vulkan12_properties: &vk::PhysicalDeviceVulkan12Properties,
+        features: hal::Features,
+    ) -> hal::Limits {
+        hal::Limits {
+            // Sparse residency is the main feature level 12_0 adds over what Vulkan 1.2 requires
+            feature_level: if features.contains(hal::Features::TILED_RESOURCES) {
+                hal::FeatureLevel::L12_0
+            } else {
+                hal::FeatureLevel::L11_0
+            },
+            max_image_dimension_1d: limits.max_image_dimension1_d,
+            max_image_dimension_2d: limits.max_image_dimension2_d,
+            max_image_dimension_3d: limits.max_image_dimension3_d,
+            max_image_dimension_cube: limits.max_image_dimension_cube,
+            max_image_array_layers: limits.max_image_array_layers,
+            max_color_attachments: limits.max_color_attachments,
+            max_descriptors: limits.max_per_stage_resources,
+            max_samplers: limits.max_descriptor_set_samplers,
+            max_descriptor_sets: limits.max_bound_descriptor_sets,
+            max_bindless_descriptors: if features.contains(hal::Features::BINDLESS) {
+                vulkan12_properties.max_per_stage_update_after_bind_resources
+            } else {
+                0
+            },
+            max_push_constants_size: limits.max_push_constants_size,
+            sample_counts: (limits.framebuffer_color_sample_counts
+                & limits.framebuffer_depth_sample_counts)
+                .as_raw(),
+            min_constant_buffer_offset_alignment: limits.min_uniform_buffer_offset_alignment,
+        }
+    }
+
+    fn get_features(
+        features: &vk::PhysicalDeviceFeatures,
+        vulkan12_features: &vk::PhysicalDeviceVulkan12Features,
+    ) -> hal::Features {
+        let mut hal_features = hal::Features::empty();
+        if features.sparse_binding != 0
+            && features.sparse_residency_buffer != 0
+            && features.sparse_residency_image2_d != 0
+        {
+            hal_features |= hal::Features::TILED_RESOURCES;
+        }
+        if features.sampler_anisotropy != 0 {
+            hal_features |= hal::Features::SAMPLER_ANISOTROPY;
+        }
+        if features.texture_compression_bc != 0 {
+            hal_features |= hal::Features::TEXTURE_COMPRESSION_BC;
+        }
+        if features.shader_float64 != 0 {
+            hal_features |= hal::Features::SHADER_FLOAT64;
+        }
+        // Bindless sets are arrays of every descriptor type, written while bound and left partly
+        // unwritten, which shaders index with values varying across invocations
+        if vulkan12_features.runtime_descriptor_array != 0
+            && vulkan12_features.descriptor_binding_partially_bound != 0
+            && vulkan12_features.descriptor_binding_uniform_buffer_update_after_bind != 0
+            && vulkan12_features.descriptor_binding_storage_buffer_update_after_bind != 0
+            && vulkan12_features.descriptor_binding_sampled_image_update_after_bind != 0
+            && vulkan12_features.descriptor_binding_storage_image_update_after_bind != 0
+            && vulkan12_features.shader_sampled_image_array_non_uniform_indexing != 0
+            && vulkan12_features.shader_storage_buffer_array_non_uniform_indexing != 0
+        {
+            hal_features |= hal::Features::BINDLESS;
+        }
+        // Any memory can hold both buffers and images, with requirements aligned to keep them
+        // bufferImageGranularity apart
+        hal_features |= hal::Features::MIXED_RESOURCE_HEAPS;
+        hal_features
+    }
+}
+
+impl hal::Device<Backend> for Device {
+    fn create_command_queue(&self, queue_type: hal::QueueType) -> hal::Result<CommandQueue> {
+        Ok(CommandQueue::new(self, queue_type))
+    }
+
+    fn create_command_pool(
+        &self,
+        pool_type: hal::QueueType,
+        flags: hal::CommandPoolFlags,
+    ) -> hal::Result<CommandPool> {
+        CommandPool::new(self, pool_type, flags)
+    }
+
+    fn create_buffer(
+        &self,
+        size: u64,
+        usage: hal::BufferUsage,
+        memory_type: hal::MemoryType,
+    ) -> hal::Result<Buffer> {
+        Buffer::new(&self.raw, size, usage, memory_type)
+    }
+
+    fn map_buffer(&self, buffer: &Buffer) -> hal::Result<*mut u8> {
+        buffer.map()
+    }
+
+    fn unmap_buffer(&self, buffer: &Buffer) {
+        buffer.unmap()
+    }
+
+    fn limits(&self) -> hal::Limits {
+        self.limits
+    }
+
+    fn features(&self) -> hal::Features {
+        self.features
+    }
+
+    fn format_properties(&self, format: hal::Format) -> hal::FormatProperties {
+        let properties = unsafe {
+            self.raw
+                .instance
+                .native
+                .get_physical_device_format_properties(
+                    self.raw.physical_device,
+                    window::get_native_format(format),
+                )
+        };
+        get_format_properties(properties.optimal_tiling_features)
+    }
+
+    fn create_image(&self, desc: hal::ImageDesc) -> hal::Result<Image> {
+        Image::new(self, desc)
+    }
+
+    fn create_heap(
+        &self,
+        size: u64,
+        memory_type: hal::MemoryType,
+        usage: hal::HeapUsage,
+    ) -> hal::Result<Heap> {
+        Heap::new(&self.raw, size, memory_type, usage)
+    }
+
+    fn get_buffer_requirements(
+        &self,
+        size: u64,
+        usage: hal::BufferUsage,
+    ) -> hal::Result<hal::MemoryRequirements> {
+        // Vulkan only reports requirements of existing buffers
+        let native = resource::create_native_buffer(&self.raw, size, usage)?;
+        let requirements = unsafe {
+            let requirements = self.raw.native.get_buffer_memory_requirements(native);
+            self.raw.native.destroy_buffer(native, None);
+            requirements
+        };
+        Ok(self.get_requirements(requirements))
+    }
+
+    fn get_image_requirements(
+        &self,
+        desc: &hal::ImageDesc,
+    ) -> hal::Result<hal::MemoryRequirements> {
+        let native = resource::create_native_image(&self.raw, desc)?;
+        let requirements = unsafe {
+            let requirements = self.raw.native.get_image_memory_requirements(native);
+            self.raw.native.destroy_image(native, None);
+            requirements
+        };
+        Ok(self.get_requirements(requirements))
+    }
+
+    fn create_placed_buffer(
+        &self,
+        heap: &Heap,
+        offset: u64,
+        size: u64,
+        usage: hal::BufferUsage,
+    ) -> hal::Result<Buffer> {
+        Buffer::new_placed(&self.raw, heap, offset, size, usage)
+    }
+
+    fn create_placed_image(
+        &self,
+        heap: &Heap,
+        offset: u64,
+        desc: hal::ImageDesc,
+    ) -> hal::Result<Image> {
+        Image::new_placed(self, heap, offset, desc)
+    }
+
+    fn create_render_pass(&self, desc: &hal::RenderPassDesc) -> hal::Result<RenderPass> {
+        // Attachments are cleared as color images, depth-stencil clears aren't recorded yet
+        if !desc.has_only_color_attachments() {
+            return Err(hal::Error::Unsupported);
+        }
+
+        Ok(RenderPass {
+            attachments: desc.attachments.clone(),
+        })
+    }
+
+    fn create_framebuffer(&self, attachments: &[&Image]) -> hal::Result<FrameBuffer> {
+        // Only color attachments are supported, see `create_render_pass`
+        if attachments.iter().any(|image| {
+            !image
+                .desc()
+                .usage
+                .contains(hal::ImageUsage::COLOR_ATTACHMENT)
+        }) {
+            return Err(hal::Error::Unsupported);
+        }
+
+        let attachments = attachments
+            .iter()
+            .map(|image| BufferView {
+                image: image.native,
+                range: image.range,
+                state: image.state.clone(),
+            })
+            .collect();
+
+        Ok(FrameBuffer { attachments })
+    }
+
+    fn create_descriptor_set_layout(
+        &self,
+        desc: &hal::DescriptorSetLayoutDesc,
+    ) -> hal::Result<DescriptorSetLayout> {
+        if desc.update_after_bind
+            && desc.get_descriptor_count() > self.limits.max_bindless_descriptors
+        {
+            return Err(hal::Error::Unsupported);
+        }
+        DescriptorSetLayout::new(&self.raw, desc)
+    }
+
+    fn create_descriptor_set(&self, layout: &DescriptorSetLayout) -> hal::Result<DescriptorSet> {
+        DescriptorSet::new(&self.raw, layout)
+    }
+
+    fn write_descriptors(&self, writes: &[hal::DescriptorWrite<Backend>]) -> hal::Result<()> {
+        for write in writes {
+            write.set.write(write)?;
+        }
+        Ok(())
+    }
+
+    fn create_pipeline_layout(
+        &self,
+        set_layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[hal::PushConstantRange],
+    ) -> hal::Result<PipelineLayout> {
+        let push_constants_size = hal::PushConstantRange::get_total_size(push_constant_ranges);
+        if set_layouts.len() as u32 > self.limits.max_descriptor_sets
+            || push_constants_size > self.limits.max_push_constants_size
+        {
+            return Err(hal::Error::Unsupported);
+        }
+        PipelineLayout::new(&self.raw, set_layouts, push_constant_ranges)
+    }
+
+    fn create_fence(&self, initial_value: u64) -> hal::Result<Fence> {
+        Fence::new(&self.raw, initial_value)
+    }
+
+    fn reset_fence(&self, fence: &Fence) -> hal::Result<()> {
+        fence.reset()
+    }
+
+    fn wait_for_fence_with_timeout(
+        &self,
+        fence: &Fence,
+        value: u64,
+        timeout: u64,
+    ) -> hal::Result<()> {
+        fence.wait_for_value_with_timeout(value, timeout)
+    }
+
+    fn device_lost_reason(&self) -> Option<hal::DeviceLostReason> {
+        match unsafe { self.raw.native.get_fence_status(self.raw.lost_probe) } {
+            // Vulkan doesn't report why a device was lost
+            Err(vk::Result::ERROR_DEVICE_LOST) => Some(hal::DeviceLostReason::Unknown),
+            _ => None,
+        }
+    }
+}
+
+fn get_format_properties(features: vk::FormatFeatureFlags) -> hal::FormatProperties {
+    let mut properties = hal::FormatProperties::empty();
+    if features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE) {
+        properties |= hal::FormatProperties::SAMPLED;
+    }
+    if features.contains(vk::FormatFeatureFlags::STORAGE_IMAGE) {
+        properties |= hal::FormatProperties::STORAGE;
+    }
+    if features.contains(vk::FormatFeatureFlags::COLOR_ATTACHMENT) {
+        properties |= hal::FormatProperties::COLOR_ATTACHMENT;
+    }
+    if features.contains(vk::FormatFeatureFlags::COLOR_ATTACHMENT_BLEND) {
+        properties |= hal::FormatProperties::BLEND;
+    }
+    if features.contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT) {
+        properties |= hal::FormatProperties::DEPTH_STENCIL_ATTACHMENT;
+    }
+    properties
+}
+
+pub(crate) fn get_hal_error(result: vk::Result) -> hal::Error {
+    match result {
+        vk::Result::ERROR_OUT_OF_HOST_MEMORY => hal::Error::OutOfHostMemory,
+        vk::Result::ERROR_OUT_OF_DEVICE_MEMORY
+        | vk::Result::ERROR_OUT_OF_POOL_MEMORY
+        | vk::Result::ERROR_FRAGMENTED_POOL => hal::Error::OutOfDeviceMemory,
+        vk::Result::ERROR_DEVICE_LOST => hal::Error::DeviceLost,
+        vk::Result::ERROR_SURFACE_LOST_KHR => hal::Error::SurfaceLost,
+        vk::Result::ERROR_OUT_OF_DATE_KHR => hal::Error::OutOfDate,
+        vk::Result::TIMEOUT => hal::Error::Timeout,
+        vk::Result::ERROR_INITIALIZATION_FAILED
+        | vk::Result::ERROR_LAYER_NOT_PRESENT
+        | vk::Result::ERROR_EXTENSION_NOT_PRESENT
+        | vk::Result::ERROR_FEATURE_NOT_PRESENT
+        | vk::Result::ERROR_INCOMPATIBLE_DRIVER
+        | vk::Result::ERROR_FORMAT_NOT_SUPPORTED
+        | vk::Result::ERROR_NATIVE_WINDOW_IN_USE_KHR => hal::Error::Unsupported,
+        _ => hal::Error::Internal(result.as_raw()),
+    }
+}
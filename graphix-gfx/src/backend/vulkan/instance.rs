@@ -0,0 +1,243 @@
+use crate::backend::vulkan::adapter::PhysicalAdapter;
+use crate::backend::vulkan::command::{CommandBuffer, CommandPool};
+use crate::backend::vulkan::descriptor::{DescriptorSet, DescriptorSetLayout, PipelineLayout};
+use crate::backend::vulkan::device::Device;
+use crate::backend::vulkan::queue::{self, CommandQueue};
+use crate::backend::vulkan::resource::{Buffer, FrameBuffer, Heap, Image, RenderPass};
+use crate::backend::vulkan::sync::Fence;
+use crate::backend::vulkan::window::{Surface, Swapchain};
+use crate::hal;
+
+use ash::extensions::khr;
+use ash::version::{EntryV1_0, InstanceV1_0};
+use ash::vk;
+
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::rc::Rc;
+
+pub enum Backend {}
+impl hal::Backend for Backend {
+    type PhysicalAdapter = PhysicalAdapter;
+    type Device = Device;
+
+    type CommandQueue = CommandQueue;
+    type CommandPool = CommandPool;
+    type CommandBuffer = CommandBuffer;
+
+    type Surface = Surface;
+    type Swapchain = Swapchain;
+
+    type RenderPass = RenderPass;
+    type FrameBuffer = FrameBuffer;
+    type Buffer = Buffer;
+    type Image = Image;
+    type Heap = Heap;
+    type DescriptorSetLayout = DescriptorSetLayout;
+    type DescriptorSet = DescriptorSet;
+    type PipelineLayout = PipelineLayout;
+
+    type Fence = Fence;
+}
+
+// Timeline semaphores, used to implement fences, are core since Vulkan 1.2
+const API_VERSION: u32 = vk::make_version(1, 2, 0);
+
+pub(crate) struct RawInstance {
+    // Keeps the Vulkan library loaded for as long as the instance lives
+    #[allow(dead_code)]
+    pub(crate) entry: ash::Entry,
+    pub(crate) native: ash::Instance,
+    pub(crate) surface: khr::Surface,
+    pub(crate) headless_surface: Option<vk::ExtHeadlessSurfaceFn>,
+    #[cfg(unix)]
+    pub(crate) xlib_surface: Option<khr::XlibSurface>,
+}
+
+impl Drop for RawInstance {
+    fn drop(&mut self) {
+        unsafe {
+            self.native.destroy_instance(None);
+        }
+    }
+}
+
+pub struct Instance {
+    pub(crate) raw: Rc<RawInstance>,
+}
+
+impl Instance {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn get_layer_names(entry: &ash::Entry) -> Vec<&'static CStr> {
+        let validation_layer = CStr::from_bytes_with_nul(b"VK_LAYER_KHRONOS_validation\0").unwrap();
+
+        #[cfg(debug_assertions)]
+        {
+            // Enable the validation layer when it is installed
+            let layers = entry
+                .enumerate_instance_layer_properties()
+                .expect("Failed to enumerate Vulkan instance layers");
+            if layers.iter().any(
+                |layer| unsafe { CStr::from_ptr(layer.layer_name.as_ptr()) } == validation_layer,
+            ) {
+                return vec![validation_layer];
+            }
+        }
+
+        let _ = (entry, validation_layer);
+        Vec::new()
+    }
+
+    fn get_adapter_info(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+    ) -> hal::AdapterInfo {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let memory_properties =
+            unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+        let device_name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
+            .to_string_lossy()
+            .into_owned();
+
+        let video_memory = memory_properties.memory_heaps
+            [..memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size as usize)
+            .sum();
+
+        hal::AdapterInfo {
+            name: device_name,
+            vendor: properties.vendor_id,
+            device: properties.device_id,
+            video_memory,
+            device_type: match properties.device_type {
+                vk::PhysicalDeviceType::DISCRETE_GPU => hal::DeviceType::DiscreteGpu,
+                vk::PhysicalDeviceType::INTEGRATED_GPU => hal::DeviceType::IntegratedGpu,
+                vk::PhysicalDeviceType::CPU => hal::DeviceType::Cpu,
+                _ => hal::DeviceType::VirtualGpu,
+            },
+        }
+    }
+
+    fn is_adapter_supported(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> bool {
+        let properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        if properties.api_version < API_VERSION {
+            return false;
+        }
+
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
+        queue::QueueFamilies::select(&queue_families).is_some()
+    }
+}
+
+impl Instance {
+    /// Creates an instance, or returns `None` if no Vulkan 1.2 runtime is available.
+    pub fn try_new() -> Option<Self> {
+        let entry = ash::Entry::new().ok()?;
+
+        let available_extensions = entry.enumerate_instance_extension_properties().ok()?;
+        let is_extension_available = |name: &CStr| {
+            available_extensions.iter().any(
+                |extension| unsafe { CStr::from_ptr(extension.extension_name.as_ptr()) } == name,
+            )
+        };
+
+        let mut extensions = vec![khr::Surface::name()];
+        let has_headless_surface = is_extension_available(vk::ExtHeadlessSurfaceFn::name());
+        if has_headless_surface {
+            extensions.push(vk::ExtHeadlessSurfaceFn::name());
+        }
+        #[cfg(unix)]
+        let has_xlib_surface = is_extension_available(khr::XlibSurface::name());
+        #[cfg(unix)]
+        {
+            if has_xlib_surface {
+                extensions.push(khr::XlibSurface::name());
+            }
+        }
+        let extension_names = extensions
+            .iter()
+            .map(|name| name.as_ptr())
+            .collect::<Vec<_>>();
+
+        let layers = Instance::get_layer_names(&entry);
+        let layer_names = layers.iter().map(|name| name.as_ptr()).collect::<Vec<_>>();
+
+        let application_name = CString::new("graphix").unwrap();
+        let application_info = vk::ApplicationInfo::builder()
+            .application_name(&application_name)
+            .engine_name(&application_name)
+            .api_version(API_VERSION);
+        let create_info = vk::InstanceCreateInfo::builder()
+            .application_info(&application_info)
+            .enabled_extension_names(&extension_names)
+            .enabled_layer_names(&layer_names);
+
+        let native = unsafe { entry.create_instance(&create_info, None) }.ok()?;
+
+        let surface = khr::Surface::new(&entry, &native);
+        let headless_surface = if has_headless_surface {
+            Some(vk::ExtHeadlessSurfaceFn::load(|name| unsafe {
+                mem::transmute(entry.get_instance_proc_addr(native.handle(), name.as_ptr()))
+            }))
+        } else {
+            None
+        };
+        #[cfg(unix)]
+        let xlib_surface = if has_xlib_surface {
+            Some(khr::XlibSurface::new(&entry, &native))
+        } else {
+            None
+        };
+
+        Some(Instance {
+            raw: Rc::new(RawInstance {
+                entry,
+                native,
+                surface,
+                headless_surface,
+                #[cfg(unix)]
+                xlib_surface,
+            }),
+        })
+    }
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Instance::try_new().expect("Failed to create Vulkan instance")
+    }
+}
+
+impl hal::Instance for Instance {
+    type Backend = Backend;
+
+    fn enumerate_adapters(&self) -> Vec<hal::Adapter<Backend>> {
+        // A failing driver leaves the instance without usable adapters
+        let physical_devices =
+            unsafe { self.raw.native.enumerate_physical_devices() }.unwrap_or_default();
+
+        physical_devices
+            .into_iter()
+            .filter(|&physical_device| {
+                Instance::is_adapter_supported(&self.raw.native, physical_device)
+            })
+            .map(|physical_device| {
+                let info = Instance::get_adapter_info(&self.raw.native, physical_device);
+                hal::Adapter {
+                    adapter: PhysicalAdapter {
+                        instance: self.raw.clone(),
+                        native: physical_device,
+                    },
+                    info,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,122 @@
+use crate::backend::vulkan::command::CommandBuffer;
+use crate::backend::vulkan::device::{get_hal_error, Device, RawDevice};
+use crate::backend::vulkan::instance::Backend;
+use crate::backend::vulkan::sync::Fence;
+use crate::hal;
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::rc::Rc;
+
+/// Queue family chosen for each `hal::QueueType`, preferring the most specialised family.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct QueueFamilies {
+    graphics: u32,
+    compute: u32,
+    transfer: u32,
+}
+
+impl QueueFamilies {
+    pub(crate) fn select(properties: &[vk::QueueFamilyProperties]) -> Option<Self> {
+        let graphics = QueueFamilies::find(properties, hal::QueueType::Graphics)?;
+
+        // Graphics queues can always execute compute and transfer work
+        Some(QueueFamilies {
+            graphics,
+            compute: QueueFamilies::find(properties, hal::QueueType::Compute).unwrap_or(graphics),
+            transfer: QueueFamilies::find(properties, hal::QueueType::Transfer).unwrap_or(graphics),
+        })
+    }
+
+    fn find(properties: &[vk::QueueFamilyProperties], queue_type: hal::QueueType) -> Option<u32> {
+        let flags = get_native_flags(queue_type);
+        properties
+            .iter()
+            .enumerate()
+            .filter(|(_, family)| family.queue_count > 0 && family.queue_flags.contains(flags))
+            .min_by_key(|(_, family)| family.queue_flags.as_raw().count_ones())
+            .map(|(index, _)| index as u32)
+    }
+
+    pub(crate) fn get(&self, queue_type: hal::QueueType) -> u32 {
+        match queue_type {
+            hal::QueueType::Graphics => self.graphics,
+            hal::QueueType::Compute => self.compute,
+            hal::QueueType::Transfer => self.transfer,
+        }
+    }
+
+    pub(crate) fn unique(&self) -> Vec<u32> {
+        let mut families = vec![self.graphics, self.compute, self.transfer];
+        families.sort();
+        families.dedup();
+        families
+    }
+}
+
+pub struct CommandQueue {
+    pub(crate) device: Rc<RawDevice>,
+    pub(crate) native: vk::Queue,
+    pub(crate) family_index: u32,
+}
+
+impl CommandQueue {
+    pub(crate) fn new(device: &Device, queue_type: hal::QueueType) -> Self {
+        let family_index = device.raw.queue_families.get(queue_type);
+        let queue = unsafe { device.raw.native.get_device_queue(family_index, 0) };
+
+        CommandQueue {
+            device: device.raw.clone(),
+            native: queue,
+            family_index,
+        }
+    }
+}
+
+impl hal::CommandQueue<Backend> for CommandQueue {
+    fn submit(&self, command_buffers: Vec<&CommandBuffer>) -> hal::Result<()> {
+        // Resources are patched to the states each command buffer starts with right before it
+        let mut buffers = Vec::with_capacity(command_buffers.len());
+        for command_buffer in command_buffers {
+            if let Some(patch) = command_buffer.record_patch()? {
+                buffers.push(patch);
+            }
+            buffers.push(command_buffer.native);
+            command_buffer.tracker.borrow().commit();
+        }
+        let submit_info = vk::SubmitInfo::builder().command_buffers(&buffers);
+
+        unsafe {
+            self.device
+                .native
+                .queue_submit(self.native, &[submit_info.build()], vk::Fence::null())
+        }
+        .map_err(get_hal_error)
+    }
+
+    fn signal_fence(&self, fence: &Fence, value: u64) -> hal::Result<()> {
+        let semaphores = [fence.native.get()];
+        let values = [value];
+        let mut timeline_info =
+            vk::TimelineSemaphoreSubmitInfo::builder().signal_semaphore_values(&values);
+        let submit_info = vk::SubmitInfo::builder()
+            .signal_semaphores(&semaphores)
+            .push_next(&mut timeline_info);
+
+        unsafe {
+            self.device
+                .native
+                .queue_submit(self.native, &[submit_info.build()], vk::Fence::null())
+        }
+        .map_err(get_hal_error)
+    }
+}
+
+pub(crate) fn get_native_flags(queue_type: hal::QueueType) -> vk::QueueFlags {
+    match queue_type {
+        hal::QueueType::Graphics => vk::QueueFlags::GRAPHICS,
+        hal::QueueType::Compute => vk::QueueFlags::COMPUTE,
+        hal::QueueType::Transfer => vk::QueueFlags::TRANSFER,
+    }
+}
@@ -0,0 +1,587 @@
+use crate::backend::tracker::SubmittedState;
+use crate::backend::vulkan::command::{self, CommandPool};
+use crate::backend::vulkan::device::{get_hal_error, Device, RawDevice};
+use crate::backend::vulkan::window;
+use crate::hal;
+
+use ash::version::DeviceV1_0;
+use ash::vk;
+
+use std::ptr;
+use std::rc::Rc;
+
+pub struct Buffer {
+    device: Rc<RawDevice>,
+    pub(crate) native: vk::Buffer,
+    pub(crate) memory: vk::DeviceMemory,
+    pub(crate) state: SubmittedState<hal::ResourceState>,
+    size: u64,
+    usage: hal::BufferUsage,
+    memory_type: hal::MemoryType,
+    // Heap owning the memory of placed buffers, and where the buffer starts in it
+    heap: Option<Rc<RawHeap>>,
+    offset: u64,
+}
+
+impl Buffer {
+    pub(crate) fn new(
+        device: &Rc<RawDevice>,
+        size: u64,
+        usage: hal::BufferUsage,
+        memory_type: hal::MemoryType,
+    ) -> hal::Result<Self> {
+        // Memory is bound after the buffer exists, so that drop cleans up if that fails
+        let mut buffer = Buffer::create(device, size, usage, memory_type)?;
+
+        let requirements = unsafe { device.native.get_buffer_memory_requirements(buffer.native) };
+        let memory_type_index = device
+            .find_memory_type_index(requirements.memory_type_bits, memory_type)
+            .ok_or(hal::Error::Unsupported)?;
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index);
+        buffer.memory = unsafe { device.native.allocate_memory(&allocate_info, None) }
+            .map_err(get_hal_error)?;
+        unsafe {
+            device
+                .native
+                .bind_buffer_memory(buffer.native, buffer.memory, 0)
+        }
+        .map_err(get_hal_error)?;
+
+        Ok(buffer)
+    }
+
+    pub(crate) fn new_placed(
+        device: &Rc<RawDevice>,
+        heap: &Heap,
+        offset: u64,
+        size: u64,
+        usage: hal::BufferUsage,
+    ) -> hal::Result<Self> {
+        let mut buffer = Buffer::create(device, size, usage, heap.raw.memory_type)?;
+
+        let requirements = unsafe { device.native.get_buffer_memory_requirements(buffer.native) };
+        heap.raw
+            .check_placement(hal::HeapUsage::BUFFERS, &requirements, offset)?;
+        unsafe {
+            device
+                .native
+                .bind_buffer_memory(buffer.native, heap.raw.memory, offset)
+        }
+        .map_err(get_hal_error)?;
+        buffer.memory = heap.raw.memory;
+        buffer.heap = Some(heap.raw.clone());
+        buffer.offset = offset;
+
+        Ok(buffer)
+    }
+
+    fn create(
+        device: &Rc<RawDevice>,
+        size: u64,
+        usage: hal::BufferUsage,
+        memory_type: hal::MemoryType,
+    ) -> hal::Result<Self> {
+        Ok(Buffer {
+            device: device.clone(),
+            native: create_native_buffer(device, size, usage)?,
+            memory: vk::DeviceMemory::null(),
+            state: SubmittedState::new(hal::ResourceState::COMMON),
+            size,
+            usage,
+            memory_type,
+            heap: None,
+            offset: 0,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn usage(&self) -> hal::BufferUsage {
+        self.usage
+    }
+
+    pub(crate) fn map(&self) -> hal::Result<*mut u8> {
+        if !self.memory_type.is_host_visible() {
+            return Err(hal::Error::Unsupported);
+        }
+
+        if let Some(ref heap) = self.heap {
+            return Ok(unsafe { heap.data.add(self.offset as usize) });
+        }
+
+        unsafe {
+            self.device.native.map_memory(
+                self.memory,
+                0,
+                vk::WHOLE_SIZE,
+                vk::MemoryMapFlags::empty(),
+            )
+        }
+        .map(|data| data as *mut u8)
+        .map_err(get_hal_error)
+    }
+
+    pub(crate) fn unmap(&self) {
+        if self.heap.is_none() {
+            unsafe {
+                self.device.native.unmap_memory(self.memory);
+            }
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.native.destroy_buffer(self.native, None);
+            if self.heap.is_none() {
+                self.device.native.free_memory(self.memory, None);
+            }
+        }
+    }
+}
+
+pub struct Image {
+    device: Rc<RawDevice>,
+    pub(crate) native: vk::Image,
+    pub(crate) memory: vk::DeviceMemory,
+    pub(crate) range: vk::ImageSubresourceRange,
+    pub(crate) state: SubmittedState<hal::ResourceState>,
+    desc: hal::ImageDesc,
+    // Heap owning the memory of placed images
+    heap: Option<Rc<RawHeap>>,
+}
+
+impl Image {
+    pub(crate) fn new(device: &Device, desc: hal::ImageDesc) -> hal::Result<Self> {
+        let raw = &device.raw;
+        // Memory is bound after the image exists, so that drop cleans up if that fails
+        let mut image = Image::create(raw, desc)?;
+
+        let requirements = unsafe { raw.native.get_image_memory_requirements(image.native) };
+        let memory_type_index = raw
+            .find_memory_type_index(requirements.memory_type_bits, hal::MemoryType::DeviceLocal)
+            .ok_or(hal::Error::Unsupported)?;
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(requirements.size)
+            .memory_type_index(memory_type_index);
+        image.memory =
+            unsafe { raw.native.allocate_memory(&allocate_info, None) }.map_err(get_hal_error)?;
+        unsafe { raw.native.bind_image_memory(image.native, image.memory, 0) }
+            .map_err(get_hal_error)?;
+
+        image.initialize_layout()?;
+        Ok(image)
+    }
+
+    pub(crate) fn new_placed(
+        device: &Device,
+        heap: &Heap,
+        offset: u64,
+        desc: hal::ImageDesc,
+    ) -> hal::Result<Self> {
+        let raw = &device.raw;
+        let mut image = Image::create(raw, desc)?;
+
+        let requirements = unsafe { raw.native.get_image_memory_requirements(image.native) };
+        heap.raw.check_placement(
+            hal::HeapUsage::from_image_usage(desc.usage),
+            &requirements,
+            offset,
+        )?;
+        unsafe {
+            raw.native
+                .bind_image_memory(image.native, heap.raw.memory, offset)
+        }
+        .map_err(get_hal_error)?;
+        image.memory = heap.raw.memory;
+        image.heap = Some(heap.raw.clone());
+
+        image.initialize_layout()?;
+        Ok(image)
+    }
+
+    fn create(device: &Rc<RawDevice>, desc: hal::ImageDesc) -> hal::Result<Self> {
+        let state = if desc.usage.contains(hal::ImageUsage::COLOR_ATTACHMENT) {
+            hal::ResourceState::RENDER_TARGET
+        } else {
+            hal::ResourceState::COMMON
+        };
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: get_native_aspects(desc.format.aspects()),
+            base_mip_level: 0,
+            level_count: desc.mip_levels,
+            base_array_layer: 0,
+            layer_count: desc.array_layers,
+        };
+
+        Ok(Image {
+            device: device.clone(),
+            native: create_native_image(device, &desc)?,
+            memory: vk::DeviceMemory::null(),
+            range,
+            state: SubmittedState::with_subresources(state, desc.get_subresource_count()),
+            desc,
+            heap: None,
+        })
+    }
+
+    // Submitted states are only tracked once the image exists, so the layout has to match already
+    fn initialize_layout(&self) -> hal::Result<()> {
+        let family_index = self.device.queue_families.get(hal::QueueType::Graphics);
+        let queue = unsafe { self.device.native.get_device_queue(family_index, 0) };
+        initialize_image_layouts(
+            &self.device,
+            family_index,
+            queue,
+            &[self.native],
+            command::get_native_layout(self.state.get()),
+            self.range,
+        )
+    }
+
+    pub fn desc(&self) -> &hal::ImageDesc {
+        &self.desc
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.native.destroy_image(self.native, None);
+            if self.heap.is_none() {
+                self.device.native.free_memory(self.memory, None);
+            }
+        }
+    }
+}
+
+pub(crate) struct RawHeap {
+    device: Rc<RawDevice>,
+    pub(crate) memory: vk::DeviceMemory,
+    memory_type_index: u32,
+    // Host visible heaps stay mapped, as memory can't be mapped twice for two placed buffers
+    data: *mut u8,
+    size: u64,
+    memory_type: hal::MemoryType,
+    usage: hal::HeapUsage,
+}
+
+impl RawHeap {
+    fn check_placement(
+        &self,
+        usage: hal::HeapUsage,
+        requirements: &vk::MemoryRequirements,
+        offset: u64,
+    ) -> hal::Result<()> {
+        assert!(
+            self.usage.contains(usage),
+            "{:?} placed in a heap for {:?}",
+            usage,
+            self.usage
+        );
+        let fits = hal::MemoryRequirements {
+            size: requirements.size,
+            alignment: requirements.alignment,
+        }
+        .fits(offset, self.size);
+        assert!(
+            fits,
+            "Resource of {:?} placed at offset {} of a {} bytes heap",
+            requirements, offset, self.size
+        );
+
+        // Heaps get the first memory type matching their hal type, which not all resources allow
+        if requirements.memory_type_bits & (1 << self.memory_type_index) == 0 {
+            return Err(hal::Error::Unsupported);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for RawHeap {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.native.free_memory(self.memory, None);
+        }
+    }
+}
+
+pub struct Heap {
+    pub(crate) raw: Rc<RawHeap>,
+}
+
+impl Heap {
+    pub(crate) fn new(
+        device: &Rc<RawDevice>,
+        size: u64,
+        memory_type: hal::MemoryType,
+        usage: hal::HeapUsage,
+    ) -> hal::Result<Self> {
+        let memory_type_index = device
+            .find_memory_type_index(!0, memory_type)
+            .ok_or(hal::Error::Unsupported)?;
+        let allocate_info = vk::MemoryAllocateInfo::builder()
+            .allocation_size(size)
+            .memory_type_index(memory_type_index);
+        let memory = unsafe { device.native.allocate_memory(&allocate_info, None) }
+            .map_err(get_hal_error)?;
+
+        let mut raw = RawHeap {
+            device: device.clone(),
+            memory,
+            memory_type_index,
+            data: ptr::null_mut(),
+            size,
+            memory_type,
+            usage,
+        };
+        if memory_type.is_host_visible() {
+            raw.data = unsafe {
+                device
+                    .native
+                    .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+            }
+            .map_err(get_hal_error)? as *mut u8;
+        }
+
+        Ok(Heap { raw: Rc::new(raw) })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.raw.size
+    }
+}
+
+#[derive(Clone)]
+pub struct BufferView {
+    pub(crate) image: vk::Image,
+    pub(crate) range: vk::ImageSubresourceRange,
+    pub(crate) state: SubmittedState<hal::ResourceState>,
+}
+
+// Emulated with barriers and transfer clears for now, as native render passes would also need
+// image views and pipelines. Store ops are only a hint and get ignored until then.
+pub struct RenderPass {
+    pub(crate) attachments: Vec<hal::AttachmentDesc>,
+}
+
+#[derive(Clone)]
+pub struct FrameBuffer {
+    pub attachments: Vec<BufferView>,
+}
+
+pub(crate) fn create_native_buffer(
+    device: &RawDevice,
+    size: u64,
+    usage: hal::BufferUsage,
+) -> hal::Result<vk::Buffer> {
+    let create_info = vk::BufferCreateInfo::builder()
+        .size(size)
+        .usage(get_native_buffer_usage(usage))
+        .sharing_mode(vk::SharingMode::EXCLUSIVE);
+    unsafe { device.native.create_buffer(&create_info, None) }.map_err(get_hal_error)
+}
+
+pub(crate) fn create_native_image(
+    device: &RawDevice,
+    desc: &hal::ImageDesc,
+) -> hal::Result<vk::Image> {
+    let (image_type, flags) = match desc.kind {
+        hal::ImageKind::D1 => (vk::ImageType::TYPE_1D, vk::ImageCreateFlags::empty()),
+        hal::ImageKind::D2 => (vk::ImageType::TYPE_2D, vk::ImageCreateFlags::empty()),
+        hal::ImageKind::D3 => (vk::ImageType::TYPE_3D, vk::ImageCreateFlags::empty()),
+        hal::ImageKind::Cube => (
+            vk::ImageType::TYPE_2D,
+            vk::ImageCreateFlags::CUBE_COMPATIBLE,
+        ),
+    };
+    let create_info = vk::ImageCreateInfo::builder()
+        .flags(flags)
+        .image_type(image_type)
+        .format(window::get_native_format(desc.format))
+        .extent(vk::Extent3D {
+            width: desc.extent.width,
+            height: desc.extent.height,
+            depth: desc.extent.depth,
+        })
+        .mip_levels(desc.mip_levels)
+        .array_layers(desc.array_layers)
+        .samples(vk::SampleCountFlags::from_raw(desc.samples))
+        .tiling(vk::ImageTiling::OPTIMAL)
+        .usage(get_native_image_usage(desc.usage))
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .initial_layout(vk::ImageLayout::UNDEFINED);
+    unsafe { device.native.create_image(&create_info, None) }.map_err(get_hal_error)
+}
+
+pub(crate) fn get_color_subresource_range() -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange {
+        aspect_mask: vk::ImageAspectFlags::COLOR,
+        base_mip_level: 0,
+        level_count: 1,
+        base_array_layer: 0,
+        layer_count: 1,
+    }
+}
+
+pub(crate) fn get_native_aspects(aspects: hal::Aspects) -> vk::ImageAspectFlags {
+    let mut flags = vk::ImageAspectFlags::empty();
+    if aspects.contains(hal::Aspects::COLOR) {
+        flags |= vk::ImageAspectFlags::COLOR;
+    }
+    if aspects.contains(hal::Aspects::DEPTH) {
+        flags |= vk::ImageAspectFlags::DEPTH;
+    }
+    if aspects.contains(hal::Aspects::STENCIL) {
+        flags |= vk::ImageAspectFlags::STENCIL;
+    }
+    flags
+}
+
+/// Moves images out of their undefined initial layout, blocking until the GPU is done.
+pub(crate) fn initialize_image_layouts(
+    device: &RawDevice,
+    family_index: u32,
+    queue: vk::Queue,
+    images: &[vk::Image],
+    layout: vk::ImageLayout,
+    range: vk::ImageSubresourceRange,
+) -> hal::Result<()> {
+    let pool = CommandPool::create_native_pool(
+        device,
+        family_index,
+        hal::CommandPoolFlags::MULTIPLE_ALLOCATOR,
+    )?;
+    let command_buffer = match CommandPool::allocate_native_buffer(device, pool) {
+        Ok(command_buffer) => command_buffer,
+        Err(err) => {
+            unsafe {
+                device.native.destroy_command_pool(pool, None);
+            }
+            return Err(err);
+        }
+    };
+
+    let barriers = images
+        .iter()
+        .map(|&image| {
+            vk::ImageMemoryBarrier::builder()
+                .old_layout(vk::ImageLayout::UNDEFINED)
+                .new_layout(layout)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(range)
+                .build()
+        })
+        .collect::<Vec<_>>();
+
+    let command_buffers = [command_buffer];
+    let submit_info = vk::SubmitInfo::builder().command_buffers(&command_buffers);
+    let begin_info =
+        vk::CommandBufferBeginInfo::builder().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+
+    let result = unsafe {
+        device
+            .native
+            .begin_command_buffer(command_buffer, &begin_info)
+            .and_then(|()| {
+                device.native.cmd_pipeline_barrier(
+                    command_buffer,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &barriers,
+                );
+                device.native.end_command_buffer(command_buffer)
+            })
+            .and_then(|()| {
+                device
+                    .native
+                    .queue_submit(queue, &[submit_info.build()], vk::Fence::null())
+            })
+            .and_then(|()| device.native.queue_wait_idle(queue))
+    };
+
+    unsafe {
+        device.native.destroy_command_pool(pool, None);
+    }
+    result.map_err(get_hal_error)
+}
+
+pub(crate) fn get_native_image_usage(usage: hal::ImageUsage) -> vk::ImageUsageFlags {
+    let mut flags = vk::ImageUsageFlags::empty();
+    if usage.contains(hal::ImageUsage::TRANSFER_SRC) {
+        flags |= vk::ImageUsageFlags::TRANSFER_SRC;
+    }
+    if usage.contains(hal::ImageUsage::TRANSFER_DST) {
+        flags |= vk::ImageUsageFlags::TRANSFER_DST;
+    }
+    if usage.contains(hal::ImageUsage::SAMPLED) {
+        flags |= vk::ImageUsageFlags::SAMPLED;
+    }
+    if usage.contains(hal::ImageUsage::STORAGE) {
+        flags |= vk::ImageUsageFlags::STORAGE;
+    }
+    if usage.contains(hal::ImageUsage::COLOR_ATTACHMENT) {
+        // Clears outside of render passes are transfers
+        flags |= vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST;
+    }
+    if usage.contains(hal::ImageUsage::DEPTH_STENCIL_ATTACHMENT) {
+        flags |= vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT;
+    }
+    flags
+}
+
+pub(crate) fn get_native_buffer_usage(usage: hal::BufferUsage) -> vk::BufferUsageFlags {
+    let mut flags = vk::BufferUsageFlags::empty();
+    if usage.contains(hal::BufferUsage::TRANSFER_SRC) {
+        flags |= vk::BufferUsageFlags::TRANSFER_SRC;
+    }
+    if usage.contains(hal::BufferUsage::TRANSFER_DST) {
+        flags |= vk::BufferUsageFlags::TRANSFER_DST;
+    }
+    if usage.contains(hal::BufferUsage::VERTEX) {
+        flags |= vk::BufferUsageFlags::VERTEX_BUFFER;
+    }
+    if usage.contains(hal::BufferUsage::INDEX) {
+        flags |= vk::BufferUsageFlags::INDEX_BUFFER;
+    }
+    if usage.contains(hal::BufferUsage::CONSTANT) {
+        flags |= vk::BufferUsageFlags::UNIFORM_BUFFER;
+    }
+    if usage.contains(hal::BufferUsage::STORAGE) {
+        flags |= vk::BufferUsageFlags::STORAGE_BUFFER;
+    }
+    if usage.contains(hal::BufferUsage::INDIRECT) {
+        flags |= vk::BufferUsageFlags::INDIRECT_BUFFER;
+    }
+    flags
+}
+
+/// Property flags a memory type must have, and the ones it should have if possible.
+pub(crate) fn get_native_memory_flags(
+    memory_type: hal::MemoryType,
+) -> (vk::MemoryPropertyFlags, vk::MemoryPropertyFlags) {
+    match memory_type {
+        hal::MemoryType::DeviceLocal => (
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::MemoryPropertyFlags::empty(),
+        ),
+        hal::MemoryType::Upload => (
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            vk::MemoryPropertyFlags::empty(),
+        ),
+        hal::MemoryType::Readback => (
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+            vk::MemoryPropertyFlags::HOST_CACHED,
+        ),
+    }
+}
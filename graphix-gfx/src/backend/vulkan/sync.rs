@@ -0,0 +1,76 @@
+use crate::backend::vulkan::device::{get_hal_error, RawDevice};
+use crate::hal;
+
+use ash::version::{DeviceV1_0, DeviceV1_2};
+use ash::vk;
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// A fence backed by a timeline semaphore, so it carries a monotonic value like a D3D12 fence.
+pub struct Fence {
+    device: Rc<RawDevice>,
+    pub(crate) native: Cell<vk::Semaphore>,
+}
+
+impl Fence {
+    pub(crate) fn new(device: &Rc<RawDevice>, value: u64) -> hal::Result<Self> {
+        Ok(Fence {
+            device: device.clone(),
+            native: Cell::new(Fence::create_semaphore(device, value)?),
+        })
+    }
+
+    fn create_semaphore(device: &RawDevice, value: u64) -> hal::Result<vk::Semaphore> {
+        let mut type_create_info = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(value);
+        let create_info = vk::SemaphoreCreateInfo::builder().push_next(&mut type_create_info);
+
+        unsafe { device.native.create_semaphore(&create_info, None) }.map_err(get_hal_error)
+    }
+
+    pub(crate) fn reset(&self) -> hal::Result<()> {
+        // Timeline semaphores can't go backwards, so swap in a new one. There must be no
+        // pending signal operation on the old one at this point.
+        let semaphore = Fence::create_semaphore(&self.device, 0)?;
+        let old_semaphore = self.native.replace(semaphore);
+        unsafe {
+            self.device.native.destroy_semaphore(old_semaphore, None);
+        }
+        Ok(())
+    }
+
+    pub fn get_value(&self) -> hal::Result<u64> {
+        unsafe {
+            self.device
+                .native
+                .get_semaphore_counter_value(self.native.get())
+        }
+        .map_err(get_hal_error)
+    }
+
+    pub(crate) fn wait_for_value_with_timeout(
+        &self,
+        value: u64,
+        timeout_ns: u64,
+    ) -> hal::Result<()> {
+        let semaphores = [self.native.get()];
+        let values = [value];
+        let wait_info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(&semaphores)
+            .values(&values);
+
+        unsafe { self.device.native.wait_semaphores(&wait_info, timeout_ns) }.map_err(get_hal_error)
+    }
+}
+
+impl Drop for Fence {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .native
+                .destroy_semaphore(self.native.get(), None);
+        }
+    }
+}
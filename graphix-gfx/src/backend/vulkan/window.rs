@@ -0,0 +1,403 @@
+use crate::backend::tracker::SubmittedState;
+use crate::backend::vulkan::command;
+use crate::backend::vulkan::device::{get_hal_error, Device, RawDevice};
+use crate::backend::vulkan::instance::{Backend, Instance, RawInstance};
+use crate::backend::vulkan::queue::CommandQueue;
+use crate::backend::vulkan::resource::{self, BufferView, FrameBuffer};
+use crate::hal;
+
+use ash::extensions::khr;
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use ash::vk;
+
+use std::cell::Cell;
+use std::ptr;
+use std::rc::Rc;
+
+impl Instance {
+    /// Creates a surface with no window behind it, for CPU drivers such as lavapipe and CI.
+    pub fn create_surface_headless(&self) -> Surface {
+        let headless_surface = self
+            .raw
+            .headless_surface
+            .as_ref()
+            .expect("VK_EXT_headless_surface is not supported by the Vulkan instance");
+
+        let create_info = vk::HeadlessSurfaceCreateInfoEXT::builder();
+        let mut surface = vk::SurfaceKHR::null();
+        let result = unsafe {
+            headless_surface.create_headless_surface_ext(
+                self.raw.native.handle(),
+                &*create_info,
+                ptr::null(),
+                &mut surface,
+            )
+        };
+        assert_eq!(
+            result,
+            vk::Result::SUCCESS,
+            "Failed to create Vulkan headless surface"
+        );
+
+        Surface {
+            instance: self.raw.clone(),
+            native: surface,
+        }
+    }
+
+    #[cfg(all(feature = "winit", unix))]
+    pub fn create_surface(&self, window: &winit::Window) -> Surface {
+        use winit::os::unix::WindowExt;
+
+        let xlib_surface = self
+            .raw
+            .xlib_surface
+            .as_ref()
+            .expect("VK_KHR_xlib_surface is not supported by the Vulkan instance");
+
+        let create_info = vk::XlibSurfaceCreateInfoKHR::builder()
+            .dpy(
+                window
+                    .get_xlib_display()
+                    .expect("Window is not an Xlib window") as *mut _,
+            )
+            .window(
+                window
+                    .get_xlib_window()
+                    .expect("Window is not an Xlib window"),
+            );
+        let surface = unsafe { xlib_surface.create_xlib_surface(&create_info, None) }
+            .expect("Failed to create Vulkan Xlib surface");
+
+        Surface {
+            instance: self.raw.clone(),
+            native: surface,
+        }
+    }
+}
+
+pub struct Surface {
+    pub(crate) instance: Rc<RawInstance>,
+    pub(crate) native: vk::SurfaceKHR,
+}
+
+impl hal::Surface<Backend> for Surface {
+    fn create_swapchain(
+        &self,
+        device: &Device,
+        command_queue: &CommandQueue,
+        config: hal::SwapchainConfig,
+    ) -> hal::Result<Swapchain> {
+        Swapchain::new(self, device, command_queue, config)
+    }
+}
+
+impl Drop for Surface {
+    fn drop(&mut self) {
+        unsafe {
+            self.instance.surface.destroy_surface(self.native, None);
+        }
+    }
+}
+
+pub struct Swapchain {
+    device: Rc<RawDevice>,
+    loader: khr::Swapchain,
+    pub(crate) native: vk::SwapchainKHR,
+    queue: vk::Queue,
+    pub(crate) images: Vec<vk::Image>,
+    states: Vec<SubmittedState<hal::ResourceState>>,
+    acquire_fence: vk::Fence,
+    present_semaphores: Vec<vk::Semaphore>,
+    current_index: Cell<Option<hal::SwapchainBufferIndex>>,
+}
+
+impl Swapchain {
+    pub fn new(
+        surface: &Surface,
+        device: &Device,
+        command_queue: &CommandQueue,
+        config: hal::window::SwapchainConfig,
+    ) -> hal::Result<Self> {
+        let surface_loader = &surface.instance.surface;
+        let physical_device = device.raw.physical_device;
+
+        let is_supported = unsafe {
+            surface_loader.get_physical_device_surface_support(
+                physical_device,
+                command_queue.family_index,
+                surface.native,
+            )
+        }
+        .map_err(get_hal_error)?;
+        if !is_supported {
+            return Err(hal::Error::Unsupported);
+        }
+
+        let capabilities = unsafe {
+            surface_loader.get_physical_device_surface_capabilities(physical_device, surface.native)
+        }
+        .map_err(get_hal_error)?;
+
+        // Surfaces without a fixed size (e.g. headless ones) take the size of the swapchain
+        let extent = if capabilities.current_extent.width == u32::MAX {
+            vk::Extent2D {
+                width: config.width,
+                height: config.height,
+            }
+        } else {
+            capabilities.current_extent
+        };
+
+        let mut image_count = (config.buffer_count as u32).max(capabilities.min_image_count);
+        if capabilities.max_image_count > 0 {
+            image_count = image_count.min(capabilities.max_image_count);
+        }
+
+        let present_modes = unsafe {
+            surface_loader
+                .get_physical_device_surface_present_modes(physical_device, surface.native)
+        }
+        .map_err(get_hal_error)?;
+        let present_mode = if config.sync_interval == 0 {
+            [vk::PresentModeKHR::IMMEDIATE, vk::PresentModeKHR::MAILBOX]
+                .iter()
+                .cloned()
+                .find(|mode| present_modes.contains(mode))
+                .unwrap_or(vk::PresentModeKHR::FIFO)
+        } else {
+            vk::PresentModeKHR::FIFO
+        };
+
+        let create_info = vk::SwapchainCreateInfoKHR::builder()
+            .surface(surface.native)
+            .min_image_count(image_count)
+            .image_format(get_native_format(config.format))
+            .image_color_space(vk::ColorSpaceKHR::SRGB_NONLINEAR)
+            .image_extent(extent)
+            .image_array_layers(1)
+            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST)
+            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .pre_transform(capabilities.current_transform)
+            .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+            .present_mode(present_mode)
+            .clipped(true);
+
+        let loader = khr::Swapchain::new(&device.raw.instance.native, &device.raw.native);
+        let native =
+            unsafe { loader.create_swapchain(&create_info, None) }.map_err(get_hal_error)?;
+
+        // Objects are filled in one at a time so that a failure part way through releases
+        // everything created so far on drop
+        let mut swapchain = Swapchain {
+            device: device.raw.clone(),
+            loader,
+            native,
+            queue: command_queue.native,
+            images: Vec::new(),
+            states: Vec::new(),
+            acquire_fence: vk::Fence::null(),
+            present_semaphores: Vec::new(),
+            current_index: Cell::new(None),
+        };
+
+        swapchain.images =
+            unsafe { swapchain.loader.get_swapchain_images(native) }.map_err(get_hal_error)?;
+        swapchain.acquire_fence = unsafe {
+            device
+                .raw
+                .native
+                .create_fence(&vk::FenceCreateInfo::default(), None)
+        }
+        .map_err(get_hal_error)?;
+        for _ in 0..swapchain.images.len() {
+            let semaphore = unsafe {
+                device
+                    .raw
+                    .native
+                    .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)
+            }
+            .map_err(get_hal_error)?;
+            swapchain.present_semaphores.push(semaphore);
+        }
+
+        // Swapchain images start out undefined, but the hal expects back buffers to be ready for present
+        resource::initialize_image_layouts(
+            &device.raw,
+            command_queue.family_index,
+            command_queue.native,
+            &swapchain.images,
+            command::get_native_layout(hal::ResourceState::PRESENT),
+            resource::get_color_subresource_range(),
+        )?;
+        swapchain.states = swapchain
+            .images
+            .iter()
+            .map(|_| SubmittedState::new(hal::ResourceState::PRESENT))
+            .collect();
+
+        Ok(swapchain)
+    }
+}
+
+impl hal::Swapchain<Backend> for Swapchain {
+    fn acquire_buffer(&self) -> hal::Result<hal::SwapchainBufferIndex> {
+        if let Some(index) = self.current_index.get() {
+            return Ok(index);
+        }
+
+        // Wait on the CPU until the image is available, so later submissions need no semaphore
+        let (index, _suboptimal) = unsafe {
+            self.loader.acquire_next_image(
+                self.native,
+                u64::MAX,
+                vk::Semaphore::null(),
+                self.acquire_fence,
+            )
+        }
+        .map_err(get_hal_error)?;
+        let fences = [self.acquire_fence];
+        unsafe {
+            self.device
+                .native
+                .wait_for_fences(&fences, true, u64::MAX)
+                .and_then(|()| self.device.native.reset_fences(&fences))
+        }
+        .map_err(get_hal_error)?;
+
+        self.current_index.set(Some(index));
+        Ok(index)
+    }
+
+    fn present(&self) -> hal::Result<()> {
+        let index = self.acquire_buffer()?;
+        debug_assert_eq!(
+            self.states[index as usize].get(),
+            hal::ResourceState::PRESENT,
+            "Back buffer presented while not in present state"
+        );
+
+        // An empty batch signals once all work submitted so far to the queue has completed
+        let semaphores = [self.present_semaphores[index as usize]];
+        let submit_info = vk::SubmitInfo::builder().signal_semaphores(&semaphores);
+        unsafe {
+            self.device
+                .native
+                .queue_submit(self.queue, &[submit_info.build()], vk::Fence::null())
+        }
+        .map_err(get_hal_error)?;
+
+        let swapchains = [self.native];
+        let image_indices = [index];
+        let present_info = vk::PresentInfoKHR::builder()
+            .wait_semaphores(&semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&image_indices);
+        let result = unsafe { self.loader.queue_present(self.queue, &present_info) };
+
+        // The image goes back to the presentation engine even when presenting fails
+        self.current_index.set(None);
+        result.map(|_suboptimal| ()).map_err(get_hal_error)
+    }
+
+    fn create_backbuffer(&self) -> hal::BackBuffer<Backend> {
+        let framebuffers = self
+            .images
+            .iter()
+            .zip(self.states.iter())
+            .map(|(&image, state)| FrameBuffer {
+                attachments: vec![BufferView {
+                    image,
+                    range: resource::get_color_subresource_range(),
+                    state: state.clone(),
+                }],
+            })
+            .collect();
+
+        hal::BackBuffer { framebuffers }
+    }
+}
+
+impl Drop for Swapchain {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.native.device_wait_idle();
+            for &semaphore in &self.present_semaphores {
+                self.device.native.destroy_semaphore(semaphore, None);
+            }
+            self.device.native.destroy_fence(self.acquire_fence, None);
+            self.loader.destroy_swapchain(self.native, None);
+        }
+    }
+}
+
+pub(crate) fn get_native_format(format: hal::Format) -> vk::Format {
+    match format {
+        hal::Format::R8Unorm => vk::Format::R8_UNORM,
+        hal::Format::R8Snorm => vk::Format::R8_SNORM,
+        hal::Format::R8Uint => vk::Format::R8_UINT,
+        hal::Format::R8Sint => vk::Format::R8_SINT,
+        hal::Format::Rg8Unorm => vk::Format::R8G8_UNORM,
+        hal::Format::Rg8Snorm => vk::Format::R8G8_SNORM,
+        hal::Format::Rg8Uint => vk::Format::R8G8_UINT,
+        hal::Format::Rg8Sint => vk::Format::R8G8_SINT,
+        hal::Format::Rgba8Unorm => vk::Format::R8G8B8A8_UNORM,
+        hal::Format::Rgba8UnormSrgb => vk::Format::R8G8B8A8_SRGB,
+        hal::Format::Rgba8Snorm => vk::Format::R8G8B8A8_SNORM,
+        hal::Format::Rgba8Uint => vk::Format::R8G8B8A8_UINT,
+        hal::Format::Rgba8Sint => vk::Format::R8G8B8A8_SINT,
+        hal::Format::Bgra8Unorm => vk::Format::B8G8R8A8_UNORM,
+        hal::Format::Bgra8UnormSrgb => vk::Format::B8G8R8A8_SRGB,
+        hal::Format::R16Unorm => vk::Format::R16_UNORM,
+        hal::Format::R16Snorm => vk::Format::R16_SNORM,
+        hal::Format::R16Uint => vk::Format::R16_UINT,
+        hal::Format::R16Sint => vk::Format::R16_SINT,
+        hal::Format::R16Float => vk::Format::R16_SFLOAT,
+        hal::Format::Rg16Unorm => vk::Format::R16G16_UNORM,
+        hal::Format::Rg16Snorm => vk::Format::R16G16_SNORM,
+        hal::Format::Rg16Uint => vk::Format::R16G16_UINT,
+        hal::Format::Rg16Sint => vk::Format::R16G16_SINT,
+        hal::Format::Rg16Float => vk::Format::R16G16_SFLOAT,
+        hal::Format::Rgba16Unorm => vk::Format::R16G16B16A16_UNORM,
+        hal::Format::Rgba16Snorm => vk::Format::R16G16B16A16_SNORM,
+        hal::Format::Rgba16Uint => vk::Format::R16G16B16A16_UINT,
+        hal::Format::Rgba16Sint => vk::Format::R16G16B16A16_SINT,
+        hal::Format::Rgba16Float => vk::Format::R16G16B16A16_SFLOAT,
+        hal::Format::R32Uint => vk::Format::R32_UINT,
+        hal::Format::R32Sint => vk::Format::R32_SINT,
+        hal::Format::R32Float => vk::Format::R32_SFLOAT,
+        hal::Format::Rg32Uint => vk::Format::R32G32_UINT,
+        hal::Format::Rg32Sint => vk::Format::R32G32_SINT,
+        hal::Format::Rg32Float => vk::Format::R32G32_SFLOAT,
+        hal::Format::Rgb32Uint => vk::Format::R32G32B32_UINT,
+        hal::Format::Rgb32Sint => vk::Format::R32G32B32_SINT,
+        hal::Format::Rgb32Float => vk::Format::R32G32B32_SFLOAT,
+        hal::Format::Rgba32Uint => vk::Format::R32G32B32A32_UINT,
+        hal::Format::Rgba32Sint => vk::Format::R32G32B32A32_SINT,
+        hal::Format::Rgba32Float => vk::Format::R32G32B32A32_SFLOAT,
+        hal::Format::B5g6r5Unorm => vk::Format::R5G6B5_UNORM_PACK16,
+        hal::Format::Bgr5a1Unorm => vk::Format::A1R5G5B5_UNORM_PACK16,
+        hal::Format::Rgb10a2Unorm => vk::Format::A2B10G10R10_UNORM_PACK32,
+        hal::Format::Rgb10a2Uint => vk::Format::A2B10G10R10_UINT_PACK32,
+        hal::Format::Rg11b10Float => vk::Format::B10G11R11_UFLOAT_PACK32,
+        hal::Format::Rgb9e5Float => vk::Format::E5B9G9R9_UFLOAT_PACK32,
+        hal::Format::D16Unorm => vk::Format::D16_UNORM,
+        hal::Format::D24UnormS8Uint => vk::Format::D24_UNORM_S8_UINT,
+        hal::Format::D32Float => vk::Format::D32_SFLOAT,
+        hal::Format::D32FloatS8Uint => vk::Format::D32_SFLOAT_S8_UINT,
+        hal::Format::Bc1Unorm => vk::Format::BC1_RGBA_UNORM_BLOCK,
+        hal::Format::Bc1UnormSrgb => vk::Format::BC1_RGBA_SRGB_BLOCK,
+        hal::Format::Bc2Unorm => vk::Format::BC2_UNORM_BLOCK,
+        hal::Format::Bc2UnormSrgb => vk::Format::BC2_SRGB_BLOCK,
+        hal::Format::Bc3Unorm => vk::Format::BC3_UNORM_BLOCK,
+        hal::Format::Bc3UnormSrgb => vk::Format::BC3_SRGB_BLOCK,
+        hal::Format::Bc4Unorm => vk::Format::BC4_UNORM_BLOCK,
+        hal::Format::Bc4Snorm => vk::Format::BC4_SNORM_BLOCK,
+        hal::Format::Bc5Unorm => vk::Format::BC5_UNORM_BLOCK,
+        hal::Format::Bc5Snorm => vk::Format::BC5_SNORM_BLOCK,
+        hal::Format::Bc6hUfloat => vk::Format::BC6H_UFLOAT_BLOCK,
+        hal::Format::Bc6hSfloat => vk::Format::BC6H_SFLOAT_BLOCK,
+        hal::Format::Bc7Unorm => vk::Format::BC7_UNORM_BLOCK,
+        hal::Format::Bc7UnormSrgb => vk::Format::BC7_SRGB_BLOCK,
+    }
+}
@@ -0,0 +1,468 @@
+//! Bindless access to buffers and images.
+//!
+//! Rather than sets being written and bound for each draw, a `BindlessTable` keeps descriptors of
+//! all the resources in one large `update_after_bind` set, bound once, holding an array per
+//! descriptor type. Shaders index the arrays with the integers the table hands out, which stay
+//! the same for as long as resources are in the table. Freed slots are only reused once the GPU
+//! is done with the work that may still read them, as told by a fence.
+
+use crate::hal::{
+    Backend, Descriptor, DescriptorBinding, DescriptorSetLayoutDesc, DescriptorType,
+    DescriptorWrite, Device, Error, Features, Result, ShaderStageFlags,
+};
+
+// Order of the arrays of a table
+const DESCRIPTOR_TYPES: [DescriptorType; 4] = [
+    DescriptorType::UniformBuffer,
+    DescriptorType::SampledImage,
+    DescriptorType::StorageBuffer,
+    DescriptorType::StorageImage,
+];
+
+/// Sizes of the arrays of a `BindlessTable`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BindlessConfig {
+    pub uniform_buffers: u32,
+    pub sampled_images: u32,
+    pub storage_buffers: u32,
+    pub storage_images: u32,
+    /// Stages shaders index the table from.
+    pub stages: ShaderStageFlags,
+}
+
+impl BindlessConfig {
+    pub fn get_capacity(&self, ty: DescriptorType) -> u32 {
+        match ty {
+            DescriptorType::UniformBuffer => self.uniform_buffers,
+            DescriptorType::SampledImage => self.sampled_images,
+            DescriptorType::StorageBuffer => self.storage_buffers,
+            DescriptorType::StorageImage => self.storage_images,
+        }
+    }
+
+    /// Number of descriptors of all types.
+    pub fn get_descriptor_count(&self) -> u32 {
+        DESCRIPTOR_TYPES
+            .iter()
+            .map(|&ty| self.get_capacity(ty))
+            .sum()
+    }
+
+    /// Binding of the array of `ty` in the set of the table. Storage images come after all the
+    /// storage buffers, so that the arrays don't overlap on DX12 where they're both bound to `u`
+    /// registers.
+    pub fn get_binding(&self, ty: DescriptorType) -> u32 {
+        match ty {
+            DescriptorType::UniformBuffer => 0,
+            DescriptorType::SampledImage => 1,
+            DescriptorType::StorageBuffer => 2,
+            DescriptorType::StorageImage => 2 + self.storage_buffers,
+        }
+    }
+}
+
+impl Default for BindlessConfig {
+    fn default() -> Self {
+        BindlessConfig {
+            uniform_buffers: 1024,
+            sampled_images: 16384,
+            storage_buffers: 4096,
+            storage_images: 1024,
+            stages: ShaderStageFlags::ALL,
+        }
+    }
+}
+
+/// A resource in a `BindlessTable`, at `index` in the array of its descriptor type.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct BindlessHandle {
+    ty: DescriptorType,
+    index: u32,
+}
+
+impl BindlessHandle {
+    pub fn ty(self) -> DescriptorType {
+        self.ty
+    }
+
+    /// Index shaders read the resource at.
+    pub fn index(self) -> u32 {
+        self.index
+    }
+}
+
+/// Slots of an array of a table.
+struct Slots {
+    capacity: u32,
+    // Slots from this one on were never allocated
+    next: u32,
+    free: Vec<u32>,
+    // Freed slots and the fence value from which they can be reused
+    pending: Vec<(u32, u64)>,
+}
+
+impl Slots {
+    fn new(capacity: u32) -> Self {
+        Slots {
+            capacity,
+            next: 0,
+            free: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    fn allocate(&mut self) -> Option<u32> {
+        self.free.pop().or_else(|| {
+            if self.next < self.capacity {
+                self.next += 1;
+                Some(self.next - 1)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn free(&mut self, index: u32, fence_value: u64) {
+        self.pending.push((index, fence_value));
+    }
+
+    fn recycle(&mut self, completed_value: u64) {
+        let free = &mut self.free;
+        self.pending.retain(|&(index, fence_value)| {
+            let is_done = fence_value <= completed_value;
+            if is_done {
+                free.push(index);
+            }
+            !is_done
+        });
+    }
+
+    fn allocated_count(&self) -> u32 {
+        self.next - self.free.len() as u32
+    }
+}
+
+/// An `update_after_bind` descriptor set holding an array of each descriptor type, handing out
+/// stable indices in them.
+///
+/// Slots are freed along with the value a fence reaches once the GPU is done with the work
+/// submitted so far, and `recycle` makes them available again once it does. Tables need
+/// `Features::BINDLESS`, and can hold up to `Limits::max_bindless_descriptors` descriptors.
+pub struct BindlessTable<B: Backend> {
+    config: BindlessConfig,
+    layout: B::DescriptorSetLayout,
+    set: B::DescriptorSet,
+    // By descriptor type, in the order of `DESCRIPTOR_TYPES`
+    slots: Vec<Slots>,
+}
+
+impl<B: Backend> BindlessTable<B> {
+    /// Creates a table and its set, failing with `Error::Unsupported` if `device` doesn't support
+    /// bindless tables or `config` has more descriptors than `max_capacity`.
+    pub fn new(device: &B::Device, config: BindlessConfig) -> Result<Self> {
+        if !device.features().contains(Features::BINDLESS)
+            || config.get_descriptor_count() > Self::max_capacity(device)
+        {
+            return Err(Error::Unsupported);
+        }
+
+        let bindings = DESCRIPTOR_TYPES
+            .iter()
+            .filter(|&&ty| config.get_capacity(ty) > 0)
+            .map(|&ty| DescriptorBinding {
+                binding: config.get_binding(ty),
+                ty,
+                count: config.get_capacity(ty),
+                stages: config.stages,
+            })
+            .collect();
+        let layout = device.create_descriptor_set_layout(&DescriptorSetLayoutDesc {
+            bindings,
+            update_after_bind: true,
+        })?;
+        let set = device.create_descriptor_set(&layout)?;
+
+        Ok(BindlessTable {
+            config,
+            layout,
+            set,
+            slots: DESCRIPTOR_TYPES
+                .iter()
+                .map(|&ty| Slots::new(config.get_capacity(ty)))
+                .collect(),
+        })
+    }
+
+    /// Most descriptors a table can hold on `device`, 0 if it doesn't support bindless tables.
+    pub fn max_capacity(device: &B::Device) -> u32 {
+        if device.features().contains(Features::BINDLESS) {
+            device.limits().max_bindless_descriptors
+        } else {
+            0
+        }
+    }
+
+    pub fn config(&self) -> &BindlessConfig {
+        &self.config
+    }
+
+    /// Layout of the set, for the pipeline layouts of shaders indexing the table.
+    pub fn layout(&self) -> &B::DescriptorSetLayout {
+        &self.layout
+    }
+
+    /// The set shaders index the table through, which stays bound while resources are added.
+    pub fn set(&self) -> &B::DescriptorSet {
+        &self.set
+    }
+
+    /// Adds a resource to the array of `ty`, failing with `Error::OutOfDeviceMemory` if it's full
+    /// until freed slots are recycled.
+    pub fn add(
+        &mut self,
+        device: &B::Device,
+        ty: DescriptorType,
+        descriptor: Descriptor<B>,
+    ) -> Result<BindlessHandle> {
+        let slots = &mut self.slots[get_type_index(ty)];
+        let index = slots.allocate().ok_or(Error::OutOfDeviceMemory)?;
+        let handle = BindlessHandle { ty, index };
+
+        if let Err(err) = write_descriptor(device, &self.set, &self.config, handle, descriptor) {
+            // The slot was never seen by the GPU, so it can be reused right away
+            self.slots[get_type_index(ty)].free.push(index);
+            return Err(err);
+        }
+        Ok(handle)
+    }
+
+    /// Points a slot at another resource of its type. Command buffers executing must not access
+    /// the slot.
+    pub fn update(
+        &self,
+        device: &B::Device,
+        handle: BindlessHandle,
+        descriptor: Descriptor<B>,
+    ) -> Result<()> {
+        write_descriptor(device, &self.set, &self.config, handle, descriptor)
+    }
+
+    /// Frees a slot, to be reused once the fence passed to `recycle` reaches `fence_value`. That
+    /// value is expected to be signaled after any work that may access the slot.
+    pub fn free(&mut self, handle: BindlessHandle, fence_value: u64) {
+        self.slots[get_type_index(handle.ty)].free(handle.index, fence_value);
+    }
+
+    /// Makes slots available again once `fence` reached the values they were freed with, without
+    /// waiting for it. Usually called once per frame.
+    pub fn recycle(&mut self, device: &B::Device, fence: &B::Fence) -> Result<()> {
+        let mut fence_values = self
+            .slots
+            .iter()
+            .flat_map(|slots| slots.pending.iter().map(|&(_, fence_value)| fence_value))
+            .collect::<Vec<_>>();
+        fence_values.sort_unstable();
+        fence_values.dedup();
+
+        // Fences only go up, so the first value not reached yet is as far as work completed
+        let mut completed_value = None;
+        for fence_value in fence_values {
+            match device.wait_for_fence_with_timeout(fence, fence_value, 0) {
+                Ok(()) => completed_value = Some(fence_value),
+                Err(Error::Timeout) => break,
+                Err(err) => return Err(err),
+            }
+        }
+
+        if let Some(completed_value) = completed_value {
+            for slots in &mut self.slots {
+                slots.recycle(completed_value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Slots of `ty` in use, freed ones counting until recycled.
+    pub fn allocated_count(&self, ty: DescriptorType) -> u32 {
+        self.slots[get_type_index(ty)].allocated_count()
+    }
+
+    pub fn capacity(&self, ty: DescriptorType) -> u32 {
+        self.config.get_capacity(ty)
+    }
+}
+
+fn get_type_index(ty: DescriptorType) -> usize {
+    DESCRIPTOR_TYPES.iter().position(|&t| t == ty).unwrap()
+}
+
+fn write_descriptor<B: Backend>(
+    device: &B::Device,
+    set: &B::DescriptorSet,
+    config: &BindlessConfig,
+    handle: BindlessHandle,
+    descriptor: Descriptor<B>,
+) -> Result<()> {
+    device.write_descriptors(&[DescriptorWrite {
+        set,
+        binding: config.get_binding(handle.ty),
+        array_element: handle.index,
+        descriptors: &[descriptor],
+    }])
+}
+
+#[cfg(all(test, feature = "software"))]
+mod tests {
+    use super::*;
+    use crate::backend::software::{Backend, Buffer, Device};
+    use crate::hal::{
+        BufferUsage, CommandBuffer as _, CommandPool as _, CommandPoolFlags, CommandQueue as _,
+        Device as _, PipelineBindPoint, PushConstantRange, QueueType,
+    };
+    use crate::testing::{create_buffer, create_device};
+
+    fn create_table(device: &Device, storage_buffers: u32) -> BindlessTable<Backend> {
+        BindlessTable::new(
+            device,
+            BindlessConfig {
+                storage_buffers,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    /// Storage buffers to add to tables, kept alive for the whole test like the resources of
+    /// real descriptors.
+    fn create_buffers(device: &Device, count: usize) -> Vec<Buffer> {
+        (0..count)
+            .map(|_| create_buffer(device, 256, BufferUsage::STORAGE))
+            .collect()
+    }
+
+    fn add_buffer(
+        device: &Device,
+        table: &mut BindlessTable<Backend>,
+        buffer: &Buffer,
+    ) -> Result<BindlessHandle> {
+        table.add(
+            device,
+            DescriptorType::StorageBuffer,
+            Descriptor::Buffer(buffer),
+        )
+    }
+
+    #[test]
+    fn hands_out_stable_indices() {
+        let device = create_device();
+        let buffers = create_buffers(&device, 4);
+        let mut table = create_table(&device, 16);
+
+        let handles: Vec<BindlessHandle> = buffers[..3]
+            .iter()
+            .map(|buffer| add_buffer(&device, &mut table, buffer).unwrap())
+            .collect();
+        let indices: Vec<u32> = handles.iter().map(|handle| handle.index()).collect();
+        assert_eq!(indices, [0, 1, 2]);
+
+        // Freed slots aren't reused before the GPU is done with them
+        table.free(handles[1], 1);
+        assert_eq!(
+            add_buffer(&device, &mut table, &buffers[3])
+                .unwrap()
+                .index(),
+            3
+        );
+        assert_eq!(table.allocated_count(DescriptorType::StorageBuffer), 4);
+    }
+
+    #[test]
+    fn recycles_once_fence_is_reached() {
+        let device = create_device();
+        let queue = device.create_command_queue(QueueType::Graphics).unwrap();
+        let fence = device.create_fence(0).unwrap();
+        let buffers = create_buffers(&device, 4);
+        let mut table = create_table(&device, 2);
+
+        let first = add_buffer(&device, &mut table, &buffers[0]).unwrap();
+        let second = add_buffer(&device, &mut table, &buffers[1]).unwrap();
+        table.free(first, 1);
+        table.free(second, 2);
+
+        table.recycle(&device, &fence).unwrap();
+        assert_eq!(
+            add_buffer(&device, &mut table, &buffers[2]),
+            Err(Error::OutOfDeviceMemory)
+        );
+
+        queue.signal_fence(&fence, 1).unwrap();
+        table.recycle(&device, &fence).unwrap();
+        assert_eq!(add_buffer(&device, &mut table, &buffers[2]).unwrap(), first);
+        assert_eq!(table.allocated_count(DescriptorType::StorageBuffer), 2);
+
+        queue.signal_fence(&fence, 2).unwrap();
+        table.recycle(&device, &fence).unwrap();
+        assert_eq!(
+            add_buffer(&device, &mut table, &buffers[3]).unwrap(),
+            second
+        );
+    }
+
+    #[test]
+    fn lays_out_arrays_without_overlap() {
+        let config = BindlessConfig {
+            storage_buffers: 100,
+            ..Default::default()
+        };
+        assert_eq!(config.get_binding(DescriptorType::StorageBuffer), 2);
+        assert_eq!(config.get_binding(DescriptorType::StorageImage), 102);
+        assert_eq!(config.get_descriptor_count(), 1024 + 16384 + 100 + 1024);
+    }
+
+    #[test]
+    fn rejects_tables_over_device_limits() {
+        let device = create_device();
+        let max_capacity = BindlessTable::<Backend>::max_capacity(&device);
+        assert_eq!(max_capacity, device.limits().max_bindless_descriptors);
+
+        let result = BindlessTable::<Backend>::new(
+            &device,
+            BindlessConfig {
+                sampled_images: max_capacity,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(result, Err(Error::Unsupported)));
+    }
+
+    #[test]
+    fn binds_through_pipeline_layouts() {
+        let device = create_device();
+        let table = create_table(&device, 16);
+        let layout = device
+            .create_pipeline_layout(
+                &[table.layout()],
+                &[PushConstantRange {
+                    stages: ShaderStageFlags::ALL,
+                    offset: 0,
+                    size: 16,
+                }],
+            )
+            .unwrap();
+
+        let mut pool = device
+            .create_command_pool(QueueType::Graphics, CommandPoolFlags::empty())
+            .unwrap();
+        let command_buffer = pool.create_buffer().unwrap();
+        command_buffer.begin().unwrap();
+        command_buffer.bind_descriptor_sets(PipelineBindPoint::Compute, &layout, 0, &[table.set()]);
+        command_buffer.push_constants(
+            PipelineBindPoint::Compute,
+            &layout,
+            ShaderStageFlags::COMPUTE,
+            4,
+            &[0; 8],
+        );
+        command_buffer.end().unwrap();
+    }
+}
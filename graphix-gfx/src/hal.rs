@@ -1,23 +1,44 @@
 use std::any::Any;
 
-pub use crate::hal::adapter::{Adapter, AdapterInfo, DeviceType, PhysicalAdapter};
-pub use crate::hal::attachment::{Attachment, AttachmentMode};
+pub use crate::hal::adapter::{
+    Adapter, AdapterInfo, AdapterPreference, DeviceType, PhysicalAdapter,
+};
+pub use crate::hal::attachment::{AttachmentDesc, LoadOp, StoreOp};
+pub use crate::hal::buffer::{BufferUsage, MemoryType};
 pub use crate::hal::command::{
-    BarrierPoint, ClearColor, CommandBuffer, CommandPool, CommandPoolFlags,
+    Barrier, BarrierResource, ClearColor, CommandBuffer, CommandPool, CommandPoolFlags,
+};
+pub use crate::hal::descriptor::{
+    Descriptor, DescriptorBinding, DescriptorSetLayoutDesc, DescriptorType, DescriptorWrite,
+    PipelineBindPoint, PushConstantRange, ShaderStageFlags,
 };
-pub use crate::hal::device::Device;
-pub use crate::hal::format::Format;
+pub use crate::hal::device::{Device, DeviceLostReason};
+pub use crate::hal::error::{Error, Result};
+pub use crate::hal::format::{Aspects, Format, FormatDesc, FormatProperties, NumericType};
+pub use crate::hal::image::{Extent, ImageDesc, ImageKind, ImageUsage, SubresourceRange};
+pub use crate::hal::limits::{FeatureLevel, Features, Limits};
+pub use crate::hal::memory::{HeapUsage, MemoryRequirements};
+pub use crate::hal::pass::RenderPassDesc;
 pub use crate::hal::queue::{CommandQueue, QueueType};
+pub use crate::hal::state::ResourceState;
 pub use crate::hal::window::{
     BackBuffer, Surface, Swapchain, SwapchainBufferIndex, SwapchainConfig,
 };
 
 pub mod adapter;
 pub mod attachment;
+pub mod buffer;
 pub mod command;
+pub mod descriptor;
 pub mod device;
+pub mod error;
 pub mod format;
+pub mod image;
+pub mod limits;
+pub mod memory;
+pub mod pass;
 pub mod queue;
+pub mod state;
 pub mod window;
 
 pub trait Backend: Sized {
@@ -30,7 +51,14 @@ pub trait Backend: Sized {
 
     type Surface: Surface<Self>;
     type Swapchain: Swapchain<Self>;
+    type RenderPass: Any;
     type FrameBuffer: Any;
+    type Buffer: Any;
+    type Image: Any;
+    type Heap: Any;
+    type DescriptorSetLayout: Any;
+    type DescriptorSet: Any;
+    type PipelineLayout: Any;
 
     type Fence: Any;
 }
@@ -39,4 +67,9 @@ pub trait Instance {
     type Backend: Backend;
 
     fn enumerate_adapters(&self) -> Vec<Adapter<Self::Backend>>;
+
+    /// Picks an adapter according to `preference`, or `None` if no adapter matches it.
+    fn select_adapter(&self, preference: &AdapterPreference) -> Option<Adapter<Self::Backend>> {
+        preference.select(self.enumerate_adapters())
+    }
 }
@@ -1,9 +1,15 @@
+use crate::hal::error::Result;
 use crate::hal::Backend;
 
+use std::cmp::Reverse;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum DeviceType {
     DiscreteGpu,
+    IntegratedGpu,
     VirtualGpu,
+    /// A software rasterizer such as WARP or lavapipe.
+    Cpu,
 }
 
 #[derive(Clone, Debug)]
@@ -15,17 +21,69 @@ pub struct AdapterInfo {
     pub device_type: DeviceType,
 }
 
+/// How `Instance::select_adapter` picks among the available adapters.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AdapterPreference {
+    /// Discrete GPUs with the most video memory first, falling back to software adapters last.
+    HighPerformance,
+    /// Integrated GPUs first, falling back to software adapters last.
+    LowPower,
+    /// Only software adapters such as WARP, which work without any GPU.
+    Software,
+    /// The adapter with a PCI vendor id and, if given, device id.
+    Id { vendor: u32, device: Option<u32> },
+    /// The first adapter whose name contains the string, ignoring case.
+    Name(String),
+}
+
+impl AdapterPreference {
+    /// Picks the adapter best matching the preference, keeping the enumeration order on ties.
+    pub fn select<B: Backend>(&self, adapters: Vec<Adapter<B>>) -> Option<Adapter<B>> {
+        let mut adapters = adapters.into_iter();
+        match *self {
+            AdapterPreference::HighPerformance => adapters.min_by_key(|adapter| {
+                let rank = match adapter.info.device_type {
+                    DeviceType::DiscreteGpu => 0,
+                    DeviceType::IntegratedGpu => 1,
+                    DeviceType::VirtualGpu => 2,
+                    DeviceType::Cpu => 3,
+                };
+                (rank, Reverse(adapter.info.video_memory))
+            }),
+            AdapterPreference::LowPower => {
+                adapters.min_by_key(|adapter| match adapter.info.device_type {
+                    DeviceType::IntegratedGpu => 0,
+                    DeviceType::DiscreteGpu => 1,
+                    DeviceType::VirtualGpu => 2,
+                    DeviceType::Cpu => 3,
+                })
+            }
+            AdapterPreference::Software => {
+                adapters.find(|adapter| adapter.info.device_type == DeviceType::Cpu)
+            }
+            AdapterPreference::Id { vendor, device } => adapters.find(|adapter| {
+                adapter.info.vendor == vendor
+                    && device.is_none_or(|device| adapter.info.device == device)
+            }),
+            AdapterPreference::Name(ref name) => {
+                let name = name.to_lowercase();
+                adapters.find(|adapter| adapter.info.name.to_lowercase().contains(&name))
+            }
+        }
+    }
+}
+
 pub struct Adapter<B: Backend> {
     pub adapter: B::PhysicalAdapter,
     pub info: AdapterInfo,
 }
 
 impl<B: Backend> Adapter<B> {
-    pub fn create_device(&self) -> B::Device {
+    pub fn create_device(&self) -> Result<B::Device> {
         self.adapter.create_device()
     }
 }
 
 pub trait PhysicalAdapter<B: Backend> {
-    fn create_device(&self) -> B::Device;
+    fn create_device(&self) -> Result<B::Device>;
 }
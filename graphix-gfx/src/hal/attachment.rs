@@ -1,12 +1,30 @@
-use std::ops::Range;
+use crate::hal::format::Format;
+use crate::hal::state::ResourceState;
 
+/// What happens to the contents of an attachment when a render pass begins.
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
-pub enum AttachmentMode {
-    Present,
-    RenderTarget,
+pub enum LoadOp {
+    Load,
+    /// Fills the attachment with its clear value.
+    Clear,
+    /// The previous contents aren't needed and may be left undefined.
+    DontCare,
 }
 
-#[derive(Clone)]
-pub struct Attachment {
-    pub states: Range<AttachmentMode>,
+/// What happens to the contents of an attachment when a render pass ends.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum StoreOp {
+    Store,
+    /// The rendered contents aren't needed after the pass and may be discarded.
+    DontCare,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AttachmentDesc {
+    pub format: Format,
+    pub load_op: LoadOp,
+    pub store_op: StoreOp,
+    /// State the attachment is left in when the render pass ends, e.g. `PRESENT` for back buffers
+    /// presented right after.
+    pub final_state: ResourceState,
 }
@@ -0,0 +1,33 @@
+use bitflags::bitflags;
+
+bitflags! {
+    pub struct BufferUsage: u32 {
+        const TRANSFER_SRC = 0x1;
+        const TRANSFER_DST = 0x2;
+        const VERTEX = 0x4;
+        const INDEX = 0x8;
+        const CONSTANT = 0x10;
+        const STORAGE = 0x20;
+        const INDIRECT = 0x40;
+    }
+}
+
+/// Where the memory of a resource lives and who can access it.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum MemoryType {
+    /// Fastest memory for the GPU, not accessible from the CPU.
+    DeviceLocal,
+    /// CPU writable memory read by the GPU, for staging uploads and data updated every frame.
+    Upload,
+    /// CPU readable memory written by the GPU, for reading results back.
+    Readback,
+}
+
+impl MemoryType {
+    pub fn is_host_visible(self) -> bool {
+        match self {
+            MemoryType::DeviceLocal => false,
+            MemoryType::Upload | MemoryType::Readback => true,
+        }
+    }
+}
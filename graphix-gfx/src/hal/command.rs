@@ -1,5 +1,8 @@
 
-use crate::hal::attachment::Attachment;
+use crate::hal::descriptor::{PipelineBindPoint, ShaderStageFlags};
+use crate::hal::error::Result;
+use crate::hal::image::SubresourceRange;
+use crate::hal::state::ResourceState;
 use crate::hal::Backend;
 
 use bitflags::bitflags;
@@ -11,34 +14,110 @@ bitflags! {
     }
 }
 
-pub enum BarrierPoint {
-    Pre,
-    Post,
+pub type ClearColor = [f32; 4];
+
+/// A buffer or image a barrier applies to.
+pub enum BarrierResource<'a, B: Backend> {
+    Buffer(&'a B::Buffer),
+    Image(&'a B::Image),
 }
 
-pub type ClearColor = [f32; 4];
+impl<'a, B: Backend> Clone for BarrierResource<'a, B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, B: Backend> Copy for BarrierResource<'a, B> {}
+
+pub enum Barrier<'a, B: Backend> {
+    /// Moves a buffer to the state it's used in next.
+    Buffer {
+        buffer: &'a B::Buffer,
+        state: ResourceState,
+    },
+    /// Moves subresources of an image to the state they're used in next, the whole image if
+    /// `range` is `None`.
+    Image {
+        image: &'a B::Image,
+        range: Option<SubresourceRange>,
+        state: ResourceState,
+    },
+    /// Makes storage writes to a resource staying in `ResourceState::STORAGE` visible to the
+    /// accesses that follow.
+    Storage(BarrierResource<'a, B>),
+    /// Hands memory over from `before` to `after`, which share it. `before` must not be used
+    /// anymore and the contents of `after` are undefined until written. `None` stands for any
+    /// resource placed in the memory.
+    Aliasing {
+        before: Option<BarrierResource<'a, B>>,
+        after: Option<BarrierResource<'a, B>>,
+    },
+}
 
 pub trait CommandPool<B: Backend> {
-    fn reset(&self);
+    fn reset(&self) -> Result<()>;
 
-    fn create_buffer(&mut self) -> B::CommandBuffer {
-        self.create_buffers(1).pop().unwrap()
+    fn create_buffer(&mut self) -> Result<B::CommandBuffer> {
+        self.create_buffers(1)
+            .map(|mut buffers| buffers.pop().unwrap())
     }
 
-    fn create_buffers(&mut self, count: usize) -> Vec<B::CommandBuffer> {
+    fn create_buffers(&mut self, count: usize) -> Result<Vec<B::CommandBuffer>> {
         (0..count).map(|_| self.create_buffer()).collect()
     }
 }
 
 pub trait CommandBuffer<B: Backend> {
-    fn begin(&self);
-    fn end(&self);
+    fn begin(&self) -> Result<()>;
+    fn end(&self) -> Result<()>;
+
+    /// Moves resources to the states they're used in next. Resources already in their target
+    /// state are skipped, and the states they start in are patched in at submission. Aliasing
+    /// barriers are ordered before the others.
+    fn insert_barriers(&self, barriers: &[Barrier<B>]);
+    /// Starts transitions finished by `end_split_barriers` with the same barriers, so that the
+    /// GPU can overlap them with the work recorded in between. Resources can't be used until
+    /// their transition ends, and only buffer and image transitions can be split.
+    fn begin_split_barriers(&self, barriers: &[Barrier<B>]);
+    /// Finishes transitions started by `begin_split_barriers`.
+    fn end_split_barriers(&self, barriers: &[Barrier<B>]);
 
-    fn insert_barriers(
+    /// Begins rendering into `framebuffer`, moving its attachments to `ResourceState::RENDER_TARGET`
+    /// and applying their load ops. `clear_values` holds one value per attachment, only read for
+    /// attachments cleared on load.
+    fn begin_render_pass(
         &self,
-        barrier_point: BarrierPoint,
-        attachments: &[Attachment],
+        render_pass: &B::RenderPass,
         framebuffer: &B::FrameBuffer,
+        clear_values: &[ClearColor],
+    );
+    /// Ends the current render pass, leaving its attachments in their final state.
+    fn end_render_pass(&self);
+
+    /// Binds `sets` to the set indices starting at `first_set`, for the pipelines of
+    /// `bind_point` with `layout`, the sets matching its set layouts. Sets bound at lower indices
+    /// stay bound if they were bound with a layout of identical set layouts up to theirs, and
+    /// those at higher indices get unbound. Binding doesn't move resources to any state, see
+    /// `DescriptorType` for the states they're read in. Backends running out of memory for the
+    /// sets bound fail `end`.
+    fn bind_descriptor_sets(
+        &self,
+        bind_point: PipelineBindPoint,
+        layout: &B::PipelineLayout,
+        first_set: u32,
+        sets: &[&B::DescriptorSet],
+    );
+    /// Sets the push constants of `layout` starting at `offset` to `data`, for the pipelines of
+    /// `bind_point`. The bytes set must lie in ranges of the layout visible to `stages`, and
+    /// `offset` and the length of `data` must be multiples of 4. Push constants become undefined
+    /// once sets or push constants are bound with another layout.
+    fn push_constants(
+        &self,
+        bind_point: PipelineBindPoint,
+        layout: &B::PipelineLayout,
+        stages: ShaderStageFlags,
+        offset: u32,
+        data: &[u8],
     );
-    fn clear(&self, clear_colors: &[ClearColor], framebuffer: &B::FrameBuffer);
 }
@@ -0,0 +1,150 @@
+use crate::hal::buffer::BufferUsage;
+use crate::hal::image::ImageUsage;
+use crate::hal::Backend;
+
+use bitflags::bitflags;
+
+bitflags! {
+    pub struct ShaderStageFlags: u32 {
+        const VERTEX = 0x1;
+        const FRAGMENT = 0x2;
+        const COMPUTE = 0x4;
+
+        const ALL_GRAPHICS = Self::VERTEX.bits | Self::FRAGMENT.bits;
+        const ALL = Self::ALL_GRAPHICS.bits | Self::COMPUTE.bits;
+    }
+}
+
+/// Kind of pipeline descriptor sets are bound for.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum PipelineBindPoint {
+    Graphics,
+    Compute,
+}
+
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum DescriptorType {
+    /// A buffer created with `BufferUsage::CONSTANT`, read in `ResourceState::CONSTANT_BUFFER`.
+    UniformBuffer,
+    /// A buffer created with `BufferUsage::STORAGE`, read and written in
+    /// `ResourceState::STORAGE`.
+    StorageBuffer,
+    /// An image created with `ImageUsage::SAMPLED`, read in a `ResourceState::SHADER_READ` state.
+    SampledImage,
+    /// An image created with `ImageUsage::STORAGE`, read and written in
+    /// `ResourceState::STORAGE`.
+    StorageImage,
+}
+
+impl DescriptorType {
+    pub fn is_buffer(self) -> bool {
+        match self {
+            DescriptorType::UniformBuffer | DescriptorType::StorageBuffer => true,
+            DescriptorType::SampledImage | DescriptorType::StorageImage => false,
+        }
+    }
+
+    /// Usage buffers need to be described by descriptors of this type, if it's a buffer type.
+    pub fn get_buffer_usage(self) -> BufferUsage {
+        match self {
+            DescriptorType::UniformBuffer => BufferUsage::CONSTANT,
+            DescriptorType::StorageBuffer => BufferUsage::STORAGE,
+            DescriptorType::SampledImage | DescriptorType::StorageImage => BufferUsage::empty(),
+        }
+    }
+
+    /// Usage images need to be described by descriptors of this type, if it's an image type.
+    pub fn get_image_usage(self) -> ImageUsage {
+        match self {
+            DescriptorType::SampledImage => ImageUsage::SAMPLED,
+            DescriptorType::StorageImage => ImageUsage::STORAGE,
+            DescriptorType::UniformBuffer | DescriptorType::StorageBuffer => ImageUsage::empty(),
+        }
+    }
+}
+
+/// An array of descriptors of a set, seen by shaders at `binding` of the set. On DX12 each set
+/// is a descriptor table in the register space of its index, and bindings are registers of the
+/// `b`, `t` or `u` kind depending on their type.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    pub binding: u32,
+    pub ty: DescriptorType,
+    pub count: u32,
+    pub stages: ShaderStageFlags,
+}
+
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct DescriptorSetLayoutDesc {
+    pub bindings: Vec<DescriptorBinding>,
+    /// Descriptors of sets can be written while command buffers they're bound in are executing,
+    /// as long as those don't access them, and descriptors shaders don't access can be left
+    /// unwritten. Needs `Features::BINDLESS`.
+    pub update_after_bind: bool,
+}
+
+impl DescriptorSetLayoutDesc {
+    pub fn get_binding(&self, binding: u32) -> Option<&DescriptorBinding> {
+        self.bindings
+            .iter()
+            .find(|desc_binding| desc_binding.binding == binding)
+    }
+
+    /// Number of descriptors in sets of this layout.
+    pub fn get_descriptor_count(&self) -> u32 {
+        self.bindings.iter().map(|binding| binding.count).sum()
+    }
+
+    /// Stages any binding is visible to.
+    pub fn get_stages(&self) -> ShaderStageFlags {
+        self.bindings
+            .iter()
+            .fold(ShaderStageFlags::empty(), |stages, binding| {
+                stages | binding.stages
+            })
+    }
+}
+
+/// Bytes `offset` to `offset + size` of the push constants of a pipeline layout, visible to
+/// `stages`, both multiples of 4. On DX12 the push constants of a layout are root constants
+/// shaders see as a constant buffer at register `b0` of register space 32, past those of sets.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct PushConstantRange {
+    pub stages: ShaderStageFlags,
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl PushConstantRange {
+    /// Bytes of push constants holding all of `ranges`.
+    pub fn get_total_size(ranges: &[PushConstantRange]) -> u32 {
+        ranges
+            .iter()
+            .map(|range| range.offset + range.size)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// A whole buffer or image described to shaders.
+pub enum Descriptor<'a, B: Backend> {
+    Buffer(&'a B::Buffer),
+    Image(&'a B::Image),
+}
+
+impl<'a, B: Backend> Clone for Descriptor<'a, B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, B: Backend> Copy for Descriptor<'a, B> {}
+
+/// Descriptors written to consecutive array elements of a binding of a set, starting at
+/// `array_element`.
+pub struct DescriptorWrite<'a, B: Backend> {
+    pub set: &'a B::DescriptorSet,
+    pub binding: u32,
+    pub array_element: u32,
+    pub descriptors: &'a [Descriptor<'a, B>],
+}
@@ -1,17 +1,141 @@
+use crate::hal::buffer::{BufferUsage, MemoryType};
 use crate::hal::command::CommandPoolFlags;
+use crate::hal::descriptor::{DescriptorSetLayoutDesc, DescriptorWrite, PushConstantRange};
+use crate::hal::error::Result;
+use crate::hal::format::{Format, FormatProperties};
+use crate::hal::image::ImageDesc;
+use crate::hal::limits::{Features, Limits};
+use crate::hal::memory::{HeapUsage, MemoryRequirements};
+use crate::hal::pass::RenderPassDesc;
 use crate::hal::queue::QueueType;
 use crate::hal::Backend;
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DeviceLostReason {
+    /// The GPU took too long to execute its commands and was reset by the OS.
+    Hung,
+    /// The GPU was physically removed, disabled or its driver was upgraded.
+    Removed,
+    /// The device was reset because of a badly formed command, possibly from another process.
+    Reset,
+    DriverInternalError,
+    InvalidCall,
+    /// The backend can't tell why the device was lost.
+    Unknown,
+}
+
+/// A logical device created from an `Adapter`.
+///
+/// # Device loss
+///
+/// A device can be lost at any time, e.g. after a GPU hang or a driver update. From then on any
+/// call on the device, or on the queues, command pools, command buffers and swapchains created
+/// from it, may fail with `Error::DeviceLost`, and `device_lost_reason` tells why.
+///
+/// A lost device can't be recovered and there's no point waiting on its fences. To carry on
+/// rendering drop the swapchain, command buffers, command pools, fences, queues and finally the
+/// device, then call `Adapter::create_device` again and recreate everything from the new device.
+/// Surfaces belong to the instance and can be reused. If creating the device fails again the
+/// adapter itself is gone, so enumerate the adapters of the instance and pick a new one.
 pub trait Device<B: Backend> {
-    fn create_command_queue(&self, queue_type: QueueType) -> B::CommandQueue;
+    fn create_command_queue(&self, queue_type: QueueType) -> Result<B::CommandQueue>;
+
+    fn create_command_pool(
+        &self,
+        queue_type: QueueType,
+        flags: CommandPoolFlags,
+    ) -> Result<B::CommandPool>;
 
-    fn create_command_pool(&self, queue_type: QueueType, flags: CommandPoolFlags)
-        -> B::CommandPool;
+    fn create_buffer(
+        &self,
+        size: u64,
+        usage: BufferUsage,
+        memory_type: MemoryType,
+    ) -> Result<B::Buffer>;
+    /// Maps the whole of a buffer in host visible memory, failing with `Error::Unsupported` for
+    /// `MemoryType::DeviceLocal` buffers. The pointer stays valid until `unmap_buffer` is called.
+    fn map_buffer(&self, buffer: &B::Buffer) -> Result<*mut u8>;
+    fn unmap_buffer(&self, buffer: &B::Buffer);
 
-    fn create_fence(&self, initial_value: u64) -> B::Fence;
-    fn reset_fence(&self, fence: &B::Fence);
-    fn wait_for_fence(&self, fence: &B::Fence, value: u64) -> bool {
-        self.wait_for_fence_with_timeout(fence, value, u64::max_value())
+    fn limits(&self) -> Limits;
+    fn features(&self) -> Features;
+    fn format_properties(&self, format: Format) -> FormatProperties;
+    /// Returns the first of `candidates`, in order of preference, supporting all of `required`.
+    fn find_supported_format(
+        &self,
+        candidates: &[Format],
+        required: FormatProperties,
+    ) -> Option<Format> {
+        candidates
+            .iter()
+            .cloned()
+            .find(|&format| self.format_properties(format).contains(required))
     }
-    fn wait_for_fence_with_timeout(&self, fence: &B::Fence, value: u64, timeout_ns: u64) -> bool;
+
+    fn create_image(&self, desc: ImageDesc) -> Result<B::Image>;
+
+    /// Allocates `size` bytes of memory to place resources of `usage` in, failing with
+    /// `Error::Unsupported` if `usage` mixes kinds of resources without
+    /// `Features::MIXED_RESOURCE_HEAPS`. Heaps are kept alive by the resources placed in them.
+    fn create_heap(&self, size: u64, memory_type: MemoryType, usage: HeapUsage) -> Result<B::Heap>;
+    fn get_buffer_requirements(&self, size: u64, usage: BufferUsage) -> Result<MemoryRequirements>;
+    fn get_image_requirements(&self, desc: &ImageDesc) -> Result<MemoryRequirements>;
+    /// Creates a buffer in the memory of `heap` starting at `offset`, which must fit the buffer
+    /// requirements. Resources placed in overlapping memory alias each other and only the last
+    /// one handed the memory with `Barrier::Aliasing` can be used.
+    fn create_placed_buffer(
+        &self,
+        heap: &B::Heap,
+        offset: u64,
+        size: u64,
+        usage: BufferUsage,
+    ) -> Result<B::Buffer>;
+    /// Creates an image in the memory of `heap` starting at `offset`, see `create_placed_buffer`.
+    /// Its contents are undefined until written.
+    fn create_placed_image(&self, heap: &B::Heap, offset: u64, desc: ImageDesc)
+        -> Result<B::Image>;
+    /// Creates a render pass, failing with `Error::Unsupported` if the backend can't render to
+    /// one of the attachment formats.
+    fn create_render_pass(&self, desc: &RenderPassDesc) -> Result<B::RenderPass>;
+    /// Creates a framebuffer rendering into the first mip level and array layer of `attachments`,
+    /// failing with `Error::Unsupported` if one of them isn't a color attachment.
+    fn create_framebuffer(&self, attachments: &[&B::Image]) -> Result<B::FrameBuffer>;
+
+    fn create_descriptor_set_layout(
+        &self,
+        desc: &DescriptorSetLayoutDesc,
+    ) -> Result<B::DescriptorSetLayout>;
+    /// Allocates a set of descriptors matching `layout`, freed once dropped. Its descriptors are
+    /// undefined until written.
+    fn create_descriptor_set(&self, layout: &B::DescriptorSetLayout) -> Result<B::DescriptorSet>;
+    /// Points descriptors at buffers and images. Sets must not be written while command buffers
+    /// they're bound in are executing, unless their layout is `update_after_bind` and those
+    /// command buffers don't access the descriptors written.
+    fn write_descriptors(&self, writes: &[DescriptorWrite<B>]) -> Result<()>;
+    /// Creates the layout of the resources pipelines access, sets of `set_layouts` bound at the
+    /// indices of the layouts and push constants in `push_constant_ranges`. Fails with
+    /// `Error::Unsupported` if there are more sets than `Limits::max_descriptor_sets` or the
+    /// ranges end past `Limits::max_push_constants_size`.
+    fn create_pipeline_layout(
+        &self,
+        set_layouts: &[&B::DescriptorSetLayout],
+        push_constant_ranges: &[PushConstantRange],
+    ) -> Result<B::PipelineLayout>;
+
+    fn create_fence(&self, initial_value: u64) -> Result<B::Fence>;
+    fn reset_fence(&self, fence: &B::Fence) -> Result<()>;
+    fn wait_for_fence(&self, fence: &B::Fence, value: u64) -> Result<()> {
+        self.wait_for_fence_with_timeout(fence, value, u64::MAX)
+    }
+    /// Blocks until `fence` reaches `value`, failing with `Error::Timeout` if it takes longer
+    /// than `timeout_ns` nanoseconds.
+    fn wait_for_fence_with_timeout(
+        &self,
+        fence: &B::Fence,
+        value: u64,
+        timeout_ns: u64,
+    ) -> Result<()>;
+
+    /// Why the device was lost, or `None` while it's still usable.
+    fn device_lost_reason(&self) -> Option<DeviceLostReason>;
 }
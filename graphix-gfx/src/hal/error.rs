@@ -0,0 +1,35 @@
+use std::error;
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    OutOfHostMemory,
+    OutOfDeviceMemory,
+    DeviceLost,
+    SurfaceLost,
+    OutOfDate,
+    Timeout,
+    Unsupported,
+    /// An error code of the underlying API the backend has no mapping for, kept for diagnostics.
+    Internal(i32),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match *self {
+            Error::OutOfHostMemory => "out of host memory",
+            Error::OutOfDeviceMemory => "out of device memory",
+            Error::DeviceLost => "device lost",
+            Error::SurfaceLost => "surface lost",
+            Error::OutOfDate => "swapchain out of date",
+            Error::Timeout => "timeout expired",
+            Error::Unsupported => "operation not supported",
+            Error::Internal(code) => return write!(f, "internal error {}", code),
+        };
+        f.write_str(description)
+    }
+}
+
+impl error::Error for Error {}
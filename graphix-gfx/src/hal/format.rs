@@ -1,5 +1,282 @@
-#[derive(Copy, Clone, Debug)]
+use bitflags::bitflags;
+
+/// Formats of image texels, named after their channels from the lowest to the highest bits for
+/// packed formats and from the first to the last byte otherwise.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
 pub enum Format {
+    R8Unorm,
+    R8Snorm,
+    R8Uint,
+    R8Sint,
+    Rg8Unorm,
+    Rg8Snorm,
+    Rg8Uint,
+    Rg8Sint,
     Rgba8Unorm,
-    // TODO: Add other formats
+    Rgba8UnormSrgb,
+    Rgba8Snorm,
+    Rgba8Uint,
+    Rgba8Sint,
+    Bgra8Unorm,
+    Bgra8UnormSrgb,
+
+    R16Unorm,
+    R16Snorm,
+    R16Uint,
+    R16Sint,
+    R16Float,
+    Rg16Unorm,
+    Rg16Snorm,
+    Rg16Uint,
+    Rg16Sint,
+    Rg16Float,
+    Rgba16Unorm,
+    Rgba16Snorm,
+    Rgba16Uint,
+    Rgba16Sint,
+    Rgba16Float,
+
+    R32Uint,
+    R32Sint,
+    R32Float,
+    Rg32Uint,
+    Rg32Sint,
+    Rg32Float,
+    Rgb32Uint,
+    Rgb32Sint,
+    Rgb32Float,
+    Rgba32Uint,
+    Rgba32Sint,
+    Rgba32Float,
+
+    B5g6r5Unorm,
+    Bgr5a1Unorm,
+    Rgb10a2Unorm,
+    Rgb10a2Uint,
+    Rg11b10Float,
+    Rgb9e5Float,
+
+    D16Unorm,
+    D24UnormS8Uint,
+    D32Float,
+    D32FloatS8Uint,
+
+    Bc1Unorm,
+    Bc1UnormSrgb,
+    Bc2Unorm,
+    Bc2UnormSrgb,
+    Bc3Unorm,
+    Bc3UnormSrgb,
+    Bc4Unorm,
+    Bc4Snorm,
+    Bc5Unorm,
+    Bc5Snorm,
+    Bc6hUfloat,
+    Bc6hSfloat,
+    Bc7Unorm,
+    Bc7UnormSrgb,
+}
+
+/// How the values stored in a format are read back, for depth/stencil formats that of the depth.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum NumericType {
+    Unorm,
+    Snorm,
+    Uint,
+    Sint,
+    Float,
+}
+
+bitflags! {
+    pub struct Aspects: u32 {
+        const COLOR = 0x1;
+        const DEPTH = 0x2;
+        const STENCIL = 0x4;
+    }
+}
+
+bitflags! {
+    /// What images of a format can be used for on a device.
+    pub struct FormatProperties: u32 {
+        const SAMPLED = 0x1;
+        const STORAGE = 0x2;
+        const COLOR_ATTACHMENT = 0x4;
+        /// Color attachments of the format support blending.
+        const BLEND = 0x8;
+        const DEPTH_STENCIL_ATTACHMENT = 0x10;
+    }
+}
+
+/// Layout and interpretation of a format. Uncompressed formats have 1x1 blocks, i.e. a block is a
+/// texel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FormatDesc {
+    pub bytes_per_block: u32,
+    pub block_width: u32,
+    pub block_height: u32,
+    pub channel_count: u32,
+    pub numeric_type: NumericType,
+    /// Whether the color channels, but not alpha, are sRGB encoded.
+    pub is_srgb: bool,
+    /// Whether the channels share words instead of each taking whole bytes.
+    pub is_packed: bool,
+    pub aspects: Aspects,
+}
+
+impl FormatDesc {
+    fn color(bytes_per_block: u32, channel_count: u32, numeric_type: NumericType) -> Self {
+        FormatDesc {
+            bytes_per_block,
+            block_width: 1,
+            block_height: 1,
+            channel_count,
+            numeric_type,
+            is_srgb: false,
+            is_packed: false,
+            aspects: Aspects::COLOR,
+        }
+    }
+
+    fn srgb(self) -> Self {
+        FormatDesc {
+            is_srgb: true,
+            ..self
+        }
+    }
+
+    fn packed(self) -> Self {
+        FormatDesc {
+            is_packed: true,
+            ..self
+        }
+    }
+
+    fn block(bytes_per_block: u32, channel_count: u32, numeric_type: NumericType) -> Self {
+        FormatDesc {
+            bytes_per_block,
+            block_width: 4,
+            block_height: 4,
+            channel_count,
+            numeric_type,
+            is_srgb: false,
+            is_packed: true,
+            aspects: Aspects::COLOR,
+        }
+    }
+
+    fn depth(bytes_per_block: u32, numeric_type: NumericType, has_stencil: bool) -> Self {
+        FormatDesc {
+            bytes_per_block,
+            block_width: 1,
+            block_height: 1,
+            channel_count: if has_stencil { 2 } else { 1 },
+            numeric_type,
+            is_srgb: false,
+            is_packed: has_stencil,
+            aspects: if has_stencil {
+                Aspects::DEPTH | Aspects::STENCIL
+            } else {
+                Aspects::DEPTH
+            },
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.block_width > 1 || self.block_height > 1
+    }
+}
+
+impl Format {
+    pub fn desc(self) -> FormatDesc {
+        use self::NumericType::*;
+
+        match self {
+            Format::R8Unorm => FormatDesc::color(1, 1, Unorm),
+            Format::R8Snorm => FormatDesc::color(1, 1, Snorm),
+            Format::R8Uint => FormatDesc::color(1, 1, Uint),
+            Format::R8Sint => FormatDesc::color(1, 1, Sint),
+            Format::Rg8Unorm => FormatDesc::color(2, 2, Unorm),
+            Format::Rg8Snorm => FormatDesc::color(2, 2, Snorm),
+            Format::Rg8Uint => FormatDesc::color(2, 2, Uint),
+            Format::Rg8Sint => FormatDesc::color(2, 2, Sint),
+            Format::Rgba8Unorm | Format::Bgra8Unorm => FormatDesc::color(4, 4, Unorm),
+            Format::Rgba8UnormSrgb | Format::Bgra8UnormSrgb => {
+                FormatDesc::color(4, 4, Unorm).srgb()
+            }
+            Format::Rgba8Snorm => FormatDesc::color(4, 4, Snorm),
+            Format::Rgba8Uint => FormatDesc::color(4, 4, Uint),
+            Format::Rgba8Sint => FormatDesc::color(4, 4, Sint),
+
+            Format::R16Unorm => FormatDesc::color(2, 1, Unorm),
+            Format::R16Snorm => FormatDesc::color(2, 1, Snorm),
+            Format::R16Uint => FormatDesc::color(2, 1, Uint),
+            Format::R16Sint => FormatDesc::color(2, 1, Sint),
+            Format::R16Float => FormatDesc::color(2, 1, Float),
+            Format::Rg16Unorm => FormatDesc::color(4, 2, Unorm),
+            Format::Rg16Snorm => FormatDesc::color(4, 2, Snorm),
+            Format::Rg16Uint => FormatDesc::color(4, 2, Uint),
+            Format::Rg16Sint => FormatDesc::color(4, 2, Sint),
+            Format::Rg16Float => FormatDesc::color(4, 2, Float),
+            Format::Rgba16Unorm => FormatDesc::color(8, 4, Unorm),
+            Format::Rgba16Snorm => FormatDesc::color(8, 4, Snorm),
+            Format::Rgba16Uint => FormatDesc::color(8, 4, Uint),
+            Format::Rgba16Sint => FormatDesc::color(8, 4, Sint),
+            Format::Rgba16Float => FormatDesc::color(8, 4, Float),
+
+            Format::R32Uint => FormatDesc::color(4, 1, Uint),
+            Format::R32Sint => FormatDesc::color(4, 1, Sint),
+            Format::R32Float => FormatDesc::color(4, 1, Float),
+            Format::Rg32Uint => FormatDesc::color(8, 2, Uint),
+            Format::Rg32Sint => FormatDesc::color(8, 2, Sint),
+            Format::Rg32Float => FormatDesc::color(8, 2, Float),
+            Format::Rgb32Uint => FormatDesc::color(12, 3, Uint),
+            Format::Rgb32Sint => FormatDesc::color(12, 3, Sint),
+            Format::Rgb32Float => FormatDesc::color(12, 3, Float),
+            Format::Rgba32Uint => FormatDesc::color(16, 4, Uint),
+            Format::Rgba32Sint => FormatDesc::color(16, 4, Sint),
+            Format::Rgba32Float => FormatDesc::color(16, 4, Float),
+
+            Format::B5g6r5Unorm => FormatDesc::color(2, 3, Unorm).packed(),
+            Format::Bgr5a1Unorm => FormatDesc::color(2, 4, Unorm).packed(),
+            Format::Rgb10a2Unorm => FormatDesc::color(4, 4, Unorm).packed(),
+            Format::Rgb10a2Uint => FormatDesc::color(4, 4, Uint).packed(),
+            Format::Rg11b10Float => FormatDesc::color(4, 3, Float).packed(),
+            Format::Rgb9e5Float => FormatDesc::color(4, 3, Float).packed(),
+
+            Format::D16Unorm => FormatDesc::depth(2, Unorm, false),
+            Format::D24UnormS8Uint => FormatDesc::depth(4, Unorm, true),
+            Format::D32Float => FormatDesc::depth(4, Float, false),
+            Format::D32FloatS8Uint => FormatDesc::depth(8, Float, true),
+
+            Format::Bc1Unorm => FormatDesc::block(8, 4, Unorm),
+            Format::Bc1UnormSrgb => FormatDesc::block(8, 4, Unorm).srgb(),
+            Format::Bc2Unorm => FormatDesc::block(16, 4, Unorm),
+            Format::Bc2UnormSrgb => FormatDesc::block(16, 4, Unorm).srgb(),
+            Format::Bc3Unorm => FormatDesc::block(16, 4, Unorm),
+            Format::Bc3UnormSrgb => FormatDesc::block(16, 4, Unorm).srgb(),
+            Format::Bc4Unorm => FormatDesc::block(8, 1, Unorm),
+            Format::Bc4Snorm => FormatDesc::block(8, 1, Snorm),
+            Format::Bc5Unorm => FormatDesc::block(16, 2, Unorm),
+            Format::Bc5Snorm => FormatDesc::block(16, 2, Snorm),
+            Format::Bc6hUfloat | Format::Bc6hSfloat => FormatDesc::block(16, 3, Float),
+            Format::Bc7Unorm => FormatDesc::block(16, 4, Unorm),
+            Format::Bc7UnormSrgb => FormatDesc::block(16, 4, Unorm).srgb(),
+        }
+    }
+
+    pub fn aspects(self) -> Aspects {
+        self.desc().aspects
+    }
+
+    pub fn is_depth_stencil(self) -> bool {
+        self.aspects().intersects(Aspects::DEPTH | Aspects::STENCIL)
+    }
+
+    /// Size in bytes of a tightly packed `width` x `height` region, rounded up to whole blocks.
+    pub fn get_region_size(self, width: u32, height: u32) -> u64 {
+        let desc = self.desc();
+        let blocks_wide = width.div_ceil(desc.block_width);
+        let blocks_high = height.div_ceil(desc.block_height);
+        u64::from(blocks_wide) * u64::from(blocks_high) * u64::from(desc.bytes_per_block)
+    }
 }
@@ -0,0 +1,134 @@
+use crate::hal::format::{Aspects, Format};
+
+use bitflags::bitflags;
+
+use std::ops::Range;
+
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ImageKind {
+    D1,
+    D2,
+    D3,
+    /// A 2D image whose array layers are grouped in sets of 6 cube faces.
+    Cube,
+}
+
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct Extent {
+    pub width: u32,
+    pub height: u32,
+    pub depth: u32,
+}
+
+bitflags! {
+    pub struct ImageUsage: u32 {
+        const TRANSFER_SRC = 0x1;
+        const TRANSFER_DST = 0x2;
+        const SAMPLED = 0x4;
+        const STORAGE = 0x8;
+        const COLOR_ATTACHMENT = 0x10;
+        const DEPTH_STENCIL_ATTACHMENT = 0x20;
+    }
+}
+
+/// Part of an image, e.g. a single mip level or cube face. The depth and stencil aspects of depth
+/// stencil formats are separate planes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubresourceRange {
+    pub aspects: Aspects,
+    pub mip_levels: Range<u32>,
+    pub array_layers: Range<u32>,
+}
+
+/// Description of an image in device local memory. Images used as color attachments start out
+/// in `ResourceState::RENDER_TARGET`, others in `ResourceState::COMMON`.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ImageDesc {
+    pub kind: ImageKind,
+    pub extent: Extent,
+    pub mip_levels: u32,
+    /// Number of layers, a multiple of 6 for `ImageKind::Cube` and 1 for `ImageKind::D3`.
+    pub array_layers: u32,
+    pub samples: u32,
+    pub format: Format,
+    pub usage: ImageUsage,
+}
+
+impl ImageDesc {
+    /// Extent of a mip level, each one being half the size of the previous one.
+    pub fn get_mip_extent(&self, mip_level: u32) -> Extent {
+        Extent {
+            width: (self.extent.width >> mip_level).max(1),
+            height: (self.extent.height >> mip_level).max(1),
+            depth: (self.extent.depth >> mip_level).max(1),
+        }
+    }
+
+    /// Size of all subresources with their texels tightly packed, which backends may round up.
+    pub fn get_packed_size(&self) -> u64 {
+        let layer_size = (0..self.mip_levels)
+            .map(|mip_level| {
+                let extent = self.get_mip_extent(mip_level);
+                let slice_size = self.format.get_region_size(extent.width, extent.height);
+                slice_size * u64::from(extent.depth) * u64::from(self.samples)
+            })
+            .sum::<u64>();
+        layer_size * u64::from(self.array_layers)
+    }
+
+    /// Aspects stored in separate planes, in plane order.
+    pub fn get_planes(&self) -> Vec<Aspects> {
+        let aspects = self.format.aspects();
+        if aspects.contains(Aspects::DEPTH | Aspects::STENCIL) {
+            vec![Aspects::DEPTH, Aspects::STENCIL]
+        } else {
+            vec![aspects]
+        }
+    }
+
+    pub fn get_subresource_count(&self) -> u32 {
+        self.mip_levels * self.array_layers * self.get_planes().len() as u32
+    }
+
+    /// Index of a subresource, mip levels coming first, then array layers and then planes.
+    pub fn get_subresource_index(&self, mip_level: u32, array_layer: u32, plane: u32) -> u32 {
+        mip_level + (array_layer + plane * self.array_layers) * self.mip_levels
+    }
+
+    /// Indices of the subresources in `range`, which must lie within the image.
+    pub fn get_subresource_indices(&self, range: &SubresourceRange) -> Vec<u32> {
+        assert!(
+            range.mip_levels.end <= self.mip_levels,
+            "Mip levels {:?} out of range for an image of {} mip levels",
+            range.mip_levels,
+            self.mip_levels
+        );
+        assert!(
+            range.array_layers.end <= self.array_layers,
+            "Array layers {:?} out of range for an image of {} array layers",
+            range.array_layers,
+            self.array_layers
+        );
+
+        let mut indices = Vec::new();
+        for (plane, &aspects) in self.get_planes().iter().enumerate() {
+            if !range.aspects.intersects(aspects) {
+                continue;
+            }
+            for array_layer in range.array_layers.clone() {
+                for mip_level in range.mip_levels.clone() {
+                    indices.push(self.get_subresource_index(mip_level, array_layer, plane as u32));
+                }
+            }
+        }
+        indices
+    }
+
+    pub fn get_full_range(&self) -> SubresourceRange {
+        SubresourceRange {
+            aspects: self.format.aspects(),
+            mip_levels: 0..self.mip_levels,
+            array_layers: 0..self.array_layers,
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use bitflags::bitflags;
+
+/// Capability tier of a device, following the Direct3D feature levels. Backends other than DX12
+/// report the closest level their core features match.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FeatureLevel {
+    L11_0,
+    L11_1,
+    L12_0,
+    L12_1,
+}
+
+bitflags! {
+    /// Optional features, which are enabled whenever the device supports them.
+    pub struct Features: u32 {
+        /// Images and buffers can be partially backed by memory.
+        const TILED_RESOURCES = 0x1;
+        const CONSERVATIVE_RASTERIZATION = 0x2;
+        const SAMPLER_ANISOTROPY = 0x4;
+        const TEXTURE_COMPRESSION_BC = 0x8;
+        const SHADER_FLOAT64 = 0x10;
+        const RASTERIZER_ORDERED_VIEWS = 0x20;
+        /// Heaps can hold buffers, images and attachments together.
+        const MIXED_RESOURCE_HEAPS = 0x40;
+        /// Descriptor set layouts can be `update_after_bind`, for sets shaders index into.
+        const BINDLESS = 0x80;
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub feature_level: FeatureLevel,
+    pub max_image_dimension_1d: u32,
+    pub max_image_dimension_2d: u32,
+    pub max_image_dimension_3d: u32,
+    pub max_image_dimension_cube: u32,
+    pub max_image_array_layers: u32,
+    pub max_color_attachments: u32,
+    /// Most resource descriptors shaders can access at once.
+    pub max_descriptors: u32,
+    /// Most sampler descriptors shaders can access at once.
+    pub max_samplers: u32,
+    /// Most descriptor sets bound at once.
+    pub max_descriptor_sets: u32,
+    /// Most descriptors in `update_after_bind` sets shaders can access at once, 0 without
+    /// `Features::BINDLESS`.
+    pub max_bindless_descriptors: u32,
+    /// Most bytes of push constants of a pipeline layout.
+    pub max_push_constants_size: u32,
+    /// Supported color and depth attachment sample counts, each count `n` setting the bit of
+    /// value `n`.
+    pub sample_counts: u32,
+    pub min_constant_buffer_offset_alignment: u64,
+}
+
+impl Limits {
+    pub fn supports_sample_count(&self, samples: u32) -> bool {
+        samples.is_power_of_two() && self.sample_counts & samples != 0
+    }
+}
@@ -0,0 +1,41 @@
+use crate::hal::image::ImageUsage;
+
+use bitflags::bitflags;
+
+bitflags! {
+    /// Kinds of resources a heap can hold. Heaps holding more than one kind need
+    /// `Features::MIXED_RESOURCE_HEAPS`.
+    pub struct HeapUsage: u32 {
+        const BUFFERS = 0x1;
+        /// Images other than color and depth stencil attachments.
+        const IMAGES = 0x2;
+        /// Images used as color or depth stencil attachments.
+        const ATTACHMENTS = 0x4;
+    }
+}
+
+impl HeapUsage {
+    /// Kind of heap images of `usage` can be placed in.
+    pub fn from_image_usage(usage: ImageUsage) -> Self {
+        if usage.intersects(ImageUsage::COLOR_ATTACHMENT | ImageUsage::DEPTH_STENCIL_ATTACHMENT) {
+            HeapUsage::ATTACHMENTS
+        } else {
+            HeapUsage::IMAGES
+        }
+    }
+}
+
+/// Memory a resource takes once placed in a heap.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct MemoryRequirements {
+    pub size: u64,
+    /// Offsets the resource can be placed at are multiples of it, always a power of two.
+    pub alignment: u64,
+}
+
+impl MemoryRequirements {
+    /// Whether a resource placed at `offset` fits in a heap of `heap_size` bytes.
+    pub fn fits(&self, offset: u64, heap_size: u64) -> bool {
+        offset.is_multiple_of(self.alignment) && offset + self.size <= heap_size
+    }
+}
@@ -0,0 +1,18 @@
+use crate::hal::attachment::AttachmentDesc;
+use crate::hal::format::Aspects;
+
+/// Description of a render pass. Its attachments match those of the framebuffers it's begun
+/// with, in the same order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenderPassDesc {
+    pub attachments: Vec<AttachmentDesc>,
+}
+
+impl RenderPassDesc {
+    /// Whether all the attachments have color formats, the only ones backends render to for now.
+    pub fn has_only_color_attachments(&self) -> bool {
+        self.attachments
+            .iter()
+            .all(|attachment| attachment.format.desc().aspects == Aspects::COLOR)
+    }
+}
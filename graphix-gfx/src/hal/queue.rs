@@ -1,3 +1,4 @@
+use crate::hal::error::Result;
 use crate::hal::Backend;
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -8,7 +9,10 @@ pub enum QueueType {
 }
 
 pub trait CommandQueue<B: Backend> {
-    fn submit(&self, command_buffers: Vec<&B::CommandBuffer>);
+    /// Submits command buffers for execution in order. Resource states are tracked across
+    /// submissions, and resources left in another state than a command buffer first uses them in
+    /// are transitioned right before it.
+    fn submit(&self, command_buffers: Vec<&B::CommandBuffer>) -> Result<()>;
 
-    fn signal_fence(&self, fence: &B::Fence, value: u64);
+    fn signal_fence(&self, fence: &B::Fence, value: u64) -> Result<()>;
 }
@@ -0,0 +1,52 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// How a buffer or image is used by the GPU, which decides the transitions and caches flushed
+    /// between uses. Read-only states can be combined, e.g. a buffer read as both vertex and
+    /// index buffer, whereas write states can't be combined with any other.
+    ///
+    /// `COMMON`, the empty state, allows any access but may be slower. Device local buffers and
+    /// images other than attachments start out in it.
+    pub struct ResourceState: u32 {
+        const COMMON = 0;
+        const VERTEX_BUFFER = 0x1;
+        const INDEX_BUFFER = 0x2;
+        const CONSTANT_BUFFER = 0x4;
+        const INDIRECT_ARGUMENT = 0x8;
+        /// Read by shaders other than pixel shaders.
+        const NON_PIXEL_SHADER_READ = 0x10;
+        const PIXEL_SHADER_READ = 0x20;
+        const DEPTH_READ = 0x40;
+        const COPY_SRC = 0x80;
+        const RESOLVE_SRC = 0x100;
+        const RENDER_TARGET = 0x200;
+        const DEPTH_WRITE = 0x400;
+        /// Read and written by shaders as storage buffers or images.
+        const STORAGE = 0x800;
+        const COPY_DST = 0x1000;
+        const RESOLVE_DST = 0x2000;
+        /// Ready to be presented by a swapchain.
+        const PRESENT = 0x4000;
+
+        const SHADER_READ = Self::NON_PIXEL_SHADER_READ.bits | Self::PIXEL_SHADER_READ.bits;
+        const READ_ONLY = Self::VERTEX_BUFFER.bits
+            | Self::INDEX_BUFFER.bits
+            | Self::CONSTANT_BUFFER.bits
+            | Self::INDIRECT_ARGUMENT.bits
+            | Self::SHADER_READ.bits
+            | Self::DEPTH_READ.bits
+            | Self::COPY_SRC.bits
+            | Self::RESOLVE_SRC.bits;
+    }
+}
+
+impl ResourceState {
+    pub fn is_read_only(self) -> bool {
+        !self.is_empty() && Self::READ_ONLY.contains(self)
+    }
+
+    /// Whether the state is a single write state or a combination of read-only states.
+    pub fn is_valid(self) -> bool {
+        self.bits().count_ones() <= 1 || self.is_read_only()
+    }
+}
@@ -1,3 +1,4 @@
+use crate::hal::error::Result;
 use crate::hal::format::Format;
 use crate::hal::Backend;
 
@@ -21,13 +22,15 @@ pub trait Surface<B: Backend> {
         device: &B::Device,
         command_queue: &B::CommandQueue,
         config: SwapchainConfig,
-    ) -> B::Swapchain;
+    ) -> Result<B::Swapchain>;
 }
 
+/// Acquiring and presenting fail with `Error::DeviceLost` once the device the swapchain was
+/// created from is lost, see `Device` for how to recover.
 pub trait Swapchain<B: Backend> {
-    fn acquire_buffer(&self) -> SwapchainBufferIndex;
+    fn acquire_buffer(&self) -> Result<SwapchainBufferIndex>;
 
-    fn present(&self);
+    fn present(&self) -> Result<()>;
 
     fn create_backbuffer(&self) -> BackBuffer<B>;
 }
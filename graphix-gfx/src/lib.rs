@@ -1,8 +1,26 @@
-pub use crate::backend::adapter::PhysicalAdapter;
-pub use crate::backend::device::Device;
-pub use crate::backend::instance::Instance;
-pub use crate::backend::queue::CommandQueue;
-pub use crate::backend::window::{Surface, Swapchain};
+#[cfg(any(
+    feature = "dx12",
+    feature = "vulkan",
+    feature = "software",
+    feature = "null"
+))]
+pub use crate::backend::{
+    any::{self, Backend as AnyBackend, BackendType, Instance as DynInstance},
+    default::{Backend, CommandQueue, Device, Instance, PhysicalAdapter, Surface, Swapchain},
+};
 
+#[cfg(feature = "dx12")]
+pub use crate::backend::dx12;
+#[cfg(feature = "null")]
+pub use crate::backend::null;
+#[cfg(feature = "software")]
+pub use crate::backend::software;
+#[cfg(feature = "vulkan")]
+pub use crate::backend::vulkan;
+
+pub mod allocator;
 mod backend;
+pub mod bindless;
 pub mod hal;
+#[cfg(all(test, feature = "software"))]
+mod testing;
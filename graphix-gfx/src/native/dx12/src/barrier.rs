@@ -13,11 +13,15 @@ bitflags! {
     }
 }
 
+pub const ALL_SUBRESOURCES: u32 = d3d12::D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES;
+
 #[derive(Clone)]
 pub struct BarrierDesc {
     pub index: usize,
     pub flags: BarrierFlags,
     pub states: Range<ResourceState>,
+    /// Subresource to transition, `ALL_SUBRESOURCES` for the whole resource.
+    pub subresource: u32,
 }
 
 impl BarrierDesc {
@@ -26,6 +30,15 @@ impl BarrierDesc {
             index,
             flags: BarrierFlags::NONE,
             states,
+            subresource: ALL_SUBRESOURCES,
         }
     }
 }
+
+/// Aliasing barrier between the resources at `before` and `after`, `None` standing for any
+/// resource that may alias.
+#[derive(Clone)]
+pub struct AliasingBarrierDesc {
+    pub before: Option<usize>,
+    pub after: Option<usize>,
+}
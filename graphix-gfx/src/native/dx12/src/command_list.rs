@@ -1,10 +1,14 @@
-use crate::barrier::BarrierDesc;
+use self::render_pass::*;
+use crate::barrier::{AliasingBarrierDesc, BarrierDesc};
 use crate::command_allocator::CommandAllocator;
 use crate::device::Device;
-use crate::heap::CPUDescriptor;
+use crate::dxgi::Format;
+use crate::heap::{CPUDescriptor, DescriptorHeap, GPUDescriptor};
 use crate::resource::Resource;
+use crate::root_signature::RootSignature;
 use crate::Result;
 
+use winapi::shared::minwindef::FALSE;
 use winapi::shared::winerror;
 use winapi::um::d3d12;
 use winapi::Interface;
@@ -25,6 +29,29 @@ pub struct CommandList(pub(crate) ComPtr<d3d12::ID3D12CommandList>);
 
 pub struct GraphicsCommandList(pub(crate) ComPtr<d3d12::ID3D12GraphicsCommandList>);
 
+/// A command list supporting native render passes, available since Windows 10 1809.
+pub struct GraphicsCommandList4(pub(crate) ComPtr<ID3D12GraphicsCommandList4>);
+
+#[derive(Copy, Clone)]
+pub enum BeginningAccess {
+    Discard,
+    Preserve,
+    Clear { format: Format, color: [f32; 4] },
+}
+
+#[derive(Copy, Clone)]
+pub enum EndingAccess {
+    Discard,
+    Preserve,
+}
+
+#[derive(Copy, Clone)]
+pub struct RenderPassRenderTarget {
+    pub descriptor: CPUDescriptor,
+    pub beginning_access: BeginningAccess,
+    pub ending_access: EndingAccess,
+}
+
 impl GraphicsCommandList {
     pub fn new(
         device: &Device,
@@ -56,6 +83,14 @@ impl GraphicsCommandList {
         CommandList(self.0.clone().up::<d3d12::ID3D12CommandList>())
     }
 
+    /// Returns `None` when the runtime predates render passes.
+    pub fn as_graphics_command_list4(&self) -> Option<GraphicsCommandList4> {
+        self.0
+            .cast::<ID3D12GraphicsCommandList4>()
+            .ok()
+            .map(GraphicsCommandList4)
+    }
+
     pub fn reset(&self, command_allocator: &CommandAllocator) -> Result<()> {
         let hr = unsafe { self.0.Reset(command_allocator.0.as_raw(), ptr::null_mut()) };
         if winerror::SUCCEEDED(hr) {
@@ -78,7 +113,7 @@ impl GraphicsCommandList {
                 *unsafe { resource_barrier.u.Transition_mut() } =
                     d3d12::D3D12_RESOURCE_TRANSITION_BARRIER {
                         pResource: resources[barrier.index].0.as_raw(),
-                        Subresource: d3d12::D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
+                        Subresource: barrier.subresource,
                         StateBefore: barrier.states.start.bits(),
                         StateAfter: barrier.states.end.bits(),
                     };
@@ -95,6 +130,67 @@ impl GraphicsCommandList {
         }
     }
 
+    pub fn insert_uav_barriers(&self, resources: &[Resource]) {
+        let uav_barriers = resources
+            .iter()
+            .map(|resource| {
+                let mut resource_barrier = d3d12::D3D12_RESOURCE_BARRIER {
+                    Type: d3d12::D3D12_RESOURCE_BARRIER_TYPE_UAV,
+                    Flags: d3d12::D3D12_RESOURCE_BARRIER_FLAG_NONE,
+                    u: unsafe { mem::zeroed() },
+                };
+
+                *unsafe { resource_barrier.u.UAV_mut() } = d3d12::D3D12_RESOURCE_UAV_BARRIER {
+                    pResource: resource.0.as_raw(),
+                };
+
+                resource_barrier
+            })
+            .collect::<Vec<_>>();
+
+        if !uav_barriers.is_empty() {
+            unsafe {
+                self.0
+                    .ResourceBarrier(uav_barriers.len() as _, uav_barriers.as_ptr())
+            };
+        }
+    }
+
+    pub fn insert_aliasing_barriers(
+        &self,
+        barriers: &[AliasingBarrierDesc],
+        resources: &[Resource],
+    ) {
+        let get_raw = |index: Option<usize>| {
+            index.map_or(ptr::null_mut(), |index| resources[index].0.as_raw())
+        };
+        let aliasing_barriers = barriers
+            .iter()
+            .map(|barrier| {
+                let mut resource_barrier = d3d12::D3D12_RESOURCE_BARRIER {
+                    Type: d3d12::D3D12_RESOURCE_BARRIER_TYPE_ALIASING,
+                    Flags: d3d12::D3D12_RESOURCE_BARRIER_FLAG_NONE,
+                    u: unsafe { mem::zeroed() },
+                };
+
+                *unsafe { resource_barrier.u.Aliasing_mut() } =
+                    d3d12::D3D12_RESOURCE_ALIASING_BARRIER {
+                        pResourceBefore: get_raw(barrier.before),
+                        pResourceAfter: get_raw(barrier.after),
+                    };
+
+                resource_barrier
+            })
+            .collect::<Vec<_>>();
+
+        if !aliasing_barriers.is_empty() {
+            unsafe {
+                self.0
+                    .ResourceBarrier(aliasing_barriers.len() as _, aliasing_barriers.as_ptr())
+            };
+        }
+    }
+
     pub fn clear_render_target_view(&self, rtv: CPUDescriptor, clear_color: [f32; 4]) {
         unsafe {
             self.0
@@ -102,6 +198,69 @@ impl GraphicsCommandList {
         };
     }
 
+    pub fn set_render_targets(&self, rtvs: &[CPUDescriptor]) {
+        unsafe {
+            self.0
+                .OMSetRenderTargets(rtvs.len() as _, rtvs.as_ptr(), FALSE, ptr::null())
+        };
+    }
+
+    /// Sets the shader-visible heaps, at most one of CBV_SRV_UAV type and one of sampler type.
+    pub fn set_descriptor_heaps(&self, heaps: &[DescriptorHeap]) {
+        let mut raw_heaps = heaps.iter().map(|heap| heap.0.as_raw()).collect::<Vec<_>>();
+        unsafe {
+            self.0
+                .SetDescriptorHeaps(raw_heaps.len() as _, raw_heaps.as_mut_ptr())
+        };
+    }
+
+    pub fn set_graphics_root_signature(&self, root_signature: &RootSignature) {
+        unsafe { self.0.SetGraphicsRootSignature(root_signature.0.as_raw()) };
+    }
+
+    pub fn set_compute_root_signature(&self, root_signature: &RootSignature) {
+        unsafe { self.0.SetComputeRootSignature(root_signature.0.as_raw()) };
+    }
+
+    pub fn set_graphics_root_descriptor_table(&self, parameter_index: u32, table: GPUDescriptor) {
+        unsafe {
+            self.0
+                .SetGraphicsRootDescriptorTable(parameter_index, table)
+        };
+    }
+
+    pub fn set_compute_root_descriptor_table(&self, parameter_index: u32, table: GPUDescriptor) {
+        unsafe { self.0.SetComputeRootDescriptorTable(parameter_index, table) };
+    }
+
+    /// Sets the 32-bit values of a constants parameter starting at `offset`, in 32-bit values.
+    pub fn set_graphics_root_32bit_constants(
+        &self,
+        parameter_index: u32,
+        data: &[u8],
+        offset: u32,
+    ) {
+        unsafe {
+            self.0.SetGraphicsRoot32BitConstants(
+                parameter_index,
+                (data.len() / 4) as _,
+                data.as_ptr() as *const _,
+                offset,
+            )
+        };
+    }
+
+    pub fn set_compute_root_32bit_constants(&self, parameter_index: u32, data: &[u8], offset: u32) {
+        unsafe {
+            self.0.SetComputeRoot32BitConstants(
+                parameter_index,
+                (data.len() / 4) as _,
+                data.as_ptr() as *const _,
+                offset,
+            )
+        };
+    }
+
     pub fn close(&self) -> Result<()> {
         let hr = unsafe { self.0.Close() };
         if winerror::SUCCEEDED(hr) {
@@ -117,3 +276,179 @@ impl Clone for GraphicsCommandList {
         GraphicsCommandList(self.0.clone())
     }
 }
+
+impl GraphicsCommandList4 {
+    pub fn begin_render_pass(&self, render_targets: &[RenderPassRenderTarget]) {
+        let render_targets = render_targets
+            .iter()
+            .map(|render_target| D3D12_RENDER_PASS_RENDER_TARGET_DESC {
+                cpuDescriptor: render_target.descriptor,
+                BeginningAccess: get_beginning_access(render_target.beginning_access),
+                EndingAccess: get_ending_access(render_target.ending_access),
+            })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            self.0.BeginRenderPass(
+                render_targets.len() as _,
+                render_targets.as_ptr(),
+                ptr::null(),
+                D3D12_RENDER_PASS_FLAG_NONE,
+            )
+        };
+    }
+
+    pub fn end_render_pass(&self) {
+        unsafe { self.0.EndRenderPass() };
+    }
+}
+
+impl Clone for GraphicsCommandList4 {
+    fn clone(&self) -> Self {
+        GraphicsCommandList4(self.0.clone())
+    }
+}
+
+fn get_beginning_access(access: BeginningAccess) -> D3D12_RENDER_PASS_BEGINNING_ACCESS {
+    let mut clear_value = d3d12::D3D12_CLEAR_VALUE {
+        Format: Format::UNKNOWN as _,
+        u: unsafe { mem::zeroed() },
+    };
+    let access_type = match access {
+        BeginningAccess::Discard => D3D12_RENDER_PASS_BEGINNING_ACCESS_TYPE_DISCARD,
+        BeginningAccess::Preserve => D3D12_RENDER_PASS_BEGINNING_ACCESS_TYPE_PRESERVE,
+        BeginningAccess::Clear { format, color } => {
+            clear_value.Format = format as _;
+            *unsafe { clear_value.u.Color_mut() } = color;
+            D3D12_RENDER_PASS_BEGINNING_ACCESS_TYPE_CLEAR
+        }
+    };
+
+    D3D12_RENDER_PASS_BEGINNING_ACCESS {
+        Type: access_type,
+        Clear: D3D12_RENDER_PASS_BEGINNING_ACCESS_CLEAR_PARAMETERS {
+            ClearValue: clear_value,
+        },
+    }
+}
+
+fn get_ending_access(access: EndingAccess) -> D3D12_RENDER_PASS_ENDING_ACCESS {
+    D3D12_RENDER_PASS_ENDING_ACCESS {
+        Type: match access {
+            EndingAccess::Discard => D3D12_RENDER_PASS_ENDING_ACCESS_TYPE_DISCARD,
+            EndingAccess::Preserve => D3D12_RENDER_PASS_ENDING_ACCESS_TYPE_PRESERVE,
+        },
+        Resolve: unsafe { mem::zeroed() },
+    }
+}
+
+// winapi stops at ID3D12GraphicsCommandList1, so the render pass API is declared here. The methods
+// of ID3D12GraphicsCommandList2 and ID3D12GraphicsCommandList3 are inlined to keep the vtable
+// layout, and the unions holding a single struct are declared as that struct.
+#[allow(non_camel_case_types, non_snake_case, dead_code)]
+mod render_pass {
+    use winapi::shared::dxgiformat::DXGI_FORMAT;
+    use winapi::shared::minwindef::{BOOL, UINT};
+    use winapi::um::d3d12::{self, ID3D12GraphicsCommandList1, ID3D12GraphicsCommandList1Vtbl};
+    use winapi::RIDL;
+
+    use std::ffi::c_void;
+
+    pub(crate) type D3D12_RENDER_PASS_BEGINNING_ACCESS_TYPE = u32;
+    pub(crate) const D3D12_RENDER_PASS_BEGINNING_ACCESS_TYPE_DISCARD:
+        D3D12_RENDER_PASS_BEGINNING_ACCESS_TYPE = 0;
+    pub(crate) const D3D12_RENDER_PASS_BEGINNING_ACCESS_TYPE_PRESERVE:
+        D3D12_RENDER_PASS_BEGINNING_ACCESS_TYPE = 1;
+    pub(crate) const D3D12_RENDER_PASS_BEGINNING_ACCESS_TYPE_CLEAR:
+        D3D12_RENDER_PASS_BEGINNING_ACCESS_TYPE = 2;
+
+    pub(crate) type D3D12_RENDER_PASS_ENDING_ACCESS_TYPE = u32;
+    pub(crate) const D3D12_RENDER_PASS_ENDING_ACCESS_TYPE_DISCARD:
+        D3D12_RENDER_PASS_ENDING_ACCESS_TYPE = 0;
+    pub(crate) const D3D12_RENDER_PASS_ENDING_ACCESS_TYPE_PRESERVE:
+        D3D12_RENDER_PASS_ENDING_ACCESS_TYPE = 1;
+
+    pub(crate) type D3D12_RENDER_PASS_FLAGS = u32;
+    pub(crate) const D3D12_RENDER_PASS_FLAG_NONE: D3D12_RENDER_PASS_FLAGS = 0;
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    pub(crate) struct D3D12_RENDER_PASS_BEGINNING_ACCESS_CLEAR_PARAMETERS {
+        pub(crate) ClearValue: d3d12::D3D12_CLEAR_VALUE,
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    pub(crate) struct D3D12_RENDER_PASS_BEGINNING_ACCESS {
+        pub(crate) Type: D3D12_RENDER_PASS_BEGINNING_ACCESS_TYPE,
+        pub(crate) Clear: D3D12_RENDER_PASS_BEGINNING_ACCESS_CLEAR_PARAMETERS,
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    pub(crate) struct D3D12_RENDER_PASS_ENDING_ACCESS_RESOLVE_SUBRESOURCE_PARAMETERS {
+        pub(crate) SrcSubresource: UINT,
+        pub(crate) DstSubresource: UINT,
+        pub(crate) DstX: UINT,
+        pub(crate) DstY: UINT,
+        pub(crate) SrcRect: d3d12::D3D12_RECT,
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    pub(crate) struct D3D12_RENDER_PASS_ENDING_ACCESS_RESOLVE_PARAMETERS {
+        pub(crate) pSrcResource: *mut d3d12::ID3D12Resource,
+        pub(crate) pDstResource: *mut d3d12::ID3D12Resource,
+        pub(crate) SubresourceCount: UINT,
+        pub(crate) pSubresourceParameters:
+            *const D3D12_RENDER_PASS_ENDING_ACCESS_RESOLVE_SUBRESOURCE_PARAMETERS,
+        pub(crate) Format: DXGI_FORMAT,
+        pub(crate) ResolveMode: d3d12::D3D12_RESOLVE_MODE,
+        pub(crate) PreserveResolveSource: BOOL,
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    pub(crate) struct D3D12_RENDER_PASS_ENDING_ACCESS {
+        pub(crate) Type: D3D12_RENDER_PASS_ENDING_ACCESS_TYPE,
+        pub(crate) Resolve: D3D12_RENDER_PASS_ENDING_ACCESS_RESOLVE_PARAMETERS,
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    pub(crate) struct D3D12_RENDER_PASS_RENDER_TARGET_DESC {
+        pub(crate) cpuDescriptor: d3d12::D3D12_CPU_DESCRIPTOR_HANDLE,
+        pub(crate) BeginningAccess: D3D12_RENDER_PASS_BEGINNING_ACCESS,
+        pub(crate) EndingAccess: D3D12_RENDER_PASS_ENDING_ACCESS,
+    }
+
+    #[repr(C)]
+    #[derive(Copy, Clone)]
+    pub(crate) struct D3D12_RENDER_PASS_DEPTH_STENCIL_DESC {
+        pub(crate) cpuDescriptor: d3d12::D3D12_CPU_DESCRIPTOR_HANDLE,
+        pub(crate) DepthBeginningAccess: D3D12_RENDER_PASS_BEGINNING_ACCESS,
+        pub(crate) StencilBeginningAccess: D3D12_RENDER_PASS_BEGINNING_ACCESS,
+        pub(crate) DepthEndingAccess: D3D12_RENDER_PASS_ENDING_ACCESS,
+        pub(crate) StencilEndingAccess: D3D12_RENDER_PASS_ENDING_ACCESS,
+    }
+
+    RIDL! {#[uuid(0x8754318e, 0xd3a9, 0x4541, 0x98, 0xcf, 0x64, 0x5b, 0x50, 0xdc, 0x48, 0x74)]
+    interface ID3D12GraphicsCommandList4(ID3D12GraphicsCommandList4Vtbl):
+        ID3D12GraphicsCommandList1(ID3D12GraphicsCommandList1Vtbl) {
+        fn WriteBufferImmediate(
+            Count: UINT,
+            pParams: *const c_void,
+            pModes: *const c_void,
+        ) -> (),
+        fn SetProtectedResourceSession(
+            pProtectedResourceSession: *mut c_void,
+        ) -> (),
+        fn BeginRenderPass(
+            NumRenderTargets: UINT,
+            pRenderTargets: *const D3D12_RENDER_PASS_RENDER_TARGET_DESC,
+            pDepthStencil: *const D3D12_RENDER_PASS_DEPTH_STENCIL_DESC,
+            Flags: D3D12_RENDER_PASS_FLAGS,
+        ) -> (),
+        fn EndRenderPass() -> (),
+    }}
+}
@@ -1,6 +1,12 @@
 use crate::dxgi::Adapter;
-use crate::heap::{CPUDescriptor, DescriptorHeapType, RenderTargetViewDesc};
-use crate::resource::Resource;
+use crate::heap::{
+    CPUDescriptor, ConstantBufferViewDesc, DescriptorHeapType, RenderTargetViewDesc,
+    ShaderResourceViewDesc, UnorderedAccessViewDesc,
+};
+use crate::resource::{
+    AllocationInfo, Heap, HeapFlags, HeapType, Resource, ResourceDesc, ResourceState,
+};
+use crate::root_signature::{RootSignature, RootSignatureDesc};
 use crate::Result;
 
 use bitflags::bitflags;
@@ -71,6 +77,105 @@ impl Device {
         }
     }
 
+    pub fn create_committed_resource(
+        &self,
+        heap_type: HeapType,
+        desc: &ResourceDesc,
+        initial_state: ResourceState,
+    ) -> Result<Resource> {
+        let mut resource: *mut d3d12::ID3D12Resource = ptr::null_mut();
+
+        let heap_properties = d3d12::D3D12_HEAP_PROPERTIES {
+            Type: heap_type as _,
+            CPUPageProperty: d3d12::D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
+            MemoryPoolPreference: d3d12::D3D12_MEMORY_POOL_UNKNOWN,
+            CreationNodeMask: 0,
+            VisibleNodeMask: 0,
+        };
+
+        let hr = unsafe {
+            self.0.CreateCommittedResource(
+                &heap_properties,
+                d3d12::D3D12_HEAP_FLAG_NONE,
+                &desc.0,
+                initial_state.bits(),
+                ptr::null(),
+                &d3d12::ID3D12Resource::uuidof(),
+                &mut resource as *mut *mut _ as *mut *mut _,
+            )
+        };
+        if winerror::SUCCEEDED(hr) {
+            Ok(Resource(unsafe { ComPtr::from_raw(resource) }))
+        } else {
+            Err(hr)
+        }
+    }
+
+    pub fn create_heap(&self, size: u64, heap_type: HeapType, flags: HeapFlags) -> Result<Heap> {
+        let mut heap: *mut d3d12::ID3D12Heap = ptr::null_mut();
+
+        let desc = d3d12::D3D12_HEAP_DESC {
+            SizeInBytes: size,
+            Properties: d3d12::D3D12_HEAP_PROPERTIES {
+                Type: heap_type as _,
+                CPUPageProperty: d3d12::D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
+                MemoryPoolPreference: d3d12::D3D12_MEMORY_POOL_UNKNOWN,
+                CreationNodeMask: 0,
+                VisibleNodeMask: 0,
+            },
+            Alignment: u64::from(d3d12::D3D12_DEFAULT_RESOURCE_PLACEMENT_ALIGNMENT),
+            Flags: flags.bits(),
+        };
+
+        let hr = unsafe {
+            self.0.CreateHeap(
+                &desc,
+                &d3d12::ID3D12Heap::uuidof(),
+                &mut heap as *mut *mut _ as *mut *mut _,
+            )
+        };
+        if winerror::SUCCEEDED(hr) {
+            Ok(Heap(unsafe { ComPtr::from_raw(heap) }))
+        } else {
+            Err(hr)
+        }
+    }
+
+    pub fn create_placed_resource(
+        &self,
+        heap: &Heap,
+        offset: u64,
+        desc: &ResourceDesc,
+        initial_state: ResourceState,
+    ) -> Result<Resource> {
+        let mut resource: *mut d3d12::ID3D12Resource = ptr::null_mut();
+
+        let hr = unsafe {
+            self.0.CreatePlacedResource(
+                heap.0.as_raw(),
+                offset,
+                &desc.0,
+                initial_state.bits(),
+                ptr::null(),
+                &d3d12::ID3D12Resource::uuidof(),
+                &mut resource as *mut *mut _ as *mut *mut _,
+            )
+        };
+        if winerror::SUCCEEDED(hr) {
+            Ok(Resource(unsafe { ComPtr::from_raw(resource) }))
+        } else {
+            Err(hr)
+        }
+    }
+
+    pub fn get_resource_allocation_info(&self, desc: &ResourceDesc) -> AllocationInfo {
+        let info = unsafe { self.0.GetResourceAllocationInfo(0, 1, &desc.0) };
+        AllocationInfo {
+            size: info.SizeInBytes,
+            alignment: info.Alignment,
+        }
+    }
+
     pub fn create_render_target_view(
         &self,
         resource: &Resource,
@@ -83,6 +188,78 @@ impl Device {
         };
     }
 
+    pub fn create_constant_buffer_view(
+        &self,
+        desc: &ConstantBufferViewDesc,
+        descriptor: CPUDescriptor,
+    ) {
+        unsafe { self.0.CreateConstantBufferView(&desc.0, descriptor) };
+    }
+
+    pub fn create_shader_resource_view(
+        &self,
+        resource: &Resource,
+        desc: &ShaderResourceViewDesc,
+        descriptor: CPUDescriptor,
+    ) {
+        unsafe {
+            self.0
+                .CreateShaderResourceView(resource.0.as_raw(), &desc.0, descriptor)
+        };
+    }
+
+    pub fn create_unordered_access_view(
+        &self,
+        resource: &Resource,
+        desc: &UnorderedAccessViewDesc,
+        descriptor: CPUDescriptor,
+    ) {
+        unsafe {
+            self.0.CreateUnorderedAccessView(
+                resource.0.as_raw(),
+                ptr::null_mut(),
+                &desc.0,
+                descriptor,
+            )
+        };
+    }
+
+    /// Copies `count` descriptors starting at `src`, which can't be in a shader-visible heap, to
+    /// the ones starting at `dst`.
+    pub fn copy_descriptors_simple(
+        &self,
+        count: u32,
+        dst: CPUDescriptor,
+        src: CPUDescriptor,
+        descriptor_heap_type: DescriptorHeapType,
+    ) {
+        unsafe {
+            self.0
+                .CopyDescriptorsSimple(count, dst, src, descriptor_heap_type as _)
+        };
+    }
+
+    /// Serializes a root signature description and creates the root signature from it.
+    pub fn create_root_signature(&self, desc: &RootSignatureDesc) -> Result<RootSignature> {
+        let blob = desc.serialize()?;
+        let mut root_signature: *mut d3d12::ID3D12RootSignature = ptr::null_mut();
+
+        let hr = unsafe {
+            self.0.CreateRootSignature(
+                0,
+                blob.GetBufferPointer(),
+                blob.GetBufferSize(),
+                &d3d12::ID3D12RootSignature::uuidof(),
+                &mut root_signature as *mut *mut _ as *mut *mut _,
+            )
+        };
+        if winerror::SUCCEEDED(hr) {
+            Ok(RootSignature(unsafe { ComPtr::from_raw(root_signature) }))
+        } else {
+            Err(hr)
+        }
+    }
+
     pub fn get_descriptor_increment_size(&self, descriptor_heap_type: DescriptorHeapType) -> u32 {
         unsafe {
             self.0
@@ -107,6 +284,16 @@ impl Device {
         }
     }
 
+    /// Returns the reason the device was removed, or `Ok` if it's still operational.
+    pub fn get_device_removed_reason(&self) -> Result<()> {
+        let hr = unsafe { self.0.GetDeviceRemovedReason() };
+        if winerror::SUCCEEDED(hr) {
+            Ok(())
+        } else {
+            Err(hr)
+        }
+    }
+
     pub fn init_info_queue(&self) {
         if let Ok(info_queue) = self.0.cast::<d3d12sdklayers::ID3D12InfoQueue>() {
             unsafe {
@@ -1,9 +1,10 @@
 use crate::device::Device;
 use crate::dxgi::Format;
+use crate::resource::GPUVirtualAddress;
 use crate::Result;
 
 use bitflags::bitflags;
-use winapi::shared::winerror;
+use winapi::shared::{dxgiformat, winerror};
 use winapi::um::d3d12;
 use winapi::Interface;
 use wio::com::ComPtr;
@@ -11,8 +12,12 @@ use wio::com::ComPtr;
 use std::mem;
 use std::ptr;
 
+// D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING, a macro winapi doesn't expand, reading the components
+// in order
+const DEFAULT_SHADER_4_COMPONENT_MAPPING: u32 = 0x1688;
+
 #[repr(u32)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum DescriptorHeapType {
     Rtv = d3d12::D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
     Dsv = d3d12::D3D12_DESCRIPTOR_HEAP_TYPE_DSV,
@@ -23,13 +28,14 @@ pub enum DescriptorHeapType {
 bitflags! {
     pub struct DescriptorHeapFlags: u32 {
         const NONE = d3d12::D3D12_DESCRIPTOR_HEAP_FLAG_NONE;
+        const SHADER_VISIBLE = d3d12::D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE;
     }
 }
 
 pub type CPUDescriptor = d3d12::D3D12_CPU_DESCRIPTOR_HANDLE;
 pub type GPUDescriptor = d3d12::D3D12_GPU_DESCRIPTOR_HANDLE;
 
-pub struct DescriptorHeap(ComPtr<d3d12::ID3D12DescriptorHeap>);
+pub struct DescriptorHeap(pub(crate) ComPtr<d3d12::ID3D12DescriptorHeap>);
 
 impl DescriptorHeap {
     pub fn new(
@@ -71,17 +77,169 @@ impl DescriptorHeap {
     }
 }
 
+impl Clone for DescriptorHeap {
+    fn clone(&self) -> Self {
+        DescriptorHeap(self.0.clone())
+    }
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone)]
+pub enum RtvDimension {
+    Texture1D = d3d12::D3D12_RTV_DIMENSION_TEXTURE1D,
+    Texture2D = d3d12::D3D12_RTV_DIMENSION_TEXTURE2D,
+    Texture2DMs = d3d12::D3D12_RTV_DIMENSION_TEXTURE2DMS,
+    Texture2DArray = d3d12::D3D12_RTV_DIMENSION_TEXTURE2DARRAY,
+    Texture2DMsArray = d3d12::D3D12_RTV_DIMENSION_TEXTURE2DMSARRAY,
+    Texture3D = d3d12::D3D12_RTV_DIMENSION_TEXTURE3D,
+}
+
 //#[repr(transparent)]
 pub struct RenderTargetViewDesc(pub(crate) d3d12::D3D12_RENDER_TARGET_VIEW_DESC);
 
 impl RenderTargetViewDesc {
     pub fn new(format: Format) -> Self {
-        let desc = d3d12::D3D12_RENDER_TARGET_VIEW_DESC {
+        RenderTargetViewDesc::with_dimension(format, RtvDimension::Texture2D)
+    }
+
+    /// Describes a view of the first mip level and the first array layer or depth slice.
+    pub fn with_dimension(format: Format, dimension: RtvDimension) -> Self {
+        let mut desc = d3d12::D3D12_RENDER_TARGET_VIEW_DESC {
             Format: format as _,
-            ViewDimension: d3d12::D3D12_RTV_DIMENSION_TEXTURE2D,
+            ViewDimension: dimension as _,
             ..unsafe { mem::zeroed() }
         };
 
+        // Zeroed views cover no layers, so the sizes need to be set explicitly
+        unsafe {
+            match dimension {
+                RtvDimension::Texture2DArray => desc.u.Texture2DArray_mut().ArraySize = 1,
+                RtvDimension::Texture2DMsArray => desc.u.Texture2DMSArray_mut().ArraySize = 1,
+                RtvDimension::Texture3D => desc.u.Texture3D_mut().WSize = 1,
+                _ => {}
+            }
+        }
+
         RenderTargetViewDesc(desc)
     }
 }
+
+pub struct ConstantBufferViewDesc(pub(crate) d3d12::D3D12_CONSTANT_BUFFER_VIEW_DESC);
+
+impl ConstantBufferViewDesc {
+    /// Describes a view of `size` bytes at `location`, which must be a multiple of 256 as well.
+    pub fn new(location: GPUVirtualAddress, size: u32) -> Self {
+        ConstantBufferViewDesc(d3d12::D3D12_CONSTANT_BUFFER_VIEW_DESC {
+            BufferLocation: location,
+            SizeInBytes: size,
+        })
+    }
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone)]
+pub enum SrvDimension {
+    Texture1D = d3d12::D3D12_SRV_DIMENSION_TEXTURE1D,
+    Texture1DArray = d3d12::D3D12_SRV_DIMENSION_TEXTURE1DARRAY,
+    Texture2D = d3d12::D3D12_SRV_DIMENSION_TEXTURE2D,
+    Texture2DArray = d3d12::D3D12_SRV_DIMENSION_TEXTURE2DARRAY,
+    Texture3D = d3d12::D3D12_SRV_DIMENSION_TEXTURE3D,
+    TextureCube = d3d12::D3D12_SRV_DIMENSION_TEXTURECUBE,
+    TextureCubeArray = d3d12::D3D12_SRV_DIMENSION_TEXTURECUBEARRAY,
+}
+
+pub struct ShaderResourceViewDesc(pub(crate) d3d12::D3D12_SHADER_RESOURCE_VIEW_DESC);
+
+impl ShaderResourceViewDesc {
+    /// Describes a view of all `mip_levels` and `array_size` layers, or cubes for cube dimensions.
+    pub fn texture(
+        format: Format,
+        dimension: SrvDimension,
+        mip_levels: u32,
+        array_size: u32,
+    ) -> Self {
+        let mut desc = d3d12::D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: format as _,
+            ViewDimension: dimension as _,
+            Shader4ComponentMapping: DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            ..unsafe { mem::zeroed() }
+        };
+
+        unsafe {
+            match dimension {
+                SrvDimension::Texture1D => desc.u.Texture1D_mut().MipLevels = mip_levels,
+                SrvDimension::Texture1DArray => {
+                    let view = desc.u.Texture1DArray_mut();
+                    view.MipLevels = mip_levels;
+                    view.ArraySize = array_size;
+                }
+                SrvDimension::Texture2D => desc.u.Texture2D_mut().MipLevels = mip_levels,
+                SrvDimension::Texture2DArray => {
+                    let view = desc.u.Texture2DArray_mut();
+                    view.MipLevels = mip_levels;
+                    view.ArraySize = array_size;
+                }
+                SrvDimension::Texture3D => desc.u.Texture3D_mut().MipLevels = mip_levels,
+                SrvDimension::TextureCube => desc.u.TextureCube_mut().MipLevels = mip_levels,
+                SrvDimension::TextureCubeArray => {
+                    let view = desc.u.TextureCubeArray_mut();
+                    view.MipLevels = mip_levels;
+                    view.NumCubes = array_size;
+                }
+            }
+        }
+
+        ShaderResourceViewDesc(desc)
+    }
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone)]
+pub enum UavDimension {
+    Texture1D = d3d12::D3D12_UAV_DIMENSION_TEXTURE1D,
+    Texture1DArray = d3d12::D3D12_UAV_DIMENSION_TEXTURE1DARRAY,
+    Texture2D = d3d12::D3D12_UAV_DIMENSION_TEXTURE2D,
+    Texture2DArray = d3d12::D3D12_UAV_DIMENSION_TEXTURE2DARRAY,
+    Texture3D = d3d12::D3D12_UAV_DIMENSION_TEXTURE3D,
+}
+
+pub struct UnorderedAccessViewDesc(pub(crate) d3d12::D3D12_UNORDERED_ACCESS_VIEW_DESC);
+
+impl UnorderedAccessViewDesc {
+    /// Describes a raw view of a whole buffer of `size` bytes, a multiple of 4.
+    pub fn raw_buffer(size: u64) -> Self {
+        let mut desc = d3d12::D3D12_UNORDERED_ACCESS_VIEW_DESC {
+            Format: dxgiformat::DXGI_FORMAT_R32_TYPELESS,
+            ViewDimension: d3d12::D3D12_UAV_DIMENSION_BUFFER,
+            ..unsafe { mem::zeroed() }
+        };
+
+        unsafe {
+            let view = desc.u.Buffer_mut();
+            view.NumElements = (size / 4) as _;
+            view.Flags = d3d12::D3D12_BUFFER_UAV_FLAG_RAW;
+        }
+
+        UnorderedAccessViewDesc(desc)
+    }
+
+    /// Describes a view of the first mip level and all `array_size` layers or depth slices.
+    pub fn texture(format: Format, dimension: UavDimension, array_size: u32) -> Self {
+        let mut desc = d3d12::D3D12_UNORDERED_ACCESS_VIEW_DESC {
+            Format: format as _,
+            ViewDimension: dimension as _,
+            ..unsafe { mem::zeroed() }
+        };
+
+        unsafe {
+            match dimension {
+                UavDimension::Texture1DArray => desc.u.Texture1DArray_mut().ArraySize = array_size,
+                UavDimension::Texture2DArray => desc.u.Texture2DArray_mut().ArraySize = array_size,
+                UavDimension::Texture3D => desc.u.Texture3D_mut().WSize = array_size,
+                _ => {}
+            }
+        }
+
+        UnorderedAccessViewDesc(desc)
+    }
+}
@@ -1,3 +1,5 @@
+#![cfg(windows)]
+
 use winapi::shared::winerror;
 
 pub type Result<T> = std::result::Result<T, winerror::HRESULT>;
@@ -10,4 +12,5 @@ pub mod dxgi;
 pub mod heap;
 pub mod queue;
 pub mod resource;
+pub mod root_signature;
 pub mod sync;
@@ -1,17 +1,193 @@
-use bitflags::bitflags;
+use crate::dxgi::Format;
+use crate::Result;
 
+use bitflags::bitflags;
+use winapi::shared::{dxgiformat, dxgitype, winerror};
 use winapi::um::d3d12;
 use wio::com::ComPtr;
 
+use std::ops::Range;
+use std::ptr;
+
 bitflags! {
     pub struct ResourceState: u32 {
-        const PRESENT = d3d12::D3D12_RESOURCE_STATE_PRESENT;
+        const COMMON = d3d12::D3D12_RESOURCE_STATE_COMMON;
+        const VERTEX_AND_CONSTANT_BUFFER = d3d12::D3D12_RESOURCE_STATE_VERTEX_AND_CONSTANT_BUFFER;
+        const INDEX_BUFFER = d3d12::D3D12_RESOURCE_STATE_INDEX_BUFFER;
         const RENDER_TARGET = d3d12::D3D12_RESOURCE_STATE_RENDER_TARGET;
+        const UNORDERED_ACCESS = d3d12::D3D12_RESOURCE_STATE_UNORDERED_ACCESS;
+        const DEPTH_WRITE = d3d12::D3D12_RESOURCE_STATE_DEPTH_WRITE;
+        const DEPTH_READ = d3d12::D3D12_RESOURCE_STATE_DEPTH_READ;
+        const NON_PIXEL_SHADER_RESOURCE = d3d12::D3D12_RESOURCE_STATE_NON_PIXEL_SHADER_RESOURCE;
+        const PIXEL_SHADER_RESOURCE = d3d12::D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE;
+        const INDIRECT_ARGUMENT = d3d12::D3D12_RESOURCE_STATE_INDIRECT_ARGUMENT;
+        const COPY_DEST = d3d12::D3D12_RESOURCE_STATE_COPY_DEST;
+        const COPY_SOURCE = d3d12::D3D12_RESOURCE_STATE_COPY_SOURCE;
+        const RESOLVE_DEST = d3d12::D3D12_RESOURCE_STATE_RESOLVE_DEST;
+        const RESOLVE_SOURCE = d3d12::D3D12_RESOURCE_STATE_RESOLVE_SOURCE;
+        const GENERIC_READ = d3d12::D3D12_RESOURCE_STATE_GENERIC_READ;
+        const PRESENT = d3d12::D3D12_RESOURCE_STATE_PRESENT;
+    }
+}
+
+bitflags! {
+    pub struct ResourceFlags: u32 {
+        const NONE = d3d12::D3D12_RESOURCE_FLAG_NONE;
+        const ALLOW_RENDER_TARGET = d3d12::D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET;
+        const ALLOW_DEPTH_STENCIL = d3d12::D3D12_RESOURCE_FLAG_ALLOW_DEPTH_STENCIL;
+        const ALLOW_UNORDERED_ACCESS = d3d12::D3D12_RESOURCE_FLAG_ALLOW_UNORDERED_ACCESS;
+        const DENY_SHADER_RESOURCE = d3d12::D3D12_RESOURCE_FLAG_DENY_SHADER_RESOURCE;
+    }
+}
+
+bitflags! {
+    pub struct HeapFlags: u32 {
+        const NONE = d3d12::D3D12_HEAP_FLAG_NONE;
+        const ALLOW_ALL_BUFFERS_AND_TEXTURES = d3d12::D3D12_HEAP_FLAG_ALLOW_ALL_BUFFERS_AND_TEXTURES;
+        const ALLOW_ONLY_BUFFERS = d3d12::D3D12_HEAP_FLAG_ALLOW_ONLY_BUFFERS;
+        const ALLOW_ONLY_NON_RT_DS_TEXTURES = d3d12::D3D12_HEAP_FLAG_ALLOW_ONLY_NON_RT_DS_TEXTURES;
+        const ALLOW_ONLY_RT_DS_TEXTURES = d3d12::D3D12_HEAP_FLAG_ALLOW_ONLY_RT_DS_TEXTURES;
+    }
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone)]
+pub enum HeapType {
+    Default = d3d12::D3D12_HEAP_TYPE_DEFAULT,
+    Upload = d3d12::D3D12_HEAP_TYPE_UPLOAD,
+    Readback = d3d12::D3D12_HEAP_TYPE_READBACK,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone)]
+pub enum ResourceDimension {
+    Texture1D = d3d12::D3D12_RESOURCE_DIMENSION_TEXTURE1D,
+    Texture2D = d3d12::D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+    Texture3D = d3d12::D3D12_RESOURCE_DIMENSION_TEXTURE3D,
+}
+
+pub struct ResourceDesc(pub(crate) d3d12::D3D12_RESOURCE_DESC);
+
+impl ResourceDesc {
+    pub fn buffer(size: u64, flags: ResourceFlags) -> Self {
+        let desc = d3d12::D3D12_RESOURCE_DESC {
+            Dimension: d3d12::D3D12_RESOURCE_DIMENSION_BUFFER,
+            Alignment: 0,
+            Width: size,
+            Height: 1,
+            DepthOrArraySize: 1,
+            MipLevels: 1,
+            Format: dxgiformat::DXGI_FORMAT_UNKNOWN,
+            SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Layout: d3d12::D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+            Flags: flags.bits(),
+        };
+
+        ResourceDesc(desc)
+    }
+
+    /// Describes a texture, `depth_or_array_size` is the depth of 3D textures and the layer count otherwise.
+    #[allow(clippy::too_many_arguments)]
+    pub fn texture(
+        dimension: ResourceDimension,
+        width: u64,
+        height: u32,
+        depth_or_array_size: u16,
+        mip_levels: u16,
+        format: Format,
+        sample_count: u32,
+        flags: ResourceFlags,
+    ) -> Self {
+        let desc = d3d12::D3D12_RESOURCE_DESC {
+            Dimension: dimension as _,
+            Alignment: 0,
+            Width: width,
+            Height: height,
+            DepthOrArraySize: depth_or_array_size,
+            MipLevels: mip_levels,
+            Format: format as _,
+            SampleDesc: dxgitype::DXGI_SAMPLE_DESC {
+                Count: sample_count,
+                Quality: 0,
+            },
+            Layout: d3d12::D3D12_TEXTURE_LAYOUT_UNKNOWN,
+            Flags: flags.bits(),
+        };
+
+        ResourceDesc(desc)
+    }
+}
+
+/// Size and alignment a resource takes in a heap.
+#[derive(Copy, Clone, Debug)]
+pub struct AllocationInfo {
+    pub size: u64,
+    pub alignment: u64,
+}
+
+pub struct Heap(pub(crate) ComPtr<d3d12::ID3D12Heap>);
+
+impl Clone for Heap {
+    fn clone(&self) -> Self {
+        Heap(self.0.clone())
     }
 }
 
+pub type GPUVirtualAddress = d3d12::D3D12_GPU_VIRTUAL_ADDRESS;
+
 pub struct Resource(pub(crate) ComPtr<d3d12::ID3D12Resource>);
 
+impl Resource {
+    /// Address of the start of a buffer, for views and root arguments.
+    pub fn get_gpu_virtual_address(&self) -> GPUVirtualAddress {
+        unsafe { self.0.GetGPUVirtualAddress() }
+    }
+
+    /// Maps a subresource, `read_range` is the range the CPU may read, `None` meaning all of it.
+    pub fn map(&self, subresource: u32, read_range: Option<Range<usize>>) -> Result<*mut u8> {
+        let range = read_range.map(|range| d3d12::D3D12_RANGE {
+            Begin: range.start,
+            End: range.end,
+        });
+        let mut data: *mut u8 = ptr::null_mut();
+
+        let hr = unsafe {
+            self.0.Map(
+                subresource,
+                range
+                    .as_ref()
+                    .map_or(ptr::null(), |range| range as *const _),
+                &mut data as *mut *mut _ as *mut *mut _,
+            )
+        };
+        if winerror::SUCCEEDED(hr) {
+            Ok(data)
+        } else {
+            Err(hr)
+        }
+    }
+
+    /// Unmaps a subresource, `written_range` is the range the CPU wrote to, `None` meaning all of it.
+    pub fn unmap(&self, subresource: u32, written_range: Option<Range<usize>>) {
+        let range = written_range.map(|range| d3d12::D3D12_RANGE {
+            Begin: range.start,
+            End: range.end,
+        });
+
+        unsafe {
+            self.0.Unmap(
+                subresource,
+                range
+                    .as_ref()
+                    .map_or(ptr::null(), |range| range as *const _),
+            )
+        };
+    }
+}
+
 impl Clone for Resource {
     fn clone(&self) -> Self {
         Resource(self.0.clone())
@@ -0,0 +1,169 @@
+use crate::Result;
+
+use log::error;
+use winapi::shared::winerror;
+use winapi::um::{d3d12, d3dcommon};
+use wio::com::ComPtr;
+
+use std::mem;
+use std::ptr;
+use std::slice;
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DescriptorRangeType {
+    Srv = d3d12::D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+    Uav = d3d12::D3D12_DESCRIPTOR_RANGE_TYPE_UAV,
+    Cbv = d3d12::D3D12_DESCRIPTOR_RANGE_TYPE_CBV,
+    Sampler = d3d12::D3D12_DESCRIPTOR_RANGE_TYPE_SAMPLER,
+}
+
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShaderVisibility {
+    All = d3d12::D3D12_SHADER_VISIBILITY_ALL,
+    Vertex = d3d12::D3D12_SHADER_VISIBILITY_VERTEX,
+    Pixel = d3d12::D3D12_SHADER_VISIBILITY_PIXEL,
+}
+
+/// Descriptors of a table seen by shaders at consecutive registers starting at `base_register`.
+#[derive(Copy, Clone, Debug)]
+pub struct DescriptorRange {
+    pub ty: DescriptorRangeType,
+    pub count: u32,
+    pub base_register: u32,
+    pub register_space: u32,
+    pub offset_in_table: u32,
+}
+
+#[derive(Clone, Debug)]
+pub enum RootParameter {
+    DescriptorTable {
+        ranges: Vec<DescriptorRange>,
+        visibility: ShaderVisibility,
+    },
+    /// 32-bit values stored in the root signature, seen by shaders as a constant buffer.
+    Constants {
+        count: u32,
+        register: u32,
+        register_space: u32,
+        visibility: ShaderVisibility,
+    },
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RootSignatureDesc {
+    pub parameters: Vec<RootParameter>,
+}
+
+impl RootSignatureDesc {
+    /// Serializes the description as a version 1.0 root signature allowing input layouts.
+    pub(crate) fn serialize(&self) -> Result<ComPtr<d3dcommon::ID3DBlob>> {
+        // The native parameters point into the ranges, which need to outlive serialization
+        let ranges = self
+            .parameters
+            .iter()
+            .map(|parameter| match parameter {
+                RootParameter::DescriptorTable { ranges, .. } => ranges
+                    .iter()
+                    .map(|range| d3d12::D3D12_DESCRIPTOR_RANGE {
+                        RangeType: range.ty as _,
+                        NumDescriptors: range.count,
+                        BaseShaderRegister: range.base_register,
+                        RegisterSpace: range.register_space,
+                        OffsetInDescriptorsFromTableStart: range.offset_in_table,
+                    })
+                    .collect::<Vec<_>>(),
+                RootParameter::Constants { .. } => Vec::new(),
+            })
+            .collect::<Vec<_>>();
+        let parameters = self
+            .parameters
+            .iter()
+            .zip(&ranges)
+            .map(|(parameter, ranges)| match parameter {
+                RootParameter::DescriptorTable { visibility, .. } => {
+                    let mut native = d3d12::D3D12_ROOT_PARAMETER {
+                        ParameterType: d3d12::D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+                        ShaderVisibility: *visibility as _,
+                        ..unsafe { mem::zeroed() }
+                    };
+                    let table = unsafe { native.u.DescriptorTable_mut() };
+                    table.NumDescriptorRanges = ranges.len() as _;
+                    table.pDescriptorRanges = ranges.as_ptr();
+                    native
+                }
+                RootParameter::Constants {
+                    count,
+                    register,
+                    register_space,
+                    visibility,
+                } => {
+                    let mut native = d3d12::D3D12_ROOT_PARAMETER {
+                        ParameterType: d3d12::D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS,
+                        ShaderVisibility: *visibility as _,
+                        ..unsafe { mem::zeroed() }
+                    };
+                    let constants = unsafe { native.u.Constants_mut() };
+                    constants.ShaderRegister = *register;
+                    constants.RegisterSpace = *register_space;
+                    constants.Num32BitValues = *count;
+                    native
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let desc = d3d12::D3D12_ROOT_SIGNATURE_DESC {
+            NumParameters: parameters.len() as _,
+            pParameters: parameters.as_ptr(),
+            NumStaticSamplers: 0,
+            pStaticSamplers: ptr::null(),
+            Flags: d3d12::D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT,
+        };
+
+        let mut blob: *mut d3dcommon::ID3DBlob = ptr::null_mut();
+        let mut error_blob: *mut d3dcommon::ID3DBlob = ptr::null_mut();
+        let hr = unsafe {
+            d3d12::D3D12SerializeRootSignature(
+                &desc,
+                d3d12::D3D_ROOT_SIGNATURE_VERSION_1,
+                &mut blob,
+                &mut error_blob,
+            )
+        };
+        if !error_blob.is_null() {
+            let error_blob = unsafe { ComPtr::from_raw(error_blob) };
+            let message = unsafe {
+                slice::from_raw_parts(
+                    error_blob.GetBufferPointer() as *const u8,
+                    error_blob.GetBufferSize(),
+                )
+            };
+            error!(
+                "Root signature serialization failed: {}",
+                String::from_utf8_lossy(message)
+            );
+        }
+        if winerror::SUCCEEDED(hr) {
+            Ok(unsafe { ComPtr::from_raw(blob) })
+        } else {
+            Err(hr)
+        }
+    }
+}
+
+pub struct RootSignature(pub(crate) ComPtr<d3d12::ID3D12RootSignature>);
+
+impl Clone for RootSignature {
+    fn clone(&self) -> Self {
+        RootSignature(self.0.clone())
+    }
+}
+
+impl PartialEq for RootSignature {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_raw() == other.0.as_raw()
+    }
+}
+
+impl Eq for RootSignature {}
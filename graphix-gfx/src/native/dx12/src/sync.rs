@@ -108,7 +108,15 @@ impl Fence {
             return Ok(true);
         }
 
+        // Events wait in milliseconds, round up so that short timeouts don't turn into polls
+        let timeout_ms = if timeout_ns == u64::max_value() {
+            winbase::INFINITE
+        } else {
+            let timeout_ms = timeout_ns.saturating_add(999_999) / 1_000_000;
+            timeout_ms.min(u64::from(winbase::INFINITE - 1)) as u32
+        };
+
         self.set_event_on_completion(event, value)?;
-        Ok(event.wait(timeout_ns as _))
+        Ok(event.wait(timeout_ms))
     }
 }
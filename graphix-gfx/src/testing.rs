@@ -0,0 +1,34 @@
+//! Setup shared by the unit tests running on the software backend.
+
+use crate::backend::software::{Buffer, Device};
+use crate::hal::{
+    BufferUsage, Device as _, Extent, Format, ImageDesc, ImageKind, ImageUsage, MemoryType,
+};
+
+pub(crate) fn create_device() -> Device {
+    Device::new()
+}
+
+/// Creates a buffer in device local memory.
+pub(crate) fn create_buffer(device: &Device, size: u64, usage: BufferUsage) -> Buffer {
+    device
+        .create_buffer(size, usage, MemoryType::DeviceLocal)
+        .unwrap()
+}
+
+/// Description of a square 2D `Rgba8Unorm` image without mip levels.
+pub(crate) fn image_desc(width: u32, usage: ImageUsage) -> ImageDesc {
+    ImageDesc {
+        kind: ImageKind::D2,
+        extent: Extent {
+            width,
+            height: width,
+            depth: 1,
+        },
+        mip_levels: 1,
+        array_layers: 1,
+        samples: 1,
+        format: Format::Rgba8Unorm,
+        usage,
+    }
+}
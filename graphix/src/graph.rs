@@ -0,0 +1,1083 @@
+use self::placement::Allocation;
+
+use graphix_gfx::hal::{
+    AttachmentDesc, Backend, Barrier, BarrierResource, BufferUsage, ClearColor, CommandBuffer,
+    Device, Features, Format, HeapUsage, ImageDesc, ImageUsage, LoadOp, MemoryRequirements,
+    RenderPassDesc, ResourceState, Result, StoreOp,
+};
+
+use std::ops::Range;
+
+pub use self::pool::TransientPool;
+
+mod placement;
+mod pool;
+
+/// Handle to a resource of a `RenderGraph`.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub struct ResourceId(usize);
+
+enum ResourceKind<'a, B: Backend> {
+    /// Placed in the memory of the transient pool for the frame, with the usage of its
+    /// description extended to what the passes do with it.
+    TransientImage(ImageDesc),
+    /// Placed like transient images, with its usage extended the same way.
+    TransientBuffer { size: u64, usage: BufferUsage },
+    Image {
+        image: &'a B::Image,
+        format: Format,
+        final_state: ResourceState,
+    },
+    FrameBuffer {
+        framebuffer: &'a B::FrameBuffer,
+        format: Format,
+        final_state: ResourceState,
+    },
+    Buffer {
+        buffer: &'a B::Buffer,
+        final_state: ResourceState,
+    },
+}
+
+struct Resource<'a, B: Backend> {
+    name: &'a str,
+    kind: ResourceKind<'a, B>,
+}
+
+impl<'a, B: Backend> Resource<'a, B> {
+    fn is_imported(&self) -> bool {
+        match self.kind {
+            ResourceKind::TransientImage(_) | ResourceKind::TransientBuffer { .. } => false,
+            ResourceKind::Image { .. }
+            | ResourceKind::FrameBuffer { .. }
+            | ResourceKind::Buffer { .. } => true,
+        }
+    }
+
+    fn is_buffer(&self) -> bool {
+        matches!(
+            self.kind,
+            ResourceKind::TransientBuffer { .. } | ResourceKind::Buffer { .. }
+        )
+    }
+
+    fn get_format(&self) -> Format {
+        match self.kind {
+            ResourceKind::TransientImage(desc) => desc.format,
+            ResourceKind::Image { format, .. } | ResourceKind::FrameBuffer { format, .. } => format,
+            ResourceKind::TransientBuffer { .. } | ResourceKind::Buffer { .. } => {
+                unreachable!("Buffers have no format")
+            }
+        }
+    }
+
+    fn get_final_state(&self) -> Option<ResourceState> {
+        match self.kind {
+            ResourceKind::TransientImage(_) | ResourceKind::TransientBuffer { .. } => None,
+            ResourceKind::Image { final_state, .. }
+            | ResourceKind::FrameBuffer { final_state, .. }
+            | ResourceKind::Buffer { final_state, .. } => Some(final_state),
+        }
+    }
+}
+
+struct ColorAttachment {
+    resource: ResourceId,
+    clear_color: Option<ClearColor>,
+}
+
+type ExecuteFn<'a, B> = Box<dyn FnOnce(&<B as Backend>::CommandBuffer, &PassResources<B>) + 'a>;
+
+struct Pass<'a, B: Backend> {
+    reads: Vec<(ResourceId, ResourceState)>,
+    writes: Vec<(ResourceId, ResourceState)>,
+    color_attachments: Vec<ColorAttachment>,
+    has_side_effects: bool,
+    execute: ExecuteFn<'a, B>,
+}
+
+impl<'a, B: Backend> Pass<'a, B> {
+    fn uses(&self, resource: ResourceId) -> bool {
+        self.get_state(resource).is_some()
+    }
+
+    fn writes(&self, resource: ResourceId) -> bool {
+        self.writes.iter().any(|&(written, _)| written == resource)
+            || self.get_attachment(resource).is_some()
+    }
+
+    fn clears(&self, resource: ResourceId) -> bool {
+        self.get_attachment(resource)
+            .is_some_and(|attachment| attachment.clear_color.is_some())
+    }
+
+    fn get_attachment(&self, resource: ResourceId) -> Option<&ColorAttachment> {
+        self.color_attachments
+            .iter()
+            .find(|attachment| attachment.resource == resource)
+    }
+
+    /// State the pass uses `resource` in, all the states it's read and written in combined.
+    fn get_state(&self, resource: ResourceId) -> Option<ResourceState> {
+        if self.get_attachment(resource).is_some() {
+            return Some(ResourceState::RENDER_TARGET);
+        }
+
+        self.reads
+            .iter()
+            .chain(&self.writes)
+            .filter(|&&(used, _)| used == resource)
+            .map(|&(_, state)| state)
+            .fold(None, |combined, state| {
+                Some(combined.unwrap_or_else(ResourceState::empty) | state)
+            })
+    }
+
+    /// Resources used outside of render passes, each one once.
+    fn get_accessed_resources(&self) -> Vec<ResourceId> {
+        let mut resources: Vec<ResourceId> = Vec::new();
+        for &(resource, _) in self.reads.iter().chain(&self.writes) {
+            if !resources.contains(&resource) {
+                resources.push(resource);
+            }
+        }
+        resources
+    }
+}
+
+/// A pass of the graph as it's recorded, after culling.
+struct CompiledPass {
+    index: usize,
+    /// Transitions of the resources used outside of render passes.
+    barriers: Vec<(ResourceId, ResourceState)>,
+    attachments: Vec<ResourceId>,
+    render_pass: Option<RenderPassDesc>,
+    clear_values: Vec<ClearColor>,
+}
+
+/// How the passes left use a resource.
+struct ResourceUsage {
+    /// All the states the resource is used in combined.
+    states: ResourceState,
+    /// Positions of the first and past the last passes using the resource, among the passes left.
+    lifetime: Range<usize>,
+}
+
+struct CompiledGraph {
+    passes: Vec<CompiledPass>,
+    /// Transitions of imported images and buffers to their final states, after the last pass.
+    final_barriers: Vec<(ResourceId, ResourceState)>,
+    /// Usage of each resource by the passes left, `None` for unused resources.
+    usages: Vec<Option<ResourceUsage>>,
+}
+
+/// Where the transient resources of a graph live for the frame.
+struct TransientMemory {
+    /// Index of the pooled image or buffer backing each transient resource used.
+    indices: Vec<Option<usize>>,
+    /// Resource of the graph whose memory each transient resource takes over, if any.
+    aliased: Vec<Option<ResourceId>>,
+    report: TransientMemoryReport,
+}
+
+/// Memory taken by the transient resources of a graph, as returned by `RenderGraph::execute`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransientMemoryReport {
+    /// Memory the transient resources would take without aliasing, in bytes.
+    pub total_size: u64,
+    /// Memory taken by the transient resources alive at the same time, at the pass where it's
+    /// the highest. No placement can use less memory than that.
+    pub peak_size: u64,
+    /// Memory the transient resources are placed in, over all heaps.
+    pub heap_size: u64,
+}
+
+/// Images and buffers of the graph, as seen by passes while they're recorded.
+pub struct PassResources<'b, B: Backend> {
+    images: Vec<Option<&'b B::Image>>,
+    buffers: Vec<Option<&'b B::Buffer>>,
+}
+
+impl<'b, B: Backend> PassResources<'b, B> {
+    /// Image backing `resource`, which must be a transient or imported image used by the pass.
+    pub fn get_image(&self, resource: ResourceId) -> &B::Image {
+        self.images[resource.0].expect("Resource isn't an image used by the graph")
+    }
+
+    /// Buffer backing `resource`, which must be a transient or imported buffer used by the pass.
+    pub fn get_buffer(&self, resource: ResourceId) -> &B::Buffer {
+        self.buffers[resource.0].expect("Resource isn't a buffer used by the graph")
+    }
+}
+
+/// The passes making up a frame and the resources they read and write.
+///
+/// Passes are recorded in the order they're added, and those whose results aren't used by an
+/// imported resource or by a pass with side effects are culled. The transitions between passes
+/// are derived from the states resources are used in: render passes leave their attachments in
+/// the state of their next use, and imported resources end up in their final state. Load and store
+/// ops are derived too, attachments being loaded only if written before and stored only if used
+/// after.
+///
+/// Transient images and buffers live for the frame only, from the first pass using them to the
+/// last one. They're placed in the heaps of a `TransientPool`, those whose lifetimes don't overlap
+/// sharing memory, with aliasing barriers handing the memory over from one to the next.
+pub struct RenderGraph<'a, B: Backend> {
+    resources: Vec<Resource<'a, B>>,
+    passes: Vec<Pass<'a, B>>,
+}
+
+impl<'a, B: Backend> RenderGraph<'a, B> {
+    pub fn new() -> Self {
+        RenderGraph {
+            resources: Vec::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    /// Declares an image living for the frame only. Its contents are undefined until a pass
+    /// writes it.
+    pub fn create_image(&mut self, name: &'a str, desc: ImageDesc) -> ResourceId {
+        self.add_resource(name, ResourceKind::TransientImage(desc))
+    }
+
+    /// Declares a buffer living for the frame only. Its contents are undefined until a pass
+    /// writes it.
+    pub fn create_buffer(&mut self, name: &'a str, size: u64, usage: BufferUsage) -> ResourceId {
+        self.add_resource(name, ResourceKind::TransientBuffer { size, usage })
+    }
+
+    /// Declares an image created outside the graph, left in `final_state` once the graph is
+    /// executed. `format` is only used when the image is rendered to.
+    pub fn import_image(
+        &mut self,
+        name: &'a str,
+        image: &'a B::Image,
+        format: Format,
+        final_state: ResourceState,
+    ) -> ResourceId {
+        self.add_resource(
+            name,
+            ResourceKind::Image {
+                image,
+                format,
+                final_state,
+            },
+        )
+    }
+
+    /// Declares a buffer created outside the graph, left in `final_state` once the graph is
+    /// executed.
+    pub fn import_buffer(
+        &mut self,
+        name: &'a str,
+        buffer: &'a B::Buffer,
+        final_state: ResourceState,
+    ) -> ResourceId {
+        self.add_resource(
+            name,
+            ResourceKind::Buffer {
+                buffer,
+                final_state,
+            },
+        )
+    }
+
+    /// Declares a framebuffer with a single attachment created outside the graph, e.g. a back
+    /// buffer, left in `final_state` once the graph is executed. Framebuffers can only be used as
+    /// the sole color attachment of a pass.
+    pub fn import_framebuffer(
+        &mut self,
+        name: &'a str,
+        framebuffer: &'a B::FrameBuffer,
+        format: Format,
+        final_state: ResourceState,
+    ) -> ResourceId {
+        self.add_resource(
+            name,
+            ResourceKind::FrameBuffer {
+                framebuffer,
+                format,
+                final_state,
+            },
+        )
+    }
+
+    /// Starts declaring a pass, added to the graph by `PassBuilder::execute`.
+    pub fn add_pass<'g>(&'g mut self, name: &'a str) -> PassBuilder<'g, 'a, B> {
+        PassBuilder {
+            graph: self,
+            name,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            color_attachments: Vec::new(),
+            has_side_effects: false,
+        }
+    }
+
+    /// Records the passes left after culling into `command_buffer`, which must be recording, and
+    /// reports the memory taken by the transient resources.
+    pub fn execute(
+        self,
+        device: &B::Device,
+        pool: &mut TransientPool<B>,
+        command_buffer: &B::CommandBuffer,
+    ) -> Result<TransientMemoryReport> {
+        let result = self.record(device, pool, command_buffer);
+        pool.release_resources();
+        result
+    }
+
+    fn add_resource(&mut self, name: &'a str, kind: ResourceKind<'a, B>) -> ResourceId {
+        self.resources.push(Resource { name, kind });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    fn record(
+        self,
+        device: &B::Device,
+        pool: &mut TransientPool<B>,
+        command_buffer: &B::CommandBuffer,
+    ) -> Result<TransientMemoryReport> {
+        let compiled = self.compile();
+        let memory = self.place_transients(device, pool, &compiled)?;
+
+        let render_pass_indices = compiled
+            .passes
+            .iter()
+            .map(|pass| {
+                pass.render_pass
+                    .as_ref()
+                    .map(|desc| pool.acquire_render_pass(device, desc))
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let pool = &*pool;
+        let resources = &self.resources;
+        let pass_resources = PassResources {
+            images: resources
+                .iter()
+                .zip(&memory.indices)
+                .map(|(resource, &index)| match resource.kind {
+                    ResourceKind::TransientImage(_) => index.map(|index| pool.get_image(index)),
+                    ResourceKind::Image { image, .. } => Some(image),
+                    _ => None,
+                })
+                .collect(),
+            buffers: resources
+                .iter()
+                .zip(&memory.indices)
+                .map(|(resource, &index)| match resource.kind {
+                    ResourceKind::TransientBuffer { .. } => {
+                        index.map(|index| pool.get_buffer(index))
+                    }
+                    ResourceKind::Buffer { buffer, .. } => Some(buffer),
+                    _ => None,
+                })
+                .collect(),
+        };
+        let get_barrier_resource = |resource: ResourceId| {
+            if resources[resource.0].is_buffer() {
+                BarrierResource::Buffer(pass_resources.get_buffer(resource))
+            } else {
+                BarrierResource::Image(pass_resources.get_image(resource))
+            }
+        };
+        let insert_barriers =
+            |aliasing: &[ResourceId], transitions: &[(ResourceId, ResourceState)]| {
+                if aliasing.is_empty() && transitions.is_empty() {
+                    return;
+                }
+                // Every transient resource takes its memory over on first use, as it may have
+                // been used by another resource last frame even if not by this graph
+                let aliasing = aliasing.iter().map(|&resource| Barrier::Aliasing {
+                    before: memory.aliased[resource.0].map(get_barrier_resource),
+                    after: Some(get_barrier_resource(resource)),
+                });
+                let transitions = transitions.iter().map(|&(resource, state)| {
+                    match get_barrier_resource(resource) {
+                        BarrierResource::Buffer(buffer) => Barrier::Buffer { buffer, state },
+                        BarrierResource::Image(image) => Barrier::Image {
+                            image,
+                            range: None,
+                            state,
+                        },
+                    }
+                });
+                command_buffer.insert_barriers(&aliasing.chain(transitions).collect::<Vec<_>>());
+            };
+
+        let mut executes: Vec<_> = self
+            .passes
+            .into_iter()
+            .map(|pass| Some(pass.execute))
+            .collect();
+        for (position, (compiled_pass, render_pass_index)) in
+            compiled.passes.iter().zip(render_pass_indices).enumerate()
+        {
+            let starting: Vec<ResourceId> = (0..resources.len())
+                .filter(|&index| {
+                    memory.indices[index].is_some()
+                        && compiled.usages[index]
+                            .as_ref()
+                            .is_some_and(|usage| usage.lifetime.start == position)
+                })
+                .map(ResourceId)
+                .collect();
+            insert_barriers(&starting, &compiled_pass.barriers);
+
+            let execute = executes[compiled_pass.index].take().unwrap();
+            match render_pass_index {
+                Some(render_pass_index) => {
+                    let created_framebuffer;
+                    let framebuffer = match resources[compiled_pass.attachments[0].0].kind {
+                        ResourceKind::FrameBuffer { framebuffer, .. } => framebuffer,
+                        _ => {
+                            let attachments = compiled_pass
+                                .attachments
+                                .iter()
+                                .map(|&resource| pass_resources.get_image(resource))
+                                .collect::<Vec<_>>();
+                            created_framebuffer = device.create_framebuffer(&attachments)?;
+                            &created_framebuffer
+                        }
+                    };
+
+                    command_buffer.begin_render_pass(
+                        pool.get_render_pass(render_pass_index),
+                        framebuffer,
+                        &compiled_pass.clear_values,
+                    );
+                    execute(command_buffer, &pass_resources);
+                    command_buffer.end_render_pass();
+                }
+                None => execute(command_buffer, &pass_resources),
+            }
+        }
+
+        insert_barriers(&[], &compiled.final_barriers);
+        Ok(memory.report)
+    }
+
+    /// Places the transient resources used in the heaps of `pool`, one heap per kind of resource
+    /// unless the device supports mixing them.
+    fn place_transients(
+        &self,
+        device: &B::Device,
+        pool: &mut TransientPool<B>,
+        compiled: &CompiledGraph,
+    ) -> Result<TransientMemory> {
+        let is_mixed = device.features().contains(Features::MIXED_RESOURCE_HEAPS);
+
+        let mut report = TransientMemoryReport::default();
+        let mut requirements: Vec<Option<MemoryRequirements>> = vec![None; self.resources.len()];
+        let mut groups: Vec<(HeapUsage, Vec<ResourceId>)> = Vec::new();
+        for (index, resource) in self.resources.iter().enumerate() {
+            let states = match compiled.usages[index] {
+                Some(ref usage) => usage.states,
+                None => continue,
+            };
+            let (resource_requirements, heap_usage) = match resource.kind {
+                ResourceKind::TransientImage(desc) => {
+                    let desc = get_transient_image_desc(desc, states);
+                    (
+                        pool.get_image_requirements(device, &desc)?,
+                        HeapUsage::from_image_usage(desc.usage),
+                    )
+                }
+                ResourceKind::TransientBuffer { size, usage } => (
+                    pool.get_buffer_requirements(device, size, usage | get_buffer_usage(states))?,
+                    HeapUsage::BUFFERS,
+                ),
+                _ => continue,
+            };
+
+            let heap_usage = if is_mixed {
+                HeapUsage::all()
+            } else {
+                heap_usage
+            };
+            match groups.iter_mut().find(|(usage, _)| *usage == heap_usage) {
+                Some((_, group)) => group.push(ResourceId(index)),
+                None => groups.push((heap_usage, vec![ResourceId(index)])),
+            }
+            requirements[index] = Some(resource_requirements);
+            report.total_size += resource_requirements.size;
+        }
+
+        let get_lifetime = |resource: ResourceId| {
+            compiled.usages[resource.0]
+                .as_ref()
+                .map(|usage| usage.lifetime.clone())
+                .unwrap()
+        };
+        report.peak_size = (0..compiled.passes.len())
+            .map(|position| {
+                (0..self.resources.len())
+                    .filter(|&index| {
+                        requirements[index].is_some()
+                            && get_lifetime(ResourceId(index)).contains(&position)
+                    })
+                    .map(|index| requirements[index].unwrap().size)
+                    .sum()
+            })
+            .max()
+            .unwrap_or(0);
+
+        let mut indices = vec![None; self.resources.len()];
+        let mut aliased = vec![None; self.resources.len()];
+        for (heap_usage, group) in groups {
+            let allocations: Vec<Allocation> = group
+                .iter()
+                .map(|&resource| Allocation {
+                    requirements: requirements[resource.0].unwrap(),
+                    lifetime: get_lifetime(resource),
+                })
+                .collect();
+            let (offsets, heap_size) = placement::place(&allocations);
+            let heap = pool.acquire_heap(device, heap_usage, heap_size)?;
+            report.heap_size += heap_size;
+
+            for (position, &resource) in group.iter().enumerate() {
+                let offset = offsets[position];
+                indices[resource.0] = Some(match self.resources[resource.0].kind {
+                    ResourceKind::TransientImage(desc) => {
+                        let states = compiled.usages[resource.0].as_ref().unwrap().states;
+                        pool.acquire_image(
+                            device,
+                            heap,
+                            offset,
+                            &get_transient_image_desc(desc, states),
+                        )?
+                    }
+                    ResourceKind::TransientBuffer { size, usage } => {
+                        let states = compiled.usages[resource.0].as_ref().unwrap().states;
+                        pool.acquire_buffer(
+                            device,
+                            heap,
+                            offset,
+                            size,
+                            usage | get_buffer_usage(states),
+                        )?
+                    }
+                    _ => unreachable!(),
+                });
+
+                // Resources sharing memory are never alive at the same time, the one handing it
+                // over is the last to die before this one starts
+                let end = offset + allocations[position].requirements.size;
+                let start = allocations[position].lifetime.start;
+                aliased[resource.0] = (0..group.len())
+                    .filter(|&other| {
+                        offsets[other] < end
+                            && offset < offsets[other] + allocations[other].requirements.size
+                            && allocations[other].lifetime.end <= start
+                    })
+                    .max_by_key(|&other| allocations[other].lifetime.end)
+                    .map(|other| group[other]);
+            }
+        }
+
+        Ok(TransientMemory {
+            indices,
+            aliased,
+            report,
+        })
+    }
+
+    /// Culls the passes not contributing to the imported resources, walking the graph backwards
+    /// from them.
+    fn cull_passes(&self) -> Vec<bool> {
+        // Whether the contents a resource has at this point of the graph are used later on
+        let mut is_needed: Vec<bool> = self.resources.iter().map(Resource::is_imported).collect();
+        let mut is_kept = vec![false; self.passes.len()];
+        for (index, pass) in self.passes.iter().enumerate().rev() {
+            let is_used = (0..self.resources.len())
+                .any(|resource| is_needed[resource] && pass.writes(ResourceId(resource)));
+            if !is_used && !pass.has_side_effects {
+                continue;
+            }
+
+            is_kept[index] = true;
+            for attachment in &pass.color_attachments {
+                // Attachments cleared on load don't depend on the passes before
+                is_needed[attachment.resource.0] = attachment.clear_color.is_none();
+            }
+            for &(resource, _) in &pass.reads {
+                is_needed[resource.0] = true;
+            }
+        }
+
+        is_kept
+    }
+
+    fn compile(&self) -> CompiledGraph {
+        let is_kept = self.cull_passes();
+        let kept: Vec<usize> = (0..self.passes.len())
+            .filter(|&index| is_kept[index])
+            .collect();
+
+        let mut states: Vec<Option<ResourceState>> = vec![None; self.resources.len()];
+        let mut has_contents: Vec<bool> =
+            self.resources.iter().map(Resource::is_imported).collect();
+        let mut usages: Vec<Option<ResourceUsage>> = self.resources.iter().map(|_| None).collect();
+        let mut add_usage = |resource: ResourceId, state: ResourceState, position: usize| {
+            let usage = usages[resource.0].get_or_insert_with(|| ResourceUsage {
+                states: ResourceState::empty(),
+                lifetime: position..position,
+            });
+            usage.states |= state;
+            usage.lifetime.end = position + 1;
+        };
+
+        let mut passes = Vec::with_capacity(kept.len());
+        for (position, &index) in kept.iter().enumerate() {
+            let pass = &self.passes[index];
+            let later_passes = &kept[position + 1..];
+
+            let mut barriers = Vec::new();
+            for resource in pass.get_accessed_resources() {
+                let state = pass.get_state(resource).unwrap();
+                if states[resource.0] != Some(state) {
+                    barriers.push((resource, state));
+                    states[resource.0] = Some(state);
+                }
+                add_usage(resource, state, position);
+            }
+            for &(resource, _) in &pass.writes {
+                has_contents[resource.0] = true;
+            }
+
+            let mut render_pass = None;
+            let mut clear_values = Vec::new();
+            if !pass.color_attachments.is_empty() {
+                let attachments = pass
+                    .color_attachments
+                    .iter()
+                    .map(|attachment| {
+                        let resource = &self.resources[attachment.resource.0];
+                        let next_pass = later_passes
+                            .iter()
+                            .map(|&later| &self.passes[later])
+                            .find(|later| later.uses(attachment.resource));
+
+                        let load_op = if attachment.clear_color.is_some() {
+                            LoadOp::Clear
+                        } else if has_contents[attachment.resource.0] {
+                            LoadOp::Load
+                        } else {
+                            LoadOp::DontCare
+                        };
+                        let is_read_later = match next_pass {
+                            Some(next_pass) => !next_pass.clears(attachment.resource),
+                            None => resource.is_imported(),
+                        };
+                        let store_op = if is_read_later {
+                            StoreOp::Store
+                        } else {
+                            StoreOp::DontCare
+                        };
+                        let final_state = next_pass
+                            .and_then(|next_pass| next_pass.get_state(attachment.resource))
+                            .or_else(|| resource.get_final_state())
+                            .unwrap_or(ResourceState::RENDER_TARGET);
+
+                        states[attachment.resource.0] = Some(final_state);
+                        has_contents[attachment.resource.0] = true;
+                        add_usage(attachment.resource, ResourceState::RENDER_TARGET, position);
+                        clear_values.push(attachment.clear_color.unwrap_or([0.0; 4]));
+                        AttachmentDesc {
+                            format: resource.get_format(),
+                            load_op,
+                            store_op,
+                            final_state,
+                        }
+                    })
+                    .collect();
+                render_pass = Some(RenderPassDesc { attachments });
+            }
+
+            passes.push(CompiledPass {
+                index,
+                barriers,
+                attachments: pass
+                    .color_attachments
+                    .iter()
+                    .map(|attachment| attachment.resource)
+                    .collect(),
+                render_pass,
+                clear_values,
+            });
+        }
+
+        let final_barriers = self
+            .resources
+            .iter()
+            .enumerate()
+            .filter_map(|(index, resource)| match resource.kind {
+                ResourceKind::Image { final_state, .. }
+                | ResourceKind::Buffer { final_state, .. } => match states[index] {
+                    Some(state) if state != final_state => Some((ResourceId(index), final_state)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+
+        CompiledGraph {
+            passes,
+            final_barriers,
+            usages,
+        }
+    }
+}
+
+impl<'a, B: Backend> Default for RenderGraph<'a, B> {
+    fn default() -> Self {
+        RenderGraph::new()
+    }
+}
+
+/// Declares the resources a pass uses. The pass is only added to the graph by `execute`.
+pub struct PassBuilder<'g, 'a, B: Backend> {
+    graph: &'g mut RenderGraph<'a, B>,
+    name: &'a str,
+    reads: Vec<(ResourceId, ResourceState)>,
+    writes: Vec<(ResourceId, ResourceState)>,
+    color_attachments: Vec<ColorAttachment>,
+    has_side_effects: bool,
+}
+
+impl<'g, 'a, B: Backend> PassBuilder<'g, 'a, B> {
+    /// Reads `resource` in `state`, e.g. `ResourceState::PIXEL_SHADER_READ` for sampled images.
+    pub fn read(mut self, resource: ResourceId, state: ResourceState) -> Self {
+        self.check_access(resource, state);
+        self.reads.push((resource, state));
+        self
+    }
+
+    /// Writes `resource` in `state` outside of a render pass, e.g. `ResourceState::STORAGE`.
+    /// Whatever isn't written keeps its previous contents.
+    pub fn write(mut self, resource: ResourceId, state: ResourceState) -> Self {
+        self.check_access(resource, state);
+        self.writes.push((resource, state));
+        self
+    }
+
+    /// Renders into `resource`, keeping its previous contents.
+    pub fn write_color(self, resource: ResourceId) -> Self {
+        self.add_color_attachment(resource, None)
+    }
+
+    /// Renders into `resource`, filling it with `clear_color` first.
+    pub fn clear_color(self, resource: ResourceId, clear_color: ClearColor) -> Self {
+        self.add_color_attachment(resource, Some(clear_color))
+    }
+
+    /// Keeps the pass even if nothing uses what it writes.
+    pub fn has_side_effects(mut self) -> Self {
+        self.has_side_effects = true;
+        self
+    }
+
+    /// Adds the pass to the graph, `execute` being called to record it. Passes with color
+    /// attachments are recorded within a render pass.
+    pub fn execute<F>(self, execute: F)
+    where
+        F: FnOnce(&B::CommandBuffer, &PassResources<B>) + 'a,
+    {
+        self.graph.passes.push(Pass {
+            reads: self.reads,
+            writes: self.writes,
+            color_attachments: self.color_attachments,
+            has_side_effects: self.has_side_effects,
+            execute: Box::new(execute),
+        });
+    }
+
+    fn add_color_attachment(
+        mut self,
+        resource: ResourceId,
+        clear_color: Option<ClearColor>,
+    ) -> Self {
+        let is_framebuffer = |resource: ResourceId| {
+            matches!(
+                self.graph.resources[resource.0].kind,
+                ResourceKind::FrameBuffer { .. }
+            )
+        };
+        assert!(
+            self.color_attachments.is_empty()
+                || !is_framebuffer(resource) && !is_framebuffer(self.color_attachments[0].resource),
+            "Pass '{}' renders into an imported framebuffer along with other attachments",
+            self.name
+        );
+        assert!(
+            !self.graph.resources[resource.0].is_buffer(),
+            "Pass '{}' uses buffer '{}' as color attachment",
+            self.name,
+            self.graph.resources[resource.0].name
+        );
+        assert!(
+            self.reads
+                .iter()
+                .chain(&self.writes)
+                .all(|&(used, _)| used != resource)
+                && self
+                    .color_attachments
+                    .iter()
+                    .all(|attachment| attachment.resource != resource),
+            "Pass '{}' uses '{}' as color attachment and in another way",
+            self.name,
+            self.graph.resources[resource.0].name
+        );
+
+        self.color_attachments.push(ColorAttachment {
+            resource,
+            clear_color,
+        });
+        self
+    }
+
+    fn check_access(&self, resource: ResourceId, state: ResourceState) {
+        let used = &self.graph.resources[resource.0];
+        if let ResourceKind::FrameBuffer { .. } = used.kind {
+            panic!(
+                "Pass '{}' uses framebuffer '{}' other than as color attachment",
+                self.name, used.name
+            );
+        }
+        assert!(
+            state.is_valid() && state != ResourceState::RENDER_TARGET,
+            "Pass '{}' uses '{}' in {:?} state",
+            self.name,
+            used.name,
+            state
+        );
+        assert!(
+            self.color_attachments
+                .iter()
+                .all(|attachment| attachment.resource != resource),
+            "Pass '{}' uses '{}' as color attachment and in another way",
+            self.name,
+            used.name
+        );
+    }
+}
+
+fn get_transient_image_desc(desc: ImageDesc, states: ResourceState) -> ImageDesc {
+    ImageDesc {
+        usage: desc.usage | get_image_usage(states),
+        ..desc
+    }
+}
+
+fn get_image_usage(state: ResourceState) -> ImageUsage {
+    let mut usage = ImageUsage::empty();
+    if state.contains(ResourceState::RENDER_TARGET) {
+        usage |= ImageUsage::COLOR_ATTACHMENT;
+    }
+    if state.intersects(ResourceState::SHADER_READ) {
+        usage |= ImageUsage::SAMPLED;
+    }
+    if state.intersects(ResourceState::STORAGE) {
+        usage |= ImageUsage::STORAGE;
+    }
+    if state.intersects(ResourceState::COPY_SRC | ResourceState::RESOLVE_SRC) {
+        usage |= ImageUsage::TRANSFER_SRC;
+    }
+    if state.intersects(ResourceState::COPY_DST | ResourceState::RESOLVE_DST) {
+        usage |= ImageUsage::TRANSFER_DST;
+    }
+    if state.intersects(ResourceState::DEPTH_READ | ResourceState::DEPTH_WRITE) {
+        usage |= ImageUsage::DEPTH_STENCIL_ATTACHMENT;
+    }
+    usage
+}
+
+fn get_buffer_usage(state: ResourceState) -> BufferUsage {
+    let mut usage = BufferUsage::empty();
+    if state.contains(ResourceState::VERTEX_BUFFER) {
+        usage |= BufferUsage::VERTEX;
+    }
+    if state.contains(ResourceState::INDEX_BUFFER) {
+        usage |= BufferUsage::INDEX;
+    }
+    if state.contains(ResourceState::CONSTANT_BUFFER) {
+        usage |= BufferUsage::CONSTANT;
+    }
+    if state.contains(ResourceState::INDIRECT_ARGUMENT) {
+        usage |= BufferUsage::INDIRECT;
+    }
+    // Buffers read by shaders other than as constants are bound as storage buffers
+    if state.intersects(ResourceState::SHADER_READ | ResourceState::STORAGE) {
+        usage |= BufferUsage::STORAGE;
+    }
+    if state.contains(ResourceState::COPY_SRC) {
+        usage |= BufferUsage::TRANSFER_SRC;
+    }
+    if state.contains(ResourceState::COPY_DST) {
+        usage |= BufferUsage::TRANSFER_DST;
+    }
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graphix_gfx::hal::{
+        AdapterPreference, CommandPool, CommandPoolFlags, CommandQueue, Extent, ImageKind,
+        Instance, QueueType,
+    };
+    use graphix_gfx::software;
+
+    fn create_device() -> software::Device {
+        software::Instance::new()
+            .select_adapter(&AdapterPreference::HighPerformance)
+            .unwrap()
+            .create_device()
+            .unwrap()
+    }
+
+    fn image_desc(usage: ImageUsage) -> ImageDesc {
+        ImageDesc {
+            kind: ImageKind::D2,
+            extent: Extent {
+                width: 4,
+                height: 4,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            samples: 1,
+            format: Format::Rgba8Unorm,
+            usage,
+        }
+    }
+
+    #[test]
+    fn culls_unused_passes() {
+        let device = create_device();
+        let output = device
+            .create_image(image_desc(ImageUsage::COLOR_ATTACHMENT))
+            .unwrap();
+
+        let mut graph = RenderGraph::<software::Backend>::new();
+        let unused = graph.create_image("unused", image_desc(ImageUsage::empty()));
+        let output = graph.import_image(
+            "output",
+            &output,
+            Format::Rgba8Unorm,
+            ResourceState::COPY_SRC,
+        );
+        graph
+            .add_pass("unused")
+            .clear_color(unused, [0.0; 4])
+            .execute(|_, _| {});
+        graph
+            .add_pass("output")
+            .clear_color(output, [0.0; 4])
+            .execute(|_, _| {});
+        graph
+            .add_pass("side effects")
+            .clear_color(unused, [0.0; 4])
+            .has_side_effects()
+            .execute(|_, _| {});
+
+        let compiled = graph.compile();
+        let indices: Vec<usize> = compiled.passes.iter().map(|pass| pass.index).collect();
+        assert_eq!(indices, [1, 2]);
+        // Culled passes don't keep resources alive either
+        assert_eq!(compiled.usages[unused.0].as_ref().unwrap().lifetime, 1..2);
+    }
+
+    #[test]
+    fn clears_cut_dependencies() {
+        let device = create_device();
+        let output = device
+            .create_image(image_desc(ImageUsage::COLOR_ATTACHMENT))
+            .unwrap();
+
+        let mut graph = RenderGraph::<software::Backend>::new();
+        let output = graph.import_image(
+            "output",
+            &output,
+            Format::Rgba8Unorm,
+            ResourceState::COPY_SRC,
+        );
+        graph
+            .add_pass("overwritten")
+            .clear_color(output, [0.0; 4])
+            .execute(|_, _| {});
+        graph
+            .add_pass("cleared")
+            .clear_color(output, [0.0; 4])
+            .execute(|_, _| {});
+        graph
+            .add_pass("blended")
+            .write_color(output)
+            .execute(|_, _| {});
+
+        // The second pass clears what the first one rendered, but the last one loads it
+        let compiled = graph.compile();
+        let indices: Vec<usize> = compiled.passes.iter().map(|pass| pass.index).collect();
+        assert_eq!(indices, [1, 2]);
+        let load_ops: Vec<LoadOp> = compiled
+            .passes
+            .iter()
+            .map(|pass| pass.render_pass.as_ref().unwrap().attachments[0].load_op)
+            .collect();
+        assert_eq!(load_ops, [LoadOp::Clear, LoadOp::Load]);
+    }
+
+    #[test]
+    fn skips_barriers_after_render_passes() {
+        let device = create_device();
+        let queue = device.create_command_queue(QueueType::Graphics).unwrap();
+        let mut command_pool = device
+            .create_command_pool(QueueType::Graphics, CommandPoolFlags::empty())
+            .unwrap();
+        let command_buffer = command_pool.create_buffer().unwrap();
+        let output_image = device
+            .create_image(image_desc(ImageUsage::COLOR_ATTACHMENT))
+            .unwrap();
+
+        let mut graph = RenderGraph::<software::Backend>::new();
+        let lighting = graph.create_image("lighting", image_desc(ImageUsage::empty()));
+        let output = graph.import_image(
+            "output",
+            &output_image,
+            Format::Rgba8Unorm,
+            ResourceState::COPY_SRC,
+        );
+        graph
+            .add_pass("lighting")
+            .clear_color(lighting, [0.0; 4])
+            .execute(|_, _| {});
+        graph
+            .add_pass("tonemap")
+            .read(lighting, ResourceState::PIXEL_SHADER_READ)
+            .clear_color(output, [1.0; 4])
+            .execute(|_, _| {});
+
+        // The render pass leaves the image in the state it's read in next
+        let compiled = graph.compile();
+        let attachment = &compiled.passes[0].render_pass.as_ref().unwrap().attachments[0];
+        assert_eq!(attachment.final_state, ResourceState::PIXEL_SHADER_READ);
+        assert_eq!(attachment.store_op, StoreOp::Store);
+        assert!(compiled.passes[1].barriers.is_empty());
+        let attachment = &compiled.passes[1].render_pass.as_ref().unwrap().attachments[0];
+        assert_eq!(attachment.final_state, ResourceState::COPY_SRC);
+        assert!(compiled.final_barriers.is_empty());
+
+        // Recording checks the states resources are in when they're used
+        let mut pool = TransientPool::new();
+        command_buffer.begin().unwrap();
+        graph.execute(&device, &mut pool, &command_buffer).unwrap();
+        command_buffer.end().unwrap();
+        queue.submit(vec![&command_buffer]).unwrap();
+        assert_eq!(output_image.state(), ResourceState::COPY_SRC);
+        assert!(output_image.data().iter().all(|&byte| byte == 255));
+    }
+}
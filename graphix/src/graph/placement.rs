@@ -0,0 +1,151 @@
+use graphix_gfx::hal::MemoryRequirements;
+
+use std::ops::Range;
+
+/// A resource to place in a heap, alive from the pass at `lifetime.start` up to the one before
+/// `lifetime.end`.
+pub(crate) struct Allocation {
+    pub requirements: MemoryRequirements,
+    pub lifetime: Range<usize>,
+}
+
+impl Allocation {
+    fn is_alive_with(&self, other: &Allocation) -> bool {
+        self.lifetime.start < other.lifetime.end && other.lifetime.start < self.lifetime.end
+    }
+}
+
+/// Places resources so that those alive at the same time don't overlap, returning their offsets
+/// and the size of the heap they need.
+///
+/// Larger resources are placed first, each one at the lowest offset left free by the resources
+/// already placed whose lifetimes overlap its own.
+pub(crate) fn place(allocations: &[Allocation]) -> (Vec<u64>, u64) {
+    let mut order: Vec<usize> = (0..allocations.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(allocations[index].requirements.size));
+
+    let mut offsets = vec![0; allocations.len()];
+    let mut heap_size = 0;
+    for (position, &index) in order.iter().enumerate() {
+        let allocation = &allocations[index];
+        let mut occupied: Vec<Range<u64>> = order[..position]
+            .iter()
+            .filter(|&&placed| allocations[placed].is_alive_with(allocation))
+            .map(|&placed| offsets[placed]..offsets[placed] + allocations[placed].requirements.size)
+            .collect();
+        occupied.sort_by_key(|range| range.start);
+
+        let MemoryRequirements { size, alignment } = allocation.requirements;
+        let mut offset = 0;
+        for range in occupied {
+            if offset + size <= range.start {
+                break;
+            }
+            offset = offset.max(align(range.end, alignment));
+        }
+
+        offsets[index] = offset;
+        heap_size = heap_size.max(offset + size);
+    }
+
+    (offsets, heap_size)
+}
+
+fn align(offset: u64, alignment: u64) -> u64 {
+    (offset + alignment - 1) & !(alignment - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocation(size: u64, alignment: u64, lifetime: Range<usize>) -> Allocation {
+        Allocation {
+            requirements: MemoryRequirements { size, alignment },
+            lifetime,
+        }
+    }
+
+    /// Checks that allocations alive together don't overlap, that offsets are aligned and that the
+    /// heap holds the most memory alive at any pass.
+    fn check_placement(allocations: &[Allocation]) -> u64 {
+        let (offsets, heap_size) = place(allocations);
+        for (index, allocation) in allocations.iter().enumerate() {
+            let MemoryRequirements { size, alignment } = allocation.requirements;
+            assert_eq!(offsets[index] % alignment, 0);
+            assert!(offsets[index] + size <= heap_size);
+            for (other_index, other) in allocations.iter().enumerate().skip(index + 1) {
+                if allocation.is_alive_with(other) {
+                    assert!(
+                        offsets[index] + size <= offsets[other_index]
+                            || offsets[other_index] + other.requirements.size <= offsets[index],
+                        "Allocations {} and {} overlap",
+                        index,
+                        other_index
+                    );
+                }
+            }
+        }
+
+        let pass_count = allocations
+            .iter()
+            .map(|allocation| allocation.lifetime.end)
+            .max()
+            .unwrap_or(0);
+        let peak_size = (0..pass_count)
+            .map(|pass| {
+                allocations
+                    .iter()
+                    .filter(|allocation| allocation.lifetime.contains(&pass))
+                    .map(|allocation| allocation.requirements.size)
+                    .sum::<u64>()
+            })
+            .max()
+            .unwrap_or(0);
+        assert!(peak_size <= heap_size);
+        heap_size
+    }
+
+    #[test]
+    fn shares_memory_between_lifetimes() {
+        let allocations = [
+            allocation(256, 256, 0..2),
+            allocation(256, 256, 2..4),
+            allocation(128, 256, 1..3),
+        ];
+        assert_eq!(check_placement(&allocations), 384);
+    }
+
+    #[test]
+    fn aligns_offsets() {
+        let allocations = [
+            allocation(100, 4, 0..1),
+            allocation(64, 64, 0..1),
+            allocation(10, 256, 0..1),
+        ];
+        // The smallest allocation can't fit in the gap between the others
+        assert_eq!(check_placement(&allocations), 266);
+    }
+
+    #[test]
+    fn places_without_overlap() {
+        // Pseudo-random graphs, seeded for reproducibility
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next = |max: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % max
+        };
+        for _ in 0..100 {
+            let allocations: Vec<Allocation> = (0..next(16) + 1)
+                .map(|_| {
+                    let start = next(8) as usize;
+                    let end = start + next(4) as usize + 1;
+                    allocation(next(4096) + 1, 1 << next(9), start..end)
+                })
+                .collect();
+            check_placement(&allocations);
+        }
+    }
+}
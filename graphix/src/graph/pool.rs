@@ -0,0 +1,369 @@
+use graphix_gfx::hal::{
+    Backend, BufferUsage, Device, HeapUsage, ImageDesc, MemoryRequirements, MemoryType,
+    RenderPassDesc, Result,
+};
+
+use std::collections::HashMap;
+
+struct PooledHeap<B: Backend> {
+    usage: HeapUsage,
+    size: u64,
+    heap: B::Heap,
+    /// Fence value of the work recorded when a larger heap replaced this one, after which it's
+    /// no longer used.
+    outgrown_at: Option<u64>,
+}
+
+struct PlacedImage<B: Backend> {
+    heap: usize,
+    offset: u64,
+    desc: ImageDesc,
+    image: B::Image,
+    is_used: bool,
+}
+
+struct PlacedBuffer<B: Backend> {
+    heap: usize,
+    offset: u64,
+    size: u64,
+    usage: BufferUsage,
+    buffer: B::Buffer,
+    is_used: bool,
+}
+
+/// Memory, resources and render passes created by render graphs, kept from one frame to the next
+/// so that graphs executed every frame don't create them again.
+///
+/// Transient resources are placed in heaps, resources alive at different times of a graph sharing
+/// memory. Each heap only grows when a graph needs more memory than it has. The heaps it outgrew
+/// may still be used by work in flight, so they're kept until `drop_outgrown_heaps` is told that
+/// work has completed, tracked by the fence values given to `set_fence_value`.
+///
+/// Transient resources are only handed to one graph at a time. As the GPU runs the work of a queue
+/// in order, graphs executed on the same queue can share a pool even with several frames in flight.
+pub struct TransientPool<B: Backend> {
+    heaps: Vec<PooledHeap<B>>,
+    images: Vec<PlacedImage<B>>,
+    buffers: Vec<PlacedBuffer<B>>,
+    image_requirements: HashMap<ImageDesc, MemoryRequirements>,
+    buffer_requirements: HashMap<(u64, BufferUsage), MemoryRequirements>,
+    render_passes: Vec<(RenderPassDesc, B::RenderPass)>,
+    fence_value: u64,
+}
+
+impl<B: Backend> TransientPool<B> {
+    pub fn new() -> Self {
+        TransientPool {
+            heaps: Vec::new(),
+            images: Vec::new(),
+            buffers: Vec::new(),
+            image_requirements: HashMap::new(),
+            buffer_requirements: HashMap::new(),
+            render_passes: Vec::new(),
+            fence_value: 0,
+        }
+    }
+
+    /// Sets the value a fence is signaled with once the work of the graphs executed from now on
+    /// completes. Values must not decrease.
+    pub fn set_fence_value(&mut self, value: u64) {
+        debug_assert!(value >= self.fence_value, "Fence values must not decrease");
+        self.fence_value = value;
+    }
+
+    /// Drops the heaps outgrown by graphs whose work completed, i.e. executed with a fence value
+    /// up to `completed_value`, along with the resources placed in them.
+    pub fn drop_outgrown_heaps(&mut self, completed_value: u64) {
+        let is_retired = |pooled: &PooledHeap<B>| {
+            pooled
+                .outgrown_at
+                .is_some_and(|value| value <= completed_value)
+        };
+        let mut heap_indices = Vec::with_capacity(self.heaps.len());
+        let mut kept_count = 0;
+        for pooled in &self.heaps {
+            if is_retired(pooled) {
+                heap_indices.push(None);
+            } else {
+                heap_indices.push(Some(kept_count));
+                kept_count += 1;
+            }
+        }
+        if kept_count == self.heaps.len() {
+            return;
+        }
+
+        // Placed resources go before the heaps they live in
+        self.images
+            .retain(|placed| heap_indices[placed.heap].is_some());
+        self.buffers
+            .retain(|placed| heap_indices[placed.heap].is_some());
+        for placed in &mut self.images {
+            placed.heap = heap_indices[placed.heap].unwrap();
+        }
+        for placed in &mut self.buffers {
+            placed.heap = heap_indices[placed.heap].unwrap();
+        }
+        self.heaps.retain(|pooled| !is_retired(pooled));
+    }
+
+    /// Number of images created so far.
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Number of buffers created so far.
+    pub fn buffer_count(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// Memory taken by the heaps created so far, in bytes.
+    pub fn heap_size(&self) -> u64 {
+        self.heaps.iter().map(|pooled| pooled.size).sum()
+    }
+
+    /// Drops everything created so far, e.g. once the back buffers are resized. The GPU must be
+    /// done with the work recorded by the graphs that used the pool.
+    pub fn clear(&mut self) {
+        // Placed resources go before the heaps they live in
+        self.images.clear();
+        self.buffers.clear();
+        self.heaps.clear();
+        self.render_passes.clear();
+    }
+
+    pub(crate) fn get_image_requirements(
+        &mut self,
+        device: &B::Device,
+        desc: &ImageDesc,
+    ) -> Result<MemoryRequirements> {
+        if let Some(&requirements) = self.image_requirements.get(desc) {
+            return Ok(requirements);
+        }
+
+        let requirements = device.get_image_requirements(desc)?;
+        self.image_requirements.insert(*desc, requirements);
+        Ok(requirements)
+    }
+
+    pub(crate) fn get_buffer_requirements(
+        &mut self,
+        device: &B::Device,
+        size: u64,
+        usage: BufferUsage,
+    ) -> Result<MemoryRequirements> {
+        if let Some(&requirements) = self.buffer_requirements.get(&(size, usage)) {
+            return Ok(requirements);
+        }
+
+        let requirements = device.get_buffer_requirements(size, usage)?;
+        self.buffer_requirements.insert((size, usage), requirements);
+        Ok(requirements)
+    }
+
+    /// Finds the heap holding resources of `usage`, creating a new one if it's smaller than
+    /// `size`.
+    pub(crate) fn acquire_heap(
+        &mut self,
+        device: &B::Device,
+        usage: HeapUsage,
+        size: u64,
+    ) -> Result<usize> {
+        let latest = self.heaps.iter().rposition(|pooled| pooled.usage == usage);
+        if let Some(index) = latest {
+            if self.heaps[index].size >= size {
+                return Ok(index);
+            }
+        }
+
+        let heap = device.create_heap(size, MemoryType::DeviceLocal, usage)?;
+        if let Some(index) = latest {
+            self.heaps[index].outgrown_at = Some(self.fence_value);
+        }
+        self.heaps.push(PooledHeap {
+            usage,
+            size,
+            heap,
+            outgrown_at: None,
+        });
+        Ok(self.heaps.len() - 1)
+    }
+
+    /// Finds an unused image matching `desc` at `offset` of `heap`, placing one there if there's
+    /// none.
+    pub(crate) fn acquire_image(
+        &mut self,
+        device: &B::Device,
+        heap: usize,
+        offset: u64,
+        desc: &ImageDesc,
+    ) -> Result<usize> {
+        let index = match self.images.iter().position(|placed| {
+            !placed.is_used
+                && placed.heap == heap
+                && placed.offset == offset
+                && placed.desc == *desc
+        }) {
+            Some(index) => index,
+            None => {
+                let image = device.create_placed_image(&self.heaps[heap].heap, offset, *desc)?;
+                self.images.push(PlacedImage {
+                    heap,
+                    offset,
+                    desc: *desc,
+                    image,
+                    is_used: false,
+                });
+                self.images.len() - 1
+            }
+        };
+
+        self.images[index].is_used = true;
+        Ok(index)
+    }
+
+    /// Finds an unused buffer matching `size` and `usage` at `offset` of `heap`, placing one
+    /// there if there's none.
+    pub(crate) fn acquire_buffer(
+        &mut self,
+        device: &B::Device,
+        heap: usize,
+        offset: u64,
+        size: u64,
+        usage: BufferUsage,
+    ) -> Result<usize> {
+        let index = match self.buffers.iter().position(|placed| {
+            !placed.is_used
+                && placed.heap == heap
+                && placed.offset == offset
+                && placed.size == size
+                && placed.usage == usage
+        }) {
+            Some(index) => index,
+            None => {
+                let buffer =
+                    device.create_placed_buffer(&self.heaps[heap].heap, offset, size, usage)?;
+                self.buffers.push(PlacedBuffer {
+                    heap,
+                    offset,
+                    size,
+                    usage,
+                    buffer,
+                    is_used: false,
+                });
+                self.buffers.len() - 1
+            }
+        };
+
+        self.buffers[index].is_used = true;
+        Ok(index)
+    }
+
+    /// Hands the resources back once the graph using them has been recorded.
+    pub(crate) fn release_resources(&mut self) {
+        for placed in &mut self.images {
+            placed.is_used = false;
+        }
+        for placed in &mut self.buffers {
+            placed.is_used = false;
+        }
+    }
+
+    pub(crate) fn get_image(&self, index: usize) -> &B::Image {
+        &self.images[index].image
+    }
+
+    pub(crate) fn get_buffer(&self, index: usize) -> &B::Buffer {
+        &self.buffers[index].buffer
+    }
+
+    pub(crate) fn acquire_render_pass(
+        &mut self,
+        device: &B::Device,
+        desc: &RenderPassDesc,
+    ) -> Result<usize> {
+        if let Some(index) = self
+            .render_passes
+            .iter()
+            .position(|(pass_desc, _)| pass_desc == desc)
+        {
+            return Ok(index);
+        }
+
+        let render_pass = device.create_render_pass(desc)?;
+        self.render_passes.push((desc.clone(), render_pass));
+        Ok(self.render_passes.len() - 1)
+    }
+
+    pub(crate) fn get_render_pass(&self, index: usize) -> &B::RenderPass {
+        &self.render_passes[index].1
+    }
+}
+
+impl<B: Backend> Default for TransientPool<B> {
+    fn default() -> Self {
+        TransientPool::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use graphix_gfx::hal::{AdapterPreference, Instance};
+    use graphix_gfx::software;
+
+    const KIB: u64 = 1024;
+
+    #[test]
+    fn drops_outgrown_heaps() {
+        let device = software::Instance::new()
+            .select_adapter(&AdapterPreference::HighPerformance)
+            .unwrap()
+            .create_device()
+            .unwrap();
+        let mut pool = TransientPool::<software::Backend>::new();
+
+        pool.set_fence_value(1);
+        let heap = pool
+            .acquire_heap(&device, HeapUsage::BUFFERS, 64 * KIB)
+            .unwrap();
+        pool.acquire_buffer(&device, heap, 0, 64 * KIB, BufferUsage::STORAGE)
+            .unwrap();
+        pool.release_resources();
+
+        // Smaller graphs keep using the heap, larger ones outgrow it
+        pool.set_fence_value(2);
+        assert_eq!(
+            pool.acquire_heap(&device, HeapUsage::BUFFERS, 32 * KIB)
+                .unwrap(),
+            heap
+        );
+        let heap = pool
+            .acquire_heap(&device, HeapUsage::BUFFERS, 128 * KIB)
+            .unwrap();
+        pool.acquire_buffer(&device, heap, 0, 128 * KIB, BufferUsage::STORAGE)
+            .unwrap();
+        pool.release_resources();
+        assert_eq!(pool.heap_size(), 192 * KIB);
+
+        // The outgrown heap is kept while work recorded with it may still run
+        pool.drop_outgrown_heaps(1);
+        assert_eq!(pool.heap_size(), 192 * KIB);
+        assert_eq!(pool.buffer_count(), 2);
+
+        pool.drop_outgrown_heaps(2);
+        assert_eq!(pool.heap_size(), 128 * KIB);
+        assert_eq!(pool.buffer_count(), 1);
+        assert_eq!(
+            pool.acquire_heap(&device, HeapUsage::BUFFERS, 128 * KIB)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            pool.acquire_buffer(&device, 0, 0, 128 * KIB, BufferUsage::STORAGE)
+                .unwrap(),
+            0
+        );
+        assert_eq!(pool.buffer_count(), 1);
+    }
+}
@@ -3,9 +3,13 @@ extern crate winit;
 use std::fmt;
 
 mod engine;
+mod graph;
 mod timer;
 
 pub use self::engine::Engine;
+pub use self::graph::{
+    PassBuilder, PassResources, RenderGraph, ResourceId, TransientMemoryReport, TransientPool,
+};
 pub use self::timer::Timer;
 
 #[derive(Debug)]
@@ -4,7 +4,7 @@ pub struct Timer {}
 impl Timer {
     pub fn tick<F>(&self, update: F)
     where
-        F: Fn() -> (),
+        F: Fn(),
     {
         update();
     }